@@ -0,0 +1,528 @@
+//! Dynamic AABB tree is a self-balancing binary tree of bounding rectangles for a large amount of
+//! continuously moving objects.
+
+use crate::{
+    geometry::{contains_rect, union},
+    quadtree::{BoundsProvider, QueryStorage},
+    Rect,
+};
+use nalgebra::Vector2;
+use std::ops::ControlFlow;
+
+/// Marks the absence of a node.
+const NULL: usize = usize::MAX;
+
+/// The fat bounds are stretched by the displacement of an object, multiplied by this value, to
+/// predict where the object will be moving.
+const DISPLACEMENT_MULTIPLIER: f32 = 4.0;
+
+/// Handle of an object in [`DynamicAabbTree`]. It is valid until the object is removed, after
+/// that the handle could be reused by other objects.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProxyId(usize);
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct TreeNode<I> {
+    /// Enlarged bounds of a leaf, or the union of the fat bounds of the children of a branch.
+    fat_bounds: Rect<f32>,
+    /// Exact bounds of the object of a leaf.
+    bounds: Rect<f32>,
+    /// Parent of the node, or the next free node if the node is free.
+    parent: usize,
+    /// Children of a branch, both are [`NULL`] for leaves.
+    children: [usize; 2],
+    /// Height of the subtree, zero for leaves.
+    height: usize,
+    /// Id of the object of a leaf.
+    id: Option<I>,
+}
+
+impl<I> TreeNode<I> {
+    fn is_leaf(&self) -> bool {
+        self.children[0] == NULL
+    }
+}
+
+fn perimeter(rect: &Rect<f32>) -> f32 {
+    2.0 * (rect.w() + rect.h())
+}
+
+/// Dynamic bounding volume tree in the style of Box2D. Objects are inserted one by one with
+/// bounds, that are enlarged by a margin ("fat" bounds), and the tree is kept balanced with tree
+/// rotations. When an object moves, it is reinserted only if it leaves its fat bounds, so small
+/// movements are almost free. This makes the tree the right choice for thousands of continuously
+/// moving objects.
+///
+/// The nodes are tested against the fat bounds, but queries report only the objects, whose exact
+/// bounds satisfy the query.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DynamicAabbTree<I> {
+    nodes: Vec<TreeNode<I>>,
+    root: usize,
+    free_list: usize,
+    margin: f32,
+    len: usize,
+}
+
+impl<I> Default for DynamicAabbTree<I> {
+    fn default() -> Self {
+        Self::new(0.1)
+    }
+}
+
+impl<I> DynamicAabbTree<I> {
+    /// Creates new empty tree, that enlarges the bounds of the objects by the given margin on
+    /// each side.
+    pub fn new(margin: f32) -> Self {
+        Self {
+            nodes: Default::default(),
+            root: NULL,
+            free_list: NULL,
+            margin,
+            len: 0,
+        }
+    }
+
+    /// Returns the margin, by which the bounds of the objects are enlarged.
+    pub fn margin(&self) -> f32 {
+        self.margin
+    }
+
+    /// Returns the amount of objects in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the height of the tree, zero for an empty tree or a tree with one object.
+    pub fn height(&self) -> usize {
+        if self.root == NULL {
+            0
+        } else {
+            self.nodes[self.root].height
+        }
+    }
+
+    /// Returns the bounds of the root node, or None if the tree is empty.
+    pub fn bounds(&self) -> Option<Rect<f32>> {
+        (self.root != NULL).then(|| self.nodes[self.root].fat_bounds)
+    }
+
+    fn leaf(&self, proxy: ProxyId) -> &TreeNode<I> {
+        self.nodes
+            .get(proxy.0)
+            .filter(|node| node.id.is_some())
+            .expect("invalid proxy")
+    }
+
+    /// Returns the exact bounds of the object with the given handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is invalid.
+    pub fn proxy_bounds(&self, proxy: ProxyId) -> Rect<f32> {
+        self.leaf(proxy).bounds
+    }
+
+    /// Returns the enlarged bounds of the object with the given handle, that are stored in the
+    /// tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is invalid.
+    pub fn fat_bounds(&self, proxy: ProxyId) -> Rect<f32> {
+        self.leaf(proxy).fat_bounds
+    }
+
+    /// Returns the id of the object with the given handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is invalid.
+    pub fn id(&self, proxy: ProxyId) -> &I {
+        self.leaf(proxy).id.as_ref().expect("leaves have ids")
+    }
+
+    fn allocate(&mut self, node: TreeNode<I>) -> usize {
+        if self.free_list == NULL {
+            self.nodes.push(node);
+            self.nodes.len() - 1
+        } else {
+            let index = self.free_list;
+            self.free_list = self.nodes[index].parent;
+            self.nodes[index] = node;
+            index
+        }
+    }
+
+    fn free(&mut self, index: usize) -> Option<I> {
+        let node = &mut self.nodes[index];
+        node.parent = self.free_list;
+        node.children = [NULL; 2];
+        self.free_list = index;
+        node.id.take()
+    }
+
+    /// Adds the object to the tree and returns its handle.
+    pub fn insert<T>(&mut self, object: T) -> ProxyId
+    where
+        T: BoundsProvider<Id = I>,
+    {
+        let bounds = object.bounds();
+        let leaf = self.allocate(TreeNode {
+            fat_bounds: bounds.inflate(self.margin, self.margin),
+            bounds,
+            parent: NULL,
+            children: [NULL; 2],
+            height: 0,
+            id: Some(object.id()),
+        });
+        self.insert_leaf(leaf);
+        self.len += 1;
+        ProxyId(leaf)
+    }
+
+    /// Removes the object with the given handle from the tree and returns its id, or `None` if
+    /// the handle is not used by any object.
+    pub fn remove(&mut self, proxy: ProxyId) -> Option<I> {
+        self.nodes.get(proxy.0)?.id.as_ref()?;
+        self.remove_leaf(proxy.0);
+        self.len -= 1;
+        self.free(proxy.0)
+    }
+
+    /// Sets new bounds of the object with the given handle. The displacement of the object since
+    /// the last update is used to stretch the fat bounds in the direction of the movement. The
+    /// object is reinserted only if it left its fat bounds, or if the fat bounds became too large
+    /// for the object, and then `true` is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle is invalid.
+    pub fn move_proxy(
+        &mut self,
+        proxy: ProxyId,
+        bounds: Rect<f32>,
+        displacement: Vector2<f32>,
+    ) -> bool {
+        let tree_bounds = self.leaf(proxy).fat_bounds;
+        let leaf = proxy.0;
+        self.nodes[leaf].bounds = bounds;
+
+        let mut fat_bounds = bounds.inflate(self.margin, self.margin);
+        let stretch = displacement * DISPLACEMENT_MULTIPLIER;
+        for axis in 0..2 {
+            if stretch[axis] < 0.0 {
+                fat_bounds.position[axis] += stretch[axis];
+            }
+            fat_bounds.size[axis] += stretch[axis].abs();
+        }
+
+        if contains_rect(&tree_bounds, &bounds) {
+            // Keep the old fat bounds, unless they are much larger than needed.
+            let margin = 4.0 * self.margin;
+            if contains_rect(&fat_bounds.inflate(margin, margin), &tree_bounds) {
+                return false;
+            }
+        }
+
+        self.remove_leaf(leaf);
+        self.nodes[leaf].fat_bounds = fat_bounds;
+        self.insert_leaf(leaf);
+        true
+    }
+
+    /// Removes all the objects.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.root = NULL;
+        self.free_list = NULL;
+        self.len = 0;
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf].parent = NULL;
+            return;
+        }
+
+        // Descend to the sibling, that gives the smallest increase of the perimeters of the
+        // nodes.
+        let leaf_bounds = self.nodes[leaf].fat_bounds;
+        let mut index = self.root;
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let combined = perimeter(&union(&node.fat_bounds, &leaf_bounds));
+            // Cost of making a new parent for this node and the leaf.
+            let cost = 2.0 * combined;
+            // Minimum cost of pushing the leaf further down the tree.
+            let inheritance = 2.0 * (combined - perimeter(&node.fat_bounds));
+            let child_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let enlarged = perimeter(&union(&child.fat_bounds, &leaf_bounds));
+                if child.is_leaf() {
+                    enlarged + inheritance
+                } else {
+                    enlarged - perimeter(&child.fat_bounds) + inheritance
+                }
+            };
+            let [first, second] = node.children;
+            let (first_cost, second_cost) = (child_cost(first), child_cost(second));
+            if cost < first_cost && cost < second_cost {
+                break;
+            }
+            index = if first_cost < second_cost {
+                first
+            } else {
+                second
+            };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate(TreeNode {
+            fat_bounds: union(&self.nodes[sibling].fat_bounds, &leaf_bounds),
+            bounds: Rect::default(),
+            parent: old_parent,
+            children: [sibling, leaf],
+            height: self.nodes[sibling].height + 1,
+            id: None,
+        });
+        self.replace_child(old_parent, sibling, parent);
+        self.nodes[sibling].parent = parent;
+        self.nodes[leaf].parent = parent;
+        self.fix_upwards(old_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+        let parent = self.nodes[leaf].parent;
+        let grandparent = self.nodes[parent].parent;
+        let [first, second] = self.nodes[parent].children;
+        let sibling = if first == leaf { second } else { first };
+        self.replace_child(grandparent, parent, sibling);
+        self.nodes[sibling].parent = grandparent;
+        self.free(parent);
+        self.fix_upwards(grandparent);
+    }
+
+    /// Makes the `new` node a child of the parent instead of the `old` one, or the root if there
+    /// is no parent.
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if parent == NULL {
+            self.root = new;
+        } else {
+            let children = &mut self.nodes[parent].children;
+            if children[0] == old {
+                children[0] = new;
+            } else {
+                children[1] = new;
+            }
+        }
+    }
+
+    /// Recomputes the height and the bounds of a branch from its children.
+    fn update(&mut self, index: usize) {
+        let [first, second] = self.nodes[index].children;
+        let (first, second) = (&self.nodes[first], &self.nodes[second]);
+        let height = 1 + first.height.max(second.height);
+        let fat_bounds = union(&first.fat_bounds, &second.fat_bounds);
+        let node = &mut self.nodes[index];
+        node.height = height;
+        node.fat_bounds = fat_bounds;
+    }
+
+    /// Walks from the given node to the root, balancing and updating the nodes on the way.
+    fn fix_upwards(&mut self, mut index: usize) {
+        while index != NULL {
+            index = self.balance(index);
+            self.update(index);
+            index = self.nodes[index].parent;
+        }
+    }
+
+    /// Rotates the node, if the heights of its children differ by more than one, and returns the
+    /// node, that took its place.
+    fn balance(&mut self, index: usize) -> usize {
+        let node = &self.nodes[index];
+        if node.is_leaf() || node.height < 2 {
+            return index;
+        }
+        let [first, second] = node.children;
+        let (first, second) = (self.nodes[first].height, self.nodes[second].height);
+        if second > first + 1 {
+            self.rotate(index, 1)
+        } else if first > second + 1 {
+            self.rotate(index, 0)
+        } else {
+            index
+        }
+    }
+
+    /// Lifts the child on the given side of the node in place of the node. The node takes the
+    /// lower child of the lifted one.
+    fn rotate(&mut self, index: usize, side: usize) -> usize {
+        let heavy = self.nodes[index].children[side];
+        let [first, second] = self.nodes[heavy].children;
+        let (taller, shorter) = if self.nodes[first].height > self.nodes[second].height {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let parent = self.nodes[index].parent;
+        self.replace_child(parent, index, heavy);
+        self.nodes[heavy].parent = parent;
+        self.nodes[heavy].children = [index, taller];
+        self.nodes[index].parent = heavy;
+        self.nodes[index].children[side] = shorter;
+        self.nodes[shorter].parent = index;
+        self.update(index);
+        self.update(heavy);
+        heavy
+    }
+
+    /// Passes the objects, whose bounds pass the filter, to the given closure. The filter must
+    /// accept the bounds of a branch, if it accepts the bounds of any object inside of it.
+    pub(crate) fn query<F>(
+        &self,
+        filter: impl Fn(&Rect<f32>) -> bool,
+        mut func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(ProxyId, &I) -> ControlFlow<()>,
+    {
+        let mut stack = Vec::new();
+        if self.root != NULL {
+            stack.push(self.root);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !filter(&node.fat_bounds) {
+                continue;
+            }
+            if let Some(id) = node.id.as_ref() {
+                if filter(&node.bounds) {
+                    func(ProxyId(index), id)?;
+                }
+            } else {
+                stack.extend(node.children);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the handles and the ids of the objects, that intersect the given rectangle, to the
+    /// given closure. The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(ProxyId, &I) -> ControlFlow<()>,
+    {
+        self.query(|bounds| bounds.intersects(rect), func)
+    }
+
+    /// Passes the handles and the ids of the objects, that contain the given point, to the given
+    /// closure. The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn point_query_with<F>(&self, point: Vector2<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(ProxyId, &I) -> ControlFlow<()>,
+    {
+        self.query(|bounds| bounds.contains(point), func)
+    }
+
+    /// Writes the ids of the objects, that intersect the given rectangle, to the output storage.
+    pub fn rect_query<S>(&self, rect: Rect<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.rect_query_with(rect, |_, id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the objects, that contain the given point, to the output storage.
+    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.point_query_with(point, |_, id| storage.push_or_break(id.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DynamicAabbTree;
+    use crate::{test_utils::TestObject, Rect};
+    use nalgebra::Vector2;
+
+    fn check_query(tree: &DynamicAabbTree<usize>, objects: &[TestObject], query: Rect<f32>) {
+        let mut found = Vec::new();
+        tree.rect_query(query, &mut found);
+        found.sort_unstable();
+        let expected = objects
+            .iter()
+            .filter(|object| object.bounds.intersects(query))
+            .map(|object| object.id)
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn aabb_tree_insert_move_remove() {
+        let mut objects = (0..256)
+            .map(|id| TestObject {
+                bounds: Rect::new((id % 16) as f32 * 10.0, (id / 16) as f32 * 10.0, 5.0, 5.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let mut tree = DynamicAabbTree::new(1.0);
+        let proxies = objects
+            .iter()
+            .map(|object| tree.insert(object))
+            .collect::<Vec<_>>();
+        assert_eq!(tree.len(), 256);
+        // Rotations keep the tree close to balanced even for the sorted input.
+        assert!(tree.height() <= 16, "{}", tree.height());
+        check_query(&tree, &objects, Rect::new(12.0, 12.0, 30.0, 20.0));
+
+        // Small movements stay within the fat bounds.
+        let step = Vector2::new(0.1, 0.0);
+        assert!(!tree.move_proxy(proxies[0], objects[0].bounds.translate(step), step));
+        objects[0].bounds = objects[0].bounds.translate(step);
+
+        let mut reinserted = 0;
+        for (object, proxy) in objects.iter_mut().zip(proxies.iter()) {
+            let step = Vector2::new(3.0, -2.0);
+            object.bounds = object.bounds.translate(step);
+            if tree.move_proxy(*proxy, object.bounds, step) {
+                reinserted += 1;
+            }
+        }
+        assert_eq!(reinserted, 256);
+        assert!(tree.height() <= 16, "{}", tree.height());
+        check_query(&tree, &objects, Rect::new(50.0, 0.0, 40.0, 60.0));
+        assert!(tree.fat_bounds(proxies[5]).intersects(objects[5].bounds));
+
+        for proxy in proxies.iter().step_by(2) {
+            assert!(tree.remove(*proxy).is_some());
+        }
+        assert!(tree.remove(proxies[0]).is_none());
+        assert_eq!(tree.len(), 128);
+        objects.retain(|object| object.id % 2 == 1);
+        check_query(&tree, &objects, Rect::new(-100.0, -100.0, 500.0, 500.0));
+
+        let mut found = Vec::new();
+        tree.point_query(objects[3].bounds.center(), &mut found);
+        assert_eq!(found, [objects[3].id]);
+    }
+}
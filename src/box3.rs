@@ -0,0 +1,260 @@
+//! Axis-aligned box, a three-dimensional counterpart of [`crate::Rect`].
+
+use crate::Number;
+use nalgebra::{SimdPartialOrd, Vector3};
+use num_traits::Zero;
+
+/// An axis-aligned box defined by position and size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Box3<T> {
+    /// Position of the box (its corner with the smallest coordinates).
+    pub position: Vector3<T>,
+    /// Size of the box, where X - width, Y - height, Z - depth.
+    pub size: Vector3<T>,
+}
+
+impl<T> Default for Box3<T>
+where
+    T: Number,
+{
+    fn default() -> Self {
+        Self {
+            position: Vector3::new(Zero::zero(), Zero::zero(), Zero::zero()),
+            size: Vector3::new(Zero::zero(), Zero::zero(), Zero::zero()),
+        }
+    }
+}
+
+impl<T> Box3<T>
+where
+    T: Number,
+{
+    /// Creates a new box from X, Y, Z, width, height, depth.
+    #[inline]
+    pub fn new(x: T, y: T, z: T, w: T, h: T, d: T) -> Self {
+        Self {
+            position: Vector3::new(x, y, z),
+            size: Vector3::new(w, h, d),
+        }
+    }
+
+    /// Create a new box from two diagonally opposite corner points.
+    pub fn from_points(p0: Vector3<T>, p1: Vector3<T>) -> Self
+    where
+        T: SimdPartialOrd,
+    {
+        let inf = p0.inf(&p1);
+        let sup = p0.sup(&p1);
+        Self {
+            position: inf,
+            size: sup - inf,
+        }
+    }
+
+    /// Sets the new position of the box.
+    #[inline]
+    pub fn with_position(mut self, position: Vector3<T>) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Sets the new size of the box.
+    #[inline]
+    pub fn with_size(mut self, size: Vector3<T>) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Inflates the box by the given amounts. It offsets the box by `(-dw, -dh, -dd)` and
+    /// increases its size by `(2 * dw, 2 * dh, 2 * dd)`.
+    #[inline]
+    #[must_use = "this method creates new instance of box"]
+    pub fn inflate(&self, dw: T, dh: T, dd: T) -> Self {
+        let delta = Vector3::new(dw, dh, dd);
+        Self {
+            position: self.position - delta,
+            size: self.size + delta + delta,
+        }
+    }
+
+    /// Checks if the given point lies within the bounds of the box.
+    #[inline]
+    pub fn contains(&self, pt: Vector3<T>) -> bool {
+        (0..3).all(|i| pt[i] >= self.position[i] && pt[i] <= self.position[i] + self.size[i])
+    }
+
+    /// Checks if the other box lies entirely within the bounds of the box.
+    #[inline]
+    pub fn contains_box(&self, other: &Box3<T>) -> bool {
+        self.contains(other.min_corner()) && self.contains(other.max_corner())
+    }
+
+    /// Returns center point of the box.
+    #[inline]
+    pub fn center(&self) -> Vector3<T> {
+        let two = T::one() + T::one();
+        self.position + self.size.map(|s| s / two)
+    }
+
+    /// Clips the box by some other box and returns a new box that corresponds to the intersection
+    /// of both boxes. If the boxes do not intersect, the method returns none.
+    #[inline]
+    #[must_use = "this method creates new instance of box"]
+    pub fn clip_by(&self, other: Box3<T>) -> Option<Box3<T>> {
+        let mut min = self.position;
+        let mut max = self.max_corner();
+        let other_max = other.max_corner();
+        for i in 0..3 {
+            if other_max[i] < min[i] || other.position[i] > max[i] {
+                return None;
+            }
+            if min[i] < other.position[i] {
+                min[i] = other.position[i];
+            }
+            if max[i] > other_max[i] {
+                max[i] = other_max[i];
+            }
+        }
+        Some(Self {
+            position: min,
+            size: max - min,
+        })
+    }
+
+    /// Checks if the box intersects with some other box. Boxes, that only touch each other, do
+    /// not intersect.
+    #[inline]
+    pub fn intersects(&self, other: Box3<T>) -> bool {
+        (0..3).all(|i| {
+            other.position[i] < self.position[i] + self.size[i]
+                && self.position[i] < other.position[i] + other.size[i]
+        })
+    }
+
+    /// Checks if the box intersects a sphere represented by a center point and a radius.
+    #[inline]
+    pub fn intersects_sphere(&self, center: Vector3<T>, radius: T) -> bool {
+        let mut distance_squared = T::zero();
+        for i in 0..3 {
+            let max = self.position[i] + self.size[i];
+            let closest = if center[i] < self.position[i] {
+                self.position[i]
+            } else if center[i] > max {
+                max
+            } else {
+                center[i]
+            };
+            let distance = center[i] - closest;
+            distance_squared += distance * distance;
+        }
+        distance_squared < radius * radius
+    }
+
+    /// Offsets the given box and returns a new box.
+    #[inline]
+    #[must_use = "this method creates new instance of box"]
+    pub fn translate(&self, translation: Vector3<T>) -> Self {
+        Self {
+            position: self.position + translation,
+            size: self.size,
+        }
+    }
+
+    /// Extends the box so it will contain the other box.
+    #[inline]
+    pub fn extend_to_contain(&mut self, other: Box3<T>)
+    where
+        T: SimdPartialOrd,
+    {
+        let min = self.position.inf(&other.position);
+        let max = self.max_corner().sup(&other.max_corner());
+        *self = Self::from_points(min, max);
+    }
+
+    /// Returns the corner of the box with the smallest coordinates.
+    #[inline(always)]
+    pub fn min_corner(&self) -> Vector3<T> {
+        self.position
+    }
+
+    /// Returns the corner of the box with the largest coordinates.
+    #[inline(always)]
+    pub fn max_corner(&self) -> Vector3<T> {
+        self.position + self.size
+    }
+
+    /// Returns width of the box.
+    #[inline(always)]
+    pub fn w(&self) -> T {
+        self.size.x
+    }
+
+    /// Returns height of the box.
+    #[inline(always)]
+    pub fn h(&self) -> T {
+        self.size.y
+    }
+
+    /// Returns depth of the box.
+    #[inline(always)]
+    pub fn d(&self) -> T {
+        self.size.z
+    }
+
+    /// Returns horizontal position of the box.
+    #[inline(always)]
+    pub fn x(&self) -> T {
+        self.position.x
+    }
+
+    /// Returns vertical position of the box.
+    #[inline(always)]
+    pub fn y(&self) -> T {
+        self.position.y
+    }
+
+    /// Returns position of the box along the depth axis.
+    #[inline(always)]
+    pub fn z(&self) -> T {
+        self.position.z
+    }
+
+    /// Returns the volume of the box.
+    #[inline]
+    pub fn volume(&self) -> T {
+        self.size.x * self.size.y * self.size.z
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Box3;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn box3_operations() {
+        let a = Box3::new(0.0, 0.0, 0.0, 4.0, 4.0, 4.0);
+        let b = Box3::new(2.0, 3.0, -1.0, 4.0, 4.0, 2.0);
+        assert!(a.intersects(b));
+        assert!(!a.intersects(Box3::new(4.0, 0.0, 0.0, 1.0, 1.0, 1.0)));
+        assert_eq!(a.clip_by(b), Some(Box3::new(2.0, 3.0, 0.0, 2.0, 1.0, 1.0)));
+        assert_eq!(a.clip_by(Box3::new(5.0, 0.0, 0.0, 1.0, 1.0, 1.0)), None);
+        assert!(a.contains(Vector3::new(4.0, 0.0, 2.0)));
+        assert_eq!(a.center(), Vector3::new(2.0, 2.0, 2.0));
+        assert!(a.intersects_sphere(Vector3::new(5.0, 2.0, 2.0), 1.5));
+
+        let mut c = a;
+        c.extend_to_contain(b);
+        assert_eq!(c, Box3::new(0.0, 0.0, -1.0, 6.0, 7.0, 5.0));
+        assert!(c.contains_box(&a) && c.contains_box(&b));
+        assert_eq!(c.volume(), 210.0);
+    }
+}
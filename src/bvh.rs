@@ -0,0 +1,398 @@
+//! Bounding volume hierarchy (BVH) is a binary tree of bounding rectangles, that is used for fast
+//! spatial queries over moving or deforming objects.
+
+use crate::{
+    geometry::union,
+    quadtree::{BoundsProvider, QueryStorage},
+    Rect,
+};
+use nalgebra::Vector2;
+use std::{cmp::Ordering, ops::ControlFlow};
+
+/// Maximum amount of entities in a leaf node.
+const LEAF_SIZE: usize = 4;
+
+/// A method of splitting the entities of a node between its two children.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SplitMethod {
+    /// Surface area heuristic (perimeter in 2D): the split, that minimizes the expected cost of
+    /// the queries, is chosen. It builds slower, but gives faster queries.
+    #[default]
+    Sah,
+    /// The entities are split in two equal halves along the longer axis of the bounds of their
+    /// centers. It builds faster and always gives a balanced tree.
+    Median,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct BvhNode {
+    bounds: Rect<f32>,
+    /// Index of the first entity of a leaf, or index of the right child of a branch. The left
+    /// child of a branch always follows the branch itself.
+    first: u32,
+    /// Amount of entities in a leaf, zero for branches.
+    count: u32,
+}
+
+fn to_index(value: usize) -> u32 {
+    u32::try_from(value).expect("bvh cannot hold more than u32::MAX nodes or entities")
+}
+
+fn half_perimeter(rect: &Rect<f32>) -> f32 {
+    rect.w() + rect.h()
+}
+
+fn bounds_of(bounds: &[Rect<f32>], slots: &[usize]) -> Rect<f32> {
+    slots
+        .iter()
+        .map(|slot| bounds[*slot])
+        .reduce(|a, b| union(&a, &b))
+        .unwrap_or_default()
+}
+
+fn compare_centers(bounds: &[Rect<f32>], axis: usize) -> impl Fn(&usize, &usize) -> Ordering + '_ {
+    move |a, b| {
+        bounds[*a].center()[axis]
+            .partial_cmp(&bounds[*b].center()[axis])
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Sorts the slots along the best axis and returns the amount of slots, that go to the left
+/// child.
+fn sah_split(bounds: &[Rect<f32>], slots: &mut [usize]) -> usize {
+    let mut best: Option<(f32, usize, usize)> = None;
+    let mut right = vec![0.0; slots.len()];
+    for axis in 0..2 {
+        slots.sort_by(compare_centers(bounds, axis));
+        let mut acc = bounds[slots[slots.len() - 1]];
+        for i in (1..slots.len()).rev() {
+            acc = union(&acc, &bounds[slots[i]]);
+            right[i] = half_perimeter(&acc);
+        }
+        let mut acc = bounds[slots[0]];
+        for i in 1..slots.len() {
+            let cost = half_perimeter(&acc) * i as f32 + right[i] * (slots.len() - i) as f32;
+            if best.map_or(true, |(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, i));
+            }
+            acc = union(&acc, &bounds[slots[i]]);
+        }
+    }
+    let (_, axis, split) = best.expect("a split node has at least two entities");
+    slots.sort_by(compare_centers(bounds, axis));
+    split
+}
+
+/// Partitions the slots in two halves along the longer axis of the bounds of their centers and
+/// returns the amount of slots, that go to the left child.
+fn median_split(bounds: &[Rect<f32>], slots: &mut [usize]) -> usize {
+    let mut min = Vector2::repeat(f32::INFINITY);
+    let mut max = Vector2::repeat(f32::NEG_INFINITY);
+    for slot in slots.iter() {
+        let center = bounds[*slot].center();
+        min = min.inf(&center);
+        max = max.sup(&center);
+    }
+    let extent = max - min;
+    let axis = if extent.x >= extent.y { 0 } else { 1 };
+    let middle = slots.len() / 2;
+    slots.select_nth_unstable_by(middle, compare_centers(bounds, axis));
+    middle
+}
+
+/// Returns `true` if the centers of all the given rectangles are the same, no split could separate
+/// such rectangles.
+fn same_centers(bounds: &[Rect<f32>], slots: &[usize]) -> bool {
+    let first = bounds[slots[0]].center();
+    slots.iter().all(|slot| bounds[*slot].center() == first)
+}
+
+/// Binary bounding volume hierarchy over rectangles. Every entity is stored exactly once, in a
+/// leaf node, and the bounds of the nodes may overlap.
+///
+/// Unlike other trees, the bounds of the entities could be changed after the hierarchy was built
+/// (see [`Self::set_bounds`]), and then the bounds of the nodes are recomputed with
+/// [`Self::refit`], which is much cheaper than building a new tree. The quality of the tree
+/// degrades if the entities move far from their initial places, rebuild it from time to time in
+/// this case.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bvh<I> {
+    nodes: Vec<BvhNode>,
+    ids: Vec<I>,
+    /// Bounds of the entities, in the order of the leaves.
+    entity_bounds: Vec<Rect<f32>>,
+    /// Positions of the entities in the order of the leaves, indexed by the indices of the
+    /// entities (in the order they were given to [`Self::new`]).
+    slots: Vec<u32>,
+}
+
+impl<I> Default for Bvh<I> {
+    fn default() -> Self {
+        Self {
+            nodes: Default::default(),
+            ids: Default::default(),
+            entity_bounds: Default::default(),
+            slots: Default::default(),
+        }
+    }
+}
+
+impl<I> Bvh<I> {
+    /// Builds the hierarchy over the given objects using the given split method. Entities are
+    /// identified by their indices in the sequence of objects, see [`Self::set_bounds`].
+    pub fn new<T>(objects: impl Iterator<Item = T>, split: SplitMethod) -> Self
+    where
+        T: BoundsProvider<Id = I>,
+    {
+        let mut ids = Vec::new();
+        let mut bounds = Vec::new();
+        for object in objects {
+            bounds.push(object.bounds());
+            ids.push(Some(object.id()));
+        }
+        if ids.is_empty() {
+            return Self::default();
+        }
+
+        let mut order = (0..ids.len()).collect::<Vec<_>>();
+        let mut nodes = Vec::with_capacity(2 * ids.len() / LEAF_SIZE + 1);
+        build_node(&mut nodes, &bounds, &mut order, 0, split);
+
+        let mut slots = vec![0; ids.len()];
+        for (slot, index) in order.iter().enumerate() {
+            slots[*index] = to_index(slot);
+        }
+        Self {
+            nodes,
+            entity_bounds: order.iter().map(|index| bounds[*index]).collect(),
+            ids: order
+                .iter()
+                .map(|index| ids[*index].take().expect("every entity is used once"))
+                .collect(),
+            slots,
+        }
+    }
+
+    /// Returns the amount of entities in the hierarchy.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns `true` if the hierarchy has no entities.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the bounds of the root node, or None if the hierarchy is empty.
+    pub fn bounds(&self) -> Option<Rect<f32>> {
+        self.nodes.first().map(|node| node.bounds)
+    }
+
+    /// Returns the bounds of the entity with the given index (the index of the object, that was
+    /// given to [`Self::new`]).
+    pub fn entity_bounds(&self, index: usize) -> Rect<f32> {
+        self.entity_bounds[self.slots[index] as usize]
+    }
+
+    /// Changes the bounds of the entity with the given index (the index of the object, that was
+    /// given to [`Self::new`]). The bounds of the nodes are not updated, call [`Self::refit`]
+    /// after all the entities were updated.
+    pub fn set_bounds(&mut self, index: usize, bounds: Rect<f32>) {
+        self.entity_bounds[self.slots[index] as usize] = bounds;
+    }
+
+    /// Recomputes the bounds of every node bottom-up from the bounds of the entities. It takes
+    /// linear time and keeps the structure of the tree.
+    pub fn refit(&mut self) {
+        // Children always follow their parents, so the reverse order visits them first.
+        for index in (0..self.nodes.len()).rev() {
+            let node = self.nodes[index];
+            self.nodes[index].bounds = if node.count > 0 {
+                let range = node.first as usize..(node.first + node.count) as usize;
+                self.entity_bounds[range]
+                    .iter()
+                    .copied()
+                    .reduce(|a, b| union(&a, &b))
+                    .unwrap_or_default()
+            } else {
+                union(
+                    &self.nodes[index + 1].bounds,
+                    &self.nodes[node.first as usize].bounds,
+                )
+            };
+        }
+    }
+
+    fn query<F>(&self, filter: impl Fn(&Rect<f32>) -> bool, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !filter(&node.bounds) {
+                continue;
+            }
+            if node.count > 0 {
+                for slot in node.first as usize..(node.first + node.count) as usize {
+                    if filter(&self.entity_bounds[slot]) {
+                        func(&self.ids[slot])?;
+                    }
+                }
+            } else {
+                stack.push(node.first as usize);
+                stack.push(index + 1);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the ids of the entities, that intersect the given rectangle, to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.query(|bounds| bounds.intersects(rect), func)
+    }
+
+    /// Passes the ids of the entities, that contain the given point, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn point_query_with<F>(&self, point: Vector2<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.query(|bounds| bounds.contains(point), func)
+    }
+
+    /// Writes the ids of the entities, that intersect the given rectangle, to the output storage.
+    pub fn rect_query<S>(&self, rect: Rect<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.rect_query_with(rect, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the entities, that contain the given point, to the output storage.
+    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.point_query_with(point, |id| storage.push_or_break(id.clone()));
+    }
+}
+
+fn build_node(
+    nodes: &mut Vec<BvhNode>,
+    bounds: &[Rect<f32>],
+    slots: &mut [usize],
+    first: usize,
+    split: SplitMethod,
+) -> usize {
+    let index = nodes.len();
+    nodes.push(BvhNode {
+        bounds: bounds_of(bounds, slots),
+        first: to_index(first),
+        count: to_index(slots.len()),
+    });
+    if slots.len() <= LEAF_SIZE {
+        return index;
+    }
+    // Rectangles with the same centers could not be separated by the heuristic, the median split
+    // keeps the tree balanced in this case.
+    let middle = if split == SplitMethod::Sah && !same_centers(bounds, slots) {
+        sah_split(bounds, slots)
+    } else {
+        median_split(bounds, slots)
+    };
+    let (left, right) = slots.split_at_mut(middle);
+    build_node(nodes, bounds, left, first, split);
+    let right = build_node(nodes, bounds, right, first + middle, split);
+    nodes[index].first = to_index(right);
+    nodes[index].count = 0;
+    index
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bvh, SplitMethod};
+    use crate::{test_utils::TestObject, Rect};
+    use nalgebra::Vector2;
+
+    fn check_query(bvh: &Bvh<usize>, objects: &[TestObject], query: Rect<f32>) {
+        let mut found = Vec::new();
+        bvh.rect_query(query, &mut found);
+        found.sort_unstable();
+        let expected = objects
+            .iter()
+            .filter(|object| object.bounds.intersects(query))
+            .map(|object| object.id)
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn bvh_query_and_refit() {
+        let mut objects = (0..300)
+            .map(|id| TestObject {
+                bounds: Rect::new(
+                    (id * 37 % 100) as f32 * 10.0,
+                    (id * 61 % 100) as f32 * 10.0,
+                    5.0 + (id % 7) as f32,
+                    5.0 + (id % 5) as f32,
+                ),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let query = Rect::new(200.0, 300.0, 150.0, 250.0);
+        for split in [SplitMethod::Sah, SplitMethod::Median] {
+            let mut bvh = Bvh::new(objects.iter(), split);
+            assert_eq!(bvh.len(), 300);
+            check_query(&bvh, &objects, query);
+
+            // Move every entity and refit the tree instead of rebuilding it.
+            for object in objects.iter_mut() {
+                object.bounds = object.bounds.translate(Vector2::new(13.0, -7.0));
+                bvh.set_bounds(object.id, object.bounds);
+            }
+            bvh.refit();
+            check_query(&bvh, &objects, query);
+            assert_eq!(bvh.entity_bounds(5), objects[5].bounds);
+
+            let mut found = Vec::new();
+            bvh.point_query(objects[42].bounds.center(), &mut found);
+            assert!(found.contains(&42));
+            for object in objects.iter_mut() {
+                object.bounds = object.bounds.translate(Vector2::new(-13.0, 7.0));
+            }
+        }
+    }
+
+    #[test]
+    fn bvh_degenerate() {
+        let objects = (0..1000)
+            .map(|id| TestObject {
+                bounds: Rect::new(0.0, 0.0, 1.0, 1.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let bvh = Bvh::new(objects.iter(), SplitMethod::Sah);
+        let mut found = Vec::new();
+        bvh.point_query(Vector2::new(0.5, 0.5), &mut found);
+        assert_eq!(found.len(), 1000);
+        assert!(
+            Bvh::<usize>::new(std::iter::empty::<&TestObject>(), SplitMethod::Median)
+                .bounds()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Clipping of segments and polylines by rectangles.
+
+use crate::{Number, Rect};
+use nalgebra::{RealField, Vector2};
+
+/// A sequence of points, connected by segments.
+pub type Polyline<T> = Vec<Vector2<T>>;
+
+/// Returns the range of parameters of the segment `a + (b - a) * t`, that lies within the
+/// rectangle, using Liang-Barsky clipping. The border of the rectangle is considered as inside.
+fn clip_params<T>(a: Vector2<T>, b: Vector2<T>, rect: &Rect<T>) -> Option<(T, T)>
+where
+    T: Number + RealField,
+{
+    let direction = b - a;
+    let (mut enter, mut exit) = (T::zero(), T::one());
+    for (p, q) in [
+        (-direction.x, a.x - rect.x()),
+        (direction.x, rect.x() + rect.w() - a.x),
+        (-direction.y, a.y - rect.y()),
+        (direction.y, rect.y() + rect.h() - a.y),
+    ] {
+        if p == T::zero() {
+            if q < T::zero() {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < T::zero() {
+                enter = enter.max(t);
+            } else {
+                exit = exit.min(t);
+            }
+        }
+    }
+    (enter <= exit).then_some((enter, exit))
+}
+
+/// Clips the segment by the rectangle and returns the part of the segment, that lies within the
+/// rectangle (the border included), if any.
+#[inline]
+pub fn clip_segment_to_rect<T>(
+    a: Vector2<T>,
+    b: Vector2<T>,
+    rect: Rect<T>,
+) -> Option<(Vector2<T>, Vector2<T>)>
+where
+    T: Number + RealField,
+{
+    let (enter, exit) = clip_params(a, b, &rect)?;
+    let direction = b - a;
+    Some((a + direction * enter, a + direction * exit))
+}
+
+/// Splits the polyline into the pieces, that lie within the rectangle (the border included). Each
+/// piece keeps the connectivity of the source polyline: consecutive segments, that stay inside of
+/// the rectangle, end up in the same piece, and a new piece starts every time the polyline enters
+/// the rectangle again.
+pub fn clip_polyline_to_rect<T>(points: &[Vector2<T>], rect: Rect<T>) -> Vec<Polyline<T>>
+where
+    T: Number + RealField,
+{
+    let mut pieces = Vec::new();
+    let mut current: Polyline<T> = Vec::new();
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let Some((enter, exit)) = clip_params(a, b, &rect) else {
+            continue;
+        };
+        let direction = b - a;
+        let start = a + direction * enter;
+        let end = a + direction * exit;
+        if enter != T::zero() || current.last() != Some(&start) {
+            if current.len() > 1 {
+                pieces.push(std::mem::take(&mut current));
+            }
+            current.clear();
+            current.push(start);
+        }
+        if current.last() != Some(&end) {
+            current.push(end);
+        }
+        if exit != T::one() {
+            let piece = std::mem::take(&mut current);
+            if piece.len() > 1 {
+                pieces.push(piece);
+            }
+        }
+    }
+    if current.len() > 1 {
+        pieces.push(current);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clip_polyline_to_rect, clip_segment_to_rect};
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn clip_polyline() {
+        let rect = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(
+            clip_segment_to_rect(Vector2::new(-5.0, 5.0), Vector2::new(15.0, 5.0), rect),
+            Some((Vector2::new(0.0, 5.0), Vector2::new(10.0, 5.0)))
+        );
+        assert_eq!(
+            clip_segment_to_rect(Vector2::new(-5.0, 5.0), Vector2::new(5.0, 15.0), rect),
+            Some((Vector2::new(0.0, 10.0), Vector2::new(0.0, 10.0)))
+        );
+        assert_eq!(
+            clip_segment_to_rect(Vector2::new(-5.0, 5.0), Vector2::new(-1.0, 15.0), rect),
+            None
+        );
+
+        // Goes in, wanders inside, leaves, comes back and stops inside.
+        let track = [
+            Vector2::new(-5.0, 5.0),
+            Vector2::new(5.0, 5.0),
+            Vector2::new(5.0, 2.0),
+            Vector2::new(5.0, -5.0),
+            Vector2::new(8.0, -5.0),
+            Vector2::new(8.0, 5.0),
+        ];
+        assert_eq!(
+            clip_polyline_to_rect(&track, rect),
+            vec![
+                vec![
+                    Vector2::new(0.0, 5.0),
+                    Vector2::new(5.0, 5.0),
+                    Vector2::new(5.0, 2.0),
+                    Vector2::new(5.0, 0.0),
+                ],
+                vec![Vector2::new(8.0, 0.0), Vector2::new(8.0, 5.0)],
+            ]
+        );
+        assert!(clip_polyline_to_rect(&track[3..5], rect).is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+//! Grouping of rectangles, that lie close to each other.
+
+use crate::{aabb_tree::DynamicAabbTree, geometry::IndexedRect, Rect};
+use std::ops::ControlFlow;
+
+/// Disjoint set of indices with path compression.
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        // The smaller index becomes the root, so the groups could be ordered by their roots.
+        if a < b {
+            self.parents[b] = a;
+        } else {
+            self.parents[a] = b;
+        }
+    }
+}
+
+/// Returns the distance between the closest points of the rectangles, zero if they overlap.
+fn distance(a: &Rect<f32>, b: &Rect<f32>) -> f32 {
+    let dx = (b.x() - (a.x() + a.w()))
+        .max(a.x() - (b.x() + b.w()))
+        .max(0.0);
+    let dy = (b.y() - (a.y() + a.h()))
+        .max(a.y() - (b.y() + b.h()))
+        .max(0.0);
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// Groups the rectangles, so that every rectangle is in the same group with all the rectangles,
+/// that are not farther than `max_gap` from it, directly or through the other rectangles of the
+/// group. Returns the indices of the rectangles of each group. The indices in each group are
+/// sorted and the groups are sorted by their first index.
+pub fn cluster_rects(rects: &[Rect<f32>], max_gap: f32) -> Vec<Vec<usize>> {
+    let mut sets = UnionFind::new(rects.len());
+    // Each rectangle is checked only against the already processed rectangles, that are close
+    // enough to it. The distance to the bounds of a branch of the tree is not larger than the
+    // distance to any rectangle inside of it, so the distance filter prunes the tree too.
+    let mut tree = DynamicAabbTree::new(0.0);
+    for (index, rect) in rects.iter().enumerate() {
+        let _ = tree.query(
+            |bounds| distance(rect, bounds) <= max_gap,
+            |_, &other| {
+                sets.union(index, other);
+                ControlFlow::Continue(())
+            },
+        );
+        tree.insert(IndexedRect(index, *rect));
+    }
+
+    let mut groups = Vec::<Vec<usize>>::new();
+    let mut group_of_root = vec![usize::MAX; rects.len()];
+    for index in 0..rects.len() {
+        let root = sets.find(index);
+        if group_of_root[root] == usize::MAX {
+            group_of_root[root] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[group_of_root[root]].push(index);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod test {
+    use super::cluster_rects;
+    use crate::Rect;
+
+    #[test]
+    fn cluster_words() {
+        let rects = [
+            Rect::new(0.0, 0.0, 10.0, 10.0),
+            Rect::new(100.0, 0.0, 10.0, 10.0),
+            Rect::new(12.0, 0.0, 10.0, 10.0),
+            Rect::new(24.0, 5.0, 10.0, 10.0),
+            Rect::new(100.0, 50.0, 10.0, 10.0),
+            Rect::new(36.0, 17.0, 10.0, 10.0),
+        ];
+        assert_eq!(
+            cluster_rects(&rects, 2.0),
+            vec![vec![0, 2, 3], vec![1], vec![4], vec![5]]
+        );
+        assert_eq!(
+            cluster_rects(&rects, 3.0),
+            vec![vec![0, 2, 3, 5], vec![1], vec![4]]
+        );
+        assert_eq!(cluster_rects(&rects, 40.0).len(), 2);
+
+        let piled = vec![Rect::new(0.0, 0.0, 0.0, 0.0); 100];
+        assert_eq!(
+            cluster_rects(&piled, 0.0),
+            vec![(0..100).collect::<Vec<_>>()]
+        );
+    }
+}
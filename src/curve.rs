@@ -0,0 +1,311 @@
+//! Space-filling curves (Morton and Hilbert), that map two-dimensional points to one-dimensional
+//! codes while keeping close points close. Sorting by these codes gives cache-friendly orders and
+//! allows to answer rectangle queries with ranges of codes.
+
+use crate::{OptionRect, Rect};
+use nalgebra::Vector2;
+use std::ops::RangeInclusive;
+
+/// Amount of bits per axis of the points on the curves.
+const ORDER: u32 = 16;
+
+/// Quantizes the point to 16 bits per axis within the given bounds. Points outside of the bounds
+/// are clamped to them.
+pub fn quantize(bounds: &Rect<f32>, point: Vector2<f32>) -> Vector2<u16> {
+    fn quantize(value: f32, min: f32, size: f32) -> u16 {
+        let normalized = if size > 0.0 {
+            (value - min) / size
+        } else {
+            0.0
+        };
+        (normalized.clamp(0.0, 1.0) * u16::MAX as f32) as u16
+    }
+
+    Vector2::new(
+        quantize(point.x, bounds.x(), bounds.w()),
+        quantize(point.y, bounds.y(), bounds.h()),
+    )
+}
+
+fn spread_bits(mut value: u32) -> u32 {
+    value &= 0x0000_ffff;
+    value = (value | (value << 8)) & 0x00ff_00ff;
+    value = (value | (value << 4)) & 0x0f0f_0f0f;
+    value = (value | (value << 2)) & 0x3333_3333;
+    (value | (value << 1)) & 0x5555_5555
+}
+
+fn compact_bits(mut value: u32) -> u32 {
+    value &= 0x5555_5555;
+    value = (value | (value >> 1)) & 0x3333_3333;
+    value = (value | (value >> 2)) & 0x0f0f_0f0f;
+    value = (value | (value >> 4)) & 0x00ff_00ff;
+    (value | (value >> 8)) & 0x0000_ffff
+}
+
+/// Computes the Morton (Z-order) code of the point by interleaving the bits of its coordinates,
+/// the bits of x go to the even positions.
+pub fn morton_encode(point: Vector2<u16>) -> u32 {
+    spread_bits(point.x as u32) | (spread_bits(point.y as u32) << 1)
+}
+
+/// Restores the point from its Morton code.
+pub fn morton_decode(code: u32) -> Vector2<u16> {
+    Vector2::new(compact_bits(code) as u16, compact_bits(code >> 1) as u16)
+}
+
+/// Reflects and swaps the coordinates to move into the frame of a Hilbert sub-curve.
+fn hilbert_rotate(size: u32, x: &mut u32, y: &mut u32, rx: u32, ry: u32) {
+    if ry == 0 {
+        if rx == 1 {
+            *x = size - 1 - *x;
+            *y = size - 1 - *y;
+        }
+        std::mem::swap(x, y);
+    }
+}
+
+/// Computes the index of the point along the Hilbert curve, that fills the 65536x65536 grid.
+/// Unlike Morton codes, the points with successive Hilbert codes are always adjacent.
+pub fn hilbert_encode(point: Vector2<u16>) -> u32 {
+    let (mut x, mut y) = (point.x as u32, point.y as u32);
+    let mut code = 0;
+    let mut s = 1 << (ORDER - 1);
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        code += s * s * ((3 * rx) ^ ry);
+        hilbert_rotate(1 << ORDER, &mut x, &mut y, rx, ry);
+        s /= 2;
+    }
+    code
+}
+
+/// Restores the point from its Hilbert code.
+pub fn hilbert_decode(code: u32) -> Vector2<u16> {
+    let (mut x, mut y) = (0, 0);
+    let mut t = code;
+    for level in 0..ORDER {
+        let s = 1 << level;
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        hilbert_rotate(s, &mut x, &mut y, rx, ry);
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+    }
+    Vector2::new(x as u16, y as u16)
+}
+
+/// Computes the Morton code of the point, quantized within the given bounds (see [`quantize`]).
+/// Pass the center of a rectangle to get the code of the rectangle.
+pub fn morton_code(bounds: &Rect<f32>, point: Vector2<f32>) -> u32 {
+    morton_encode(quantize(bounds, point))
+}
+
+/// Computes the Hilbert code of the point, quantized within the given bounds (see [`quantize`]).
+/// Pass the center of a rectangle to get the code of the rectangle.
+pub fn hilbert_code(bounds: &Rect<f32>, point: Vector2<f32>) -> u32 {
+    hilbert_encode(quantize(bounds, point))
+}
+
+/// Sorts the items along the Hilbert curve by the centers of their bounds, which are quantized
+/// within the bounds of all the centers. Close items end up close to each other in the slice,
+/// which improves the cache behavior of the operations, that process the items in batches, and
+/// the quality of trees, that are bulk-loaded from them.
+pub fn sort_by_hilbert_key<T, F>(items: &mut [T], bounds_of: F)
+where
+    F: Fn(&T) -> Rect<f32>,
+{
+    let mut centers = OptionRect::default();
+    for item in items.iter() {
+        centers.push(bounds_of(item).center());
+    }
+    let Some(centers) = *centers else {
+        return;
+    };
+    items.sort_by_cached_key(|item| hilbert_code(&centers, bounds_of(item).center()));
+}
+
+/// Sorts the rectangles along the Hilbert curve by their centers. See [`sort_by_hilbert_key`] for
+/// details.
+pub fn sort_by_hilbert(rects: &mut [Rect<i32>]) {
+    sort_by_hilbert_key(rects, |rect| {
+        Rect::new(
+            rect.x() as f32,
+            rect.y() as f32,
+            rect.w() as f32,
+            rect.h() as f32,
+        )
+    });
+}
+
+/// Splits the aligned square cells of the grid, that intersect the query rectangle, until they
+/// lie entirely inside of it or reach the minimum level, and writes the ranges of the codes of
+/// such cells.
+fn decompose(
+    origin: Vector2<u32>,
+    level: u32,
+    min_level: u32,
+    min: Vector2<u32>,
+    max: Vector2<u32>,
+    encode: fn(Vector2<u16>) -> u32,
+    ranges: &mut Vec<RangeInclusive<u32>>,
+) {
+    let last = origin.add_scalar((1 << level) - 1);
+    if origin.x > max.x || origin.y > max.y || last.x < min.x || last.y < min.y {
+        return;
+    }
+    if level <= min_level
+        || (origin.x >= min.x && origin.y >= min.y && last.x <= max.x && last.y <= max.y)
+    {
+        // Both curves visit all the points of an aligned cell before leaving it, so the codes of
+        // the cell form a contiguous range.
+        let cells = 1u64 << (2 * level);
+        let code = encode(Vector2::new(origin.x as u16, origin.y as u16)) as u64;
+        let start = code / cells * cells;
+        ranges.push(start as u32..=(start + cells - 1) as u32);
+        return;
+    }
+    let half = 1 << (level - 1);
+    for offset in [
+        Vector2::new(0, 0),
+        Vector2::new(half, 0),
+        Vector2::new(0, half),
+        Vector2::new(half, half),
+    ] {
+        decompose(
+            origin + offset,
+            level - 1,
+            min_level,
+            min,
+            max,
+            encode,
+            ranges,
+        );
+    }
+}
+
+fn curve_ranges(
+    min: Vector2<u16>,
+    max: Vector2<u16>,
+    min_level: u32,
+    encode: fn(Vector2<u16>) -> u32,
+) -> Vec<RangeInclusive<u32>> {
+    let mut ranges = Vec::new();
+    decompose(
+        Vector2::new(0, 0),
+        ORDER,
+        min_level,
+        min.cast::<u32>(),
+        max.cast::<u32>(),
+        encode,
+        &mut ranges,
+    );
+    ranges.sort_unstable_by_key(|range| *range.start());
+    let mut merged: Vec<RangeInclusive<u32>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if *last.end() as u64 + 1 == *range.start() as u64 => {
+                *last = *last.start()..=*range.end();
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Returns the sorted ranges of the Morton codes of the points, that lie in the rectangle from
+/// `min` to `max` (both inclusive). A point lies in the rectangle if and only if its code is in
+/// one of the ranges, which allows to answer rectangle queries over the data sorted by the codes.
+pub fn morton_ranges(min: Vector2<u16>, max: Vector2<u16>) -> Vec<RangeInclusive<u32>> {
+    curve_ranges(min, max, 0, morton_encode)
+}
+
+/// Same as [`morton_ranges`], but the cells are not split below the given level (cells of
+/// `2^min_level` points wide), the cells of this level, that intersect the rectangle, are included
+/// entirely. The ranges cover some points outside of the rectangle, but there are much fewer of
+/// them.
+pub(crate) fn coarse_morton_ranges(
+    min: Vector2<u16>,
+    max: Vector2<u16>,
+    min_level: u32,
+) -> Vec<RangeInclusive<u32>> {
+    curve_ranges(min, max, min_level, morton_encode)
+}
+
+/// Returns the sorted ranges of the Hilbert codes of the points, that lie in the rectangle from
+/// `min` to `max` (both inclusive). Hilbert curves usually need fewer ranges than Morton curves
+/// for the same rectangle.
+pub fn hilbert_ranges(min: Vector2<u16>, max: Vector2<u16>) -> Vec<RangeInclusive<u32>> {
+    curve_ranges(min, max, 0, hilbert_encode)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        hilbert_decode, hilbert_encode, hilbert_ranges, morton_decode, morton_encode,
+        morton_ranges, sort_by_hilbert,
+    };
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn curve_round_trip() {
+        for point in [
+            Vector2::new(0, 0),
+            Vector2::new(1, 0),
+            Vector2::new(12345, 54321),
+            Vector2::new(u16::MAX, u16::MAX),
+        ] {
+            assert_eq!(morton_decode(morton_encode(point)), point);
+            assert_eq!(hilbert_decode(hilbert_encode(point)), point);
+        }
+        assert_eq!(morton_encode(Vector2::new(u16::MAX, 0)), 0x5555_5555);
+        for code in 0..1000 {
+            let a = hilbert_decode(code).cast::<i32>();
+            let b = hilbert_decode(code + 1).cast::<i32>();
+            assert_eq!((a - b).abs().sum(), 1);
+        }
+    }
+
+    #[test]
+    fn curve_ranges_cover_rect() {
+        let (min, max) = (Vector2::new(3, 5), Vector2::new(20, 11));
+        for (ranges, encode) in [
+            (morton_ranges(min, max), morton_encode as fn(_) -> u32),
+            (hilbert_ranges(min, max), hilbert_encode),
+        ] {
+            let total = ranges
+                .iter()
+                .map(|range| (range.end() - range.start() + 1) as usize)
+                .sum::<usize>();
+            assert_eq!(total, 18 * 7);
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    let code = encode(Vector2::new(x, y));
+                    assert!(ranges.iter().any(|range| range.contains(&code)));
+                }
+            }
+        }
+        assert_eq!(
+            hilbert_ranges(Vector2::new(0, 0), Vector2::new(u16::MAX, u16::MAX)),
+            [0..=u32::MAX]
+        );
+    }
+
+    #[test]
+    fn hilbert_sort() {
+        // A 4x4 grid of cells, visited cell by cell along the curve.
+        let mut rects = (0..16)
+            .map(|i| Rect::new((i % 4) * 10, (i / 4) * 10, 10, 10))
+            .rev()
+            .collect::<Vec<_>>();
+        sort_by_hilbert(&mut rects);
+        for pair in rects.windows(2) {
+            let offset = pair[1].position - pair[0].position;
+            assert_eq!(offset.abs().sum(), 10);
+        }
+        assert_eq!(rects[0].position, Vector2::new(0, 0));
+    }
+}
@@ -0,0 +1,499 @@
+//! Decomposition of shapes into sets of non-overlapping rectangles.
+
+use crate::{Number, Rect};
+use nalgebra::Vector2;
+use std::cmp::Ordering;
+
+pub(crate) fn sort_and_dedup<T: Number>(values: &mut Vec<T>) {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    values.dedup();
+}
+
+/// Decomposes the rectilinear polygon (all the edges of which are horizontal or vertical) into
+/// non-overlapping rectangles. The polygon is given by its rings: the outer boundary and the
+/// boundaries of the holes, in any order and with any orientation, the inside is defined by the
+/// even-odd rule. The edges, that are neither horizontal nor vertical, are ignored.
+///
+/// The polygon is swept from the top to the bottom: the horizontal band between each pair of
+/// consecutive vertex rows is split into rectangles, and the rectangles of the neighbouring bands,
+/// that have the same horizontal extent, are merged. The result is not always the smallest
+/// possible decomposition, but it is close for typical outlines of tile maps.
+pub fn decompose_rectilinear<T>(rings: &[Vec<Vector2<T>>]) -> Vec<Rect<T>>
+where
+    T: Number,
+{
+    // Vertical edges as (x, top, bottom).
+    let mut edges = Vec::new();
+    let mut rows = Vec::new();
+    for ring in rings {
+        for (i, a) in ring.iter().enumerate() {
+            let b = ring[(i + 1) % ring.len()];
+            rows.push(a.y);
+            if a.x == b.x && a.y != b.y {
+                let (top, bottom) = if a.y < b.y { (a.y, b.y) } else { (b.y, a.y) };
+                edges.push((a.x, top, bottom));
+            }
+        }
+    }
+    sort_and_dedup(&mut rows);
+
+    let mut rects = Vec::new();
+    let mut open = Vec::<Rect<T>>::new();
+    let mut crossings = Vec::new();
+    for band in rows.windows(2) {
+        let (top, bottom) = (band[0], band[1]);
+        crossings.clear();
+        crossings.extend(
+            edges
+                .iter()
+                .filter(|(_, edge_top, edge_bottom)| *edge_top <= top && *edge_bottom >= bottom)
+                .map(|(x, _, _)| *x),
+        );
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        // Pairs of crossings bound the inside, the touching spans are joined.
+        let mut spans = Vec::<(T, T)>::new();
+        for pair in crossings.chunks_exact(2) {
+            match spans.last_mut() {
+                Some(last) if last.1 == pair[0] => last.1 = pair[1],
+                _ if pair[0] < pair[1] => spans.push((pair[0], pair[1])),
+                _ => (),
+            }
+        }
+
+        let mut next_open = Vec::with_capacity(spans.len());
+        for (left, right) in spans {
+            let continued = open.iter().position(|rect| {
+                rect.x() == left && rect.x() + rect.w() == right && rect.y() + rect.h() == top
+            });
+            match continued {
+                Some(index) => {
+                    let mut rect = open.swap_remove(index);
+                    rect.size.y = bottom - rect.y();
+                    next_open.push(rect);
+                }
+                None => next_open.push(Rect::new(left, top, right - left, bottom - top)),
+            }
+        }
+        rects.append(&mut open);
+        open = next_open;
+    }
+    rects.append(&mut open);
+    rects
+}
+
+/// Decomposes the area covered by the given rectangles into non-overlapping cells, each annotated
+/// with the amount of the rectangles, that cover it (at least one). Empty rectangles are ignored.
+///
+/// The rectangles are swept from the top to the bottom, each horizontal band between consecutive
+/// edges is split into spans of the same depth, and the spans of the neighbouring bands, that have
+/// the same horizontal extent and depth, are merged.
+pub fn coverage_depth<T>(rects: &[Rect<T>]) -> Vec<(Rect<T>, usize)>
+where
+    T: Number,
+{
+    sweep_coverage(rects, usize::MAX)
+}
+
+/// Merges the rectangles, that overlap or share edges, into a smaller set of non-overlapping
+/// rectangles, that covers exactly the same area. Empty rectangles are dropped.
+pub fn simplify_rects<T>(rects: &[Rect<T>]) -> Vec<Rect<T>>
+where
+    T: Number,
+{
+    let mut rects = sweep_coverage(rects, 1)
+        .into_iter()
+        .map(|(rect, _)| rect)
+        .collect::<Vec<_>>();
+    // The sweep only merges vertically, the cells of a band could still be joined side by side,
+    // and then the joined cells could be joined with the cells above or below them.
+    let order = |a: T, b: T| a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+    rects.sort_by(|a, b| {
+        order(a.y(), b.y())
+            .then(order(a.h(), b.h()))
+            .then(order(a.x(), b.x()))
+    });
+    let mut rects = join_neighbours(rects);
+    rects.sort_by(|a, b| {
+        order(a.x(), b.x())
+            .then(order(a.w(), b.w()))
+            .then(order(a.y(), b.y()))
+    });
+    join_neighbours(rects)
+}
+
+/// Joins each rectangle with the previous one, if they share an entire edge. The rectangles must
+/// be sorted, so that such rectangles are next to each other.
+fn join_neighbours<T: Number>(rects: Vec<Rect<T>>) -> Vec<Rect<T>> {
+    let mut joined = Vec::<Rect<T>>::with_capacity(rects.len());
+    for rect in rects {
+        if let Some(last) = joined.last_mut() {
+            if let Some(union) = join_adjacent(last, &rect) {
+                *last = union;
+                continue;
+            }
+        }
+        joined.push(rect);
+    }
+    joined
+}
+
+/// Returns the union of two rectangles, if it is a rectangle as well, that is, the rectangles share
+/// an entire edge.
+fn join_adjacent<T: Number>(a: &Rect<T>, b: &Rect<T>) -> Option<Rect<T>> {
+    if a.y() == b.y() && a.h() == b.h() {
+        if a.x() + a.w() == b.x() {
+            return Some(Rect::new(a.x(), a.y(), a.w() + b.w(), a.h()));
+        } else if b.x() + b.w() == a.x() {
+            return Some(Rect::new(b.x(), a.y(), a.w() + b.w(), a.h()));
+        }
+    }
+    if a.x() == b.x() && a.w() == b.w() {
+        if a.y() + a.h() == b.y() {
+            return Some(Rect::new(a.x(), a.y(), a.w(), a.h() + b.h()));
+        } else if b.y() + b.h() == a.y() {
+            return Some(Rect::new(a.x(), b.y(), a.w(), a.h() + b.h()));
+        }
+    }
+    None
+}
+
+/// Sweeps the rectangles, the depth of the cells is clamped to the given maximum, so the cells of
+/// the clamped depth are merged together.
+fn sweep_coverage<T>(rects: &[Rect<T>], max_depth: usize) -> Vec<(Rect<T>, usize)>
+where
+    T: Number,
+{
+    let mut sorted = rects
+        .iter()
+        .filter(|rect| rect.w() > T::zero() && rect.h() > T::zero())
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.y().partial_cmp(&b.y()).unwrap_or(Ordering::Equal));
+    let mut rows = Vec::with_capacity(sorted.len() * 2);
+    for rect in sorted.iter() {
+        rows.push(rect.y());
+        rows.push(rect.y() + rect.h());
+    }
+    sort_and_dedup(&mut rows);
+
+    let mut cells = Vec::new();
+    let mut open = Vec::<(Rect<T>, usize)>::new();
+    let mut active = Vec::<&Rect<T>>::new();
+    let mut next = 0;
+    let mut events = Vec::new();
+    for band in rows.windows(2) {
+        let (top, bottom) = (band[0], band[1]);
+        active.retain(|rect| rect.y() + rect.h() > top);
+        while next < sorted.len() && sorted[next].y() <= top {
+            active.push(sorted[next]);
+            next += 1;
+        }
+
+        events.clear();
+        for rect in active.iter() {
+            events.push((rect.x(), 1));
+            events.push((rect.x() + rect.w(), -1));
+        }
+        events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        // Spans of the band with the same depth, the touching spans of equal depth are joined.
+        let mut spans = Vec::<(T, T, usize)>::new();
+        let mut depth = 0isize;
+        for (i, (x, delta)) in events.iter().enumerate() {
+            depth += delta;
+            let Some((end, _)) = events.get(i + 1) else {
+                break;
+            };
+            if depth <= 0 || *end == *x {
+                continue;
+            }
+            let depth = (depth as usize).min(max_depth);
+            match spans.last_mut() {
+                Some(last) if last.1 == *x && last.2 == depth => last.1 = *end,
+                _ => spans.push((*x, *end, depth)),
+            }
+        }
+
+        let mut next_open = Vec::with_capacity(spans.len());
+        for (left, right, depth) in spans {
+            let continued = open.iter().position(|(rect, rect_depth)| {
+                *rect_depth == depth
+                    && rect.x() == left
+                    && rect.x() + rect.w() == right
+                    && rect.y() + rect.h() == top
+            });
+            match continued {
+                Some(index) => {
+                    let (mut rect, depth) = open.swap_remove(index);
+                    rect.size.y = bottom - rect.y();
+                    next_open.push((rect, depth));
+                }
+                None => next_open.push((Rect::new(left, top, right - left, bottom - top), depth)),
+            }
+        }
+        cells.append(&mut open);
+        open = next_open;
+    }
+    cells.append(&mut open);
+    cells
+}
+
+/// Subtracts all the holes from the base rectangle and returns the rest of it as a set of
+/// non-overlapping rectangles. The holes could overlap each other and stick out of the base
+/// rectangle. This is [`Rect::difference`] generalized to any amount of holes.
+pub fn subtract_all<T>(base: Rect<T>, holes: &[Rect<T>]) -> Vec<Rect<T>>
+where
+    T: Number,
+{
+    let mut rest = vec![base];
+    for hole in holes {
+        if !rest.iter().any(|rect| rect.intersects(*hole)) {
+            continue;
+        }
+        let mut remaining = Vec::with_capacity(rest.len() + 3);
+        for rect in rest {
+            remaining.extend(rect.difference(*hole));
+        }
+        rest = remaining;
+    }
+    rest
+}
+
+/// Greedy meshing of the mask, that is read into the `set` array, row by row or column by column.
+fn greedy_mesh(width: i32, height: i32, set: &[bool], transposed: bool) -> Vec<Rect<i32>> {
+    let (columns, rows) = if transposed {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let index = |column: i32, row: i32| {
+        let (x, y) = if transposed {
+            (row, column)
+        } else {
+            (column, row)
+        };
+        (y * width + x) as usize
+    };
+    let mut used = vec![false; set.len()];
+    let free = |used: &[bool], column: i32, row: i32| {
+        let index = index(column, row);
+        set[index] && !used[index]
+    };
+    let mut rects = Vec::new();
+    for row in 0..rows {
+        let mut column = 0;
+        while column < columns {
+            if !free(&used, column, row) {
+                column += 1;
+                continue;
+            }
+            let mut end = column + 1;
+            while end < columns && free(&used, end, row) {
+                end += 1;
+            }
+            let mut bottom = row + 1;
+            while bottom < rows && (column..end).all(|c| free(&used, c, bottom)) {
+                bottom += 1;
+            }
+            for r in row..bottom {
+                for c in column..end {
+                    used[index(c, r)] = true;
+                }
+            }
+            rects.push(if transposed {
+                Rect::new(row, column, bottom - row, end - column)
+            } else {
+                Rect::new(column, row, end - column, bottom - row)
+            });
+            column = end;
+        }
+    }
+    rects
+}
+
+fn read_mask(width: i32, height: i32, is_set: impl Fn(i32, i32) -> bool) -> Vec<bool> {
+    let (width, height) = (width.max(0), height.max(0));
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| is_set(x, y))
+        .collect()
+}
+
+/// Decomposes the binary mask of the given size into non-overlapping rectangles, that cover
+/// exactly the set cells, using greedy meshing: each free set cell starts a rectangle, that is
+/// extended as far as possible along the row and then down the rows. The rectangles are in the
+/// order of their left top cells in row-major order.
+pub fn rects_from_mask(
+    width: i32,
+    height: i32,
+    is_set: impl Fn(i32, i32) -> bool,
+) -> Vec<Rect<i32>> {
+    let set = read_mask(width, height, is_set);
+    greedy_mesh(width.max(0), height.max(0), &set, false)
+}
+
+/// Same as [`rects_from_mask`], but meshes the mask both row by row and column by column and
+/// returns the decomposition with fewer rectangles. It takes twice as long, but the output is
+/// usually close to the smallest possible one, for example for masks of tall thin shapes.
+pub fn rects_from_mask_optimized(
+    width: i32,
+    height: i32,
+    is_set: impl Fn(i32, i32) -> bool,
+) -> Vec<Rect<i32>> {
+    let set = read_mask(width, height, is_set);
+    let (width, height) = (width.max(0), height.max(0));
+    let rows = greedy_mesh(width, height, &set, false);
+    let columns = greedy_mesh(width, height, &set, true);
+    if columns.len() < rows.len() {
+        columns
+    } else {
+        rows
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        coverage_depth, decompose_rectilinear, rects_from_mask, rects_from_mask_optimized,
+        simplify_rects, subtract_all,
+    };
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    fn area(rects: &[Rect<i32>]) -> i32 {
+        rects.iter().map(|rect| rect.w() * rect.h()).sum()
+    }
+
+    #[test]
+    fn decompose_polygon() {
+        // An L-shaped room.
+        let l_shape = vec![
+            Vector2::new(0, 0),
+            Vector2::new(10, 0),
+            Vector2::new(10, 4),
+            Vector2::new(4, 4),
+            Vector2::new(4, 10),
+            Vector2::new(0, 10),
+        ];
+        assert_eq!(
+            decompose_rectilinear(&[l_shape]),
+            [Rect::new(0, 0, 10, 4), Rect::new(0, 4, 4, 6)]
+        );
+
+        // A square with a square hole, the hole goes in the opposite direction.
+        let outer = vec![
+            Vector2::new(0, 0),
+            Vector2::new(9, 0),
+            Vector2::new(9, 9),
+            Vector2::new(0, 9),
+        ];
+        let hole = vec![
+            Vector2::new(3, 3),
+            Vector2::new(3, 6),
+            Vector2::new(6, 6),
+            Vector2::new(6, 3),
+        ];
+        let rects = decompose_rectilinear(&[outer, hole]);
+        assert_eq!(rects.len(), 4);
+        assert_eq!(area(&rects), 81 - 9);
+        for (i, a) in rects.iter().enumerate() {
+            assert!(!a.intersects(Rect::new(3, 3, 3, 3)));
+            for b in rects[i + 1..].iter() {
+                assert!(!a.intersects(*b));
+            }
+        }
+    }
+
+    #[test]
+    fn decompose_mask() {
+        let mask = [
+            "##..#", //
+            "##..#", //
+            "....#", //
+            "#####", //
+        ];
+        let is_set = |x: i32, y: i32| mask[y as usize].as_bytes()[x as usize] == b'#';
+        let rects = rects_from_mask(5, 4, is_set);
+        assert_eq!(
+            rects,
+            [
+                Rect::new(0, 0, 2, 2),
+                Rect::new(4, 0, 1, 4),
+                Rect::new(0, 3, 4, 1)
+            ]
+        );
+        assert_eq!(area(&rects), 12);
+
+        // Row by row, the stem of a T-shape is grown first and cuts the bar in two.
+        let tee = |x: i32, y: i32| y == 1 || x == 1;
+        assert_eq!(
+            rects_from_mask(3, 2, tee),
+            [
+                Rect::new(1, 0, 1, 2),
+                Rect::new(0, 1, 1, 1),
+                Rect::new(2, 1, 1, 1)
+            ]
+        );
+        assert_eq!(
+            rects_from_mask_optimized(3, 2, tee),
+            [Rect::new(0, 1, 3, 1), Rect::new(1, 0, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn coverage_depth_map() {
+        let mut cells = coverage_depth(&[
+            Rect::new(0, 0, 4, 4),
+            Rect::new(2, 0, 4, 4),
+            Rect::new(2, 2, 2, 2),
+            Rect::new(10, 10, 0, 5),
+        ]);
+        cells.sort_by_key(|(rect, _)| (rect.y(), rect.x()));
+        assert_eq!(
+            cells,
+            [
+                (Rect::new(0, 0, 2, 4), 1),
+                (Rect::new(2, 0, 2, 2), 2),
+                (Rect::new(4, 0, 2, 4), 1),
+                (Rect::new(2, 2, 2, 2), 3),
+            ]
+        );
+        assert!(coverage_depth::<f32>(&[]).is_empty());
+    }
+
+    #[test]
+    fn simplify() {
+        // Two overlapping halves of a square, a sliver on its edge and a strip next to it.
+        let rects = simplify_rects(&[
+            Rect::new(0, 0, 6, 10),
+            Rect::new(4, 0, 6, 10),
+            Rect::new(10, 0, 0, 10),
+            Rect::new(10, 0, 5, 10),
+            Rect::new(2, 2, 2, 2),
+        ]);
+        assert_eq!(rects, [Rect::new(0, 0, 15, 10)]);
+
+        let rects = simplify_rects(&[Rect::new(0, 0, 4, 2), Rect::new(2, 2, 2, 2)]);
+        assert_eq!(rects.len(), 2);
+        assert_eq!(area(&rects), 12);
+    }
+
+    #[test]
+    fn subtract_holes() {
+        let base = Rect::new(0, 0, 10, 10);
+        let holes = [
+            Rect::new(2, 2, 2, 2),
+            Rect::new(3, 3, 4, 4),
+            Rect::new(-5, 8, 20, 5),
+            Rect::new(20, 20, 5, 5),
+        ];
+        let rest = subtract_all(base, &holes);
+        assert_eq!(area(&rest), 100 - 20 - 4 - 16 + 1);
+        for (i, a) in rest.iter().enumerate() {
+            assert!(holes.iter().all(|hole| !a.intersects(*hole)));
+            assert!(rest[i + 1..].iter().all(|b| !a.intersects(*b)));
+        }
+        assert_eq!(subtract_all(base, &[]), [base]);
+        assert!(subtract_all(base, &[Rect::new(-1, -1, 12, 12)]).is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! Accumulation of invalidated areas for partial redraws.
+
+use crate::{
+    geometry::{area, union},
+    Number, Rect,
+};
+
+/// Accumulator of invalidated (dirty) rectangles, that need to be redrawn. Overlapping and nearby
+/// rectangles are merged as they are added, and when there are more rectangles than the limit,
+/// the pairs, whose bounding rectangle adds the least area, are merged, until the limit is met.
+/// This keeps the amount of draw calls bounded, while not redrawing too much of the untouched
+/// area.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct DirtyRects<T> {
+    rects: Vec<Rect<T>>,
+    merge_distance: T,
+    max_rects: usize,
+}
+
+impl<T> DirtyRects<T>
+where
+    T: Number,
+{
+    /// Creates new empty accumulator, that keeps at most the given amount of rectangles (at least
+    /// one).
+    pub fn new(max_rects: usize) -> Self {
+        Self {
+            rects: Default::default(),
+            merge_distance: T::zero(),
+            max_rects: max_rects.max(1),
+        }
+    }
+
+    /// Sets the distance, closer than which the rectangles are merged, even if they do not overlap.
+    /// By default, only the overlapping rectangles are merged.
+    pub fn with_merge_distance(mut self, merge_distance: T) -> Self {
+        self.merge_distance = merge_distance;
+        self
+    }
+
+    /// Returns the accumulated rectangles.
+    pub fn rects(&self) -> &[Rect<T>] {
+        &self.rects
+    }
+
+    /// Returns the amount of accumulated rectangles.
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    /// Returns `true` if nothing needs to be redrawn.
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// Returns the bounding rectangle of all the accumulated rectangles.
+    pub fn bounds(&self) -> Option<Rect<T>> {
+        let (first, rest) = self.rects.split_first()?;
+        Some(
+            rest.iter()
+                .fold(*first, |bounds, rect| union(&bounds, rect)),
+        )
+    }
+
+    /// Removes all the rectangles.
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+
+    /// Returns the accumulated rectangles and clears the accumulator, usually once per frame.
+    pub fn take(&mut self) -> Vec<Rect<T>> {
+        std::mem::take(&mut self.rects)
+    }
+
+    fn should_merge(&self, a: &Rect<T>, b: &Rect<T>) -> bool {
+        if a.intersects(*b) {
+            return true;
+        }
+        // The coordinates could be unsigned, so only the larger value is subtracted from.
+        let gap = |min_a: T, size_a: T, min_b: T, size_b: T| {
+            let (max_a, max_b) = (min_a + size_a, min_b + size_b);
+            if min_b > max_a {
+                min_b - max_a
+            } else if min_a > max_b {
+                min_a - max_b
+            } else {
+                T::zero()
+            }
+        };
+        gap(a.x(), a.w(), b.x(), b.w()) < self.merge_distance
+            && gap(a.y(), a.h(), b.y(), b.h()) < self.merge_distance
+    }
+
+    /// Adds the invalidated rectangle. Empty rectangles and the rectangles, that are already
+    /// covered, are ignored.
+    pub fn add(&mut self, rect: Rect<T>) {
+        if rect.w() <= T::zero() || rect.h() <= T::zero() {
+            return;
+        }
+        if self.rects.iter().any(|dirty| {
+            dirty.contains(rect.left_top_corner()) && dirty.contains(rect.right_bottom_corner())
+        }) {
+            return;
+        }
+        let mut rect = rect;
+        // The merged rectangle could reach some other rectangles, so repeat until nothing merges.
+        while let Some(index) = self
+            .rects
+            .iter()
+            .position(|other| self.should_merge(&rect, other))
+        {
+            rect = union(&rect, &self.rects.swap_remove(index));
+        }
+        self.rects.push(rect);
+
+        while self.rects.len() > self.max_rects {
+            let mut best = (0, 1);
+            let mut best_cost = None;
+            for i in 0..self.rects.len() {
+                for j in i + 1..self.rects.len() {
+                    let (a, b) = (&self.rects[i], &self.rects[j]);
+                    let cost = area(&union(a, b)) - area(a) - area(b);
+                    if best_cost.map_or(true, |best_cost| cost < best_cost) {
+                        best = (i, j);
+                        best_cost = Some(cost);
+                    }
+                }
+            }
+            let (i, j) = best;
+            let second = self.rects.swap_remove(j);
+            let first = self.rects.swap_remove(i);
+            let merged = union(&first, &second);
+            self.add(merged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DirtyRects;
+    use crate::Rect;
+
+    #[test]
+    fn dirty_rects() {
+        let mut dirty = DirtyRects::new(3);
+        dirty.add(Rect::new(0, 0, 10, 10));
+        dirty.add(Rect::new(5, 5, 10, 10));
+        dirty.add(Rect::new(2, 2, 3, 3));
+        dirty.add(Rect::new(0, 0, 0, 10));
+        assert_eq!(dirty.rects(), [Rect::new(0, 0, 15, 15)]);
+
+        dirty.add(Rect::new(100, 0, 10, 10));
+        dirty.add(Rect::new(0, 100, 10, 10));
+        assert_eq!(dirty.len(), 3);
+        // Over the limit: the two closest rectangles are merged.
+        dirty.add(Rect::new(112, 0, 10, 10));
+        assert_eq!(dirty.len(), 3);
+        assert!(dirty.rects().contains(&Rect::new(100, 0, 22, 10)));
+        assert_eq!(dirty.bounds(), Some(Rect::new(0, 0, 122, 110)));
+
+        let mut dirty = DirtyRects::new(10).with_merge_distance(3);
+        dirty.add(Rect::new(0, 0, 10, 10));
+        dirty.add(Rect::new(12, 0, 10, 10));
+        dirty.add(Rect::new(0, 20, 10, 10));
+        assert_eq!(
+            dirty.take(),
+            [Rect::new(0, 0, 22, 10), Rect::new(0, 20, 10, 10)]
+        );
+        assert!(dirty.is_empty());
+
+        let mut dirty = DirtyRects::<u32>::new(10).with_merge_distance(3);
+        dirty.add(Rect::new(12, 0, 10, 10));
+        dirty.add(Rect::new(0, 0, 10, 10));
+        dirty.add(Rect::new(0, 40, 10, 10));
+        assert_eq!(
+            dirty.rects(),
+            [Rect::new(0, 0, 22, 10), Rect::new(0, 40, 10, 10)]
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Small rectangle helpers shared by the spatial structures and the packers.
+
+use crate::{quadtree::BoundsProvider, Number, Rect};
+
+fn min<T: Number>(a: T, b: T) -> T {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+fn max<T: Number>(a: T, b: T) -> T {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Rectangle of a slice, that is identified by its index in the slice.
+pub(crate) struct IndexedRect(pub(crate) usize, pub(crate) Rect<f32>);
+
+impl BoundsProvider for IndexedRect {
+    type Id = usize;
+
+    fn bounds(&self) -> Rect<f32> {
+        self.1
+    }
+
+    fn id(&self) -> usize {
+        self.0
+    }
+}
+
+pub(crate) fn area<T: Number>(rect: &Rect<T>) -> T {
+    rect.w() * rect.h()
+}
+
+/// Returns the smallest rectangle, that contains both of the given rectangles.
+pub(crate) fn union<T: Number>(a: &Rect<T>, b: &Rect<T>) -> Rect<T> {
+    let x = min(a.x(), b.x());
+    let y = min(a.y(), b.y());
+    let right = max(a.x() + a.w(), b.x() + b.w());
+    let bottom = max(a.y() + a.h(), b.y() + b.h());
+    Rect::new(x, y, right - x, bottom - y)
+}
+
+/// Checks if the inner rectangle is entirely inside of the outer one, the edges may touch.
+pub(crate) fn contains_rect<T: Number>(outer: &Rect<T>, inner: &Rect<T>) -> bool {
+    inner.x() >= outer.x()
+        && inner.y() >= outer.y()
+        && inner.x() + inner.w() <= outer.x() + outer.w()
+        && inner.y() + inner.h() <= outer.y() + outer.h()
+}
+
+/// Returns the overlap of the given rectangles, or None if they do not overlap or only touch.
+pub(crate) fn intersection<T: Number>(a: &Rect<T>, b: &Rect<T>) -> Option<Rect<T>> {
+    let x = max(a.x(), b.x());
+    let y = max(a.y(), b.y());
+    let right = min(a.x() + a.w(), b.x() + b.w());
+    let bottom = min(a.y() + a.h(), b.y() + b.h());
+    if x < right && y < bottom {
+        Some(Rect::new(x, y, right - x, bottom - y))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,480 @@
+//! Uniform and hierarchical grids are the simplest broad phases for spatial queries, that are
+//! cheap to update every frame.
+
+use crate::{
+    quadtree::{BoundsProvider, QueryStorage},
+    Rect,
+};
+use nalgebra::Vector2;
+use std::ops::{ControlFlow, Range};
+
+/// Dense uniform grid of square cells over a bounding rectangle. Each object is stored in every
+/// cell it overlaps, objects outside of the bounds are stored in the border cells. Unlike the
+/// trees, the grid has no structure to maintain, so insertion and removal are cheap enough to
+/// update the grid every frame.
+///
+/// The grid works best, when the objects are distributed roughly uniformly and the cell size is
+/// close to the size of a typical object. Queries report each object once, even if it is stored
+/// in multiple cells.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UniformGrid<I> {
+    bounds: Rect<f32>,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<(Rect<f32>, I)>>,
+    len: usize,
+}
+
+impl<I> UniformGrid<I> {
+    /// Creates new empty grid, that covers the given bounds with the cells of the given size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell size is not positive.
+    pub fn new(bounds: Rect<f32>, cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cell size must be positive");
+        let columns = ((bounds.w() / cell_size).ceil() as usize).max(1);
+        let rows = ((bounds.h() / cell_size).ceil() as usize).max(1);
+        Self {
+            bounds,
+            cell_size,
+            columns,
+            rows,
+            cells: (0..columns * rows).map(|_| Vec::new()).collect(),
+            len: 0,
+        }
+    }
+
+    /// Returns the bounds covered by the grid.
+    pub fn bounds(&self) -> Rect<f32> {
+        self.bounds
+    }
+
+    /// Returns the size of the cells of the grid.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Returns the amount of columns and rows of the grid.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.columns, self.rows)
+    }
+
+    /// Returns the amount of objects in the grid.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the grid has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all the objects from the grid, the memory of the cells is reused.
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        self.len = 0;
+    }
+
+    fn cell(&self, coordinate: f32, origin: f32, count: usize) -> usize {
+        let cell = ((coordinate - origin) / self.cell_size).floor();
+        if cell > 0.0 {
+            (cell as usize).min(count - 1)
+        } else {
+            0
+        }
+    }
+
+    /// Returns the ranges of the columns and the rows of the cells, that overlap the rectangle.
+    fn cell_range(&self, rect: &Rect<f32>) -> (Range<usize>, Range<usize>) {
+        let x0 = self.cell(rect.x(), self.bounds.x(), self.columns);
+        let y0 = self.cell(rect.y(), self.bounds.y(), self.rows);
+        let x1 = self.cell(rect.x() + rect.w(), self.bounds.x(), self.columns);
+        let y1 = self.cell(rect.y() + rect.h(), self.bounds.y(), self.rows);
+        (x0..x1 + 1, y0..y1 + 1)
+    }
+
+    /// Adds the object to every cell, that it overlaps.
+    pub fn insert<T>(&mut self, object: T)
+    where
+        T: BoundsProvider<Id = I>,
+        I: Clone,
+    {
+        let bounds = object.bounds();
+        let id = object.id();
+        let (columns, rows) = self.cell_range(&bounds);
+        for row in rows {
+            for column in columns.clone() {
+                self.cells[row * self.columns + column].push((bounds, id.clone()));
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Removes the object from the grid. The object must have the same bounds, as it had when it
+    /// was added to the grid. Returns `true` if the object was found in the grid.
+    pub fn remove<T>(&mut self, object: T) -> bool
+    where
+        T: BoundsProvider<Id = I>,
+        I: PartialEq,
+    {
+        let id = object.id();
+        let (columns, rows) = self.cell_range(&object.bounds());
+        let mut found = false;
+        for row in rows {
+            for column in columns.clone() {
+                let cell = &mut self.cells[row * self.columns + column];
+                if let Some(index) = cell.iter().position(|(_, other)| *other == id) {
+                    cell.swap_remove(index);
+                    found = true;
+                }
+            }
+        }
+        if found {
+            self.len -= 1;
+        }
+        found
+    }
+
+    /// Visits the cells, that overlap the given area, and passes the objects, that pass the
+    /// filter, to the closure. An object is reported only in the first visited cell, that it
+    /// overlaps, so each object is reported once.
+    fn query<F>(
+        &self,
+        area: Rect<f32>,
+        filter: impl Fn(&Rect<f32>) -> bool,
+        mut func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let (columns, rows) = self.cell_range(&area);
+        for row in rows.clone() {
+            for column in columns.clone() {
+                for (bounds, id) in self.cells[row * self.columns + column].iter() {
+                    let (object_columns, object_rows) = self.cell_range(bounds);
+                    if column == object_columns.start.max(columns.start)
+                        && row == object_rows.start.max(rows.start)
+                        && filter(bounds)
+                    {
+                        func(id)?;
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the ids of the objects, that intersect the given rectangle, to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.query(rect, |bounds| bounds.intersects(rect), func)
+    }
+
+    /// Passes the ids of the objects, that contain the given point, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn point_query_with<F>(&self, point: Vector2<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let area = Rect::new(point.x, point.y, 0.0, 0.0);
+        self.query(area, |bounds| bounds.contains(point), func)
+    }
+
+    /// Passes the ids of the objects, that intersect the given circle, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn circle_query_with<F>(
+        &self,
+        center: Vector2<f32>,
+        radius: f32,
+        func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let area = Rect::new(
+            center.x - radius,
+            center.y - radius,
+            radius + radius,
+            radius + radius,
+        );
+        self.query(
+            area,
+            |bounds| bounds.intersects_circle(center, radius),
+            func,
+        )
+    }
+
+    /// Writes the ids of the objects, that intersect the given rectangle, to the output storage.
+    pub fn rect_query<S>(&self, rect: Rect<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.rect_query_with(rect, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the objects, that contain the given point, to the output storage.
+    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.point_query_with(point, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the objects, that intersect the given circle, to the output storage.
+    pub fn circle_query<S>(&self, center: Vector2<f32>, radius: f32, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.circle_query_with(center, radius, |id| storage.push_or_break(id.clone()));
+    }
+}
+
+/// A stack of uniform grids over the same bounds, the size of the cells doubles from each level
+/// to the next one. Each object is stored in the level, whose cells are not smaller than the
+/// object, so it occupies at most four cells regardless of its size. Unlike a single uniform
+/// grid, a few huge objects do not fill every fine cell, which makes the hierarchical grid a good
+/// fit for objects of mixed sizes, that are updated too often for a quad tree.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HierarchicalGrid<I> {
+    levels: Vec<UniformGrid<I>>,
+}
+
+impl<I> HierarchicalGrid<I> {
+    /// Creates new empty grid over the given bounds. The finest level has the cells of the given
+    /// size, the coarsest level is a single cell, that covers the whole bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell size is not positive.
+    pub fn new(bounds: Rect<f32>, min_cell_size: f32) -> Self {
+        let mut levels = vec![UniformGrid::new(bounds, min_cell_size)];
+        let mut cell_size = min_cell_size;
+        while cell_size < bounds.w().max(bounds.h()) {
+            cell_size *= 2.0;
+            levels.push(UniformGrid::new(bounds, cell_size));
+        }
+        Self { levels }
+    }
+
+    /// Returns the levels of the grid, from the finest to the coarsest one.
+    pub fn levels(&self) -> &[UniformGrid<I>] {
+        &self.levels
+    }
+
+    /// Returns the amount of objects in the grid.
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(|level| level.len()).sum()
+    }
+
+    /// Returns `true` if the grid has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(|level| level.is_empty())
+    }
+
+    /// Removes all the objects from the grid, the memory of the cells is reused.
+    pub fn clear(&mut self) {
+        for level in self.levels.iter_mut() {
+            level.clear();
+        }
+    }
+
+    fn level_of(&self, bounds: &Rect<f32>) -> usize {
+        let size = bounds.w().max(bounds.h());
+        self.levels
+            .iter()
+            .position(|level| level.cell_size() >= size)
+            .unwrap_or(self.levels.len() - 1)
+    }
+
+    /// Adds the object to the level, that matches its size.
+    pub fn insert<T>(&mut self, object: T)
+    where
+        T: BoundsProvider<Id = I>,
+        I: Clone,
+    {
+        let level = self.level_of(&object.bounds());
+        self.levels[level].insert(object)
+    }
+
+    /// Removes the object from the grid. The object must have the same bounds, as it had when it
+    /// was added to the grid. Returns `true` if the object was found in the grid.
+    pub fn remove<T>(&mut self, object: T) -> bool
+    where
+        T: BoundsProvider<Id = I>,
+        I: PartialEq,
+    {
+        let level = self.level_of(&object.bounds());
+        self.levels[level].remove(object)
+    }
+
+    /// Passes the ids of the objects, that intersect the given rectangle, to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        for level in self.levels.iter() {
+            level.rect_query_with(rect, &mut func)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the ids of the objects, that contain the given point, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn point_query_with<F>(&self, point: Vector2<f32>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        for level in self.levels.iter() {
+            level.point_query_with(point, &mut func)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the ids of the objects, that intersect the given circle, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn circle_query_with<F>(
+        &self,
+        center: Vector2<f32>,
+        radius: f32,
+        mut func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        for level in self.levels.iter() {
+            level.circle_query_with(center, radius, &mut func)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Writes the ids of the objects, that intersect the given rectangle, to the output storage.
+    pub fn rect_query<S>(&self, rect: Rect<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.rect_query_with(rect, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the objects, that contain the given point, to the output storage.
+    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.point_query_with(point, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the objects, that intersect the given circle, to the output storage.
+    pub fn circle_query<S>(&self, center: Vector2<f32>, radius: f32, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.circle_query_with(center, radius, |id| storage.push_or_break(id.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HierarchicalGrid, UniformGrid};
+    use crate::{test_utils::TestObject, Rect};
+    use nalgebra::Vector2;
+
+    #[test]
+    fn uniform_grid_queries() {
+        let objects = [
+            Rect::new(1.0, 1.0, 2.0, 2.0),
+            // Spans many cells, but must be reported once.
+            Rect::new(5.0, 5.0, 30.0, 30.0),
+            // Partially outside of the grid.
+            Rect::new(-5.0, 90.0, 10.0, 20.0),
+            Rect::new(50.0, 50.0, 4.0, 4.0),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(id, bounds)| TestObject { bounds, id })
+        .collect::<Vec<_>>();
+        let mut grid = UniformGrid::new(Rect::new(0.0, 0.0, 100.0, 100.0), 8.0);
+        assert_eq!(grid.dimensions(), (13, 13));
+        for object in objects.iter() {
+            grid.insert(object);
+        }
+        assert_eq!(grid.len(), 4);
+
+        let mut found = Vec::new();
+        grid.rect_query(Rect::new(0.0, 0.0, 60.0, 60.0), &mut found);
+        found.sort_unstable();
+        assert_eq!(found, [0, 1, 3]);
+
+        found.clear();
+        grid.point_query(Vector2::new(0.0, 95.0), &mut found);
+        assert_eq!(found, [2]);
+
+        found.clear();
+        grid.circle_query(Vector2::new(40.0, 40.0), 15.0, &mut found);
+        found.sort_unstable();
+        assert_eq!(found, [1, 3]);
+
+        assert!(grid.remove(&objects[1]));
+        assert!(!grid.remove(&objects[1]));
+        found.clear();
+        grid.rect_query(Rect::new(0.0, 0.0, 100.0, 100.0), &mut found);
+        found.sort_unstable();
+        assert_eq!(found, [0, 2, 3]);
+        assert_eq!(grid.len(), 3);
+    }
+
+    #[test]
+    fn hierarchical_grid() {
+        let objects = [
+            Rect::new(1.0, 1.0, 2.0, 2.0),
+            Rect::new(0.0, 0.0, 100.0, 100.0),
+            Rect::new(40.0, 40.0, 20.0, 10.0),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(id, bounds)| TestObject { bounds, id })
+        .collect::<Vec<_>>();
+        let mut grid = HierarchicalGrid::new(Rect::new(0.0, 0.0, 100.0, 100.0), 4.0);
+        // 4, 8, 16, 32, 64 and 128.
+        assert_eq!(grid.levels().len(), 6);
+        for object in objects.iter() {
+            grid.insert(object);
+        }
+        assert_eq!(grid.len(), 3);
+        // The huge object is stored in a single cell of the coarsest level.
+        assert_eq!(grid.levels()[5].len(), 1);
+        assert_eq!(grid.levels()[0].len(), 1);
+        assert_eq!(grid.levels()[3].len(), 1);
+
+        let mut found = Vec::new();
+        grid.point_query(Vector2::new(2.0, 2.0), &mut found);
+        found.sort_unstable();
+        assert_eq!(found, [0, 1]);
+
+        found.clear();
+        grid.rect_query(Rect::new(30.0, 30.0, 15.0, 15.0), &mut found);
+        found.sort_unstable();
+        assert_eq!(found, [1, 2]);
+
+        assert!(grid.remove(&objects[1]));
+        found.clear();
+        grid.circle_query(Vector2::new(50.0, 45.0), 1.0, &mut found);
+        assert_eq!(found, [2]);
+        assert_eq!(grid.len(), 2);
+    }
+}
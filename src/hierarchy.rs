@@ -0,0 +1,145 @@
+//! Nesting tree of rectangles, that tells which rectangles are contained in which.
+
+use crate::{
+    aabb_tree::DynamicAabbTree,
+    geometry::{area, contains_rect, IndexedRect},
+    Rect,
+};
+use std::{cmp::Ordering, ops::ControlFlow};
+
+/// Nesting tree of a set of rectangles. The parent of a rectangle is the smallest (by area)
+/// rectangle of the set, that contains it, the borders included. Equal rectangles are nested in
+/// the order of their indices: the first one is the parent of the second one and so on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContainmentTree {
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+}
+
+impl ContainmentTree {
+    /// Builds the nesting tree of the given rectangles. Rectangles are processed from the largest
+    /// to the smallest one, and the containers of each rectangle are found with a dynamic AABB
+    /// tree of the already processed rectangles, so only the rectangles around the corner of each
+    /// rectangle are checked.
+    pub fn new(rects: &[Rect<f32>]) -> Self {
+        let mut order = (0..rects.len()).collect::<Vec<_>>();
+        // The sort is stable, so equal rectangles keep the order of their indices.
+        order.sort_by(|&a, &b| {
+            area(&rects[b])
+                .partial_cmp(&area(&rects[a]))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let mut parents = vec![None; rects.len()];
+        let mut children = vec![Vec::new(); rects.len()];
+        let mut roots = Vec::new();
+        let mut tree = DynamicAabbTree::new(0.0);
+        for index in order {
+            let rect = &rects[index];
+            let mut parent = None::<usize>;
+            let _ = tree.point_query_with(rect.position, |_, &other| {
+                let other_rect = &rects[other];
+                let tighter = |parent: usize| {
+                    let (other_area, parent_area) = (area(other_rect), area(&rects[parent]));
+                    // Equal rectangles are processed in the order of their indices, so the last
+                    // one is the deepest.
+                    other_area < parent_area || (other_area == parent_area && other > parent)
+                };
+                if contains_rect(other_rect, rect) && parent.map_or(true, tighter) {
+                    parent = Some(other);
+                }
+                ControlFlow::Continue(())
+            });
+            parents[index] = parent;
+            match parent {
+                Some(parent) => children[parent].push(index),
+                None => roots.push(index),
+            }
+            tree.insert(IndexedRect(index, *rect));
+        }
+        for list in children.iter_mut() {
+            list.sort_unstable();
+        }
+        roots.sort_unstable();
+
+        Self {
+            parents,
+            children,
+            roots,
+        }
+    }
+
+    /// Returns the index of the parent of the rectangle with the given index, or `None` if the
+    /// rectangle is not contained in any other rectangle.
+    pub fn parent(&self, index: usize) -> Option<usize> {
+        self.parents[index]
+    }
+
+    /// Returns the indices of the rectangles, that are directly nested in the rectangle with the
+    /// given index, in ascending order.
+    pub fn children(&self, index: usize) -> &[usize] {
+        &self.children[index]
+    }
+
+    /// Returns the indices of the rectangles, that are not contained in any other rectangle, in
+    /// ascending order.
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    /// Returns the parents of all the rectangles.
+    pub fn parents(&self) -> &[Option<usize>] {
+        &self.parents
+    }
+
+    /// Returns the depth of the rectangle with the given index, roots have zero depth.
+    pub fn depth(&self, mut index: usize) -> usize {
+        let mut depth = 0;
+        while let Some(parent) = self.parents[index] {
+            index = parent;
+            depth += 1;
+        }
+        depth
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ContainmentTree;
+    use crate::Rect;
+
+    #[test]
+    fn containment_hierarchy() {
+        let rects = [
+            Rect::new(10.0, 10.0, 20.0, 20.0),
+            Rect::new(0.0, 0.0, 100.0, 100.0),
+            Rect::new(12.0, 12.0, 5.0, 5.0),
+            Rect::new(200.0, 0.0, 10.0, 10.0),
+            Rect::new(0.0, 0.0, 50.0, 50.0),
+            Rect::new(25.0, 25.0, 40.0, 40.0),
+            Rect::new(200.0, 0.0, 10.0, 10.0),
+            Rect::new(200.0, 0.0, 10.0, 10.0),
+        ];
+        let tree = ContainmentTree::new(&rects);
+        assert_eq!(tree.roots(), [1, 3]);
+        assert_eq!(
+            tree.parents(),
+            [
+                Some(4),
+                None,
+                Some(0),
+                None,
+                Some(1),
+                Some(1),
+                Some(3),
+                Some(6)
+            ]
+        );
+        assert_eq!(tree.children(1), [4, 5]);
+        assert_eq!(tree.children(4), [0]);
+        assert_eq!(tree.children(3), [6]);
+        assert_eq!(tree.depth(2), 3);
+    }
+}
@@ -0,0 +1,134 @@
+//! Z-ordered stack of rectangles for mouse picking.
+
+use crate::{quadtree::QueryStorage, Rect};
+use nalgebra::Vector2;
+use std::ops::ControlFlow;
+
+/// A stack of rectangles ordered by their depth, which answers the question "what is under the
+/// cursor" the way user interfaces expect: the rectangles with larger `z` are in front of the ones
+/// with smaller `z`, and among the rectangles with the same `z` the most recently inserted one is
+/// in front. The entries are kept sorted from the front to the back, so the queries stop at the
+/// first hit, when only the topmost rectangle is needed.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitTestStack<I> {
+    entries: Vec<(Rect<f32>, i32, I)>,
+}
+
+impl<I> Default for HitTestStack<I> {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+        }
+    }
+}
+
+impl<I> HitTestStack<I> {
+    /// Creates new empty stack.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the amount of rectangles in the stack.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the stack has no rectangles.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes all the rectangles.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns an iterator over the rectangles, their depths and ids from the front to the back.
+    pub fn iter(&self) -> impl Iterator<Item = (&Rect<f32>, i32, &I)> {
+        self.entries.iter().map(|(rect, z, id)| (rect, *z, id))
+    }
+
+    /// Adds the rectangle to the stack. It is placed in front of the other rectangles with the
+    /// same depth.
+    pub fn insert(&mut self, rect: Rect<f32>, z: i32, id: I) {
+        let index = self.entries.partition_point(|(_, other, _)| *other > z);
+        self.entries.insert(index, (rect, z, id));
+    }
+
+    /// Removes the rectangle with the given id from the stack and returns its bounds and depth,
+    /// or `None` if there is no such rectangle.
+    pub fn remove(&mut self, id: &I) -> Option<(Rect<f32>, i32)>
+    where
+        I: PartialEq,
+    {
+        let index = self.entries.iter().position(|(_, _, other)| other == id)?;
+        let (rect, z, _) = self.entries.remove(index);
+        Some((rect, z))
+    }
+
+    /// Returns the id of the frontmost rectangle, that contains the point, or `None` if there is
+    /// no such rectangle.
+    pub fn topmost_at(&self, point: Vector2<f32>) -> Option<&I> {
+        self.entries
+            .iter()
+            .find(|(rect, _, _)| rect.contains(point))
+            .map(|(_, _, id)| id)
+    }
+
+    /// Passes the ids of the rectangles, that contain the point, to the given closure, from the
+    /// front to the back. The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn all_at_with<F>(&self, point: Vector2<f32>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        for (rect, _, id) in self.entries.iter() {
+            if rect.contains(point) {
+                func(id)?;
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Writes the ids of the rectangles, that contain the point, to the output storage, from the
+    /// front to the back.
+    pub fn all_at<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.all_at_with(point, |id| storage.push_or_break(id.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HitTestStack;
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn hit_test_order() {
+        let mut stack = HitTestStack::new();
+        stack.insert(Rect::new(0.0, 0.0, 100.0, 100.0), 0, "window");
+        stack.insert(Rect::new(10.0, 10.0, 20.0, 20.0), 1, "button");
+        stack.insert(Rect::new(0.0, 0.0, 50.0, 50.0), 0, "panel");
+        stack.insert(Rect::new(15.0, 15.0, 100.0, 5.0), 5, "tooltip");
+
+        let point = Vector2::new(16.0, 16.0);
+        assert_eq!(stack.topmost_at(point), Some(&"tooltip"));
+        let mut hits = Vec::new();
+        stack.all_at(point, &mut hits);
+        assert_eq!(hits, ["tooltip", "button", "panel", "window"]);
+
+        assert_eq!(
+            stack.remove(&"tooltip"),
+            Some((Rect::new(15.0, 15.0, 100.0, 5.0), 5))
+        );
+        assert_eq!(stack.remove(&"tooltip"), None);
+        assert_eq!(stack.topmost_at(point), Some(&"button"));
+        assert_eq!(stack.topmost_at(Vector2::new(40.0, 40.0)), Some(&"panel"));
+        assert_eq!(stack.topmost_at(Vector2::new(200.0, 40.0)), None);
+        assert_eq!(stack.len(), 3);
+    }
+}
@@ -0,0 +1,552 @@
+//! One-dimensional intervals and the interval and segment trees for range queries over them.
+
+use crate::{quadtree::QueryStorage, Number};
+use std::{cmp::Ordering, ops::ControlFlow};
+
+/// A closed interval of values from `min` to `max`, it is a one-dimensional counterpart of
+/// [`crate::Rect`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interval<T> {
+    /// The start of the interval.
+    pub min: T,
+    /// The end of the interval.
+    pub max: T,
+}
+
+impl<T> Interval<T>
+where
+    T: Number,
+{
+    /// Creates new interval from the given start and end.
+    #[inline]
+    pub fn new(min: T, max: T) -> Self {
+        Self { min, max }
+    }
+
+    /// Creates new interval from the given start and length.
+    #[inline]
+    pub fn from_len(min: T, len: T) -> Self {
+        Self {
+            min,
+            max: min + len,
+        }
+    }
+
+    /// Returns the length of the interval.
+    #[inline]
+    pub fn len(&self) -> T {
+        self.max - self.min
+    }
+
+    /// Returns `true` if the interval has zero (or negative) length.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.max <= self.min
+    }
+
+    /// Checks if the given value lies within the interval, the ends included.
+    #[inline]
+    pub fn contains(&self, value: T) -> bool {
+        value >= self.min && value <= self.max
+    }
+
+    /// Checks if the interval intersects with some other interval. As with
+    /// [`crate::Rect::intersects`], intervals, that only touch each other, do not intersect.
+    #[inline]
+    pub fn intersects(&self, other: Interval<T>) -> bool {
+        other.min < self.max && self.min < other.max
+    }
+
+    /// Returns the middle of the interval.
+    #[inline]
+    pub fn center(&self) -> T {
+        let two = T::one() + T::one();
+        self.min + (self.max - self.min) / two
+    }
+
+    /// Clips the interval by some other interval and returns their intersection. If the intervals
+    /// do not intersect, the method returns none. Like [`crate::Rect::clip_by`], it returns an
+    /// empty interval for the intervals, that touch each other.
+    #[inline]
+    #[must_use = "this method creates new instance of interval"]
+    pub fn clip_by(&self, other: Interval<T>) -> Option<Interval<T>> {
+        if other.max < self.min || other.min > self.max {
+            return None;
+        }
+        Some(Self {
+            min: if self.min < other.min {
+                other.min
+            } else {
+                self.min
+            },
+            max: if self.max > other.max {
+                other.max
+            } else {
+                self.max
+            },
+        })
+    }
+
+    /// Returns the smallest interval, that contains both intervals (including the gap between
+    /// them, if they do not intersect).
+    #[inline]
+    #[must_use = "this method creates new instance of interval"]
+    pub fn union(&self, other: Interval<T>) -> Interval<T> {
+        let mut union = *self;
+        union.extend_to_contain(other);
+        union
+    }
+
+    /// Extends the interval so it will contain the other interval.
+    #[inline]
+    pub fn extend_to_contain(&mut self, other: Interval<T>) {
+        if other.min < self.min {
+            self.min = other.min;
+        }
+        if other.max > self.max {
+            self.max = other.max;
+        }
+    }
+
+    /// Offsets the interval and returns a new interval.
+    #[inline]
+    #[must_use = "this method creates new instance of interval"]
+    pub fn translate(&self, offset: T) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Inflates the interval by the given amount on each side.
+    #[inline]
+    #[must_use = "this method creates new instance of interval"]
+    pub fn inflate(&self, amount: T) -> Self {
+        Self {
+            min: self.min - amount,
+            max: self.max + amount,
+        }
+    }
+}
+
+/// Static interval tree, that answers which intervals contain a value (stabbing queries) or
+/// intersect an interval (overlap queries) in logarithmic time plus the time to report the
+/// results. The intervals are kept sorted by their starts in a flat array, which is treated as an
+/// implicit balanced binary tree, where each node knows the largest end in its subtree.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IntervalTree<T, I> {
+    intervals: Vec<(Interval<T>, I)>,
+    /// The largest end of the intervals in the subtree of each node.
+    max_ends: Vec<T>,
+}
+
+impl<T, I> Default for IntervalTree<T, I> {
+    fn default() -> Self {
+        Self {
+            intervals: Default::default(),
+            max_ends: Default::default(),
+        }
+    }
+}
+
+impl<T, I> IntervalTree<T, I>
+where
+    T: Number,
+{
+    /// Builds the tree from the given intervals and their ids.
+    pub fn new(intervals: impl Iterator<Item = (Interval<T>, I)>) -> Self {
+        let mut intervals = intervals.collect::<Vec<_>>();
+        intervals.sort_by(|(a, _), (b, _)| a.min.partial_cmp(&b.min).unwrap_or(Ordering::Equal));
+        let mut max_ends = intervals
+            .iter()
+            .map(|(interval, _)| interval.max)
+            .collect::<Vec<_>>();
+        fill_max_ends(&mut max_ends, 0, intervals.len());
+        Self {
+            intervals,
+            max_ends,
+        }
+    }
+
+    /// Returns the amount of intervals in the tree.
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    /// Returns `true` if the tree has no intervals.
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Returns an iterator over the intervals and their ids sorted by the starts of the intervals.
+    pub fn iter(&self) -> impl Iterator<Item = (&Interval<T>, &I)> {
+        self.intervals.iter().map(|(interval, id)| (interval, id))
+    }
+
+    /// Visits the intervals in the subtree of the given range, skipping the subtrees, whose
+    /// intervals all end too early (`ends_after` fails) or start too late (`starts_before` fails).
+    fn query<F>(
+        &self,
+        range: (usize, usize),
+        filter: &impl Fn(&Interval<T>) -> bool,
+        ends_after: &impl Fn(T) -> bool,
+        starts_before: &impl Fn(T) -> bool,
+        func: &mut F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&Interval<T>, &I) -> ControlFlow<()>,
+    {
+        let (lo, hi) = range;
+        if lo >= hi {
+            return ControlFlow::Continue(());
+        }
+        let mid = lo + (hi - lo) / 2;
+        if !ends_after(self.max_ends[mid]) {
+            return ControlFlow::Continue(());
+        }
+        self.query((lo, mid), filter, ends_after, starts_before, func)?;
+        let (interval, id) = &self.intervals[mid];
+        if !starts_before(interval.min) {
+            // The intervals to the right start even later.
+            return ControlFlow::Continue(());
+        }
+        if filter(interval) {
+            func(interval, id)?;
+        }
+        self.query((mid + 1, hi), filter, ends_after, starts_before, func)
+    }
+
+    /// Passes the intervals, that contain the given value, and their ids to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn stabbing_query_with<F>(&self, value: T, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&Interval<T>, &I) -> ControlFlow<()>,
+    {
+        self.query(
+            (0, self.intervals.len()),
+            &|interval| interval.contains(value),
+            &|max_end| max_end >= value,
+            &|min| min <= value,
+            &mut func,
+        )
+    }
+
+    /// Passes the intervals, that intersect the given interval, and their ids to the given
+    /// closure. The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn overlap_query_with<F>(&self, interval: Interval<T>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&Interval<T>, &I) -> ControlFlow<()>,
+    {
+        self.query(
+            (0, self.intervals.len()),
+            &|other| other.intersects(interval),
+            &|max_end| max_end > interval.min,
+            &|min| min < interval.max,
+            &mut func,
+        )
+    }
+
+    /// Writes the ids of the intervals, that contain the given value, to the output storage.
+    pub fn stabbing_query<S>(&self, value: T, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.stabbing_query_with(value, |_, id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the intervals, that intersect the given interval, to the output storage.
+    pub fn overlap_query<S>(&self, interval: Interval<T>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.overlap_query_with(interval, |_, id| storage.push_or_break(id.clone()));
+    }
+}
+
+/// Replaces the end of each node of the implicit tree with the largest end in its subtree.
+fn fill_max_ends<T: Number>(max_ends: &mut [T], lo: usize, hi: usize) -> Option<T> {
+    if lo >= hi {
+        return None;
+    }
+    let mid = lo + (hi - lo) / 2;
+    let mut max_end = max_ends[mid];
+    for child in [
+        fill_max_ends(max_ends, lo, mid),
+        fill_max_ends(max_ends, mid + 1, hi),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if child > max_end {
+            max_end = child;
+        }
+    }
+    max_ends[mid] = max_end;
+    Some(max_end)
+}
+
+/// Node of [`SegmentTree`].
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct SegmentNode<T> {
+    /// The value, that was added to the whole subtree of the node. It is never pushed down.
+    add: i32,
+    /// The largest value in the subtree, including `add`.
+    max: i32,
+    /// The smallest value in the subtree, including `add`.
+    min: i32,
+    /// The total length of the segments of the subtree, that have the smallest value.
+    min_len: T,
+}
+
+/// Segment tree over a set of 1D coordinates, that splits the line into elementary segments
+/// between the adjacent coordinates. Each segment holds a counter, which could be changed for a
+/// range of segments at once, and the tree answers how many intervals cover a value, what is the
+/// maximal counter in a range and what is the length, covered by at least one interval. It is the
+/// backbone for sweep-line algorithms, like the area of a union of rectangles.
+///
+/// Intervals are expected to start and end at the coordinates of the tree, an interval, that ends
+/// between two coordinates, affects only the segments, that it covers entirely.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SegmentTree<T> {
+    coordinates: Vec<T>,
+    nodes: Vec<SegmentNode<T>>,
+}
+
+impl<T> SegmentTree<T>
+where
+    T: Number,
+{
+    /// Creates new segment tree over the given coordinates, all the counters are zero. The
+    /// coordinates are sorted and deduplicated.
+    pub fn new(coordinates: impl Iterator<Item = T>) -> Self {
+        let mut coordinates = coordinates.collect::<Vec<_>>();
+        coordinates.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        coordinates.dedup();
+        let segments = coordinates.len().saturating_sub(1);
+        let mut tree = Self {
+            coordinates,
+            nodes: vec![
+                SegmentNode {
+                    add: 0,
+                    max: 0,
+                    min: 0,
+                    min_len: T::zero(),
+                };
+                4 * segments.max(1)
+            ],
+        };
+        if segments > 0 {
+            tree.build(1, 0, segments);
+        }
+        tree
+    }
+
+    fn build(&mut self, node: usize, lo: usize, hi: usize) {
+        if hi - lo == 1 {
+            self.nodes[node].min_len = self.coordinates[hi] - self.coordinates[lo];
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        self.build(2 * node, lo, mid);
+        self.build(2 * node + 1, mid, hi);
+        self.nodes[node].min_len = self.nodes[2 * node].min_len + self.nodes[2 * node + 1].min_len;
+    }
+
+    /// Returns the sorted coordinates of the tree.
+    pub fn coordinates(&self) -> &[T] {
+        &self.coordinates
+    }
+
+    fn segments(&self) -> usize {
+        self.coordinates.len().saturating_sub(1)
+    }
+
+    /// Returns the range of the segments, that are entirely covered by the interval.
+    fn segment_range(&self, interval: Interval<T>) -> (usize, usize) {
+        let lo = self.coordinates.partition_point(|c| *c < interval.min);
+        let hi = self.coordinates.partition_point(|c| *c <= interval.max);
+        (lo, hi.saturating_sub(1).max(lo))
+    }
+
+    /// Adds the given value to the counters of the segments, that are covered by the interval.
+    /// Use `1` to insert an interval and `-1` to remove it.
+    pub fn add(&mut self, interval: Interval<T>, delta: i32) {
+        let (lo, hi) = self.segment_range(interval);
+        if lo < hi {
+            self.add_range(1, 0, self.segments(), lo, hi, delta);
+        }
+    }
+
+    fn add_range(&mut self, node: usize, lo: usize, hi: usize, from: usize, to: usize, delta: i32) {
+        if to <= lo || hi <= from {
+            return;
+        }
+        if from <= lo && hi <= to {
+            let node = &mut self.nodes[node];
+            node.add += delta;
+            node.max += delta;
+            node.min += delta;
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        self.add_range(2 * node, lo, mid, from, to, delta);
+        self.add_range(2 * node + 1, mid, hi, from, to, delta);
+        let (left, right) = (self.nodes[2 * node], self.nodes[2 * node + 1]);
+        let node = &mut self.nodes[node];
+        node.max = left.max.max(right.max) + node.add;
+        node.min = left.min.min(right.min) + node.add;
+        node.min_len = if left.min < right.min {
+            left.min_len
+        } else if right.min < left.min {
+            right.min_len
+        } else {
+            left.min_len + right.min_len
+        };
+    }
+
+    /// Returns the counter of the segment, that contains the given value, which is the amount of
+    /// the intervals, that cover the value. Values outside of the coordinates are not covered.
+    pub fn count_at(&self, value: T) -> i32 {
+        let segments = self.segments();
+        let index = self.coordinates.partition_point(|c| *c <= value);
+        if index == 0 || index > segments {
+            return 0;
+        }
+        let segment = index - 1;
+        let (mut node, mut lo, mut hi) = (1, 0, segments);
+        let mut count = 0;
+        loop {
+            count += self.nodes[node].add;
+            if hi - lo == 1 {
+                return count;
+            }
+            let mid = lo + (hi - lo) / 2;
+            if segment < mid {
+                (node, hi) = (2 * node, mid);
+            } else {
+                (node, lo) = (2 * node + 1, mid);
+            }
+        }
+    }
+
+    /// Returns the largest counter of the segments, that are covered by the interval, or `None`
+    /// if the interval covers no segments.
+    pub fn max(&self, interval: Interval<T>) -> Option<i32> {
+        let (lo, hi) = self.segment_range(interval);
+        (lo < hi).then(|| self.max_range(1, 0, self.segments(), lo, hi))
+    }
+
+    fn max_range(&self, node: usize, lo: usize, hi: usize, from: usize, to: usize) -> i32 {
+        if from <= lo && hi <= to {
+            return self.nodes[node].max;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = (from < mid).then(|| self.max_range(2 * node, lo, mid, from, to));
+        let right = (mid < to).then(|| self.max_range(2 * node + 1, mid, hi, from, to));
+        left.into_iter().chain(right).max().unwrap_or(0) + self.nodes[node].add
+    }
+
+    /// Returns the total length of the segments with a positive counter, i.e. the length, that is
+    /// covered by at least one interval. Counters must not be negative.
+    pub fn covered_len(&self) -> T {
+        if self.segments() == 0 {
+            return T::zero();
+        }
+        let total = self.coordinates[self.segments()] - self.coordinates[0];
+        let root = &self.nodes[1];
+        if root.min == 0 {
+            total - root.min_len
+        } else {
+            total
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Interval, IntervalTree, SegmentTree};
+
+    #[test]
+    fn interval_tree_queries() {
+        let intervals = (0..200)
+            .map(|i| Interval::new(i * 7 % 100, i * 7 % 100 + i % 13 + 1))
+            .collect::<Vec<_>>();
+        let tree = IntervalTree::new(intervals.iter().copied().zip(0..));
+        assert_eq!(tree.len(), 200);
+
+        for value in [0, 13, 50, 99, 200] {
+            let mut found = Vec::new();
+            tree.stabbing_query(value, &mut found);
+            found.sort_unstable();
+            let expected = (0..200)
+                .filter(|i| intervals[*i].contains(value))
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected);
+        }
+
+        for query in [
+            Interval::new(10, 12),
+            Interval::new(-5, 0),
+            Interval::new(95, 150),
+        ] {
+            let mut found = Vec::new();
+            tree.overlap_query(query, &mut found);
+            found.sort_unstable();
+            let expected = (0..200)
+                .filter(|i| intervals[*i].intersects(query))
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn interval_basics() {
+        let a = Interval::new(1.0, 3.0);
+        assert_eq!(a.len(), 2.0);
+        assert!(a.contains(3.0));
+        assert!(!a.intersects(Interval::new(3.0, 4.0)));
+        assert!(a.intersects(Interval::new(2.5, 4.0)));
+        assert!(Interval::new(2, 2).is_empty());
+        assert_eq!(a.center(), 2.0);
+        assert_eq!(
+            a.clip_by(Interval::new(2.0, 5.0)),
+            Some(Interval::new(2.0, 3.0))
+        );
+        assert_eq!(a.clip_by(Interval::new(4.0, 5.0)), None);
+        assert_eq!(a.union(Interval::new(5.0, 6.0)), Interval::new(1.0, 6.0));
+        assert_eq!(a.translate(1.0), Interval::from_len(2.0, 2.0));
+        assert_eq!(a.inflate(0.5), Interval::new(0.5, 3.5));
+    }
+
+    #[test]
+    fn segment_tree_coverage() {
+        let intervals = [
+            Interval::new(0.0, 4.0),
+            Interval::new(2.0, 6.0),
+            Interval::new(3.0, 5.0),
+            Interval::new(8.0, 9.0),
+        ];
+        let mut tree = SegmentTree::new(intervals.iter().flat_map(|i| [i.min, i.max]));
+        for interval in intervals {
+            tree.add(interval, 1);
+        }
+        assert_eq!(tree.count_at(3.5), 3);
+        assert_eq!(tree.count_at(1.0), 1);
+        assert_eq!(tree.count_at(7.0), 0);
+        assert_eq!(tree.count_at(10.0), 0);
+        assert_eq!(tree.max(Interval::new(0.0, 9.0)), Some(3));
+        assert_eq!(tree.max(Interval::new(5.0, 9.0)), Some(1));
+        assert_eq!(tree.max(Interval::new(4.5, 4.6)), None);
+        assert_eq!(tree.covered_len(), 7.0);
+
+        tree.add(intervals[0], -1);
+        assert_eq!(tree.count_at(1.0), 0);
+        assert_eq!(tree.covered_len(), 5.0);
+    }
+}
@@ -0,0 +1,255 @@
+//! KD-tree is a static binary tree for point clouds, that answers nearest-neighbor and range
+//! queries.
+
+use crate::{quadtree::QueryStorage, Rect};
+use nalgebra::Vector2;
+use std::{cmp::Ordering, ops::ControlFlow};
+
+/// Static two-dimensional tree of points. The points are kept in a flat array, which is treated as
+/// an implicit balanced binary tree: the middle point of each range splits the rest of the range
+/// by its x (on even levels) or y (on odd levels) coordinate. Unlike the other spatial structures
+/// of the crate, it stores plain points and not rectangles, which suits things like spawn locations
+/// or waypoints.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KdTree<I> {
+    points: Vec<(Vector2<f32>, I)>,
+}
+
+impl<I> Default for KdTree<I> {
+    fn default() -> Self {
+        Self {
+            points: Default::default(),
+        }
+    }
+}
+
+impl<I> KdTree<I> {
+    /// Builds the tree from the given points and their ids.
+    pub fn new(points: impl Iterator<Item = (Vector2<f32>, I)>) -> Self {
+        let mut points = points.collect::<Vec<_>>();
+        build(&mut points, 0);
+        Self { points }
+    }
+
+    /// Returns the amount of points in the tree.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Returns `true` if the tree has no points.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Returns an iterator over the points and their ids in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Vector2<f32>, &I)> {
+        self.points.iter().map(|(point, id)| (point, id))
+    }
+
+    /// Returns the point, that is the closest to the given one, and its id, or `None` if the
+    /// tree is empty.
+    pub fn nearest(&self, point: Vector2<f32>) -> Option<(&Vector2<f32>, &I)> {
+        let mut best = None;
+        self.nearest_in(point, 0, self.points.len(), 0, &mut best);
+        best.map(|(index, _)| {
+            let (point, id) = &self.points[index];
+            (point, id)
+        })
+    }
+
+    fn nearest_in(
+        &self,
+        point: Vector2<f32>,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        best: &mut Option<(usize, f32)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let (split, _) = &self.points[mid];
+        let distance = (split - point).norm_squared();
+        if best.map_or(true, |(_, best)| distance < best) {
+            *best = Some((mid, distance));
+        }
+        let axis = depth % 2;
+        let offset = point[axis] - split[axis];
+        let (near, far) = if offset < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.nearest_in(point, near.0, near.1, depth + 1, best);
+        // The far side could hold a closer point only if the splitting line is closer than the
+        // best point so far.
+        if best.map_or(true, |(_, best)| offset * offset < best) {
+            self.nearest_in(point, far.0, far.1, depth + 1, best);
+        }
+    }
+
+    /// Visits the points in the given range, skipping the subtrees, that lie entirely outside of
+    /// the area, and passes the points, that pass the filter, to the closure.
+    fn query<F>(
+        &self,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        area: &Rect<f32>,
+        filter: &impl Fn(&Vector2<f32>) -> bool,
+        func: &mut F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&Vector2<f32>, &I) -> ControlFlow<()>,
+    {
+        if lo >= hi {
+            return ControlFlow::Continue(());
+        }
+        let mid = lo + (hi - lo) / 2;
+        let (split, id) = &self.points[mid];
+        let axis = depth % 2;
+        let (min, max) = if axis == 0 {
+            (area.x(), area.x() + area.w())
+        } else {
+            (area.y(), area.y() + area.h())
+        };
+        if min <= split[axis] {
+            self.query(lo, mid, depth + 1, area, filter, func)?;
+        }
+        if filter(split) {
+            func(split, id)?;
+        }
+        if split[axis] <= max {
+            self.query(mid + 1, hi, depth + 1, area, filter, func)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the points, that lie inside the given rectangle, and their ids to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&Vector2<f32>, &I) -> ControlFlow<()>,
+    {
+        self.query(
+            0,
+            self.points.len(),
+            0,
+            &rect,
+            &|point| rect.contains(*point),
+            &mut func,
+        )
+    }
+
+    /// Passes the points, that lie inside the given circle, and their ids to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn circle_query_with<F>(
+        &self,
+        center: Vector2<f32>,
+        radius: f32,
+        mut func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&Vector2<f32>, &I) -> ControlFlow<()>,
+    {
+        let area = Rect::new(
+            center.x - radius,
+            center.y - radius,
+            radius + radius,
+            radius + radius,
+        );
+        self.query(
+            0,
+            self.points.len(),
+            0,
+            &area,
+            &|point| (point - center).norm_squared() <= radius * radius,
+            &mut func,
+        )
+    }
+
+    /// Writes the ids of the points, that lie inside the given rectangle, to the output storage.
+    pub fn rect_query<S>(&self, rect: Rect<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.rect_query_with(rect, |_, id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the points, that lie inside the given circle, to the output storage.
+    pub fn circle_query<S>(&self, center: Vector2<f32>, radius: f32, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.circle_query_with(center, radius, |_, id| storage.push_or_break(id.clone()));
+    }
+}
+
+/// Reorders the points so, that the middle one splits the rest by the coordinate of the level.
+fn build<I>(points: &mut [(Vector2<f32>, I)], depth: usize) {
+    if points.len() <= 1 {
+        return;
+    }
+    let axis = depth % 2;
+    let mid = points.len() / 2;
+    points.select_nth_unstable_by(mid, |(a, _), (b, _)| {
+        a[axis].partial_cmp(&b[axis]).unwrap_or(Ordering::Equal)
+    });
+    let (left, right) = points.split_at_mut(mid);
+    build(left, depth + 1);
+    build(&mut right[1..], depth + 1);
+}
+
+#[cfg(test)]
+mod test {
+    use super::KdTree;
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn kdtree_queries() {
+        let points = (0..300)
+            .map(|i| Vector2::new((i * 37 % 101) as f32, (i * 53 % 97) as f32))
+            .collect::<Vec<_>>();
+        let tree = KdTree::new(points.iter().copied().zip(0..));
+        assert_eq!(tree.len(), 300);
+
+        for query in [
+            Vector2::new(50.5, 50.5),
+            Vector2::new(-20.0, 3.0),
+            Vector2::new(200.0, 200.0),
+        ] {
+            let (nearest, _) = tree.nearest(query).unwrap();
+            let expected = points
+                .iter()
+                .map(|p| (p - query).norm_squared())
+                .fold(f32::MAX, f32::min);
+            assert_eq!((nearest - query).norm_squared(), expected);
+        }
+
+        let rect = Rect::new(10.0, 20.0, 30.0, 15.0);
+        let mut found = Vec::new();
+        tree.rect_query(rect, &mut found);
+        found.sort_unstable();
+        let expected = (0..300)
+            .filter(|i| rect.contains(points[*i]))
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+
+        found.clear();
+        tree.circle_query(Vector2::new(40.0, 60.0), 12.0, &mut found);
+        found.sort_unstable();
+        let expected = (0..300)
+            .filter(|i| (points[*i] - Vector2::new(40.0, 60.0)).norm() <= 12.0)
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+
+        assert!(KdTree::<usize>::default()
+            .nearest(Vector2::zeros())
+            .is_none());
+    }
+}
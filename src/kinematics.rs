@@ -0,0 +1,220 @@
+//! Continuous collision detection and collide-and-slide movement of rectangles among static
+//! rectangles, the usual movement routine of two-dimensional platformers.
+
+use crate::{quadtree::BoundsProvider, Rect};
+use nalgebra::Vector2;
+
+/// Rectangles, that overlap each other by less than this distance, are considered touching. It
+/// compensates the rounding errors, that would otherwise let a rectangle, that rests on another
+/// one, fall through it.
+const CONTACT_TOLERANCE: f32 = 1.0e-4;
+
+/// The first contact of a moving rectangle with an obstacle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// Fraction of the displacement, at which the rectangles touch, in range `0.0..1.0`.
+    pub time: f32,
+    /// Normal of the surface of the obstacle at the contact, it points towards the moving
+    /// rectangle.
+    pub normal: Vector2<f32>,
+}
+
+/// Returns the range of the times, when the moving span overlaps the static one along one axis,
+/// and the distance between the spans at the start (negative if they overlap).
+fn axis_times(
+    min: f32,
+    size: f32,
+    obstacle_min: f32,
+    obstacle_size: f32,
+    delta: f32,
+) -> (f32, f32, f32) {
+    let gap = if delta >= 0.0 {
+        obstacle_min - (min + size)
+    } else {
+        min - (obstacle_min + obstacle_size)
+    };
+    if delta == 0.0 {
+        return if min < obstacle_min + obstacle_size && obstacle_min < min + size {
+            (f32::NEG_INFINITY, f32::INFINITY, gap)
+        } else {
+            (f32::INFINITY, f32::NEG_INFINITY, gap)
+        };
+    }
+    let entry = gap / delta.abs();
+    let exit = (gap + size + obstacle_size) / delta.abs();
+    (entry, exit, gap)
+}
+
+/// Finds the first contact of the rectangle, that moves by the given displacement, with the
+/// obstacle. Returns `None` if the rectangle does not hit the obstacle or already overlaps it
+/// (more than by a tiny tolerance), so the rectangles, that got stuck in each other, could move
+/// apart.
+pub fn sweep(rect: Rect<f32>, displacement: Vector2<f32>, obstacle: Rect<f32>) -> Option<Hit> {
+    let (entry_x, exit_x, gap_x) = axis_times(
+        rect.x(),
+        rect.w(),
+        obstacle.x(),
+        obstacle.w(),
+        displacement.x,
+    );
+    let (entry_y, exit_y, gap_y) = axis_times(
+        rect.y(),
+        rect.h(),
+        obstacle.y(),
+        obstacle.h(),
+        displacement.y,
+    );
+    let (entry, gap, normal) = if entry_x >= entry_y {
+        (entry_x, gap_x, Vector2::new(-displacement.x.signum(), 0.0))
+    } else {
+        (entry_y, gap_y, Vector2::new(0.0, -displacement.y.signum()))
+    };
+    let exit = exit_x.min(exit_y);
+    if entry >= exit || entry >= 1.0 || exit <= 0.0 || gap < -CONTACT_TOLERANCE {
+        return None;
+    }
+    Some(Hit {
+        time: entry.max(0.0),
+        normal,
+    })
+}
+
+/// A contact with an obstacle, that happened during [`move_and_slide`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contact<I> {
+    /// Id of the obstacle.
+    pub id: I,
+    /// Normal of the surface of the obstacle at the contact, it points towards the moving
+    /// rectangle.
+    pub normal: Vector2<f32>,
+}
+
+/// Result of [`move_and_slide`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlideResult<I> {
+    /// The rectangle at its new position.
+    pub rect: Rect<f32>,
+    /// The velocity without the components, that point into the obstacles, that were hit. For
+    /// example, the vertical velocity of a character, that landed on a floor, becomes zero.
+    pub velocity: Vector2<f32>,
+    /// Contacts with the obstacles in the order they happened.
+    pub contacts: Vec<Contact<I>>,
+}
+
+/// Returns the rectangle, that covers the entire path of the rectangle moving by the given
+/// displacement. Use it to collect the obstacles for [`move_and_slide`] from a spatial structure,
+/// such as [`crate::quadtree::QuadTree`].
+pub fn swept_bounds(rect: Rect<f32>, displacement: Vector2<f32>) -> Rect<f32> {
+    let mut bounds = rect;
+    bounds.extend_to_contain(rect.translate(displacement));
+    bounds
+}
+
+/// Moves the rectangle with the given velocity for the given time among the static obstacles.
+/// When the rectangle hits an obstacle, it stops at the contact and continues to move with the
+/// rest of the displacement projected on the surface of the obstacle, so it slides along walls
+/// and floors instead of sticking to them. At most `max_slides` contacts are resolved, the rest
+/// of the movement is dropped after that.
+pub fn move_and_slide<T, I>(
+    rect: Rect<f32>,
+    velocity: Vector2<f32>,
+    dt: f32,
+    obstacles: impl IntoIterator<Item = T> + Clone,
+    max_slides: usize,
+) -> SlideResult<I>
+where
+    T: BoundsProvider<Id = I>,
+{
+    let mut rect = rect;
+    let mut velocity = velocity;
+    let mut displacement = velocity * dt;
+    let mut contacts = Vec::new();
+    for _ in 0..max_slides {
+        if displacement == Vector2::zeros() {
+            break;
+        }
+        let mut first = None::<(Hit, T)>;
+        for obstacle in obstacles.clone() {
+            if let Some(hit) = sweep(rect, displacement, obstacle.bounds()) {
+                if first
+                    .as_ref()
+                    .map_or(true, |(first, _)| hit.time < first.time)
+                {
+                    first = Some((hit, obstacle));
+                }
+            }
+        }
+        let Some((hit, obstacle)) = first else {
+            rect.position += displacement;
+            return SlideResult {
+                rect,
+                velocity,
+                contacts,
+            };
+        };
+        rect.position += displacement * hit.time;
+        let remaining = displacement * (1.0 - hit.time);
+        displacement = remaining - hit.normal * remaining.dot(&hit.normal);
+        velocity -= hit.normal * velocity.dot(&hit.normal).min(0.0);
+        contacts.push(Contact {
+            id: obstacle.id(),
+            normal: hit.normal,
+        });
+    }
+    SlideResult {
+        rect,
+        velocity,
+        contacts,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{move_and_slide, sweep, swept_bounds};
+    use crate::{test_utils::TestObject, Rect};
+    use nalgebra::Vector2;
+
+    #[test]
+    fn collide_and_slide() {
+        let player = Rect::new(0.0, 0.0, 10.0, 20.0);
+        let floor = Rect::new(-100.0, 30.0, 200.0, 10.0);
+        let hit = sweep(player, Vector2::new(0.0, 20.0), floor).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, Vector2::new(0.0, -1.0));
+        assert_eq!(sweep(player, Vector2::new(0.0, -20.0), floor), None);
+        assert_eq!(
+            swept_bounds(player, Vector2::new(5.0, -5.0)),
+            Rect::new(0.0, -5.0, 15.0, 25.0)
+        );
+
+        let obstacles = [
+            TestObject {
+                bounds: floor,
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(40.0, -100.0, 10.0, 130.0),
+                id: 1,
+            },
+        ];
+        // Falls diagonally, lands on the floor, slides along it and stops at the wall.
+        let result = move_and_slide(player, Vector2::new(100.0, 40.0), 0.5, obstacles.iter(), 4);
+        assert_eq!(result.rect, Rect::new(30.0, 10.0, 10.0, 20.0));
+        assert_eq!(result.velocity, Vector2::new(0.0, 0.0));
+        assert_eq!(
+            result.contacts.iter().map(|c| c.id).collect::<Vec<_>>(),
+            [0, 1]
+        );
+
+        // Standing on the floor and walking does not fall through it.
+        let result = move_and_slide(
+            result.rect,
+            Vector2::new(-10.0, 10.0),
+            1.0,
+            obstacles.iter(),
+            4,
+        );
+        assert_eq!(result.rect, Rect::new(20.0, 10.0, 10.0, 20.0));
+        assert_eq!(result.velocity, Vector2::new(-10.0, 0.0));
+    }
+}
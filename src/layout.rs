@@ -0,0 +1,5 @@
+//! Layout solvers, that arrange rectangles of user interface elements inside of a container.
+
+pub mod anchor;
+pub mod flex;
+pub mod grid;
@@ -0,0 +1,345 @@
+//! Anchor layout: the edges of each rectangle are attached to the parent rectangle or to other
+//! rectangles, and the rectangles are solved in the order of their dependencies.
+
+use crate::Rect;
+use nalgebra::Vector2;
+use std::fmt::{Display, Formatter};
+
+/// A rectangle, to which an edge is anchored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AnchorTarget {
+    /// The rectangle of the container.
+    Parent,
+    /// The solved rectangle of another node, given by its index.
+    Node(usize),
+}
+
+/// Attachment of an edge to a position along the same axis of the target rectangle: the position
+/// is `start + fraction * size + offset`, where `start` and `size` are the left (top) edge and the
+/// width (height) of the target. The fraction `0.0` is the start edge of the target, `1.0` is its
+/// end edge and `0.5` is its center.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Anchor {
+    /// The rectangle, to which the edge is attached.
+    pub target: AnchorTarget,
+    /// The relative position along the target.
+    pub fraction: f32,
+    /// The offset from the relative position.
+    pub offset: f32,
+}
+
+impl Anchor {
+    /// Creates new anchor to the given fraction of the target with the given offset.
+    pub fn new(target: AnchorTarget, fraction: f32, offset: f32) -> Self {
+        Self {
+            target,
+            fraction,
+            offset,
+        }
+    }
+
+    /// Creates new anchor to the left or top edge of the target.
+    pub fn start(target: AnchorTarget, offset: f32) -> Self {
+        Self::new(target, 0.0, offset)
+    }
+
+    /// Creates new anchor to the center of the target.
+    pub fn center(target: AnchorTarget, offset: f32) -> Self {
+        Self::new(target, 0.5, offset)
+    }
+
+    /// Creates new anchor to the right or bottom edge of the target.
+    pub fn end(target: AnchorTarget, offset: f32) -> Self {
+        Self::new(target, 1.0, offset)
+    }
+}
+
+/// Anchors of a rectangle along one axis. When both edges are anchored, the rectangle stretches
+/// between them, otherwise it keeps its preferred size and is placed by the edge, that is
+/// anchored, or by the center, or at the start of the container, if nothing is anchored.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisAnchors {
+    /// Anchor of the left or top edge.
+    pub start: Option<Anchor>,
+    /// Anchor of the center, it is used when none of the edges is anchored.
+    pub center: Option<Anchor>,
+    /// Anchor of the right or bottom edge.
+    pub end: Option<Anchor>,
+}
+
+impl AxisAnchors {
+    fn targets(&self) -> impl Iterator<Item = AnchorTarget> + '_ {
+        [self.start, self.center, self.end]
+            .into_iter()
+            .flatten()
+            .map(|anchor| anchor.target)
+    }
+}
+
+/// A rectangle of an anchor layout.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnchorNode {
+    /// The size of the rectangle along the axes, that are not stretched by the anchors.
+    pub size: Vector2<f32>,
+    /// Horizontal anchors.
+    pub horizontal: AxisAnchors,
+    /// Vertical anchors.
+    pub vertical: AxisAnchors,
+}
+
+impl AnchorNode {
+    /// Creates new node of the given preferred size without anchors.
+    pub fn new(size: Vector2<f32>) -> Self {
+        Self {
+            size,
+            ..Default::default()
+        }
+    }
+
+    /// Anchors the left edge.
+    pub fn with_left(mut self, anchor: Anchor) -> Self {
+        self.horizontal.start = Some(anchor);
+        self
+    }
+
+    /// Anchors the right edge.
+    pub fn with_right(mut self, anchor: Anchor) -> Self {
+        self.horizontal.end = Some(anchor);
+        self
+    }
+
+    /// Anchors the top edge.
+    pub fn with_top(mut self, anchor: Anchor) -> Self {
+        self.vertical.start = Some(anchor);
+        self
+    }
+
+    /// Anchors the bottom edge.
+    pub fn with_bottom(mut self, anchor: Anchor) -> Self {
+        self.vertical.end = Some(anchor);
+        self
+    }
+
+    /// Anchors the horizontal center.
+    pub fn with_center_x(mut self, anchor: Anchor) -> Self {
+        self.horizontal.center = Some(anchor);
+        self
+    }
+
+    /// Anchors the vertical center.
+    pub fn with_center_y(mut self, anchor: Anchor) -> Self {
+        self.vertical.center = Some(anchor);
+        self
+    }
+}
+
+/// An error, that may occur while solving an anchor layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnchorError {
+    /// The node is anchored to a node, that does not exist.
+    InvalidTarget {
+        /// Index of the anchored node.
+        node: usize,
+        /// Index of the missing target.
+        target: usize,
+    },
+    /// The node depends on itself through a chain of anchors.
+    Cycle {
+        /// Index of a node of the cycle.
+        node: usize,
+    },
+}
+
+impl Display for AnchorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnchorError::InvalidTarget { node, target } => {
+                write!(f, "node {node} is anchored to missing node {target}")
+            }
+            AnchorError::Cycle { node } => {
+                write!(
+                    f,
+                    "node {node} is anchored to itself through a cycle of anchors"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnchorError {}
+
+/// Solves the position and the size of a rectangle along one axis.
+fn solve_axis(
+    anchors: &AxisAnchors,
+    size: f32,
+    parent: (f32, f32),
+    rects: &[Rect<f32>],
+    axis: usize,
+) -> (f32, f32) {
+    let resolve = |anchor: Anchor| {
+        let (start, len) = match anchor.target {
+            AnchorTarget::Parent => parent,
+            AnchorTarget::Node(index) => (rects[index].position[axis], rects[index].size[axis]),
+        };
+        start + anchor.fraction * len + anchor.offset
+    };
+    match (anchors.start, anchors.center, anchors.end) {
+        (Some(start), _, Some(end)) => {
+            let (start, end) = (resolve(start), resolve(end));
+            (start, (end - start).max(0.0))
+        }
+        (Some(start), _, None) => (resolve(start), size),
+        (None, _, Some(end)) => (resolve(end) - size, size),
+        (None, Some(center), None) => (resolve(center) - size * 0.5, size),
+        (None, None, None) => (parent.0, size),
+    }
+}
+
+/// Returns the indices of the nodes, the node is anchored to.
+fn node_targets(node: &AnchorNode) -> impl Iterator<Item = usize> + '_ {
+    node.horizontal
+        .targets()
+        .chain(node.vertical.targets())
+        .filter_map(|target| match target {
+            AnchorTarget::Parent => None,
+            AnchorTarget::Node(target) => Some(target),
+        })
+}
+
+/// Solves the rectangles of the nodes inside of the parent rectangle. The nodes are solved in the
+/// order of their dependencies, so a node could be anchored to any other node, as long as the
+/// anchors do not form a cycle.
+pub fn solve_anchors(
+    parent: Rect<f32>,
+    nodes: &[AnchorNode],
+) -> Result<Vec<Rect<f32>>, AnchorError> {
+    let mut dependents = vec![Vec::new(); nodes.len()];
+    let mut pending = vec![0usize; nodes.len()];
+    for (index, node) in nodes.iter().enumerate() {
+        let mut targets = node_targets(node).collect::<Vec<_>>();
+        targets.sort_unstable();
+        targets.dedup();
+        for target in targets {
+            if target >= nodes.len() {
+                return Err(AnchorError::InvalidTarget {
+                    node: index,
+                    target,
+                });
+            }
+            dependents[target].push(index);
+            pending[index] += 1;
+        }
+    }
+
+    let mut order = (0..nodes.len())
+        .filter(|index| pending[*index] == 0)
+        .collect::<Vec<_>>();
+    let mut next = 0;
+    while next < order.len() {
+        for dependent in dependents[order[next]].iter() {
+            pending[*dependent] -= 1;
+            if pending[*dependent] == 0 {
+                order.push(*dependent);
+            }
+        }
+        next += 1;
+    }
+    if let Some(mut node) = pending.iter().position(|count| *count > 0) {
+        // Every unsolved node waits for an unsolved target, so following such targets long
+        // enough ends up going around a cycle.
+        for _ in 0..nodes.len() {
+            if let Some(target) = node_targets(&nodes[node]).find(|target| pending[*target] > 0) {
+                node = target;
+            }
+        }
+        return Err(AnchorError::Cycle { node });
+    }
+
+    let mut rects = vec![Rect::default(); nodes.len()];
+    for index in order {
+        let node = &nodes[index];
+        let (x, w) = solve_axis(
+            &node.horizontal,
+            node.size.x,
+            (parent.x(), parent.w()),
+            &rects,
+            0,
+        );
+        let (y, h) = solve_axis(
+            &node.vertical,
+            node.size.y,
+            (parent.y(), parent.h()),
+            &rects,
+            1,
+        );
+        rects[index] = Rect::new(x, y, w, h);
+    }
+    Ok(rects)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{solve_anchors, Anchor, AnchorError, AnchorNode, AnchorTarget};
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn anchor_layout() {
+        use AnchorTarget::{Node, Parent};
+        let parent = Rect::new(0.0, 0.0, 800.0, 600.0);
+        let nodes = [
+            // A health bar under the minimap, as wide as the minimap.
+            AnchorNode::new(Vector2::new(0.0, 20.0))
+                .with_left(Anchor::start(Node(1), 0.0))
+                .with_right(Anchor::end(Node(1), 0.0))
+                .with_top(Anchor::end(Node(1), 5.0)),
+            // A minimap in the right top corner.
+            AnchorNode::new(Vector2::new(150.0, 100.0))
+                .with_right(Anchor::end(Parent, -10.0))
+                .with_top(Anchor::start(Parent, 10.0)),
+            // A dialog, centered and taking a half of the width.
+            AnchorNode::new(Vector2::new(0.0, 200.0))
+                .with_left(Anchor::new(Parent, 0.25, 0.0))
+                .with_right(Anchor::new(Parent, 0.75, 0.0))
+                .with_center_y(Anchor::center(Parent, 0.0)),
+        ];
+        assert_eq!(
+            solve_anchors(parent, &nodes),
+            Ok(vec![
+                Rect::new(640.0, 115.0, 150.0, 20.0),
+                Rect::new(640.0, 10.0, 150.0, 100.0),
+                Rect::new(200.0, 200.0, 400.0, 200.0),
+            ])
+        );
+
+        let cycle = [
+            AnchorNode::default().with_left(Anchor::end(Node(1), 0.0)),
+            AnchorNode::default().with_top(Anchor::end(Node(0), 0.0)),
+        ];
+        assert!(matches!(
+            solve_anchors(parent, &cycle),
+            Err(AnchorError::Cycle { .. })
+        ));
+        // The first node is not a part of the cycle, it only depends on it.
+        let dependent = [
+            AnchorNode::default().with_left(Anchor::end(Node(1), 0.0)),
+            AnchorNode::default().with_left(Anchor::end(Node(2), 0.0)),
+            AnchorNode::default().with_top(Anchor::end(Node(1), 0.0)),
+        ];
+        assert_eq!(
+            solve_anchors(parent, &dependent),
+            Err(AnchorError::Cycle { node: 1 })
+        );
+        assert_eq!(
+            solve_anchors(
+                parent,
+                &[AnchorNode::default().with_left(Anchor::end(Node(5), 0.0))]
+            ),
+            Err(AnchorError::InvalidTarget { node: 0, target: 5 })
+        );
+    }
+}
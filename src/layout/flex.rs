@@ -0,0 +1,296 @@
+//! Flexible box layout: the items are arranged in a row or a column, grow or shrink to fill the
+//! container and optionally wrap to new lines.
+
+use crate::Rect;
+use nalgebra::Vector2;
+
+/// Main axis of a flexible layout, along which the items are arranged.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// The items go from the left to the right.
+    #[default]
+    Row,
+    /// The items go from the top to the bottom.
+    Column,
+}
+
+/// Distribution of the free space of a line along the main axis.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Justify {
+    /// The items are packed to the start of the line.
+    #[default]
+    Start,
+    /// The items are packed to the center of the line.
+    Center,
+    /// The items are packed to the end of the line.
+    End,
+    /// The free space goes between the items, the first and the last items touch the ends of the
+    /// line.
+    SpaceBetween,
+    /// Each item gets the same free space on both of its sides.
+    SpaceAround,
+    /// The free space is the same between the items and at the ends of the line.
+    SpaceEvenly,
+}
+
+/// Placement of the items inside of their line along the cross axis.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Align {
+    /// The items are placed at the start of the line.
+    Start,
+    /// The items are centered in the line.
+    Center,
+    /// The items are placed at the end of the line.
+    End,
+    /// The items take the entire cross size of the line.
+    #[default]
+    Stretch,
+}
+
+/// An item of a flexible layout.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlexItem {
+    /// The preferred size of the item, its component along the main axis is the basis, from which
+    /// the item grows or shrinks.
+    pub size: Vector2<f32>,
+    /// The share of the free space of the line, that the item takes.
+    pub grow: f32,
+    /// The share of the lacking space of the line, that the item gives up, relative to its basis.
+    pub shrink: f32,
+}
+
+impl FlexItem {
+    /// Creates new item of the given preferred size, that does not grow and shrinks as the other
+    /// items.
+    pub fn new(size: Vector2<f32>) -> Self {
+        Self {
+            size,
+            grow: 0.0,
+            shrink: 1.0,
+        }
+    }
+
+    /// Sets the share of the free space, that the item takes.
+    pub fn with_grow(mut self, grow: f32) -> Self {
+        self.grow = grow;
+        self
+    }
+
+    /// Sets the share of the lacking space, that the item gives up.
+    pub fn with_shrink(mut self, shrink: f32) -> Self {
+        self.shrink = shrink;
+        self
+    }
+}
+
+/// Options of a flexible layout.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlexLayout {
+    /// The main axis.
+    pub direction: Direction,
+    /// Whether the items, that do not fit into a line, go to the next one.
+    pub wrap: bool,
+    /// The gap between the neighbouring items of a line.
+    pub gap: f32,
+    /// The gap between the neighbouring lines.
+    pub line_gap: f32,
+    /// Distribution of the free space along the main axis.
+    pub justify: Justify,
+    /// Placement of the items along the cross axis.
+    pub align: Align,
+}
+
+impl FlexLayout {
+    /// Creates new layout along the given axis, without wrapping and gaps, that packs the items
+    /// to the start and stretches them.
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            ..Default::default()
+        }
+    }
+
+    /// Sets whether the items wrap to new lines.
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Sets the gap between the items and the gap between the lines.
+    pub fn with_gap(mut self, gap: f32, line_gap: f32) -> Self {
+        self.gap = gap;
+        self.line_gap = line_gap;
+        self
+    }
+
+    /// Sets the distribution of the free space along the main axis.
+    pub fn with_justify(mut self, justify: Justify) -> Self {
+        self.justify = justify;
+        self
+    }
+
+    /// Sets the placement of the items along the cross axis.
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Splits the sizes along the main axis into the lines, returns the ranges of the items.
+    fn lines(&self, sizes: &[f32], available: f32) -> Vec<(usize, usize)> {
+        let mut lines = Vec::new();
+        let (mut start, mut used) = (0, 0.0);
+        for (i, size) in sizes.iter().enumerate() {
+            if self.wrap && i > start && used + self.gap + size > available {
+                lines.push((start, i));
+                start = i;
+            }
+            used = if i == start {
+                *size
+            } else {
+                used + self.gap + size
+            };
+        }
+        if start < sizes.len() {
+            lines.push((start, sizes.len()));
+        }
+        lines
+    }
+
+    /// Arranges the items inside of the bounds and returns the rectangle of each item, in the
+    /// order of the items. Without wrapping, the single line takes the entire cross size of the
+    /// bounds, otherwise each line is as large as its largest item.
+    pub fn solve(&self, bounds: Rect<f32>, items: &[FlexItem]) -> Vec<Rect<f32>> {
+        let (main, cross) = match self.direction {
+            Direction::Row => (0, 1),
+            Direction::Column => (1, 0),
+        };
+        let available = bounds.size[main];
+        let bases = items
+            .iter()
+            .map(|item| item.size[main].max(0.0))
+            .collect::<Vec<_>>();
+
+        let mut rects = vec![Rect::default(); items.len()];
+        let mut line_start = bounds.position[cross];
+        for (start, end) in self.lines(&bases, available) {
+            let line = &items[start..end];
+            let count = line.len();
+            let gaps = self.gap * (count - 1) as f32;
+            let mut sizes = bases[start..end].to_vec();
+            let mut free = available - gaps - sizes.iter().sum::<f32>();
+            let grow = line.iter().map(|item| item.grow.max(0.0)).sum::<f32>();
+            let shrink = line
+                .iter()
+                .zip(sizes.iter())
+                .map(|(item, basis)| item.shrink.max(0.0) * basis)
+                .sum::<f32>();
+            if free > 0.0 && grow > 0.0 {
+                for (size, item) in sizes.iter_mut().zip(line) {
+                    *size += free * item.grow.max(0.0) / grow;
+                }
+                free = 0.0;
+            } else if free < 0.0 && shrink > 0.0 {
+                for (size, item) in sizes.iter_mut().zip(line) {
+                    *size = (*size + free * item.shrink.max(0.0) * *size / shrink).max(0.0);
+                }
+                free = 0.0;
+            }
+            let free = free.max(0.0);
+
+            let (offset, spacing) = match self.justify {
+                Justify::Start => (0.0, 0.0),
+                Justify::Center => (free * 0.5, 0.0),
+                Justify::End => (free, 0.0),
+                Justify::SpaceBetween if count > 1 => (0.0, free / (count - 1) as f32),
+                Justify::SpaceBetween => (0.0, 0.0),
+                Justify::SpaceAround => (free / count as f32 * 0.5, free / count as f32),
+                Justify::SpaceEvenly => (free / (count + 1) as f32, free / (count + 1) as f32),
+            };
+
+            let line_size = if self.wrap {
+                line.iter()
+                    .map(|item| item.size[cross].max(0.0))
+                    .fold(0.0, f32::max)
+            } else {
+                bounds.size[cross]
+            };
+
+            let mut position = bounds.position[main] + offset;
+            for (i, (item, size)) in line.iter().zip(sizes).enumerate() {
+                let item_size = match self.align {
+                    Align::Stretch => line_size,
+                    _ => item.size[cross].max(0.0),
+                };
+                let item_start = match self.align {
+                    Align::Start | Align::Stretch => line_start,
+                    Align::Center => line_start + (line_size - item_size) * 0.5,
+                    Align::End => line_start + line_size - item_size,
+                };
+                let rect = &mut rects[start + i];
+                rect.position[main] = position;
+                rect.position[cross] = item_start;
+                rect.size[main] = size;
+                rect.size[cross] = item_size;
+                position += size + self.gap + spacing;
+            }
+            line_start += line_size + self.line_gap;
+        }
+        rects
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Align, Direction, FlexItem, FlexLayout, Justify};
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn flex_layout() {
+        // A toolbar: two buttons and a search field, that takes the rest of the space.
+        let items = [
+            FlexItem::new(Vector2::new(20.0, 20.0)),
+            FlexItem::new(Vector2::new(20.0, 20.0)),
+            FlexItem::new(Vector2::new(50.0, 10.0)).with_grow(1.0),
+        ];
+        let layout = FlexLayout::new(Direction::Row)
+            .with_gap(5.0, 0.0)
+            .with_align(Align::Center);
+        assert_eq!(
+            layout.solve(Rect::new(0.0, 0.0, 200.0, 30.0), &items),
+            [
+                Rect::new(0.0, 5.0, 20.0, 20.0),
+                Rect::new(25.0, 5.0, 20.0, 20.0),
+                Rect::new(50.0, 10.0, 150.0, 10.0),
+            ]
+        );
+
+        // Not enough space: the items shrink in proportion to their bases.
+        assert_eq!(
+            FlexLayout::new(Direction::Column).solve(Rect::new(0.0, 0.0, 10.0, 45.0), &items),
+            [
+                Rect::new(0.0, 0.0, 10.0, 18.0),
+                Rect::new(0.0, 18.0, 10.0, 18.0),
+                Rect::new(0.0, 36.0, 10.0, 9.0),
+            ]
+        );
+
+        // Wrapping legend entries with the free space between them.
+        let entries = [FlexItem::new(Vector2::new(30.0, 10.0)).with_shrink(0.0); 5];
+        let rects = FlexLayout::new(Direction::Row)
+            .with_wrap(true)
+            .with_gap(5.0, 4.0)
+            .with_justify(Justify::SpaceBetween)
+            .solve(Rect::new(0.0, 0.0, 100.0, 100.0), &entries);
+        assert_eq!(rects[0], Rect::new(0.0, 0.0, 30.0, 10.0));
+        assert_eq!(rects[1], Rect::new(35.0, 0.0, 30.0, 10.0));
+        assert_eq!(rects[2], Rect::new(70.0, 0.0, 30.0, 10.0));
+        assert_eq!(rects[3], Rect::new(0.0, 14.0, 30.0, 10.0));
+        assert_eq!(rects[4], Rect::new(70.0, 14.0, 30.0, 10.0));
+    }
+}
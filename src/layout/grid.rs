@@ -0,0 +1,290 @@
+//! Grid layout: the container is split into rows and columns (tracks), and each item occupies one
+//! or more neighbouring cells.
+
+use crate::Rect;
+use nalgebra::Vector2;
+
+/// Sizing rule of a row or a column of a grid.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Track {
+    /// The track has the given size.
+    Fixed(f32),
+    /// The track takes a share of the space, that is left after the fixed and auto tracks, in
+    /// proportion to its weight (as `fr` units in CSS).
+    Weight(f32),
+    /// The track is as large as the largest minimum size of its items.
+    Auto,
+}
+
+/// An item of a grid, that occupies the given amount of cells starting from the given row and
+/// column.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GridItem {
+    /// The first row of the item.
+    pub row: usize,
+    /// The first column of the item.
+    pub column: usize,
+    /// The amount of rows, that the item spans (at least one).
+    pub row_span: usize,
+    /// The amount of columns, that the item spans (at least one).
+    pub column_span: usize,
+    /// The size of the content of the item, it is used to size the auto tracks.
+    pub min_size: Vector2<f32>,
+}
+
+impl GridItem {
+    /// Creates new item, that occupies a single cell and has no minimum size.
+    pub fn new(row: usize, column: usize) -> Self {
+        Self {
+            row,
+            column,
+            row_span: 1,
+            column_span: 1,
+            min_size: Vector2::new(0.0, 0.0),
+        }
+    }
+
+    /// Sets the amount of rows and columns, that the item spans.
+    pub fn with_span(mut self, row_span: usize, column_span: usize) -> Self {
+        self.row_span = row_span;
+        self.column_span = column_span;
+        self
+    }
+
+    /// Sets the size of the content of the item.
+    pub fn with_min_size(mut self, min_size: Vector2<f32>) -> Self {
+        self.min_size = min_size;
+        self
+    }
+}
+
+/// Definition of a grid: its rows, columns and the gaps between them.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GridLayout {
+    /// Sizing rules of the columns, from the left to the right.
+    pub columns: Vec<Track>,
+    /// Sizing rules of the rows, from the top to the bottom.
+    pub rows: Vec<Track>,
+    /// Horizontal gap between the columns and vertical gap between the rows.
+    pub gap: Vector2<f32>,
+}
+
+/// Positions and sizes of the tracks of a solved grid and the rectangles of its items.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GridSolution {
+    /// Start and size of each column.
+    pub columns: Vec<(f32, f32)>,
+    /// Start and size of each row.
+    pub rows: Vec<(f32, f32)>,
+    /// Rectangle of each item, in the order of the items.
+    pub items: Vec<Rect<f32>>,
+}
+
+impl GridSolution {
+    /// Returns the rectangle of the cell at the given row and column.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell is outside of the grid.
+    pub fn cell(&self, row: usize, column: usize) -> Rect<f32> {
+        let (x, w) = self.columns[column];
+        let (y, h) = self.rows[row];
+        Rect::new(x, y, w, h)
+    }
+}
+
+/// Returns the range of tracks, that is occupied by an item, clamped to the grid.
+fn span_range(start: usize, span: usize, count: usize) -> (usize, usize) {
+    let start = start.min(count.saturating_sub(1));
+    (start, (start + span.max(1)).min(count))
+}
+
+/// Solves the sizes of the tracks along one axis and returns their starts and sizes.
+fn solve_tracks(
+    tracks: &[Track],
+    origin: f32,
+    available: f32,
+    gap: f32,
+    items: impl Iterator<Item = (usize, usize, f32)> + Clone,
+) -> Vec<(f32, f32)> {
+    let mut sizes = tracks
+        .iter()
+        .map(|track| match track {
+            Track::Fixed(size) => size.max(0.0),
+            _ => 0.0,
+        })
+        .collect::<Vec<_>>();
+
+    // The items, that span a single track, size the auto tracks first, then the spanning items
+    // grow the auto tracks they span evenly, if they still do not fit.
+    for (start, end, min_size) in items.clone() {
+        if end - start == 1 && tracks[start] == Track::Auto {
+            sizes[start] = sizes[start].max(min_size);
+        }
+    }
+    for (start, end, min_size) in items {
+        if end - start < 2 {
+            continue;
+        }
+        let auto = (start..end).filter(|i| tracks[*i] == Track::Auto).count();
+        let spanned = sizes[start..end].iter().sum::<f32>() + gap * (end - start - 1) as f32;
+        if auto > 0 && spanned < min_size {
+            let extra = (min_size - spanned) / auto as f32;
+            for i in start..end {
+                if tracks[i] == Track::Auto {
+                    sizes[i] += extra;
+                }
+            }
+        }
+    }
+
+    let gaps = gap * tracks.len().saturating_sub(1) as f32;
+    let free = (available - gaps - sizes.iter().sum::<f32>()).max(0.0);
+    let total_weight = tracks
+        .iter()
+        .map(|track| match track {
+            Track::Weight(weight) => weight.max(0.0),
+            _ => 0.0,
+        })
+        .sum::<f32>();
+    if total_weight > 0.0 {
+        for (size, track) in sizes.iter_mut().zip(tracks) {
+            if let Track::Weight(weight) = track {
+                *size = free * weight.max(0.0) / total_weight;
+            }
+        }
+    }
+
+    let mut position = origin;
+    sizes
+        .into_iter()
+        .map(|size| {
+            let track = (position, size);
+            position += size + gap;
+            track
+        })
+        .collect()
+}
+
+impl GridLayout {
+    /// Creates new grid with the given columns and rows and no gaps.
+    pub fn new(columns: Vec<Track>, rows: Vec<Track>) -> Self {
+        Self {
+            columns,
+            rows,
+            gap: Vector2::new(0.0, 0.0),
+        }
+    }
+
+    /// Sets the gaps between the columns and the rows.
+    pub fn with_gap(mut self, gap: Vector2<f32>) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Arranges the tracks inside of the bounds and returns the rectangles of the cells and the
+    /// items. The items outside of the grid are moved to its last row or column, and the spans are
+    /// clamped by the grid. The weighted tracks share the space, that is left after the other
+    /// tracks, if there is no space left, they collapse.
+    pub fn solve(&self, bounds: Rect<f32>, items: &[GridItem]) -> GridSolution {
+        let column_spans = items
+            .iter()
+            .map(|item| span_range(item.column, item.column_span, self.columns.len()));
+        let row_spans = items
+            .iter()
+            .map(|item| span_range(item.row, item.row_span, self.rows.len()));
+        let columns = solve_tracks(
+            &self.columns,
+            bounds.x(),
+            bounds.w(),
+            self.gap.x,
+            column_spans
+                .clone()
+                .zip(items)
+                .map(|((start, end), item)| (start, end, item.min_size.x)),
+        );
+        let rows = solve_tracks(
+            &self.rows,
+            bounds.y(),
+            bounds.h(),
+            self.gap.y,
+            row_spans
+                .clone()
+                .zip(items)
+                .map(|((start, end), item)| (start, end, item.min_size.y)),
+        );
+        let items = column_spans
+            .zip(row_spans)
+            .map(|((left, right), (top, bottom))| {
+                if left == right || top == bottom {
+                    return Rect::new(bounds.x(), bounds.y(), 0.0, 0.0);
+                }
+                let (x, _) = columns[left];
+                let (y, _) = rows[top];
+                let (last_x, last_w) = columns[right - 1];
+                let (last_y, last_h) = rows[bottom - 1];
+                Rect::new(x, y, last_x + last_w - x, last_y + last_h - y)
+            })
+            .collect();
+        GridSolution {
+            columns,
+            rows,
+            items,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GridItem, GridLayout, Track};
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn grid_layout() {
+        // A dialog: a label column sized by its content, an input column taking the rest, and a
+        // row of buttons at the bottom.
+        let layout = GridLayout::new(
+            vec![Track::Auto, Track::Weight(1.0), Track::Fixed(50.0)],
+            vec![Track::Auto, Track::Weight(2.0), Track::Weight(1.0)],
+        )
+        .with_gap(Vector2::new(10.0, 5.0));
+        let items = [
+            GridItem::new(0, 0).with_min_size(Vector2::new(60.0, 20.0)),
+            GridItem::new(0, 1).with_span(1, 2),
+            GridItem::new(1, 0).with_span(2, 1),
+            GridItem::new(2, 1).with_span(1, 2),
+            GridItem::new(5, 5),
+        ];
+        let solution = layout.solve(Rect::new(0.0, 0.0, 300.0, 195.0), &items);
+        assert_eq!(
+            solution.columns,
+            [(0.0, 60.0), (70.0, 170.0), (250.0, 50.0)]
+        );
+        assert_eq!(solution.rows, [(0.0, 20.0), (25.0, 110.0), (140.0, 55.0)]);
+        assert_eq!(solution.cell(1, 1), Rect::new(70.0, 25.0, 170.0, 110.0));
+        assert_eq!(
+            solution.items,
+            [
+                Rect::new(0.0, 0.0, 60.0, 20.0),
+                Rect::new(70.0, 0.0, 230.0, 20.0),
+                Rect::new(0.0, 25.0, 60.0, 170.0),
+                Rect::new(70.0, 140.0, 230.0, 55.0),
+                Rect::new(250.0, 140.0, 50.0, 55.0),
+            ]
+        );
+
+        // A spanning item grows the auto tracks it spans.
+        let layout = GridLayout::new(vec![Track::Fixed(10.0), Track::Auto], vec![Track::Auto]);
+        let items = [GridItem::new(0, 0)
+            .with_span(1, 2)
+            .with_min_size(Vector2::new(30.0, 5.0))];
+        let solution = layout.solve(Rect::new(0.0, 0.0, 100.0, 100.0), &items);
+        assert_eq!(solution.columns, [(0.0, 10.0), (10.0, 20.0)]);
+        assert_eq!(solution.items, [Rect::new(0.0, 0.0, 30.0, 5.0)]);
+    }
+}
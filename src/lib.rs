@@ -3,12 +3,47 @@
 #![warn(missing_docs)]
 #![forbid(unsafe_code)]
 
+use arrayvec::ArrayVec;
+use interval::Interval;
 use nalgebra::{Matrix3, SimdPartialOrd, Vector2};
 use num_traits::{NumAssign, Zero};
 use std::fmt::Debug;
 
+pub mod aabb_tree;
+#[cfg(feature = "3d")]
+pub mod box3;
+pub mod bvh;
+pub mod clip;
+pub mod cluster;
+pub mod curve;
+pub mod decompose;
+pub mod dirty;
+mod geometry;
+pub mod grid;
+pub mod hierarchy;
+pub mod hit_test;
+pub mod interval;
+pub mod kdtree;
+pub mod kinematics;
+pub mod layout;
+pub mod mesh;
+pub mod obb;
+pub mod occlusion;
+pub mod occupancy;
+#[cfg(feature = "3d")]
+pub mod octree;
 pub mod pack;
 pub mod quadtree;
+pub mod raster;
+pub mod region;
+pub mod rounded_rect;
+pub mod rtree;
+pub mod separate;
+pub mod shape;
+pub mod spatial_hash;
+#[cfg(test)]
+mod test_utils;
+pub mod viewport;
 
 /// Arbitrary number.
 pub trait Number: NumAssign + 'static + Clone + PartialEq + Debug + PartialOrd + Copy {}
@@ -17,6 +52,14 @@ impl<T> Number for T where T: NumAssign + 'static + Clone + PartialEq + Debug +
 
 /// A rectangle defined by position and size.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
 pub struct Rect<T> {
     /// Position of the rectangle.
     pub position: Vector2<T>,
@@ -40,6 +83,14 @@ where
 /// This simplifies the process of creating a bounding rect from a series of points,
 /// as it can start as None and then build an initial rect from the first point.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
 pub struct OptionRect<T>(Option<Rect<T>>);
 
 impl<T> Default for OptionRect<T> {
@@ -77,6 +128,7 @@ where
     /// ```
     /// # use nalgebra::Vector2;
     /// # use rectutils::Rect;
+    /// # use rectutils::OptionRect;
     ///
     /// let vertices = [Vector2::new(1.0, 2.0), Vector2::new(-3.0, 5.0)];
     ///
@@ -267,6 +319,58 @@ where
         }
     }
 
+    /// Subtracts the other rectangle from the rectangle and returns the remaining part as up to
+    /// four non-overlapping rectangles: the full-width bands above and below the other rectangle
+    /// and the pieces to the left and to the right of it. Empty pieces are skipped, so the result
+    /// is empty if the other rectangle covers this one and contains just this rectangle, if they
+    /// do not intersect.
+    #[inline]
+    pub fn difference(&self, other: Rect<T>) -> ArrayVec<Rect<T>, 4> {
+        let mut pieces = ArrayVec::new();
+        if !self.intersects(other) {
+            pieces.push(*self);
+            return pieces;
+        }
+        let (left, top) = (self.x(), self.y());
+        let (right, bottom) = (left + self.w(), top + self.h());
+        let (other_left, other_top) = (other.x(), other.y());
+        let (other_right, other_bottom) = (other_left + other.w(), other_top + other.h());
+        let middle_top = if other_top > top { other_top } else { top };
+        let middle_bottom = if other_bottom < bottom {
+            other_bottom
+        } else {
+            bottom
+        };
+        if other_top > top {
+            pieces.push(Rect::new(left, top, self.w(), other_top - top));
+        }
+        if other_bottom < bottom {
+            pieces.push(Rect::new(
+                left,
+                other_bottom,
+                self.w(),
+                bottom - other_bottom,
+            ));
+        }
+        if other_left > left {
+            pieces.push(Rect::new(
+                left,
+                middle_top,
+                other_left - left,
+                middle_bottom - middle_top,
+            ));
+        }
+        if other_right < right {
+            pieces.push(Rect::new(
+                other_right,
+                middle_top,
+                right - other_right,
+                middle_bottom - middle_top,
+            ));
+        }
+        pieces
+    }
+
     /// Offsets the given rectangle and returns a new rectangle.
     #[inline]
     #[must_use = "this method creates new instance of rect"]
@@ -321,6 +425,24 @@ where
         *self = Self::from_points(p0.inf(&o0), p1.sup(&o1));
     }
 
+    /// Returns the horizontal extent of the rectangle.
+    #[inline]
+    pub fn project_x(&self) -> Interval<T> {
+        Interval::from_len(self.position.x, self.size.x)
+    }
+
+    /// Returns the vertical extent of the rectangle.
+    #[inline]
+    pub fn project_y(&self) -> Interval<T> {
+        Interval::from_len(self.position.y, self.size.y)
+    }
+
+    /// Creates a rectangle from its horizontal and vertical extents.
+    #[inline]
+    pub fn from_intervals(x: Interval<T>, y: Interval<T>) -> Self {
+        Self::new(x.min, y.min, x.len(), y.len())
+    }
+
     /// Returns the top left corner of the rectangle.
     #[inline(always)]
     pub fn left_top_corner(&self) -> Vector2<T> {
@@ -406,6 +528,25 @@ where
 mod tests {
     use super::*;
     #[test]
+    fn difference() {
+        let rect = Rect::new(0, 0, 10, 10);
+        assert_eq!(
+            rect.difference(Rect::new(2, 3, 4, 5)).as_slice(),
+            [
+                Rect::new(0, 0, 10, 3),
+                Rect::new(0, 8, 10, 2),
+                Rect::new(0, 3, 2, 5),
+                Rect::new(6, 3, 4, 5)
+            ]
+        );
+        assert_eq!(
+            rect.difference(Rect::new(5, -5, 10, 10)).as_slice(),
+            [Rect::new(0, 5, 10, 5), Rect::new(0, 0, 5, 5)]
+        );
+        assert!(rect.difference(Rect::new(-1, -1, 12, 12)).is_empty());
+        assert_eq!(rect.difference(Rect::new(10, 0, 5, 5)).as_slice(), [rect]);
+    }
+    #[test]
     fn intersects1() {
         let rect1 = Rect::new(-1, -2, 4, 6);
         let rect2 = Rect::new(2, 3, 2, 2);
@@ -675,4 +816,14 @@ mod tests {
             Rect::new(0.0, 0.0, 2.0, 2.0),
         );
     }
+    #[test]
+    fn project() {
+        let rect = Rect::new(1, 2, 3, 4);
+        assert_eq!(rect.project_x(), Interval::new(1, 4));
+        assert_eq!(rect.project_y(), Interval::new(2, 6));
+        assert_eq!(
+            Rect::from_intervals(rect.project_x(), rect.project_y()),
+            rect
+        );
+    }
 }
@@ -0,0 +1,150 @@
+//! Expansion of rectangles into vertex and index buffers of quads for rendering.
+
+use crate::Rect;
+use nalgebra::Vector2;
+
+/// Order of the vertices of the triangles of a quad.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Winding {
+    /// The triangles go clockwise, when the Y axis points down (as on the screen): left top, right
+    /// top, right bottom.
+    Clockwise,
+    /// The triangles go counterclockwise, when the Y axis points down (as on the screen): left top,
+    /// left bottom, right bottom.
+    CounterClockwise,
+}
+
+/// Vertex and index buffers of a set of quads. Each quad has four vertices in the order: left top,
+/// right top, right bottom, left bottom, and two triangles, that share the diagonal from the left
+/// top to the right bottom vertex.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuadMesh {
+    /// Positions of the vertices.
+    pub positions: Vec<Vector2<f32>>,
+    /// Texture coordinates of the vertices.
+    pub uvs: Vec<Vector2<f32>>,
+    /// Indices of the vertices of the triangles, three per triangle.
+    pub indices: Vec<u32>,
+    /// Order of the vertices of the triangles.
+    pub winding: Winding,
+}
+
+impl QuadMesh {
+    /// Creates new empty mesh, the triangles of which will have the given winding.
+    pub fn new(winding: Winding) -> Self {
+        Self {
+            positions: Default::default(),
+            uvs: Default::default(),
+            indices: Default::default(),
+            winding,
+        }
+    }
+
+    /// Creates new mesh with a quad for each rectangle. If texture coordinates are given, they
+    /// must have one rectangle per quad, otherwise every quad gets the full `(0, 0)..(1, 1)`
+    /// range of texture coordinates.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the amount of texture coordinate rectangles differs from the amount of
+    /// rectangles.
+    pub fn from_rects(rects: &[Rect<f32>], uvs: Option<&[Rect<f32>]>, winding: Winding) -> Self {
+        let mut mesh = Self::new(winding);
+        mesh.positions.reserve(rects.len() * 4);
+        mesh.uvs.reserve(rects.len() * 4);
+        mesh.indices.reserve(rects.len() * 6);
+        match uvs {
+            Some(uvs) => {
+                assert_eq!(rects.len(), uvs.len());
+                for (rect, uv) in rects.iter().zip(uvs) {
+                    mesh.push_quad(*rect, *uv);
+                }
+            }
+            None => {
+                for rect in rects {
+                    mesh.push_quad(*rect, Rect::new(0.0, 0.0, 1.0, 1.0));
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Returns the amount of quads in the mesh.
+    pub fn len(&self) -> usize {
+        self.positions.len() / 4
+    }
+
+    /// Returns `true` if the mesh has no quads.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Removes all the quads.
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.uvs.clear();
+        self.indices.clear();
+    }
+
+    /// Adds a quad with the given texture coordinates.
+    pub fn push_quad(&mut self, rect: Rect<f32>, uv: Rect<f32>) {
+        let first = self.positions.len() as u32;
+        self.positions.extend([
+            rect.left_top_corner(),
+            rect.right_top_corner(),
+            rect.right_bottom_corner(),
+            rect.left_bottom_corner(),
+        ]);
+        self.uvs.extend([
+            uv.left_top_corner(),
+            uv.right_top_corner(),
+            uv.right_bottom_corner(),
+            uv.left_bottom_corner(),
+        ]);
+        let order = match self.winding {
+            Winding::Clockwise => [0, 1, 2, 0, 2, 3],
+            Winding::CounterClockwise => [0, 3, 2, 0, 2, 1],
+        };
+        self.indices.extend(order.map(|index| first + index));
+    }
+}
+
+/// Converts the rectangle in pixels, such as a placement from a packer, into the rectangle of
+/// texture coordinates of a texture of the given size.
+pub fn uv_rect(rect: Rect<f32>, texture_size: Vector2<f32>) -> Rect<f32> {
+    Rect {
+        position: rect.position.component_div(&texture_size),
+        size: rect.size.component_div(&texture_size),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{uv_rect, QuadMesh, Winding};
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn quad_mesh() {
+        let rects = [
+            Rect::new(0.0, 0.0, 10.0, 20.0),
+            Rect::new(5.0, 5.0, 1.0, 1.0),
+        ];
+        let uvs = [
+            uv_rect(Rect::new(0.0, 0.0, 64.0, 32.0), Vector2::new(128.0, 64.0)),
+            uv_rect(Rect::new(64.0, 32.0, 64.0, 32.0), Vector2::new(128.0, 64.0)),
+        ];
+        let mesh = QuadMesh::from_rects(&rects, Some(&uvs), Winding::Clockwise);
+        assert_eq!(mesh.len(), 2);
+        assert_eq!(mesh.positions[2], Vector2::new(10.0, 20.0));
+        assert_eq!(mesh.uvs[1], Vector2::new(0.5, 0.0));
+        assert_eq!(mesh.uvs[7], Vector2::new(0.5, 1.0));
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]);
+
+        let mesh = QuadMesh::from_rects(&rects[..1], None, Winding::CounterClockwise);
+        assert_eq!(mesh.indices, [0, 3, 2, 0, 2, 1]);
+        assert_eq!(mesh.uvs[2], Vector2::new(1.0, 1.0));
+    }
+}
@@ -0,0 +1,167 @@
+//! Oriented bounding rectangle (OBB), a rectangle, that could be rotated around its center.
+
+use crate::{Number, Rect};
+use nalgebra::{RealField, Vector2};
+
+/// A rectangle rotated around its center by the given angle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Obb<T> {
+    /// Center of the rectangle.
+    pub center: Vector2<T>,
+    /// Half of the size of the rectangle along its own axes.
+    pub half_extents: Vector2<T>,
+    /// Rotation of the rectangle in radians, positive angles rotate the X axis towards the Y axis.
+    pub rotation: T,
+}
+
+impl<T> From<Rect<T>> for Obb<T>
+where
+    T: Number + RealField,
+{
+    fn from(rect: Rect<T>) -> Self {
+        let two = T::one() + T::one();
+        Self {
+            center: rect.center(),
+            half_extents: rect.size / two,
+            rotation: T::zero(),
+        }
+    }
+}
+
+impl<T> Obb<T>
+where
+    T: Number + RealField,
+{
+    /// Creates new oriented rectangle from its center, half extents and rotation (in radians).
+    #[inline]
+    pub fn new(center: Vector2<T>, half_extents: Vector2<T>, rotation: T) -> Self {
+        Self {
+            center,
+            half_extents,
+            rotation,
+        }
+    }
+
+    /// Sets the new rotation (in radians) of the rectangle.
+    #[inline]
+    pub fn with_rotation(mut self, rotation: T) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Offsets the rectangle and returns a new rectangle.
+    #[inline]
+    #[must_use = "this method creates new instance of obb"]
+    pub fn translate(&self, translation: Vector2<T>) -> Self {
+        Self {
+            center: self.center + translation,
+            ..*self
+        }
+    }
+
+    /// Returns the unit vectors of the local X and Y axes of the rectangle.
+    #[inline]
+    pub fn axes(&self) -> [Vector2<T>; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        [Vector2::new(cos, sin), Vector2::new(-sin, cos)]
+    }
+
+    /// Returns the corners of the rectangle. Before the rotation they are the left top, right
+    /// top, right bottom and left bottom corners.
+    #[inline]
+    pub fn corners(&self) -> [Vector2<T>; 4] {
+        let [x, y] = self.axes();
+        let x = x * self.half_extents.x;
+        let y = y * self.half_extents.y;
+        [
+            self.center - x - y,
+            self.center + x - y,
+            self.center + x + y,
+            self.center - x + y,
+        ]
+    }
+
+    /// Returns the smallest axis-aligned rectangle, that contains the oriented rectangle.
+    #[inline]
+    pub fn bounding_rect(&self) -> Rect<T> {
+        let [x, y] = self.axes();
+        let extent = Vector2::new(
+            x.x.abs() * self.half_extents.x + y.x.abs() * self.half_extents.y,
+            x.y.abs() * self.half_extents.x + y.y.abs() * self.half_extents.y,
+        );
+        Rect {
+            position: self.center - extent,
+            size: extent * (T::one() + T::one()),
+        }
+    }
+
+    /// Checks if the given point lies within the bounds of the rectangle.
+    #[inline]
+    pub fn contains(&self, point: Vector2<T>) -> bool {
+        let offset = point - self.center;
+        let [x, y] = self.axes();
+        offset.dot(&x).abs() <= self.half_extents.x && offset.dot(&y).abs() <= self.half_extents.y
+    }
+
+    /// Returns half of the length of the projection of the rectangle on the given axis.
+    fn projected_radius(&self, axis: &Vector2<T>) -> T {
+        let [x, y] = self.axes();
+        x.dot(axis).abs() * self.half_extents.x + y.dot(axis).abs() * self.half_extents.y
+    }
+
+    /// Checks if the rectangle intersects with some other oriented rectangle, using the
+    /// separating axis theorem. As with [`Rect::intersects`], rectangles, that only touch each
+    /// other, do not intersect.
+    #[inline]
+    pub fn intersects(&self, other: &Obb<T>) -> bool {
+        let offset = other.center - self.center;
+        self.axes().iter().chain(other.axes().iter()).all(|axis| {
+            offset.dot(axis).abs() < self.projected_radius(axis) + other.projected_radius(axis)
+        })
+    }
+
+    /// Checks if the rectangle intersects with the axis-aligned rectangle.
+    #[inline]
+    pub fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        self.intersects(&Obb::from(rect))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Obb;
+    use crate::Rect;
+    use nalgebra::Vector2;
+    use std::f32::consts::FRAC_PI_4;
+
+    #[test]
+    fn obb_intersections() {
+        let diamond = Obb::new(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0), FRAC_PI_4);
+        let bounds = diamond.bounding_rect();
+        assert!((bounds.w() - 2.0 * 2.0f32.sqrt()).abs() < 1.0e-5);
+        assert!(diamond.contains(Vector2::new(1.3, 0.0)));
+        assert!(!diamond.contains(Vector2::new(1.0, 1.0)));
+
+        // Intersects the bounding rectangle, but not the diamond itself.
+        let corner = Rect::new(0.9, 0.9, 0.5, 0.5);
+        assert!(bounds.intersects(corner));
+        assert!(!diamond.intersects_rect(corner));
+        assert!(diamond.intersects_rect(Rect::new(1.0, -0.1, 1.0, 0.2)));
+
+        let other = Obb::from(Rect::new(1.0, -1.0, 2.0, 2.0)).with_rotation(0.3);
+        assert!(diamond.intersects(&other));
+        assert!(!diamond.intersects(&other.with_rotation(0.0).translate(Vector2::new(0.5, 0.0))));
+
+        let corners = Obb::from(Rect::new(0.0, 0.0, 2.0, 4.0)).corners();
+        assert_eq!(corners[0], Vector2::new(0.0, 0.0));
+        assert_eq!(corners[2], Vector2::new(2.0, 4.0));
+    }
+}
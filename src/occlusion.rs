@@ -0,0 +1,82 @@
+//! Visibility of rectangles behind opaque occluders.
+
+use crate::{decompose::subtract_all, Number, Rect};
+
+/// The part of a view, that is not covered by opaque occluders. It is computed once, by
+/// subtracting the occluders from the view with [`subtract_all`], so the visibility tests
+/// only need to check a handful of visible rectangles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Occlusion<T> {
+    view: Rect<T>,
+    visible: Vec<Rect<T>>,
+}
+
+impl<T> Occlusion<T>
+where
+    T: Number,
+{
+    /// Computes the visible part of the view behind the given occluders.
+    pub fn new(view: Rect<T>, occluders: impl IntoIterator<Item = Rect<T>>) -> Self {
+        let occluders = occluders.into_iter().collect::<Vec<_>>();
+        Self {
+            view,
+            visible: subtract_all(view, &occluders),
+        }
+    }
+
+    /// Returns the view.
+    pub fn view(&self) -> Rect<T> {
+        self.view
+    }
+
+    /// Returns the visible part of the view as a set of non-overlapping rectangles.
+    pub fn visible_rects(&self) -> &[Rect<T>] {
+        &self.visible
+    }
+
+    /// Returns `true` if the entire view is covered by the occluders.
+    pub fn is_view_occluded(&self) -> bool {
+        self.visible.is_empty()
+    }
+
+    /// Checks if no part of the rectangle is visible. The parts of the rectangle outside of the
+    /// view are not visible, as well as the edges, that only touch the visible part.
+    pub fn is_fully_occluded(&self, rect: Rect<T>) -> bool {
+        !self.visible.iter().any(|visible| visible.intersects(rect))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Occlusion;
+    use crate::Rect;
+
+    #[test]
+    fn occlusion_culling() {
+        let occlusion = Occlusion::new(
+            Rect::new(0, 0, 100, 100),
+            [
+                Rect::new(-10, -10, 60, 120),
+                Rect::new(40, 0, 60, 50),
+                Rect::new(200, 200, 10, 10),
+            ],
+        );
+        assert_eq!(occlusion.visible_rects(), [Rect::new(50, 50, 50, 50)]);
+        assert!(occlusion.is_fully_occluded(Rect::new(10, 10, 20, 20)));
+        assert!(occlusion.is_fully_occluded(Rect::new(40, 40, 10, 10)));
+        assert!(!occlusion.is_fully_occluded(Rect::new(40, 40, 11, 11)));
+        assert!(occlusion.is_fully_occluded(Rect::new(150, 60, 10, 10)));
+        assert!(!occlusion.is_view_occluded());
+        assert!(
+            Occlusion::new(Rect::new(0, 0, 10, 10), [Rect::new(0, 0, 10, 10)]).is_view_occluded()
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Dense grid of occupied and free cells, addressed by rectangular regions of cells.
+
+use crate::Rect;
+use nalgebra::Vector2;
+use std::ops::Range;
+
+/// Dense two-dimensional grid, each cell of which is either free or occupied. The grid covers the
+/// given region of cells, the cell `(x, y)` of a region `Rect<i32>` lies in `x..x + w` and
+/// `y..y + h`. The cells outside of the grid are considered occupied, so nothing could be placed
+/// there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Grid2D {
+    bounds: Rect<i32>,
+    cells: Vec<bool>,
+}
+
+impl Grid2D {
+    /// Creates new grid with all the cells free. Negative sizes are treated as zero.
+    pub fn new(bounds: Rect<i32>) -> Self {
+        let bounds = bounds.with_size(Vector2::new(bounds.w().max(0), bounds.h().max(0)));
+        Self {
+            bounds,
+            cells: vec![false; bounds.w() as usize * bounds.h() as usize],
+        }
+    }
+
+    /// Returns the region of the cells, that is covered by the grid.
+    pub fn bounds(&self) -> Rect<i32> {
+        self.bounds
+    }
+
+    fn index(&self, cell: Vector2<i32>) -> Option<usize> {
+        let local = cell - self.bounds.position;
+        if local.x >= 0 && local.y >= 0 && local.x < self.bounds.w() && local.y < self.bounds.h() {
+            Some(local.y as usize * self.bounds.w() as usize + local.x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the part of the region inside of the grid as ranges of columns and rows relative
+    /// to the grid.
+    fn local_ranges(&self, region: Rect<i32>) -> (Range<usize>, Range<usize>) {
+        let clamp =
+            |value: i32, min: i32, len: i32| value.saturating_sub(min).clamp(0, len) as usize;
+        let (x, y, w, h) = (
+            self.bounds.x(),
+            self.bounds.y(),
+            self.bounds.w(),
+            self.bounds.h(),
+        );
+        let x0 = clamp(region.x(), x, w);
+        let x1 = clamp(region.x().saturating_add(region.w()), x, w);
+        let y0 = clamp(region.y(), y, h);
+        let y1 = clamp(region.y().saturating_add(region.h()), y, h);
+        (x0..x1.max(x0), y0..y1.max(y0))
+    }
+
+    /// Returns `true` if the region is entirely inside of the grid.
+    fn covers(&self, region: Rect<i32>) -> bool {
+        region.x() >= self.bounds.x()
+            && region.y() >= self.bounds.y()
+            && region.x().saturating_add(region.w()) <= self.bounds.x() + self.bounds.w()
+            && region.y().saturating_add(region.h()) <= self.bounds.y() + self.bounds.h()
+    }
+
+    /// Checks if the cell is occupied. The cells outside of the grid are always occupied.
+    pub fn is_occupied(&self, cell: Vector2<i32>) -> bool {
+        self.index(cell).map_or(true, |index| self.cells[index])
+    }
+
+    /// Marks the cell as occupied or free. The cells outside of the grid are ignored.
+    pub fn set(&mut self, cell: Vector2<i32>, occupied: bool) {
+        if let Some(index) = self.index(cell) {
+            self.cells[index] = occupied;
+        }
+    }
+
+    /// Marks every cell of the region as occupied or free. The part of the region outside of the
+    /// grid is ignored.
+    pub fn fill_rect(&mut self, region: Rect<i32>, occupied: bool) {
+        let (columns, rows) = self.local_ranges(region);
+        let width = self.bounds.w() as usize;
+        for row in rows {
+            self.cells[row * width + columns.start..row * width + columns.end].fill(occupied);
+        }
+    }
+
+    /// Marks every cell of the region as occupied.
+    pub fn occupy_rect(&mut self, region: Rect<i32>) {
+        self.fill_rect(region, true);
+    }
+
+    /// Marks every cell of the region as free.
+    pub fn free_rect(&mut self, region: Rect<i32>) {
+        self.fill_rect(region, false);
+    }
+
+    /// Checks if every cell of the region is free. A region, that sticks out of the grid, is not
+    /// free, and an empty region is always free.
+    pub fn is_rect_free(&self, region: Rect<i32>) -> bool {
+        if region.w() <= 0 || region.h() <= 0 {
+            return true;
+        }
+        if !self.covers(region) {
+            return false;
+        }
+        let (columns, rows) = self.local_ranges(region);
+        let width = self.bounds.w() as usize;
+        rows.into_iter().all(|row| {
+            !self.cells[row * width + columns.start..row * width + columns.end].contains(&true)
+        })
+    }
+
+    /// Checks if every cell of the region is occupied, the cells outside of the grid included.
+    pub fn is_rect_occupied(&self, region: Rect<i32>) -> bool {
+        let (columns, rows) = self.local_ranges(region);
+        let width = self.bounds.w() as usize;
+        rows.into_iter().all(|row| {
+            !self.cells[row * width + columns.start..row * width + columns.end].contains(&false)
+        })
+    }
+
+    /// Returns the free cells, that are connected to the given one through the sides of the
+    /// free cells (diagonal neighbours are not connected). Returns nothing, if the given cell is
+    /// occupied.
+    pub fn flood_fill(&self, start: Vector2<i32>) -> Vec<Vector2<i32>> {
+        let mut visited = vec![false; self.cells.len()];
+        self.flood_fill_into(start, &mut visited)
+    }
+
+    fn flood_fill_into(&self, start: Vector2<i32>, visited: &mut [bool]) -> Vec<Vector2<i32>> {
+        let mut region = Vec::new();
+        let Some(index) = self.index(start) else {
+            return region;
+        };
+        if self.cells[index] || visited[index] {
+            return region;
+        }
+        visited[index] = true;
+        let mut stack = vec![start];
+        while let Some(cell) = stack.pop() {
+            region.push(cell);
+            for offset in [
+                Vector2::new(1, 0),
+                Vector2::new(-1, 0),
+                Vector2::new(0, 1),
+                Vector2::new(0, -1),
+            ] {
+                let neighbour = cell + offset;
+                if let Some(index) = self.index(neighbour) {
+                    if !self.cells[index] && !visited[index] {
+                        visited[index] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+        }
+        region
+    }
+
+    /// Splits the free cells into the connected regions (see [`Self::flood_fill`]). The regions
+    /// are ordered by their first cell in row-major order.
+    pub fn free_regions(&self) -> Vec<Vec<Vector2<i32>>> {
+        let mut visited = vec![false; self.cells.len()];
+        let mut regions = Vec::new();
+        for y in 0..self.bounds.h() {
+            for x in 0..self.bounds.w() {
+                let region =
+                    self.flood_fill_into(self.bounds.position + Vector2::new(x, y), &mut visited);
+                if !region.is_empty() {
+                    regions.push(region);
+                }
+            }
+        }
+        regions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Grid2D;
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn occupancy_grid() {
+        let mut grid = Grid2D::new(Rect::new(-5, -5, 10, 10));
+        grid.occupy_rect(Rect::new(-1, -10, 2, 20));
+        assert!(grid.is_occupied(Vector2::new(0, 4)));
+        assert!(!grid.is_occupied(Vector2::new(1, 4)));
+        assert!(grid.is_occupied(Vector2::new(5, 0)));
+        assert!(grid.is_rect_occupied(Rect::new(-1, -5, 2, 10)));
+        assert!(!grid.is_rect_occupied(Rect::new(-2, -5, 2, 10)));
+        assert!(grid.is_rect_free(Rect::new(1, -5, 4, 10)));
+        assert!(!grid.is_rect_free(Rect::new(1, -5, 5, 10)));
+        assert!(!grid.is_rect_free(Rect::new(0, 0, 2, 2)));
+
+        // The wall splits the grid in two halves, until a door is made.
+        let regions = grid.free_regions();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].len(), 40);
+        assert_eq!(regions[1].len(), 40);
+        grid.free_rect(Rect::new(-1, 0, 2, 1));
+        assert_eq!(grid.flood_fill(Vector2::new(-5, -5)).len(), 82);
+        assert!(grid.flood_fill(Vector2::new(-1, -1)).is_empty());
+    }
+}
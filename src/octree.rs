@@ -0,0 +1,407 @@
+//! Octree is used for space partitioning and fast spatial queries in 3D, it is a counterpart of
+//! [`crate::quadtree::QuadTree`].
+
+use crate::{box3::Box3, quadtree::QueryStorage};
+use nalgebra::Vector3;
+use std::{
+    fmt::{Display, Formatter},
+    ops::{ControlFlow, Range},
+};
+
+/// A trait for anything that has box bounds, it is a counterpart of
+/// [`crate::quadtree::BoundsProvider`].
+pub trait BoundsProvider {
+    /// Identifier of the bounds provider.
+    type Id: Clone;
+
+    /// Returns bounds of the bounds provider.
+    fn bounds(&self) -> Box3<f32>;
+
+    /// Returns id of the bounds provider.
+    fn id(&self) -> Self::Id;
+}
+
+/// Maximum depth of the octree. Nodes at this depth cannot be split anymore.
+const MAX_DEPTH: usize = 64;
+
+/// An error, that may occur during the build of the octree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OctreeBuildError {
+    /// It means that given split threshold is too low for an algorithm to build octree. Make it
+    /// larger and try again. Also this might mean that there are too many entries sharing the same
+    /// location.
+    ReachedRecursionLimit {
+        /// Depth at which the recursion was stopped.
+        depth: usize,
+        /// Amount of entries in the node that could not be split.
+        entry_count: usize,
+        /// Bounds of the node that could not be split.
+        bounds: Box3<f32>,
+    },
+    /// Split threshold must be at least one, otherwise any non-empty node would be split forever.
+    ZeroSplitThreshold,
+}
+
+impl Display for OctreeBuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OctreeBuildError::ReachedRecursionLimit {
+                depth,
+                entry_count,
+                bounds,
+            } => write!(
+                f,
+                "octree reached recursion limit at depth {depth}: {entry_count} entries \
+                 within {bounds:?} cannot be separated, consider increasing split threshold",
+            ),
+            OctreeBuildError::ZeroSplitThreshold => {
+                write!(f, "octree split threshold must be greater than zero")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OctreeBuildError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct OctreeNode {
+    /// Index of the first of the eight children, zero for leaves (the root is never a child).
+    children: u32,
+    /// Range of the entities, that are stored in the node itself.
+    range: Range<u32>,
+}
+
+fn to_index(value: usize) -> u32 {
+    u32::try_from(value).expect("octree cannot hold more than u32::MAX nodes or entities")
+}
+
+/// Splits the box in eight at its center. The index of a child has its first bit set for the
+/// upper half along X, second bit for Y and third bit for Z.
+fn split_box(bounds: &Box3<f32>) -> [Box3<f32>; 8] {
+    let half = bounds.size * 0.5;
+    std::array::from_fn(|i| {
+        let offset = Vector3::new(
+            if i & 1 != 0 { half.x } else { 0.0 },
+            if i & 2 != 0 { half.y } else { 0.0 },
+            if i & 4 != 0 { half.z } else { 0.0 },
+        );
+        Box3 {
+            position: bounds.position + offset,
+            size: half,
+        }
+    })
+}
+
+/// Octree over boxes. Each entity is stored exactly once: in the deepest node, that entirely
+/// contains it, so queries never report an entity twice. Bounds of the entities are always stored
+/// in the tree.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Octree<I> {
+    nodes: Vec<OctreeNode>,
+    ids: Vec<I>,
+    entry_bounds: Vec<Box3<f32>>,
+    root_bounds: Box3<f32>,
+    split_threshold: usize,
+}
+
+impl<I> Default for Octree<I> {
+    fn default() -> Self {
+        Self {
+            nodes: Default::default(),
+            ids: Default::default(),
+            entry_bounds: Default::default(),
+            root_bounds: Default::default(),
+            split_threshold: 16,
+        }
+    }
+}
+
+impl<I> Octree<I> {
+    /// Creates new octree from the given initial bounds and the set of objects. Objects, that do
+    /// not intersect the initial bounds, are ignored. Nodes with more entities than the split
+    /// threshold are split.
+    pub fn new<T>(
+        root_bounds: Box3<f32>,
+        objects: impl Iterator<Item = T>,
+        split_threshold: usize,
+    ) -> Result<Self, OctreeBuildError>
+    where
+        T: BoundsProvider<Id = I>,
+    {
+        if split_threshold == 0 {
+            return Err(OctreeBuildError::ZeroSplitThreshold);
+        }
+        let entries = objects
+            .map(|object| (object.bounds(), object.id()))
+            .filter(|(bounds, _)| root_bounds.intersects(*bounds))
+            .collect::<Vec<_>>();
+        let mut tree = Self {
+            nodes: vec![OctreeNode {
+                children: 0,
+                range: 0..0,
+            }],
+            ids: Vec::with_capacity(entries.len()),
+            entry_bounds: Vec::with_capacity(entries.len()),
+            root_bounds,
+            split_threshold,
+        };
+        tree.build_node(0, root_bounds, entries, 0)?;
+        Ok(tree)
+    }
+
+    fn build_node(
+        &mut self,
+        node: usize,
+        bounds: Box3<f32>,
+        entries: Vec<(Box3<f32>, I)>,
+        depth: usize,
+    ) -> Result<(), OctreeBuildError> {
+        let first = to_index(self.ids.len());
+        if entries.len() <= self.split_threshold {
+            for (entry_bounds, id) in entries {
+                self.entry_bounds.push(entry_bounds);
+                self.ids.push(id);
+            }
+            self.nodes[node].range = first..to_index(self.ids.len());
+            return Ok(());
+        }
+        if depth >= MAX_DEPTH {
+            return Err(OctreeBuildError::ReachedRecursionLimit {
+                depth,
+                entry_count: entries.len(),
+                bounds,
+            });
+        }
+
+        let child_bounds = split_box(&bounds);
+        let mut child_entries: [Vec<(Box3<f32>, I)>; 8] = Default::default();
+        for (entry_bounds, id) in entries {
+            // An entity on the border of two children goes to the first one.
+            match child_bounds
+                .iter()
+                .position(|child| child.contains_box(&entry_bounds))
+            {
+                Some(i) => child_entries[i].push((entry_bounds, id)),
+                None => {
+                    // Entities that do not fit into a single child stay in the branch.
+                    self.entry_bounds.push(entry_bounds);
+                    self.ids.push(id);
+                }
+            }
+        }
+
+        let children = self.nodes.len();
+        self.nodes[node] = OctreeNode {
+            children: to_index(children),
+            range: first..to_index(self.ids.len()),
+        };
+        self.nodes.extend((0..8).map(|_| OctreeNode {
+            children: 0,
+            range: 0..0,
+        }));
+        for (i, (bounds, entries)) in child_bounds.into_iter().zip(child_entries).enumerate() {
+            self.build_node(children + i, bounds, entries, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the amount of entities in the tree.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns `true` if the tree has no entities.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the bounds of the root node.
+    pub fn root_bounds(&self) -> Box3<f32> {
+        self.root_bounds
+    }
+
+    /// Returns the split threshold, that was used to build the tree.
+    pub fn split_threshold(&self) -> usize {
+        self.split_threshold
+    }
+
+    /// Passes the entities of the nodes, whose bounds pass the filter, to the closure, if the
+    /// bounds of the entities pass the filter as well.
+    fn walk<P, F>(
+        &self,
+        node: usize,
+        bounds: Box3<f32>,
+        filter: &P,
+        func: &mut F,
+    ) -> ControlFlow<()>
+    where
+        P: Fn(&Box3<f32>) -> bool,
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let Some(node_data) = self.nodes.get(node) else {
+            return ControlFlow::Continue(());
+        };
+        // Entities of the root may stick out of the root bounds, so the root is always visited.
+        if node != 0 && !filter(&bounds) {
+            return ControlFlow::Continue(());
+        }
+        for index in node_data.range.start as usize..node_data.range.end as usize {
+            if filter(&self.entry_bounds[index]) {
+                func(&self.ids[index])?;
+            }
+        }
+        if node_data.children != 0 {
+            for (i, child_bounds) in split_box(&bounds).into_iter().enumerate() {
+                self.walk(node_data.children as usize + i, child_bounds, filter, func)?;
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the ids of the entities, that contain the given point, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn point_query_with<F>(&self, point: Vector3<f32>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.walk(
+            0,
+            self.root_bounds,
+            &|bounds| bounds.contains(point),
+            &mut func,
+        )
+    }
+
+    /// Passes the ids of the entities, that intersect the given box, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn box_query_with<F>(&self, area: Box3<f32>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.walk(
+            0,
+            self.root_bounds,
+            &|bounds| bounds.intersects(area),
+            &mut func,
+        )
+    }
+
+    /// Passes the ids of the entities, that intersect the given sphere, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn sphere_query_with<F>(
+        &self,
+        center: Vector3<f32>,
+        radius: f32,
+        mut func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.walk(
+            0,
+            self.root_bounds,
+            &|bounds| bounds.intersects_sphere(center, radius),
+            &mut func,
+        )
+    }
+
+    /// Writes the ids of the entities, that contain the given point, to the output storage.
+    pub fn point_query<S>(&self, point: Vector3<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.point_query_with(point, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the entities, that intersect the given box, to the output storage.
+    pub fn box_query<S>(&self, area: Box3<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.box_query_with(area, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the entities, that intersect the given sphere, to the output storage.
+    pub fn sphere_query<S>(&self, center: Vector3<f32>, radius: f32, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.sphere_query_with(center, radius, |id| storage.push_or_break(id.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BoundsProvider, Octree, OctreeBuildError};
+    use crate::box3::Box3;
+    use nalgebra::Vector3;
+
+    struct TestObject {
+        bounds: Box3<f32>,
+        id: usize,
+    }
+
+    impl BoundsProvider for &TestObject {
+        type Id = usize;
+
+        fn bounds(&self) -> Box3<f32> {
+            self.bounds
+        }
+
+        fn id(&self) -> Self::Id {
+            self.id
+        }
+    }
+
+    #[test]
+    fn octree_queries() {
+        let objects = (0..500)
+            .map(|id| TestObject {
+                bounds: Box3::new(
+                    (id * 37 % 101) as f32,
+                    (id * 53 % 97) as f32,
+                    (id * 29 % 89) as f32,
+                    (id % 7 + 1) as f32,
+                    (id % 5 + 1) as f32,
+                    (id % 3 + 1) as f32,
+                ),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let root = Box3::new(0.0, 0.0, 0.0, 110.0, 110.0, 110.0);
+        let tree = Octree::new(root, objects.iter(), 8).unwrap();
+        assert_eq!(tree.len(), 500);
+
+        let area = Box3::new(10.0, 20.0, 30.0, 40.0, 25.0, 30.0);
+        let mut found = Vec::new();
+        tree.box_query(area, &mut found);
+        found.sort_unstable();
+        let expected = objects
+            .iter()
+            .filter(|object| object.bounds.intersects(area))
+            .map(|object| object.id)
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+
+        let center = Vector3::new(50.0, 50.0, 50.0);
+        found.clear();
+        tree.sphere_query(center, 20.0, &mut found);
+        found.sort_unstable();
+        let expected = objects
+            .iter()
+            .filter(|object| object.bounds.intersects_sphere(center, 20.0))
+            .map(|object| object.id)
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+
+        assert_eq!(
+            Octree::new(root, objects.iter(), 0).unwrap_err(),
+            OctreeBuildError::ZeroSplitThreshold
+        );
+    }
+}
@@ -1,13 +1,290 @@
 //! Rectangle packer packs small rectangles into a bigger one.
+//!
+//! All the packers are generic over the coordinate type, any [`Number`] could be used: for
+//! example `u16` for compact GPU-side placements, `u64` for huge virtual atlases or `f32`.
+//!
+//! Packing is deterministic: the same sequence of operations on a packer with the same options
+//! always gives the same layout on any platform. Packers do not use hashing or randomness, ties
+//! between equally good free places are broken in favour of the place found first (see
+//! [`Heuristic`]) and batch packing uses stable sorting (see [`SortKey`]).
 
-use crate::{Number, Rect};
+use crate::{geometry::intersection, Number, Rect};
+use nalgebra::Vector2;
 use num_traits::Zero;
+use std::ops::ControlFlow;
 
+mod allocator;
+mod atlas;
+mod batch;
+mod estimate;
+pub mod export;
+mod growable;
+mod guillotine;
+mod multi_bin;
+mod shelf;
+mod svg;
+
+pub use allocator::{AllocId, Allocation, AtlasAllocator, Relocation};
+pub use atlas::{AtlasItem, AtlasPacker, AtlasPlacement, Trim};
+pub use batch::{
+    pack_uniform, PackResult, Progress, RejectReason, Rejected, SortKey, MAX_UNIFORM_SIZES,
+};
+pub use estimate::{estimate_size, SizeEstimate};
+pub use growable::GrowablePacker;
+pub use guillotine::{GuillotinePacker, SplitRule};
+pub use multi_bin::{MultiBinPacker, MultiBinPlacement};
+pub use shelf::ShelfPacker;
+pub use svg::to_svg;
+
+/// A place in the bin, that was found by a packer for a rectangle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Placement<T> {
+    /// The space occupied by the rectangle. If the rectangle was rotated, its width and height are
+    /// swapped.
+    pub rect: Rect<T>,
+    /// Whether the rectangle was rotated by 90 degrees to fit better.
+    pub rotated: bool,
+}
+
+/// Common interface of all rectangle packers, that allows to switch packing algorithms at
+/// runtime (for example, via `Box<dyn Packer<T>>`).
+pub trait Packer<T>
+where
+    T: Number,
+{
+    /// Tries to find free place to put rectangle with given size. Returns None if there
+    /// insufficient space.
+    fn insert(&mut self, w: T, h: T) -> Option<Placement<T>>;
+
+    /// Same as [`Self::insert`], but the rectangle is never rotated, even if the packer is
+    /// allowed to rotate rectangles. It is useful for the rectangles, that must keep their
+    /// orientation, such as text glyphs.
+    fn insert_unrotated(&mut self, w: T, h: T) -> Option<Placement<T>>;
+
+    /// Releases the space occupied by a rectangle, that was previously returned by
+    /// [`Self::insert`] (as [`Placement::rect`]), so it could be used again. Returns `false` if
+    /// there is no such rectangle in the packer.
+    fn remove(&mut self, rect: Rect<T>) -> bool;
+
+    /// Returns the total area, that is not occupied by the inserted rectangles. Not all of it is
+    /// necessarily usable, because of fragmentation.
+    fn free_space(&self) -> T;
+
+    /// Returns the bounds of the bin, rectangles are packed into.
+    fn bounds(&self) -> Rect<T>;
+
+    /// Returns `true` if [`Self::insert`] may rotate rectangles.
+    fn can_rotate(&self) -> bool {
+        false
+    }
+
+    /// Returns the free places of the bin, that could be used for new rectangles. A rectangle
+    /// fits in the bin (without rotation), if it fits in one of the free places.
+    fn free_rects(&self) -> Vec<Rect<T>>;
+
+    /// Returns the free place with the largest area, or None if the bin is full.
+    fn largest_free_rect(&self) -> Option<Rect<T>> {
+        self.free_rects()
+            .into_iter()
+            .fold(None, |largest, rect| match largest {
+                Some(largest) if largest.w() * largest.h() >= rect.w() * rect.h() => Some(largest),
+                _ => Some(rect),
+            })
+    }
+
+    /// Checks if a rectangle of the given size (without rotation) could be inserted right now,
+    /// without actually inserting it.
+    fn can_fit(&self, w: T, h: T) -> bool {
+        self.free_rects()
+            .iter()
+            .any(|free| free.w() >= w && free.h() >= h)
+    }
+
+    /// Enlarges the bin to the given size, the rectangles, that were already placed, stay in their
+    /// places. The new size must not be smaller than the current one.
+    fn grow(&mut self, w: T, h: T);
+
+    /// Marks the given area of the bin as occupied, so no rectangles will be placed there. It
+    /// should be done before packing, for example, to keep an existing layout or a special
+    /// region of a texture. Reserved space could not be released by [`Self::remove`], only by
+    /// [`Self::clear`]. Returns `false` if the area is not completely free (or the packer does not
+    /// support reservation), in this case nothing is changed.
+    fn reserve(&mut self, rect: Rect<T>) -> bool;
+
+    /// Removes all the rectangles and prepares the packer for another run. It is much cheaper than
+    /// create new packer, because it reuses previously allocated memory. The size of the bin is
+    /// kept.
+    fn clear(&mut self);
+
+    /// Packs a set of rectangles with the given sizes (where X - width, Y - height) at once. The
+    /// rectangles are sorted by the given key before packing, but the result contains their
+    /// placements in the original order.
+    fn pack_all(&mut self, sizes: &[Vector2<T>], sort: SortKey) -> PackResult<T> {
+        batch::pack_all(self, sizes, sort, None)
+    }
+
+    /// Same as [`Self::pack_all`], but the given callback is called periodically with the
+    /// progress of the packing. The packing is cancelled, if the callback returns
+    /// [`ControlFlow::Break`], in this case the rectangles, that were not packed yet, are
+    /// rejected with [`RejectReason::Cancelled`].
+    fn pack_all_with_progress(
+        &mut self,
+        sizes: &[Vector2<T>],
+        sort: SortKey,
+        progress: &mut dyn FnMut(&Progress<T>) -> ControlFlow<()>,
+    ) -> PackResult<T> {
+        batch::pack_all(self, sizes, sort, Some(progress))
+    }
+}
+
+/// A rule that decides which of the free places, that are large enough for a rectangle, will be
+/// used to place the rectangle. Each packer interprets the rule according to its algorithm, see
+/// the docs of the packers for more info. If several free places have equal scores, the one,
+/// that was found first, is used.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Heuristic {
+    /// Use the free place with the smallest area.
+    #[default]
+    BestAreaFit,
+    /// Use the free place, that leaves the smallest leftover along the shorter side.
+    BestShortSideFit,
+    /// Use the free place, that leaves the smallest leftover along the longer side.
+    BestLongSideFit,
+    /// Use the free place, that puts the rectangle as low (and then as far left) as possible.
+    BottomLeft,
+    /// Use the first free place found, which is the fastest option.
+    FirstFit,
+}
+
+impl Heuristic {
+    /// Returns the score of placing a rectangle of the given size in the top-left corner of the
+    /// given free rectangle. Lower scores are better, they are compared lexicographically.
+    fn score<T>(self, free: &Rect<T>, w: T, h: T) -> (T, T)
+    where
+        T: Number,
+    {
+        let leftover_w = free.w() - w;
+        let leftover_h = free.h() - h;
+        let (short, long) = if leftover_w < leftover_h {
+            (leftover_w, leftover_h)
+        } else {
+            (leftover_h, leftover_w)
+        };
+        match self {
+            Heuristic::BestAreaFit => (free.w() * free.h() - w * h, short),
+            Heuristic::BestShortSideFit => (short, long),
+            Heuristic::BestLongSideFit => (long, short),
+            Heuristic::BottomLeft => (free.y() + h, free.x()),
+            Heuristic::FirstFit => (Zero::zero(), Zero::zero()),
+        }
+    }
+
+    /// Returns a lower bound of the first component of the score of placing a rectangle of the
+    /// given size in any free rectangle, that is at least `free_w` wide, or None if the width of
+    /// the free rectangle does not bound the score.
+    fn width_bound<T>(self, free_w: T, w: T, h: T) -> Option<T>
+    where
+        T: Number,
+    {
+        let leftover_w = if free_w > w { free_w - w } else { Zero::zero() };
+        match self {
+            Heuristic::BestAreaFit => Some(leftover_w * h),
+            Heuristic::BestLongSideFit => Some(leftover_w),
+            _ => None,
+        }
+    }
+}
+
+fn min<T: Number>(a: T, b: T) -> T {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+fn max<T: Number>(a: T, b: T) -> T {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Returns the smallest power of two, that is not smaller than the given value.
+fn ceil_power_of_two<T: Number>(value: T) -> T {
+    let mut power = T::one();
+    while power < value {
+        power += power;
+    }
+    power
+}
+
+/// Cuts the given hole (that must be inside the free rectangle) out of a free rectangle with
+/// guillotine cuts. Returns the strips above and below the hole (along the whole width of the free
+/// rectangle) and the strips to the left and to the right of the hole. Some of them could be
+/// empty.
+fn carve<T: Number>(free: &Rect<T>, hole: &Rect<T>) -> [Rect<T>; 4] {
+    let hole_right = hole.x() + hole.w();
+    let hole_bottom = hole.y() + hole.h();
+    [
+        Rect::new(free.x(), free.y(), free.w(), hole.y() - free.y()),
+        Rect::new(
+            free.x(),
+            hole_bottom,
+            free.w(),
+            free.y() + free.h() - hole_bottom,
+        ),
+        Rect::new(free.x(), hole.y(), hole.x() - free.x(), hole.h()),
+        Rect::new(
+            hole_right,
+            hole.y(),
+            free.x() + free.w() - hole_right,
+            hole.h(),
+        ),
+    ]
+}
+
+/// Checks if the given rectangle is completely covered by the given non-overlapping free
+/// rectangles.
+fn is_free<'a, T, I>(rect: &Rect<T>, free: I) -> bool
+where
+    T: Number,
+    I: IntoIterator<Item = &'a Rect<T>>,
+{
+    let free_area = free
+        .into_iter()
+        .filter_map(|free| intersection(free, rect))
+        .fold(T::zero(), |area, part| area + part.w() * part.h());
+    free_area == rect.w() * rect.h()
+}
+
+/// Checks if the score `a` is strictly better (lower) than the score `b`.
+fn better_score<T>(a: (T, T), b: (T, T)) -> bool
+where
+    T: Number,
+{
+    a.0 < b.0 || (a.0 == b.0 && a.1 < b.1)
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct RectPackNode<T>
 where
     T: Number,
 {
     filled: bool,
+    /// The node is filled by [`Packer::reserve`].
+    reserved: bool,
     split: bool,
     bounds: Rect<T>,
     left: usize,
@@ -22,6 +299,7 @@ where
         Self {
             bounds,
             filled: false,
+            reserved: false,
             split: false,
             left: usize::MAX,
             right: usize::MAX,
@@ -30,6 +308,8 @@ where
 }
 
 /// Rectangle packer packs small rectangles into a bigger one.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RectPacker<T>
 where
     T: Number,
@@ -39,6 +319,8 @@ where
     width: T,
     height: T,
     unvisited: Vec<usize>,
+    used: T,
+    allow_rotation: bool,
 }
 
 impl<T> RectPacker<T>
@@ -65,9 +347,18 @@ where
             width: w,
             height: h,
             unvisited: Default::default(),
+            used: Zero::zero(),
+            allow_rotation: false,
         }
     }
 
+    /// Allows [`Packer::insert`] to rotate rectangles by 90 degrees, if they do not fit in the
+    /// original orientation.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+
     /// Clears packer and prepares it for another run. It is much cheaper than create new packer,
     /// because it reuses previously allocated memory.
     pub fn clear(&mut self) {
@@ -80,6 +371,7 @@ where
             self.height,
         )));
         self.root = 0;
+        self.used = Zero::zero();
     }
 
     /// Tries to find free place to put rectangle with given size. Returns None if there insufficient
@@ -97,6 +389,7 @@ where
             } else if !node.filled && node.bounds.w() >= w && node.bounds.h() >= h {
                 if node.bounds.w() == w && node.bounds.h() == h {
                     node.filled = true;
+                    self.used += w * h;
                     return Some(node.bounds);
                 }
 
@@ -140,12 +433,177 @@ where
 
         None
     }
+
+    /// Splits an unsplit node in two and returns the index of the second part.
+    fn split_node(&mut self, index: usize, first: Rect<T>, second: Rect<T>) -> usize {
+        let left = self.nodes.len();
+        self.nodes.push(RectPackNode::new(first));
+        let right = self.nodes.len();
+        self.nodes.push(RectPackNode::new(second));
+        let node = &mut self.nodes[index];
+        node.split = true;
+        node.left = left;
+        node.right = right;
+        right
+    }
+
+    fn add_root(&mut self, bounds: Rect<T>, added: Rect<T>) {
+        let right = self.nodes.len();
+        self.nodes.push(RectPackNode::new(added));
+        let mut root = RectPackNode::new(bounds);
+        root.split = true;
+        root.left = self.root;
+        root.right = right;
+        self.root = self.nodes.len();
+        self.nodes.push(root);
+    }
+}
+
+impl<T> Packer<T> for RectPacker<T>
+where
+    T: Number,
+{
+    fn insert(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        let rect = self.find_free(w, h);
+        rect.or_else(|| {
+            if self.allow_rotation && w != h {
+                self.find_free(h, w)
+            } else {
+                None
+            }
+        })
+        .map(|rect| Placement {
+            rotated: rect.w() != w,
+            rect,
+        })
+    }
+
+    fn insert_unrotated(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.find_free(w, h).map(|rect| Placement {
+            rect,
+            rotated: false,
+        })
+    }
+
+    fn remove(&mut self, rect: Rect<T>) -> bool {
+        match self
+            .nodes
+            .iter_mut()
+            .find(|node| node.filled && !node.reserved && !node.split && node.bounds == rect)
+        {
+            Some(node) => {
+                node.filled = false;
+                self.used -= rect.w() * rect.h();
+                // The search must start from the root again to find the released node.
+                self.unvisited.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn free_space(&self) -> T {
+        self.width * self.height - self.used
+    }
+
+    fn free_rects(&self) -> Vec<Rect<T>> {
+        self.nodes
+            .iter()
+            .filter(|node| !node.split && !node.filled)
+            .map(|node| node.bounds)
+            .collect()
+    }
+
+    fn bounds(&self) -> Rect<T> {
+        Rect::new(Zero::zero(), Zero::zero(), self.width, self.height)
+    }
+
+    fn can_rotate(&self) -> bool {
+        self.allow_rotation
+    }
+
+    fn grow(&mut self, w: T, h: T) {
+        // The old tree becomes the first child of a new root, the added space is the second one.
+        if h > self.height {
+            self.add_root(
+                Rect::new(Zero::zero(), Zero::zero(), self.width, h),
+                Rect::new(Zero::zero(), self.height, self.width, h - self.height),
+            );
+            self.height = h;
+        }
+        if w > self.width {
+            self.add_root(
+                Rect::new(Zero::zero(), Zero::zero(), w, self.height),
+                Rect::new(self.width, Zero::zero(), w - self.width, self.height),
+            );
+            self.width = w;
+        }
+        self.unvisited.clear();
+    }
+
+    fn reserve(&mut self, rect: Rect<T>) -> bool {
+        let is_leaf = |node: &RectPackNode<T>| !node.split && !node.filled;
+        if !is_free(
+            &rect,
+            self.nodes
+                .iter()
+                .filter(|node| is_leaf(node))
+                .map(|node| &node.bounds),
+        ) {
+            return false;
+        }
+
+        for index in 0..self.nodes.len() {
+            let node = &self.nodes[index];
+            if !is_leaf(node) {
+                continue;
+            }
+            let Some(hole) = intersection(&node.bounds, &rect) else {
+                continue;
+            };
+            // Cut the strips around the hole off one by one, what is left is the hole itself.
+            let [top, bottom, left, right] = carve(&node.bounds, &hole);
+            let mut index = index;
+            let mut rest = node.bounds;
+            if top.h() > Zero::zero() {
+                rest = Rect::new(rest.x(), hole.y(), rest.w(), rest.h() - top.h());
+                index = self.split_node(index, top, rest);
+            }
+            if bottom.h() > Zero::zero() {
+                rest = Rect::new(rest.x(), rest.y(), rest.w(), hole.h());
+                self.split_node(index, rest, bottom);
+                index = self.nodes[index].left;
+            }
+            if left.w() > Zero::zero() {
+                rest = Rect::new(hole.x(), rest.y(), rest.w() - left.w(), rest.h());
+                index = self.split_node(index, left, rest);
+            }
+            if right.w() > Zero::zero() {
+                self.split_node(index, hole, right);
+                index = self.nodes[index].left;
+            }
+            let node = &mut self.nodes[index];
+            node.filled = true;
+            node.reserved = true;
+            self.used += hole.w() * hole.h();
+        }
+        self.unvisited.clear();
+        true
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{RectPackNode, RectPacker};
-    use crate::Rect;
+    use super::{
+        AtlasAllocator, AtlasPacker, GuillotinePacker, Heuristic, Packer, RectPackNode, RectPacker,
+        ShelfPacker, SortKey,
+    };
+    use crate::{Number, Rect};
+    use nalgebra::Vector2;
 
     #[test]
     fn rect_pack_node_new() {
@@ -188,4 +646,244 @@ mod test {
         rp.clear();
         assert_eq!(rp.nodes.len(), 1);
     }
+
+    #[test]
+    fn packer_trait() {
+        let heuristics = [
+            Heuristic::BestAreaFit,
+            Heuristic::BestShortSideFit,
+            Heuristic::BestLongSideFit,
+            Heuristic::BottomLeft,
+            Heuristic::FirstFit,
+        ];
+        let mut packers: Vec<Box<dyn Packer<u32>>> = vec![Box::new(RectPacker::new(32, 32))];
+        for heuristic in heuristics {
+            packers.push(Box::new(
+                GuillotinePacker::new(32, 32).with_heuristic(heuristic),
+            ));
+            packers.push(Box::new(ShelfPacker::new(32, 32).with_heuristic(heuristic)));
+        }
+
+        for packer in packers.iter_mut() {
+            assert_eq!(packer.bounds(), Rect::new(0, 0, 32, 32));
+            assert_eq!(packer.free_space(), 1024);
+
+            let mut placed = Vec::new();
+            while let Some(placement) = packer.insert(8, 8) {
+                assert!(!placement.rotated);
+                placed.push(placement.rect);
+            }
+            assert_eq!(placed.len(), 16);
+            assert_eq!(packer.free_space(), 0);
+
+            assert!(packer.remove(placed[5]));
+            assert!(!packer.remove(placed[5]));
+            assert!(!packer.remove(Rect::new(1, 1, 8, 8)));
+            assert_eq!(packer.free_space(), 64);
+            assert_eq!(packer.insert(8, 8).map(|p| p.rect), Some(placed[5]));
+            assert_eq!(packer.free_space(), 0);
+        }
+    }
+
+    #[test]
+    fn packer_rotation() {
+        let mut packers: Vec<Box<dyn Packer<u32>>> = vec![
+            Box::new(RectPacker::new(10, 20).with_rotation(true)),
+            Box::new(GuillotinePacker::new(10, 20).with_rotation(true)),
+            Box::new(ShelfPacker::new(10, 20).with_rotation(true)),
+        ];
+        for packer in packers.iter_mut() {
+            assert_eq!(packer.insert_unrotated(20, 10), None);
+            let placement = packer.insert(20, 10).unwrap();
+            assert!(placement.rotated);
+            assert_eq!(placement.rect, Rect::new(0, 0, 10, 20));
+        }
+
+        let mut packer = RectPacker::new(10, 20);
+        assert_eq!(Packer::insert(&mut packer, 20, 10), None);
+
+        // A failed unrotated insertion must not split the bin for the rotated rectangle.
+        let mut packer = RectPacker::new(10, 30).with_rotation(true);
+        assert_eq!(packer.insert_unrotated(20, 1), None);
+        assert_eq!(
+            packer
+                .insert_unrotated(10, 30)
+                .map(|placement| placement.rect),
+            Some(Rect::new(0, 0, 10, 30))
+        );
+    }
+
+    #[test]
+    fn packer_free_rects() {
+        let mut packers: Vec<Box<dyn Packer<u32>>> = vec![
+            Box::new(RectPacker::new(16, 16)),
+            Box::new(GuillotinePacker::new(16, 16)),
+            Box::new(ShelfPacker::new(16, 16)),
+            Box::new(AtlasAllocator::new(16, 16)),
+            Box::new(AtlasPacker::new(GuillotinePacker::new(16, 16)).with_padding(1)),
+        ];
+        for packer in packers.iter_mut() {
+            assert_eq!(packer.largest_free_rect(), Some(Rect::new(0, 0, 16, 16)));
+            packer.insert(8, 8).unwrap();
+            let free = packer.free_rects();
+            let area = free.iter().map(|r| r.w() * r.h()).sum::<u32>();
+            assert!(area >= packer.free_space());
+            assert!(free
+                .iter()
+                .all(|r| r.x() + r.w() <= 16 && r.y() + r.h() <= 16));
+            assert!(packer.can_fit(4, 4));
+            assert!(!packer.can_fit(11, 11));
+            let largest = packer.largest_free_rect().unwrap();
+            assert!(largest.w() * largest.h() >= 16 * 4);
+        }
+    }
+
+    #[test]
+    fn packer_reserve() {
+        let mut packers: Vec<Box<dyn Packer<u32>>> = vec![
+            Box::new(RectPacker::new(16, 16)),
+            Box::new(GuillotinePacker::new(16, 16)),
+            Box::new(AtlasAllocator::new(16, 16)),
+        ];
+        let reserved = Rect::new(4, 4, 8, 8);
+        for packer in packers.iter_mut() {
+            assert!(packer.reserve(reserved));
+            assert!(!packer.reserve(Rect::new(10, 10, 4, 4)));
+            assert!(!packer.reserve(Rect::new(14, 0, 4, 4)));
+            assert_eq!(packer.free_space(), 192);
+
+            let mut placed = 0;
+            while let Some(placement) = packer.insert(4, 4) {
+                assert!(!placement.rect.intersects(reserved));
+                placed += 1;
+            }
+            assert_eq!(placed, 12);
+            assert_eq!(packer.free_space(), 0);
+            assert!(!packer.remove(reserved));
+
+            packer.clear();
+            assert_eq!(packer.free_space(), 256);
+        }
+
+        assert!(!ShelfPacker::new(16, 16).reserve(reserved));
+    }
+
+    /// Packs a fixed pseudo-random set of rectangles and returns the layout as a string.
+    fn layout(packer: &mut dyn Packer<u32>) -> String {
+        let mut seed = 12345u32;
+        let sizes = (0..64)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                Vector2::new(1 + (seed >> 16) % 24, 1 + (seed >> 8) % 24)
+            })
+            .collect::<Vec<_>>();
+        let result = packer.pack_all(&sizes, SortKey::MaxSide);
+        let mut layout = String::new();
+        for (index, placement) in result.placed() {
+            let rect = placement.rect;
+            layout += &format!(
+                "{} {} {} {} {} {}\n",
+                index,
+                rect.x(),
+                rect.y(),
+                rect.w(),
+                rect.h(),
+                placement.rotated
+            );
+        }
+        layout
+    }
+
+    /// FNV-1a hash, that is stable across platforms and Rust versions.
+    fn stable_hash(text: &str) -> u64 {
+        text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    #[test]
+    fn packer_determinism() {
+        let packers = || -> Vec<Box<dyn Packer<u32>>> {
+            vec![
+                Box::new(RectPacker::new(128, 128).with_rotation(true)),
+                Box::new(GuillotinePacker::new(128, 128).with_rotation(true)),
+                Box::new(ShelfPacker::new(128, 128).with_heuristic(Heuristic::BestAreaFit)),
+                Box::new(AtlasAllocator::new(128, 128)),
+            ]
+        };
+        let hashes = packers()
+            .into_iter()
+            .zip(packers())
+            .map(|(mut first, mut second)| {
+                let first = layout(first.as_mut());
+                assert_eq!(first, layout(second.as_mut()));
+                stable_hash(&first)
+            })
+            .collect::<Vec<_>>();
+        // Layouts must not change between runs, platforms and versions of the crate.
+        assert_eq!(
+            hashes,
+            [
+                1524938163313829317,
+                12714456323002442031,
+                14701287202977279717,
+                13565188102551578141
+            ]
+        );
+    }
+
+    fn check_coordinate_type<T>()
+    where
+        T: Number + From<u8>,
+    {
+        let n = |value: u8| T::from(value);
+        let mut packers: Vec<Box<dyn Packer<T>>> = vec![
+            Box::new(RectPacker::new(n(16), n(16))),
+            Box::new(GuillotinePacker::new(n(16), n(16))),
+            Box::new(ShelfPacker::new(n(16), n(16))),
+            Box::new(AtlasAllocator::new(n(16), n(16))),
+        ];
+        for packer in packers.iter_mut() {
+            for _ in 0..4 {
+                packer.insert(n(16), n(4)).unwrap();
+            }
+            assert_eq!(packer.insert(n(1), n(1)), None);
+            assert_eq!(packer.free_space(), n(0));
+        }
+    }
+
+    #[test]
+    fn packer_coordinate_types() {
+        check_coordinate_type::<u16>();
+        check_coordinate_type::<u32>();
+        check_coordinate_type::<i32>();
+        check_coordinate_type::<u64>();
+    }
+
+    #[cfg(feature = "serde")]
+    fn check_serde<P>(mut packer: P)
+    where
+        P: Packer<u32> + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        for _ in 0..3 {
+            packer.insert(5, 3).unwrap();
+        }
+        let bytes = bincode::serialize(&packer).unwrap();
+        let mut loaded: P = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(loaded.free_space(), packer.free_space());
+        // Resumed packer must continue exactly as the original one.
+        for _ in 0..8 {
+            assert_eq!(loaded.insert(4, 2), packer.insert(4, 2));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn packer_serde() {
+        check_serde(RectPacker::new(20, 20));
+        check_serde(GuillotinePacker::new(20, 20));
+        check_serde(ShelfPacker::new(20, 20));
+        check_serde(AtlasAllocator::new(20, 20));
+        check_serde(AtlasPacker::new(ShelfPacker::new(20, 20)).with_padding(1));
+    }
 }
@@ -0,0 +1,560 @@
+//! Atlas allocator, that allocates and deallocates rectangles in a long-lived bin.
+
+use crate::{
+    geometry::intersection,
+    pack::{
+        batch::packing_order, better_score, carve, is_free, Heuristic, Packer, Placement, SortKey,
+    },
+    Number, Rect,
+};
+use num_traits::Zero;
+
+/// Identifier of a rectangle allocated by [`AtlasAllocator`]. Identifiers of deallocated
+/// rectangles are never valid again, even if their space is reused.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllocId {
+    index: u32,
+    generation: u32,
+}
+
+/// A rectangle allocated by [`AtlasAllocator::allocate`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Allocation<T> {
+    /// Identifier of the allocation, that is used to deallocate it.
+    pub id: AllocId,
+    /// The space occupied by the allocation.
+    pub rect: Rect<T>,
+}
+
+/// A change of an allocation made by [`AtlasAllocator::repack`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Relocation<T> {
+    /// The allocation before repacking, its identifier is not valid anymore.
+    pub old: Allocation<T>,
+    /// The allocation after repacking.
+    pub new: Allocation<T>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum NodeKind {
+    Free,
+    Used,
+    /// The space is occupied by [`Packer::reserve`].
+    Reserved,
+    Split(usize, usize),
+    /// The node is not a part of the tree and could be reused.
+    Unused,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+struct AllocNode<T> {
+    rect: Rect<T>,
+    kind: NodeKind,
+    parent: Option<usize>,
+    generation: u32,
+}
+
+/// Atlas allocator is a guillotine packer for long-lived bins with frequent allocations and
+/// deallocations, such as glyph or thumbnail caches. Every split of the free space is stored in
+/// a tree, so when a rectangle is deallocated, it is merged back with its free neighbours up to
+/// the largest free rectangle, that was split to allocate it. This way the bin does not fragment
+/// over time as long as the rectangles are deallocated.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AtlasAllocator<T>
+where
+    T: Number,
+{
+    nodes: Vec<AllocNode<T>>,
+    unused: Vec<usize>,
+    root: usize,
+    width: T,
+    height: T,
+    used: T,
+    heuristic: Heuristic,
+}
+
+impl<T> AtlasAllocator<T>
+where
+    T: Number,
+{
+    /// Creates new allocator with given bounds.
+    pub fn new(w: T, h: T) -> Self {
+        Self {
+            nodes: vec![AllocNode {
+                rect: Rect::new(Zero::zero(), Zero::zero(), w, h),
+                kind: NodeKind::Free,
+                parent: None,
+                generation: 0,
+            }],
+            unused: Default::default(),
+            root: 0,
+            width: w,
+            height: h,
+            used: Zero::zero(),
+            heuristic: Default::default(),
+        }
+    }
+
+    /// Sets the rule, that is used to choose a free rectangle for a new allocation.
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    /// Tries to allocate a rectangle with given size. Returns None if there insufficient space.
+    pub fn allocate(&mut self, w: T, h: T) -> Option<Allocation<T>> {
+        let mut best: Option<(usize, (T, T))> = None;
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.kind == NodeKind::Free && node.rect.w() >= w && node.rect.h() >= h {
+                let score = self.heuristic.score(&node.rect, w, h);
+                if best.map_or(true, |(_, best_score)| better_score(score, best_score)) {
+                    best = Some((index, score));
+                }
+                if self.heuristic == Heuristic::FirstFit {
+                    break;
+                }
+            }
+        }
+
+        let (mut index, _) = best?;
+        let free = self.nodes[index].rect;
+        let leftover_w = free.w() - w;
+        let leftover_h = free.h() - h;
+        // Cut along the shorter leftover side first, to keep the larger leftover as big as
+        // possible.
+        if leftover_w <= leftover_h {
+            if leftover_h > Zero::zero() {
+                index = self.split(
+                    index,
+                    Rect::new(free.x(), free.y(), free.w(), h),
+                    Rect::new(free.x(), free.y() + h, free.w(), leftover_h),
+                );
+            }
+            if leftover_w > Zero::zero() {
+                index = self.split(
+                    index,
+                    Rect::new(free.x(), free.y(), w, h),
+                    Rect::new(free.x() + w, free.y(), leftover_w, h),
+                );
+            }
+        } else {
+            if leftover_w > Zero::zero() {
+                index = self.split(
+                    index,
+                    Rect::new(free.x(), free.y(), w, free.h()),
+                    Rect::new(free.x() + w, free.y(), leftover_w, free.h()),
+                );
+            }
+            if leftover_h > Zero::zero() {
+                index = self.split(
+                    index,
+                    Rect::new(free.x(), free.y(), w, h),
+                    Rect::new(free.x(), free.y() + h, w, leftover_h),
+                );
+            }
+        }
+
+        let node = &mut self.nodes[index];
+        node.kind = NodeKind::Used;
+        self.used += w * h;
+        Some(Allocation {
+            id: AllocId {
+                index: index as u32,
+                generation: node.generation,
+            },
+            rect: node.rect,
+        })
+    }
+
+    /// Deallocates a previously allocated rectangle and merges its space with the free
+    /// neighbours. Returns `false` if the identifier is not valid (for example, if it was
+    /// already deallocated).
+    pub fn deallocate(&mut self, id: AllocId) -> bool {
+        let index = id.index as usize;
+        match self.nodes.get(index) {
+            Some(node) if node.generation == id.generation && node.kind == NodeKind::Used => {
+                self.release(index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the rectangle of the given allocation, or None if the identifier is not valid.
+    pub fn get(&self, id: AllocId) -> Option<Rect<T>> {
+        self.nodes
+            .get(id.index as usize)
+            .filter(|node| node.generation == id.generation && node.kind == NodeKind::Used)
+            .map(|node| node.rect)
+    }
+
+    /// Repacks all the allocations from scratch (largest first), which removes the fragmentation
+    /// of the free space, caused by a long sequence of allocations and deallocations. Reserved
+    /// areas stay in their places. Returns the list of the changes of all the allocations (with new
+    /// identifiers), the content of the allocations, that were moved (`old.rect != new.rect`),
+    /// should be copied to the new places. Returns None and keeps the current layout, if the
+    /// allocations do not fit after repacking (which is possible, but rare).
+    pub fn repack(&mut self) -> Option<Vec<Relocation<T>>> {
+        let mut allocations = Vec::new();
+        let mut reserved = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            match node.kind {
+                NodeKind::Used => allocations.push(Allocation {
+                    id: AllocId {
+                        index: index as u32,
+                        generation: node.generation,
+                    },
+                    rect: node.rect,
+                }),
+                NodeKind::Reserved => reserved.push(node.rect),
+                _ => (),
+            }
+        }
+
+        let mut repacked = self.clone();
+        repacked.clear();
+        for rect in reserved {
+            repacked.reserve(rect);
+        }
+        let sizes = allocations
+            .iter()
+            .map(|allocation| allocation.rect.size)
+            .collect::<Vec<_>>();
+        let mut relocations = Vec::with_capacity(allocations.len());
+        for index in packing_order(&sizes, SortKey::Area) {
+            let old = allocations[index];
+            let new = repacked.allocate(old.rect.w(), old.rect.h())?;
+            relocations.push(Relocation { old, new });
+        }
+        *self = repacked;
+        Some(relocations)
+    }
+
+    /// Deallocates all the rectangles. It is much cheaper than create new allocator, because it
+    /// reuses previously allocated memory. Identifiers of the deallocated rectangles are not valid
+    /// anymore.
+    pub fn clear(&mut self) {
+        for node in self.nodes.iter_mut() {
+            node.kind = NodeKind::Unused;
+            node.generation = node.generation.wrapping_add(1);
+        }
+        self.unused.clear();
+        self.unused.extend((1..self.nodes.len()).rev());
+        let root = &mut self.nodes[0];
+        root.rect = Rect::new(Zero::zero(), Zero::zero(), self.width, self.height);
+        root.kind = NodeKind::Free;
+        root.parent = None;
+        self.root = 0;
+        self.used = Zero::zero();
+    }
+
+    /// Splits a free node in two and returns the index of the first part.
+    fn split(&mut self, index: usize, first: Rect<T>, second: Rect<T>) -> usize {
+        let first = self.add_node(first, index);
+        let second = self.add_node(second, index);
+        self.nodes[index].kind = NodeKind::Split(first, second);
+        first
+    }
+
+    fn second_child(&self, index: usize) -> usize {
+        match self.nodes[index].kind {
+            NodeKind::Split(_, second) => second,
+            _ => unreachable!("node must be split"),
+        }
+    }
+
+    fn add_node(&mut self, rect: Rect<T>, parent: usize) -> usize {
+        let node = AllocNode {
+            rect,
+            kind: NodeKind::Free,
+            parent: Some(parent),
+            generation: 0,
+        };
+        match self.unused.pop() {
+            Some(index) => {
+                let generation = self.nodes[index].generation;
+                self.nodes[index] = AllocNode { generation, ..node };
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Makes the old tree the first child of a new root, the added space is the second one.
+    fn add_root(&mut self, bounds: Rect<T>, added: Rect<T>) {
+        let old_root = self.root;
+        self.root = self.nodes.len();
+        self.nodes.push(AllocNode {
+            rect: bounds,
+            kind: NodeKind::Free,
+            parent: None,
+            generation: 0,
+        });
+        let added = self.add_node(added, self.root);
+        self.nodes[old_root].parent = Some(self.root);
+        self.nodes[self.root].kind = NodeKind::Split(old_root, added);
+        self.merge_up(old_root);
+    }
+
+    fn release(&mut self, index: usize) {
+        let node = &mut self.nodes[index];
+        node.kind = NodeKind::Free;
+        // Invalidates the identifier of the allocation.
+        node.generation = node.generation.wrapping_add(1);
+        self.used -= node.rect.w() * node.rect.h();
+        self.merge_up(index);
+    }
+
+    /// Merges the free siblings back into their parent, up to the root.
+    fn merge_up(&mut self, mut index: usize) {
+        while let Some(parent) = self.nodes[index].parent {
+            let NodeKind::Split(first, second) = self.nodes[parent].kind else {
+                unreachable!("parent node must be split")
+            };
+            if self.nodes[first].kind != NodeKind::Free || self.nodes[second].kind != NodeKind::Free
+            {
+                break;
+            }
+            for child in [first, second] {
+                self.nodes[child].kind = NodeKind::Unused;
+                self.unused.push(child);
+            }
+            self.nodes[parent].kind = NodeKind::Free;
+            index = parent;
+        }
+    }
+}
+
+impl<T> Packer<T> for AtlasAllocator<T>
+where
+    T: Number,
+{
+    fn insert(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.allocate(w, h).map(|allocation| Placement {
+            rect: allocation.rect,
+            rotated: false,
+        })
+    }
+
+    fn insert_unrotated(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.insert(w, h)
+    }
+
+    fn remove(&mut self, rect: Rect<T>) -> bool {
+        match self
+            .nodes
+            .iter()
+            .position(|node| node.kind == NodeKind::Used && node.rect == rect)
+        {
+            Some(index) => {
+                self.release(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn free_space(&self) -> T {
+        self.width * self.height - self.used
+    }
+
+    fn free_rects(&self) -> Vec<Rect<T>> {
+        self.nodes
+            .iter()
+            .filter(|node| node.kind == NodeKind::Free)
+            .map(|node| node.rect)
+            .collect()
+    }
+
+    fn bounds(&self) -> Rect<T> {
+        Rect::new(Zero::zero(), Zero::zero(), self.width, self.height)
+    }
+
+    fn reserve(&mut self, rect: Rect<T>) -> bool {
+        let is_free_node = |node: &AllocNode<T>| node.kind == NodeKind::Free;
+        if !is_free(
+            &rect,
+            self.nodes
+                .iter()
+                .filter(|node| is_free_node(node))
+                .map(|node| &node.rect),
+        ) {
+            return false;
+        }
+
+        for index in 0..self.nodes.len() {
+            let node = &self.nodes[index];
+            if !is_free_node(node) {
+                continue;
+            }
+            let Some(hole) = intersection(&node.rect, &rect) else {
+                continue;
+            };
+            // Cut the strips around the hole off one by one, what is left is the hole itself.
+            let [top, bottom, left, right] = carve(&node.rect, &hole);
+            let mut index = index;
+            let mut rest = node.rect;
+            if top.h() > Zero::zero() {
+                rest = Rect::new(rest.x(), hole.y(), rest.w(), rest.h() - top.h());
+                self.split(index, top, rest);
+                index = self.second_child(index);
+            }
+            if bottom.h() > Zero::zero() {
+                rest = Rect::new(rest.x(), rest.y(), rest.w(), hole.h());
+                index = self.split(index, rest, bottom);
+            }
+            if left.w() > Zero::zero() {
+                rest = Rect::new(hole.x(), rest.y(), rest.w() - left.w(), rest.h());
+                self.split(index, left, rest);
+                index = self.second_child(index);
+            }
+            if right.w() > Zero::zero() {
+                index = self.split(index, hole, right);
+            }
+            self.nodes[index].kind = NodeKind::Reserved;
+            self.used += hole.w() * hole.h();
+        }
+        true
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+
+    fn grow(&mut self, w: T, h: T) {
+        if h > self.height {
+            self.add_root(
+                Rect::new(Zero::zero(), Zero::zero(), self.width, h),
+                Rect::new(Zero::zero(), self.height, self.width, h - self.height),
+            );
+            self.height = h;
+        }
+        if w > self.width {
+            self.add_root(
+                Rect::new(Zero::zero(), Zero::zero(), w, self.height),
+                Rect::new(self.width, Zero::zero(), w - self.width, self.height),
+            );
+            self.width = w;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AtlasAllocator;
+    use crate::{pack::Packer, Rect};
+
+    #[test]
+    fn atlas_allocator_coalescing() {
+        let mut allocator = AtlasAllocator::new(16, 16);
+        let mut allocations = Vec::new();
+        while let Some(allocation) = allocator.allocate(4, 4) {
+            allocations.push(allocation);
+        }
+        assert_eq!(allocations.len(), 16);
+        assert_eq!(allocator.get(allocations[3].id), Some(allocations[3].rect));
+
+        // Deallocate in an arbitrary order, the whole bin must become free again.
+        for i in [5, 0, 15, 7, 2, 9, 1, 14, 3, 12, 4, 11, 6, 13, 8, 10] {
+            assert!(allocator.deallocate(allocations[i].id));
+        }
+        assert!(!allocator.deallocate(allocations[0].id));
+        assert_eq!(allocator.get(allocations[0].id), None);
+        assert_eq!(allocator.free_space(), 256);
+        assert_eq!(allocator.nodes.len() - allocator.unused.len(), 1);
+        assert_eq!(
+            allocator.allocate(16, 16).map(|a| a.rect),
+            Some(Rect::new(0, 0, 16, 16))
+        );
+    }
+
+    #[test]
+    fn atlas_allocator_stale_id() {
+        let mut allocator = AtlasAllocator::new(8, 8);
+        let a = allocator.allocate(8, 8).unwrap();
+        assert!(allocator.deallocate(a.id));
+        let b = allocator.allocate(8, 8).unwrap();
+        assert_eq!(a.rect, b.rect);
+        assert_ne!(a.id, b.id);
+        assert!(!allocator.deallocate(a.id));
+
+        allocator.clear();
+        assert_eq!(allocator.get(b.id), None);
+        assert_eq!(allocator.free_space(), 64);
+        let c = allocator.allocate(8, 8).unwrap();
+        assert_ne!(b.id, c.id);
+        assert!(!allocator.deallocate(b.id));
+        assert!(allocator.deallocate(c.id));
+    }
+
+    #[test]
+    fn atlas_allocator_grow() {
+        let mut allocator = AtlasAllocator::new(4, 4);
+        let a = allocator.allocate(4, 4).unwrap();
+        allocator.grow(8, 8);
+        assert_eq!(allocator.free_space(), 48);
+        assert_eq!(
+            allocator.allocate(4, 8).map(|a| a.rect),
+            Some(Rect::new(4, 0, 4, 8))
+        );
+        assert!(allocator.deallocate(a.id));
+        assert_eq!(
+            allocator.allocate(4, 4).map(|a| a.rect),
+            Some(Rect::new(0, 0, 4, 4))
+        );
+    }
+
+    #[test]
+    fn atlas_allocator_repack() {
+        let mut allocator = AtlasAllocator::new(16, 16);
+        let allocations = (0..16)
+            .map(|_| allocator.allocate(4, 4).unwrap())
+            .collect::<Vec<_>>();
+        // Leave a checkerboard of holes, there is no room for 8x8 anymore.
+        for (i, allocation) in allocations.iter().enumerate() {
+            if (i + i / 4) % 2 == 0 {
+                assert!(allocator.deallocate(allocation.id));
+            }
+        }
+        assert!(!allocator.can_fit(8, 8));
+
+        let relocations = allocator.repack().unwrap();
+        assert_eq!(relocations.len(), 8);
+        for relocation in relocations.iter() {
+            assert_eq!(allocator.get(relocation.old.id), None);
+            assert_eq!(allocator.get(relocation.new.id), Some(relocation.new.rect));
+        }
+        assert_eq!(allocator.free_space(), 128);
+        assert!(allocator.can_fit(8, 8));
+    }
+}
@@ -0,0 +1,488 @@
+//! Texture atlas packer, that reserves padding, extrusion and spacing around the packed rectangles.
+
+use crate::{
+    pack::{min, Packer, Placement},
+    Number, Rect,
+};
+use nalgebra::Vector2;
+use num_traits::Zero;
+
+/// Maximum number of attempts to find the largest size of a flexible item, that fits in the bin.
+const MAX_SHRINK_STEPS: usize = 16;
+
+/// Position of the trimmed content of a sprite (with transparent borders cut off) in its
+/// original frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Trim<T> {
+    /// Offset of the trimmed content from the top-left corner of the original frame.
+    pub offset: Vector2<T>,
+    /// Size of the original (untrimmed) frame.
+    pub source_size: Vector2<T>,
+}
+
+/// A rectangle to be packed by [`AtlasPacker`], with optional overrides of the packer-wide
+/// options.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct AtlasItem<T> {
+    /// Preferred width of the content of the item.
+    pub width: T,
+    /// Preferred height of the content of the item.
+    pub height: T,
+    /// Padding around the content of the item, overrides [`AtlasPacker::with_padding`].
+    pub padding: Option<T>,
+    /// Whether the item could be rotated, if the inner packer rotates rectangles. Items, such as
+    /// text glyphs, could opt out of rotation with `false`, it does not enable rotation for the
+    /// packers, that do not rotate rectangles.
+    pub allow_rotation: bool,
+    /// The smallest size the content of a flexible item could be shrunk to, if the item does not
+    /// fit in the bin with its preferred size. None means that the item is not flexible.
+    pub min_size: Option<Vector2<T>>,
+    /// The largest size of the content of the item, the preferred size is clamped to it.
+    pub max_size: Option<Vector2<T>>,
+    /// Position of the content in the original frame, if the content was trimmed.
+    pub trim: Option<Trim<T>>,
+}
+
+impl<T> AtlasItem<T> {
+    /// Creates new item of the given size, that uses packer-wide options.
+    pub fn new(width: T, height: T) -> Self {
+        Self {
+            width,
+            height,
+            padding: None,
+            allow_rotation: true,
+            min_size: None,
+            max_size: None,
+            trim: None,
+        }
+    }
+
+    /// Creates new item for the trimmed content of a sprite, the given rectangle is the position
+    /// of the trimmed content in the original frame of the given size. Only the trimmed content
+    /// is packed, the trim is passed to the placement as is.
+    pub fn trimmed(trimmed: Rect<T>, source_width: T, source_height: T) -> Self
+    where
+        T: Number,
+    {
+        Self {
+            trim: Some(Trim {
+                offset: trimmed.position,
+                source_size: Vector2::new(source_width, source_height),
+            }),
+            ..Self::new(trimmed.w(), trimmed.h())
+        }
+    }
+
+    /// Sets the padding around the content of the item.
+    pub fn with_padding(mut self, padding: T) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+
+    /// Sets whether the item could be rotated, see [`Self::allow_rotation`].
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+
+    /// Makes the item flexible, it could be shrunk down to the given size if the bin is nearly
+    /// full.
+    pub fn with_min_size(mut self, width: T, height: T) -> Self {
+        self.min_size = Some(Vector2::new(width, height));
+        self
+    }
+
+    /// Sets the largest size of the content of the item.
+    pub fn with_max_size(mut self, width: T, height: T) -> Self {
+        self.max_size = Some(Vector2::new(width, height));
+        self
+    }
+}
+
+/// A place in the atlas, that was found for an item by [`AtlasPacker::insert_item`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct AtlasPlacement<T> {
+    /// The rectangle of the content of the item together with the extruded border around it.
+    pub extruded: Rect<T>,
+    /// The rectangle of the content of the item.
+    pub content: Rect<T>,
+    /// Size of the content of the item before rotation. It is smaller than the preferred size of
+    /// a flexible item, if the item was shrunk to fit.
+    pub size: Vector2<T>,
+    /// Whether the item was rotated by 90 degrees to fit better.
+    pub rotated: bool,
+    /// Position of the content in the original frame, copied from [`AtlasItem::trim`].
+    pub trim: Option<Trim<T>>,
+}
+
+/// Texture atlas packer is a wrapper over any other packer, that reserves space around the
+/// packed rectangles. This prevents texture filtering from bleeding colors of the neighbouring
+/// images into each other.
+///
+/// - Extrusion is a border on each side of the content of an item, that should be filled with the
+///   duplicated edge pixels of the content. It is required for mipmapped atlases.
+/// - Padding is an empty space on each side of the extruded content of an item.
+/// - Spacing is an additional gap between any two items. It is also kept between the items and
+///   the right and bottom edges of the bin.
+/// - Alignment forces the positions of the content of the items to be multiples of the given
+///   value, for example, 4 pixels for block-compressed textures. The space around the items is
+///   enlarged to keep the alignment, it works with any packer as long as the bin is not
+///   reserved with [`Packer::reserve`] at unaligned positions.
+///
+/// Placements returned by the atlas packer via the [`Packer`] trait contain the rectangle of the
+/// content, without the space around it. Free places ([`Packer::free_rects`]) are the free
+/// places of the inner packer, the space around the content must fit in them too.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AtlasPacker<T, P>
+where
+    T: Number,
+    P: Packer<T>,
+{
+    packer: P,
+    padding: T,
+    spacing: T,
+    extrude: T,
+    alignment: T,
+    /// Pairs of content rectangles and the rectangles occupied in the inner packer.
+    placed: Vec<(Rect<T>, Rect<T>)>,
+}
+
+impl<T, P> AtlasPacker<T, P>
+where
+    T: Number,
+    P: Packer<T>,
+{
+    /// Creates new atlas packer, that uses the given packer to place the rectangles.
+    pub fn new(packer: P) -> Self {
+        Self {
+            packer,
+            padding: Zero::zero(),
+            spacing: Zero::zero(),
+            extrude: Zero::zero(),
+            alignment: Zero::zero(),
+            placed: Default::default(),
+        }
+    }
+
+    /// Sets the default padding around the content of each item.
+    pub fn with_padding(mut self, padding: T) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets the gap between the packed items.
+    pub fn with_spacing(mut self, spacing: T) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Sets the width of the border around the content of each item, that is reserved for the
+    /// duplicated edge pixels of the content.
+    pub fn with_extrude(mut self, extrude: T) -> Self {
+        self.extrude = extrude;
+        self
+    }
+
+    /// Forces the positions of the content of the items to be multiples of the given value. Zero
+    /// (default) disables the alignment.
+    pub fn with_alignment(mut self, alignment: T) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Returns a reference to the inner packer.
+    pub fn packer(&self) -> &P {
+        &self.packer
+    }
+
+    /// Returns the offset of the content of the item with the given size in the occupied rectangle
+    /// and the size of the occupied rectangle.
+    fn occupied_size(&self, item: &AtlasItem<T>, w: T, h: T) -> (T, T, T) {
+        let padding = item.padding.unwrap_or(self.padding);
+        let offset = padding + self.extrude;
+        // Occupied rectangles are placed at the sums of the sizes of other occupied rectangles,
+        // so if all the sizes and the offset of the content are aligned, the content is aligned
+        // too.
+        let lead = align(offset, self.alignment);
+        let trail = offset + self.spacing;
+        (
+            lead,
+            align(lead + w + trail, self.alignment),
+            align(lead + h + trail, self.alignment),
+        )
+    }
+
+    /// Tries to find free place for the given item. Returns None if there insufficient space.
+    /// Flexible items (see [`AtlasItem::with_min_size`]), that do not fit with their preferred
+    /// size, are shrunk to the largest size found, that fits.
+    pub fn insert_item(&mut self, item: &AtlasItem<T>) -> Option<AtlasPlacement<T>> {
+        let mut preferred = Vector2::new(item.width, item.height);
+        if let Some(max_size) = item.max_size {
+            preferred = Vector2::new(min(preferred.x, max_size.x), min(preferred.y, max_size.y));
+        }
+        if let Some(placement) = self.insert_sized(item, preferred) {
+            return Some(placement);
+        }
+
+        let min_size = item.min_size?;
+        let min_size = Vector2::new(min(min_size.x, preferred.x), min(min_size.y, preferred.y));
+        if !self.fits(item, min_size) {
+            return None;
+        }
+        // Binary search of the largest size between the smallest and the preferred one, both
+        // sides are shrunk at the same pace.
+        let two = T::one() + T::one();
+        let (mut low, mut high) = (min_size, preferred);
+        for _ in 0..MAX_SHRINK_STEPS {
+            let middle = Vector2::new(
+                low.x + (high.x - low.x) / two,
+                low.y + (high.y - low.y) / two,
+            );
+            if middle == low {
+                break;
+            }
+            if self.fits(item, middle) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        self.insert_sized(item, low)
+            .or_else(|| self.insert_sized(item, min_size))
+    }
+
+    /// Checks if the item with the given size of the content could be inserted, in any
+    /// orientation.
+    fn fits(&self, item: &AtlasItem<T>, size: Vector2<T>) -> bool {
+        let (_, w, h) = self.occupied_size(item, size.x, size.y);
+        self.packer.can_fit(w, h) || (item.allow_rotation && self.packer.can_fit(h, w))
+    }
+
+    fn insert_sized(&mut self, item: &AtlasItem<T>, size: Vector2<T>) -> Option<AtlasPlacement<T>> {
+        let (lead, w, h) = self.occupied_size(item, size.x, size.y);
+        let placement = if item.allow_rotation {
+            self.packer.insert(w, h)
+        } else {
+            self.packer.insert_unrotated(w, h)
+        }?;
+        let (w, h) = if placement.rotated {
+            (size.y, size.x)
+        } else {
+            (size.x, size.y)
+        };
+        let content = Rect::new(placement.rect.x() + lead, placement.rect.y() + lead, w, h);
+        self.placed.push((content, placement.rect));
+        Some(AtlasPlacement {
+            extruded: content.inflate(self.extrude, self.extrude),
+            content,
+            size,
+            rotated: placement.rotated,
+            trim: item.trim,
+        })
+    }
+}
+
+impl<T, P> Packer<T> for AtlasPacker<T, P>
+where
+    T: Number,
+    P: Packer<T>,
+{
+    fn insert(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.insert_item(&AtlasItem::new(w, h))
+            .map(|placement| Placement {
+                rect: placement.content,
+                rotated: placement.rotated,
+            })
+    }
+
+    fn insert_unrotated(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.insert_item(&AtlasItem::new(w, h).with_rotation(false))
+            .map(|placement| Placement {
+                rect: placement.content,
+                rotated: placement.rotated,
+            })
+    }
+
+    fn remove(&mut self, rect: Rect<T>) -> bool {
+        match self.placed.iter().position(|(content, _)| *content == rect) {
+            Some(index) => {
+                let (_, occupied) = self.placed.swap_remove(index);
+                self.packer.remove(occupied)
+            }
+            None => false,
+        }
+    }
+
+    fn free_space(&self) -> T {
+        self.packer.free_space()
+    }
+
+    fn bounds(&self) -> Rect<T> {
+        self.packer.bounds()
+    }
+
+    fn can_rotate(&self) -> bool {
+        self.packer.can_rotate()
+    }
+
+    fn free_rects(&self) -> Vec<Rect<T>> {
+        self.packer.free_rects()
+    }
+
+    fn can_fit(&self, w: T, h: T) -> bool {
+        let (_, w, h) = self.occupied_size(&AtlasItem::new(w, h), w, h);
+        self.packer.can_fit(w, h)
+    }
+
+    fn grow(&mut self, w: T, h: T) {
+        self.packer.grow(w, h)
+    }
+
+    fn reserve(&mut self, rect: Rect<T>) -> bool {
+        self.packer.reserve(rect)
+    }
+
+    fn clear(&mut self) {
+        self.packer.clear();
+        self.placed.clear();
+    }
+}
+
+/// Rounds the value up to the nearest multiple of the alignment.
+fn align<T: Number>(value: T, alignment: T) -> T {
+    if alignment == Zero::zero() {
+        return value;
+    }
+    let remainder = value % alignment;
+    if remainder == Zero::zero() {
+        value
+    } else {
+        value + alignment - remainder
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AtlasItem, AtlasPacker};
+    use crate::{
+        pack::{GuillotinePacker, Packer, ShelfPacker},
+        Rect,
+    };
+    use nalgebra::Vector2;
+
+    #[test]
+    fn atlas_packer_padding_and_spacing() {
+        let mut atlas = AtlasPacker::new(ShelfPacker::new(32, 32))
+            .with_padding(1)
+            .with_spacing(2);
+
+        let a = atlas.insert(4, 4).unwrap();
+        assert_eq!(a.rect, Rect::new(1, 1, 4, 4));
+        let b = atlas.insert(4, 4).unwrap();
+        // Padding of both items plus the spacing between them.
+        assert_eq!(b.rect, Rect::new(9, 1, 4, 4));
+        assert_eq!(b.rect.x() - (a.rect.x() + a.rect.w()), 1 + 2 + 1);
+
+        let c = atlas
+            .insert_item(&AtlasItem::new(4, 4).with_padding(0))
+            .unwrap();
+        assert_eq!(c.content, Rect::new(16, 0, 4, 4));
+
+        assert!(atlas.remove(b.rect));
+        assert!(!atlas.remove(b.rect));
+        assert_eq!(atlas.insert(4, 4).unwrap().rect, b.rect);
+    }
+
+    #[test]
+    fn atlas_packer_rotation() {
+        let mut atlas =
+            AtlasPacker::new(GuillotinePacker::new(10, 20).with_rotation(true)).with_padding(1);
+        let glyph = AtlasItem::new(18, 8).with_rotation(false);
+        assert!(atlas.insert_item(&glyph).is_none());
+        let placement = atlas.insert(18, 8).unwrap();
+        assert!(placement.rotated);
+        assert_eq!(placement.rect, Rect::new(1, 1, 8, 18));
+    }
+
+    #[test]
+    fn atlas_packer_extrude() {
+        let mut atlas = AtlasPacker::new(ShelfPacker::new(32, 32))
+            .with_padding(1)
+            .with_extrude(2);
+        let a = atlas.insert_item(&AtlasItem::new(4, 4)).unwrap();
+        assert_eq!(a.content, Rect::new(3, 3, 4, 4));
+        assert_eq!(a.extruded, Rect::new(1, 1, 8, 8));
+        let b = atlas.insert_item(&AtlasItem::new(4, 4)).unwrap();
+        assert_eq!(b.extruded, Rect::new(11, 1, 8, 8));
+    }
+
+    #[test]
+    fn atlas_packer_alignment() {
+        let mut atlas = AtlasPacker::new(GuillotinePacker::new(64, 64).with_rotation(true))
+            .with_padding(1)
+            .with_alignment(4);
+        for (w, h) in [(5, 3), (7, 9), (1, 1), (13, 2), (3, 11)] {
+            let placement = atlas.insert_item(&AtlasItem::new(w, h)).unwrap();
+            assert_eq!(placement.content.x() % 4, 0, "{:?}", placement);
+            assert_eq!(placement.content.y() % 4, 0, "{:?}", placement);
+        }
+        assert_eq!(atlas.insert(5, 3).unwrap().rect.x() % 4, 0);
+    }
+
+    #[test]
+    fn atlas_packer_flexible_items() {
+        let mut atlas = AtlasPacker::new(ShelfPacker::new(32, 16)).with_padding(1);
+        let a = atlas.insert_item(&AtlasItem::new(14, 14)).unwrap();
+        assert_eq!(a.size, Vector2::new(14, 14));
+
+        // Only 16x16 is left, the item is shrunk to the largest size, that fits with padding.
+        let item = AtlasItem::new(20, 20).with_min_size(4, 4);
+        let b = atlas.insert_item(&item).unwrap();
+        assert_eq!(b.size, Vector2::new(14, 14));
+        assert_eq!(b.content, Rect::new(17, 1, 14, 14));
+        assert!(atlas.insert_item(&item).is_none());
+
+        // Preferred size is clamped to the largest one.
+        let mut atlas = AtlasPacker::new(ShelfPacker::new(32, 32));
+        let c = atlas
+            .insert_item(&AtlasItem::new(20, 20).with_max_size(8, 30))
+            .unwrap();
+        assert_eq!(c.content, Rect::new(0, 0, 8, 20));
+    }
+
+    #[test]
+    fn atlas_packer_trim() {
+        let mut atlas = AtlasPacker::new(ShelfPacker::new(32, 32)).with_padding(1);
+        let item = AtlasItem::trimmed(Rect::new(3, 2, 10, 12), 16, 16);
+        let placement = atlas.insert_item(&item).unwrap();
+        assert_eq!(placement.content, Rect::new(1, 1, 10, 12));
+        let trim = placement.trim.unwrap();
+        assert_eq!(trim.offset, Vector2::new(3, 2));
+        assert_eq!(trim.source_size, Vector2::new(16, 16));
+    }
+}
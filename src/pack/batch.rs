@@ -0,0 +1,483 @@
+//! Batch packing, that packs a whole set of rectangles at once.
+
+use crate::{
+    pack::{ceil_power_of_two, max, to_svg, Packer, Placement},
+    Number, Rect,
+};
+use nalgebra::Vector2;
+use std::{cmp::Ordering, ops::ControlFlow};
+
+/// Amount of rectangles packed between two calls of the progress callback.
+const PROGRESS_INTERVAL: usize = 64;
+
+/// A key, that is used to sort rectangles before packing them in a batch. Rectangles are packed
+/// in descending order of the key, because packing large rectangles first gives much denser
+/// layouts. Rectangles with equal keys keep their original order.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SortKey {
+    /// Keep the original order.
+    None,
+    /// Sort by area.
+    #[default]
+    Area,
+    /// Sort by the longer side.
+    MaxSide,
+    /// Sort by perimeter.
+    Perimeter,
+    /// Sort by height.
+    Height,
+}
+
+impl SortKey {
+    fn key<T: Number>(self, size: &Vector2<T>) -> T {
+        match self {
+            SortKey::None => T::zero(),
+            SortKey::Area => size.x * size.y,
+            SortKey::MaxSide => {
+                if size.x > size.y {
+                    size.x
+                } else {
+                    size.y
+                }
+            }
+            SortKey::Perimeter => size.x + size.x + size.y + size.y,
+            SortKey::Height => size.y,
+        }
+    }
+}
+
+/// A reason, why a rectangle was rejected by [`Packer::pack_all`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RejectReason {
+    /// The rectangle is larger than the bin in every orientation allowed by the packer, it will
+    /// not fit in any bin of the same size.
+    TooLarge,
+    /// There is not enough free space left in the bin, the rectangle could fit in another (empty)
+    /// bin of the same size.
+    NoSpace,
+    /// The packing was cancelled before the rectangle was packed, see
+    /// [`Packer::pack_all_with_progress`].
+    Cancelled,
+}
+
+/// Progress of a batch packing, that is reported by [`Packer::pack_all_with_progress`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Progress<T> {
+    /// Amount of rectangles, that were packed (placed or rejected) so far.
+    pub processed: usize,
+    /// Amount of rectangles, that were placed so far.
+    pub placed: usize,
+    /// Amount of rectangles in the batch.
+    pub total: usize,
+    /// Area of the bin, that is occupied by the placed rectangles (and reserved areas).
+    pub occupied: T,
+    /// Area of the bin.
+    pub capacity: T,
+}
+
+/// A rectangle, that was rejected by [`Packer::pack_all`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Rejected<T> {
+    /// Index of the rectangle in the set passed to [`Packer::pack_all`].
+    pub index: usize,
+    /// Size of the rectangle.
+    pub size: Vector2<T>,
+    /// Why the rectangle was rejected.
+    pub reason: RejectReason,
+}
+
+/// Result of [`Packer::pack_all`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct PackResult<T> {
+    /// Placements of the rectangles in the order they were passed to [`Packer::pack_all`], `None`
+    /// for the rectangles, that did not fit.
+    pub placements: Vec<Option<Placement<T>>>,
+    /// Rectangles, that did not fit, sorted by their indices.
+    pub rejected: Vec<Rejected<T>>,
+}
+
+impl<T> PackResult<T>
+where
+    T: Number,
+{
+    /// Returns `true` if every rectangle was placed.
+    pub fn is_complete(&self) -> bool {
+        self.rejected.is_empty()
+    }
+
+    /// Returns an iterator over the indices and the placements of the placed rectangles.
+    pub fn placed(&self) -> impl Iterator<Item = (usize, &Placement<T>)> {
+        self.placements
+            .iter()
+            .enumerate()
+            .filter_map(|(index, placement)| placement.as_ref().map(|p| (index, p)))
+    }
+
+    /// Returns the size of the part of the bin, that is actually used by the placed rectangles
+    /// (the bounding size of the placements, including the space from the origin of the bin). It
+    /// could be used to allocate a texture of exact size, when packing into a generous bin.
+    pub fn used_size(&self) -> Vector2<T> {
+        self.placed().fold(
+            Vector2::new(T::zero(), T::zero()),
+            |size, (_, placement)| {
+                let rect = &placement.rect;
+                Vector2::new(
+                    max(size.x, rect.x() + rect.w()),
+                    max(size.y, rect.y() + rect.h()),
+                )
+            },
+        )
+    }
+
+    /// Draws the placed rectangles (labelled with their indices) in the given bin as an SVG
+    /// image, see [`to_svg`] for more info.
+    pub fn to_svg(&self, bounds: &Rect<T>) -> String {
+        let placed = self
+            .placed()
+            .map(|(index, placement)| (placement.rect, index))
+            .collect::<Vec<_>>();
+        to_svg(bounds, &placed, &[])
+    }
+
+    /// Same as [`Self::used_size`], but each side is rounded up to a power of two.
+    pub fn used_size_power_of_two(&self) -> Vector2<T> {
+        let size = self.used_size();
+        Vector2::new(ceil_power_of_two(size.x), ceil_power_of_two(size.y))
+    }
+}
+
+/// Returns the indices of the given sizes in the order, they should be packed in.
+pub(super) fn packing_order<T: Number>(sizes: &[Vector2<T>], sort: SortKey) -> Vec<usize> {
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    if sort != SortKey::None {
+        // Stable sort keeps the original order of the rectangles with equal keys.
+        order.sort_by(|a, b| {
+            sort.key(&sizes[*b])
+                .partial_cmp(&sort.key(&sizes[*a]))
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+    order
+}
+
+/// A callback, that receives the progress of a batch packing and decides whether to continue it.
+type ProgressCallback<'a, T> = dyn FnMut(&Progress<T>) -> ControlFlow<()> + 'a;
+
+pub(super) fn pack_all<T, P>(
+    packer: &mut P,
+    sizes: &[Vector2<T>],
+    sort: SortKey,
+    mut progress: Option<&mut ProgressCallback<'_, T>>,
+) -> PackResult<T>
+where
+    T: Number,
+    P: Packer<T> + ?Sized,
+{
+    let can_rotate = packer.can_rotate();
+    let mut placements = vec![None; sizes.len()];
+    let mut rejected = Vec::new();
+    let mut cancelled = false;
+    let order = packing_order(sizes, sort);
+    for (processed, &index) in order.iter().enumerate() {
+        let size = sizes[index];
+        if cancelled {
+            rejected.push(Rejected {
+                index,
+                size,
+                reason: RejectReason::Cancelled,
+            });
+            continue;
+        }
+        placements[index] = packer.insert(size.x, size.y);
+        if placements[index].is_none() {
+            // Growable packers enlarge the bin as far as they can before giving up, so the
+            // current bounds are the largest bin the rectangle could be placed in.
+            let bounds = packer.bounds();
+            let fits_bin = |w: T, h: T| w <= bounds.w() && h <= bounds.h();
+            let reason = if fits_bin(size.x, size.y) || (can_rotate && fits_bin(size.y, size.x)) {
+                RejectReason::NoSpace
+            } else {
+                RejectReason::TooLarge
+            };
+            rejected.push(Rejected {
+                index,
+                size,
+                reason,
+            });
+        }
+        let processed = processed + 1;
+        if let Some(progress) = progress.as_mut() {
+            if processed % PROGRESS_INTERVAL == 0 || processed == order.len() {
+                // Growable packers enlarge the bin during the batch.
+                let bounds = packer.bounds();
+                let capacity = bounds.w() * bounds.h();
+                let report = Progress {
+                    processed,
+                    placed: processed - rejected.len(),
+                    total: order.len(),
+                    occupied: capacity - packer.free_space(),
+                    capacity,
+                };
+                cancelled = progress(&report).is_break();
+            }
+        }
+    }
+    rejected.sort_by_key(|rejected| rejected.index);
+    PackResult {
+        placements,
+        rejected,
+    }
+}
+
+/// The maximum amount of distinct sizes, that [`pack_uniform`] handles.
+pub const MAX_UNIFORM_SIZES: usize = 8;
+
+/// Fast path for packing rectangles of one size (or a few distinct sizes, see
+/// [`MAX_UNIFORM_SIZES`]), such as tiles or sprites of the same size, into an empty bin of the
+/// given size. Rectangles of each size are laid out left to right in rows, taller sizes first,
+/// which takes linear time instead of the cost of the general packing algorithms. Returns None if
+/// there are too many distinct sizes, use [`Packer::pack_all`] in this case.
+pub fn pack_uniform<T: Number>(width: T, height: T, sizes: &[Vector2<T>]) -> Option<PackResult<T>> {
+    let mut distinct: Vec<Vector2<T>> = Vec::new();
+    for size in sizes {
+        if !distinct.contains(size) {
+            if distinct.len() == MAX_UNIFORM_SIZES {
+                return None;
+            }
+            distinct.push(*size);
+        }
+    }
+    // Taller sizes first, so the rows are filled with rectangles of similar height.
+    distinct.sort_by(|a, b| {
+        (b.y, b.x)
+            .partial_cmp(&(a.y, a.x))
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let mut placements = vec![None; sizes.len()];
+    let mut rejected = Vec::new();
+    let (mut x, mut y, mut row_height) = (T::zero(), T::zero(), T::zero());
+    for size in distinct {
+        for (index, _) in sizes.iter().enumerate().filter(|(_, s)| **s == size) {
+            if size.x > width || size.y > height {
+                rejected.push(Rejected {
+                    index,
+                    size,
+                    reason: RejectReason::TooLarge,
+                });
+                continue;
+            }
+            if x + size.x > width {
+                x = T::zero();
+                y += row_height;
+                row_height = T::zero();
+            }
+            if y + size.y > height {
+                rejected.push(Rejected {
+                    index,
+                    size,
+                    reason: RejectReason::NoSpace,
+                });
+                continue;
+            }
+            placements[index] = Some(Placement {
+                rect: Rect::new(x, y, size.x, size.y),
+                rotated: false,
+            });
+            x += size.x;
+            row_height = max(row_height, size.y);
+        }
+    }
+    rejected.sort_by_key(|rejected| rejected.index);
+    Some(PackResult {
+        placements,
+        rejected,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{pack_uniform, packing_order, RejectReason, Rejected, SortKey};
+    use crate::{
+        pack::{GrowablePacker, GuillotinePacker, Packer, RectPacker, ShelfPacker},
+        Rect,
+    };
+    use nalgebra::Vector2;
+    use std::ops::ControlFlow;
+
+    #[test]
+    fn packing_order_sort_keys() {
+        let sizes = [
+            Vector2::new(1, 6),
+            Vector2::new(4, 4),
+            Vector2::new(5, 1),
+            Vector2::new(2, 2),
+        ];
+        assert_eq!(packing_order(&sizes, SortKey::None), [0, 1, 2, 3]);
+        assert_eq!(packing_order(&sizes, SortKey::Area), [1, 0, 2, 3]);
+        assert_eq!(packing_order(&sizes, SortKey::MaxSide), [0, 2, 1, 3]);
+        assert_eq!(packing_order(&sizes, SortKey::Perimeter), [1, 0, 2, 3]);
+        assert_eq!(packing_order(&sizes, SortKey::Height), [0, 1, 3, 2]);
+    }
+
+    #[test]
+    fn pack_all_no_space() {
+        let mut packer = ShelfPacker::new(10, 10);
+        let sizes = [Vector2::new(10, 6), Vector2::new(10, 6)];
+        let result = packer.pack_all(&sizes, SortKey::Area);
+        assert_eq!(result.rejected.len(), 1);
+        assert_eq!(result.rejected[0].index, 1);
+        assert_eq!(result.rejected[0].reason, RejectReason::NoSpace);
+
+        // Fits only when rotated, but the packer cannot rotate.
+        let mut packer = RectPacker::new(10, 30);
+        let result = packer.pack_all(&[Vector2::new(20, 1)], SortKey::Area);
+        assert_eq!(result.rejected[0].reason, RejectReason::TooLarge);
+        let mut packer = RectPacker::new(10, 30).with_rotation(true);
+        let result = packer.pack_all(&[Vector2::new(20, 1)], SortKey::Area);
+        assert!(result.rejected.is_empty());
+
+        // Larger than the initial bin of a growable packer, but not than its maximum size.
+        let mut packer = GrowablePacker::new(ShelfPacker::new(8, 8), 16, 16);
+        let sizes = [
+            Vector2::new(16, 16),
+            Vector2::new(12, 12),
+            Vector2::new(20, 1),
+        ];
+        let result = packer.pack_all(&sizes, SortKey::None);
+        assert_eq!(result.rejected.len(), 2);
+        assert_eq!(result.rejected[0].reason, RejectReason::NoSpace);
+        assert_eq!(result.rejected[1].reason, RejectReason::TooLarge);
+    }
+
+    #[test]
+    fn pack_all_used_size() {
+        let mut packer = ShelfPacker::new(1024, 1024);
+        let sizes = [
+            Vector2::new(30, 20),
+            Vector2::new(40, 10),
+            Vector2::new(100, 5),
+        ];
+        let result = packer.pack_all(&sizes, SortKey::Height);
+        assert_eq!(result.used_size(), Vector2::new(170, 20));
+        assert_eq!(result.used_size_power_of_two(), Vector2::new(256, 32));
+    }
+
+    #[test]
+    fn pack_all() {
+        let mut packer = ShelfPacker::new(10, 10);
+        let sizes = [Vector2::new(2, 2), Vector2::new(10, 8), Vector2::new(20, 1)];
+        let result = packer.pack_all(&sizes, SortKey::Height);
+        assert!(!result.is_complete());
+        assert_eq!(
+            result.rejected,
+            [Rejected {
+                index: 2,
+                size: Vector2::new(20, 1),
+                reason: RejectReason::TooLarge
+            }]
+        );
+        assert_eq!(
+            result.placed().map(|(index, _)| index).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(result.used_size(), Vector2::new(10, 10));
+        assert_eq!(
+            result
+                .placements
+                .iter()
+                .map(|p| p.map(|p| p.rect))
+                .collect::<Vec<_>>(),
+            [
+                Some(Rect::new(0, 8, 2, 2)),
+                Some(Rect::new(0, 0, 10, 8)),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn pack_uniform_grid() {
+        let sizes = vec![Vector2::new(4, 4); 10];
+        let result = pack_uniform(10, 12, &sizes).unwrap();
+        assert_eq!(result.placements[0].unwrap().rect, Rect::new(0, 0, 4, 4));
+        assert_eq!(result.placements[1].unwrap().rect, Rect::new(4, 0, 4, 4));
+        assert_eq!(result.placements[2].unwrap().rect, Rect::new(0, 4, 4, 4));
+        assert_eq!(result.placed().count(), 6);
+        assert_eq!(
+            result.rejected.iter().map(|r| r.index).collect::<Vec<_>>(),
+            [6, 7, 8, 9]
+        );
+
+        let sizes = [Vector2::new(2, 2), Vector2::new(3, 5), Vector2::new(2, 2)];
+        let result = pack_uniform(10, 10, &sizes).unwrap();
+        assert!(result.is_complete());
+        assert_eq!(result.placements[1].unwrap().rect, Rect::new(0, 0, 3, 5));
+        assert_eq!(result.placements[2].unwrap().rect, Rect::new(5, 0, 2, 2));
+
+        let sizes = (0..9).map(|i| Vector2::new(i, 1)).collect::<Vec<_>>();
+        assert!(pack_uniform(100, 100, &sizes).is_none());
+    }
+
+    #[test]
+    fn pack_all_with_progress() {
+        let sizes = vec![Vector2::new(4u32, 4); 200];
+        let mut reports = Vec::new();
+        let mut packer = GuillotinePacker::new(64, 64);
+        let result = packer.pack_all_with_progress(&sizes, SortKey::Area, &mut |progress| {
+            reports.push(*progress);
+            if progress.processed >= 128 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].processed, 64);
+        assert_eq!(reports[0].occupied, 64 * 16);
+        assert_eq!((reports[1].placed, reports[1].total), (128, 200));
+        assert_eq!(reports[1].capacity, 64 * 64);
+
+        // Remaining rectangles were not packed.
+        assert_eq!(result.placed().count(), 128);
+        assert_eq!(result.rejected.len(), 72);
+        assert!(result
+            .rejected
+            .iter()
+            .all(|rejected| rejected.reason == RejectReason::Cancelled));
+
+        // The capacity follows the bin of a growable packer.
+        let mut packer = GrowablePacker::new(GuillotinePacker::new(8u32, 8), 1024, 1024);
+        let sizes = [Vector2::new(8, 8), Vector2::new(16, 16)];
+        let mut reports = Vec::new();
+        packer.pack_all_with_progress(&sizes, SortKey::Area, &mut |progress| {
+            reports.push(*progress);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].occupied, 8 * 8 + 16 * 16);
+        assert_eq!(
+            reports[0].capacity,
+            packer.bounds().w() * packer.bounds().h()
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Estimation of the size of a bin, that is needed to pack a set of rectangles.
+
+use crate::{
+    pack::{max, min},
+    Number,
+};
+use nalgebra::Vector2;
+use num_traits::NumCast;
+
+/// Typical share of the area of a bin, that is occupied by the rectangles packed by the
+/// packers of this crate.
+const TYPICAL_EFFICIENCY: f64 = 0.85;
+
+/// Result of [`estimate_size`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct SizeEstimate<T> {
+    /// No bin with smaller area could fit the rectangles.
+    pub min_area: T,
+    /// No bin with smaller width could fit the rectangles.
+    pub min_width: T,
+    /// No bin with smaller height could fit the rectangles.
+    pub min_height: T,
+    /// Size of a square bin, that will likely fit the rectangles. It is not guaranteed, so the
+    /// packing must still be able to handle the rectangles, that did not fit.
+    pub estimate: Vector2<T>,
+}
+
+/// Estimates the size of a bin, that is needed to pack the rectangles with the given sizes (where
+/// X - width, Y - height). Padding is added to each side of each rectangle, rotation allows to
+/// fit the rectangles in narrower bins. It is useful to choose initial size of the bin without
+/// trial-and-error packing.
+pub fn estimate_size<T>(sizes: &[Vector2<T>], padding: T, allow_rotation: bool) -> SizeEstimate<T>
+where
+    T: Number + NumCast,
+{
+    let mut min_area = T::zero();
+    let mut min_width = T::zero();
+    let mut min_height = T::zero();
+    let mut longest_side = T::zero();
+    for size in sizes {
+        let w = size.x + padding + padding;
+        let h = size.y + padding + padding;
+        min_area += w * h;
+        if allow_rotation {
+            let short = min(w, h);
+            min_width = max(min_width, short);
+            min_height = max(min_height, short);
+        } else {
+            min_width = max(min_width, w);
+            min_height = max(min_height, h);
+        }
+        longest_side = max(longest_side, max(w, h));
+    }
+
+    let mut side = <f64 as NumCast>::from(min_area)
+        .and_then(|area| T::from((area / TYPICAL_EFFICIENCY).sqrt().ceil()))
+        .unwrap_or(min_area);
+    side = max(side, max(min_width, min_height));
+    if allow_rotation {
+        side = max(side, longest_side);
+    }
+    SizeEstimate {
+        min_area,
+        min_width,
+        min_height,
+        estimate: Vector2::new(side, side),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::estimate_size;
+    use crate::pack::{Packer, ShelfPacker, SortKey};
+    use nalgebra::Vector2;
+
+    #[test]
+    fn estimate_bin_size() {
+        let sizes = vec![Vector2::new(10u32, 10); 100];
+        let estimate = estimate_size(&sizes, 0, false);
+        assert_eq!(estimate.min_area, 10000);
+        assert_eq!((estimate.min_width, estimate.min_height), (10, 10));
+        assert!(estimate.estimate.x * estimate.estimate.y >= estimate.min_area);
+        let mut packer = ShelfPacker::new(estimate.estimate.x, estimate.estimate.y);
+        assert!(packer.pack_all(&sizes, SortKey::Height).is_complete());
+
+        let estimate = estimate_size(&[Vector2::new(100u32, 2), Vector2::new(3, 4)], 1, true);
+        assert_eq!(estimate.min_area, 102 * 4 + 5 * 6);
+        assert_eq!((estimate.min_width, estimate.min_height), (5, 5));
+        assert_eq!(estimate.estimate, Vector2::new(102, 102));
+    }
+}
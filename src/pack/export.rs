@@ -0,0 +1,185 @@
+//! Export of the atlas metadata in the formats, that are commonly consumed by game engines.
+
+use crate::{pack::Trim, Number, Rect};
+use nalgebra::Vector2;
+use std::fmt::Write;
+
+/// Description of a packed item in the atlas.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frame<'a, T> {
+    /// Name of the item, usually the file name of the image.
+    pub name: &'a str,
+    /// The space occupied by the item in the page. If the item was rotated, its width and height
+    /// are swapped (the same as in [`super::Placement::rect`]).
+    pub rect: Rect<T>,
+    /// Whether the item was rotated by 90 degrees.
+    pub rotated: bool,
+    /// Index of the page of the atlas, the item was placed in.
+    pub page: usize,
+    /// Position of the item in its original frame, if the item was trimmed.
+    pub trim: Option<Trim<T>>,
+}
+
+impl<T> Frame<'_, T>
+where
+    T: Number,
+{
+    /// Returns the size of the item before rotation.
+    fn source_size(&self) -> (T, T) {
+        if self.rotated {
+            (self.rect.h(), self.rect.w())
+        } else {
+            (self.rect.w(), self.rect.h())
+        }
+    }
+}
+
+/// Writes the frames in the TexturePacker "JSON (array)" format, with an additional `page` field
+/// in each frame. As in TexturePacker, the size of a rotated frame is the size of the item before
+/// rotation.
+pub fn to_json<T: Number>(frames: &[Frame<T>], page_size: Vector2<T>) -> String {
+    let mut json = String::from("{\"frames\":[");
+    for (i, frame) in frames.iter().enumerate() {
+        let (w, h) = frame.source_size();
+        let (offset, source_size) = match frame.trim {
+            Some(trim) => (trim.offset, trim.source_size),
+            None => (Vector2::new(T::zero(), T::zero()), Vector2::new(w, h)),
+        };
+        if i > 0 {
+            json.push(',');
+        }
+        // Writing to a string never fails.
+        let _ = write!(
+            json,
+            concat!(
+                "\n{{\"filename\":\"{}\",",
+                "\"frame\":{{\"x\":{:?},\"y\":{:?},\"w\":{:?},\"h\":{:?}}},",
+                "\"rotated\":{},\"trimmed\":{},",
+                "\"spriteSourceSize\":{{\"x\":{:?},\"y\":{:?},\"w\":{:?},\"h\":{:?}}},",
+                "\"sourceSize\":{{\"w\":{:?},\"h\":{:?}}},",
+                "\"page\":{}}}"
+            ),
+            escape_json(frame.name),
+            frame.rect.x(),
+            frame.rect.y(),
+            w,
+            h,
+            frame.rotated,
+            frame.trim.is_some(),
+            offset.x,
+            offset.y,
+            w,
+            h,
+            source_size.x,
+            source_size.y,
+            frame.page
+        );
+    }
+    let _ = write!(
+        json,
+        "\n],\"meta\":{{\"size\":{{\"w\":{:?},\"h\":{:?}}}}}}}\n",
+        page_size.x, page_size.y
+    );
+    json
+}
+
+/// Writes the frames as CSV with the header `name,x,y,w,h,rotated,page`. The size is the size of
+/// the space occupied in the page (swapped for rotated frames).
+pub fn to_csv<T: Number>(frames: &[Frame<T>]) -> String {
+    let mut csv = String::from("name,x,y,w,h,rotated,page\n");
+    for frame in frames {
+        let _ = writeln!(
+            csv,
+            "{},{:?},{:?},{:?},{:?},{},{}",
+            escape_csv(frame.name),
+            frame.rect.x(),
+            frame.rect.y(),
+            frame.rect.w(),
+            frame.rect.h(),
+            frame.rotated,
+            frame.page
+        );
+    }
+    csv
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Quotes the field if it contains a separator, a quote or a line break.
+fn escape_csv(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{to_csv, to_json, Frame};
+    use crate::{pack::Trim, Rect};
+    use nalgebra::Vector2;
+
+    fn frames() -> [Frame<'static, u32>; 2] {
+        [
+            Frame {
+                name: "hero.png",
+                rect: Rect::new(0, 0, 16, 32),
+                rotated: false,
+                page: 0,
+                trim: None,
+            },
+            Frame {
+                name: "a,\"b\"",
+                rect: Rect::new(16, 0, 32, 8),
+                rotated: true,
+                page: 1,
+                trim: Some(Trim {
+                    offset: Vector2::new(2, 3),
+                    source_size: Vector2::new(12, 40),
+                }),
+            },
+        ]
+    }
+
+    #[test]
+    fn export_json() {
+        // Rotated frames have the size before rotation.
+        assert_eq!(
+            to_json(&frames(), Vector2::new(64, 64)),
+            concat!(
+                "{\"frames\":[\n",
+                "{\"filename\":\"hero.png\",\"frame\":{\"x\":0,\"y\":0,\"w\":16,\"h\":32},",
+                "\"rotated\":false,\"trimmed\":false,",
+                "\"spriteSourceSize\":{\"x\":0,\"y\":0,\"w\":16,\"h\":32},",
+                "\"sourceSize\":{\"w\":16,\"h\":32},\"page\":0},\n",
+                "{\"filename\":\"a,\\\"b\\\"\",\"frame\":{\"x\":16,\"y\":0,\"w\":8,\"h\":32},",
+                "\"rotated\":true,\"trimmed\":true,",
+                "\"spriteSourceSize\":{\"x\":2,\"y\":3,\"w\":8,\"h\":32},",
+                "\"sourceSize\":{\"w\":12,\"h\":40},\"page\":1}\n",
+                "],\"meta\":{\"size\":{\"w\":64,\"h\":64}}}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn export_csv() {
+        assert_eq!(
+            to_csv(&frames()),
+            "name,x,y,w,h,rotated,page\nhero.png,0,0,16,32,false,0\n\"a,\"\"b\"\"\",16,0,32,8,true,1\n"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Growable packer, that enlarges its bin when a rectangle does not fit.
+
+use crate::{
+    pack::{ceil_power_of_two, max, min, Packer, Placement},
+    Number, Rect,
+};
+use std::marker::PhantomData;
+
+/// Growable packer is a wrapper over any other packer, that starts from a small bin and doubles
+/// its width or height (alternately) every time a rectangle does not fit, until the maximum size
+/// is reached. The rectangles, that were already placed, stay in their places, so it is suitable
+/// for caches (such as glyph caches), that should not reserve a huge bin upfront.
+///
+/// The size of the bin could be forced to be a power of two and/or square, which is required by
+/// some GPUs. In this case the maximum size is reduced to satisfy the constraints too.
+///
+/// Growing is permanent (clearing the packer does not shrink the bin too), if a rectangle does
+/// not fit even in the bin of the maximum size (because of fragmentation), the bin stays grown.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GrowablePacker<T, P>
+where
+    T: Number,
+    P: Packer<T>,
+{
+    packer: P,
+    max_width: T,
+    max_height: T,
+    grow_width: bool,
+    power_of_two: bool,
+    square: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<T, P> GrowablePacker<T, P>
+where
+    T: Number,
+    P: Packer<T>,
+{
+    /// Creates new growable packer, that uses the given packer to place the rectangles. The bounds
+    /// of the given packer define the initial size of the bin, they must not be empty.
+    pub fn new(packer: P, max_width: T, max_height: T) -> Self {
+        Self {
+            packer,
+            max_width,
+            max_height,
+            grow_width: true,
+            power_of_two: false,
+            square: false,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the inner packer.
+    pub fn packer(&self) -> &P {
+        &self.packer
+    }
+
+    /// Forces the size of the bin to be a power of two. Default is `false`.
+    pub fn with_power_of_two(mut self, power_of_two: bool) -> Self {
+        self.power_of_two = power_of_two;
+        self
+    }
+
+    /// Forces the bin to be square. Default is `false`.
+    pub fn with_square(mut self, square: bool) -> Self {
+        self.square = square;
+        self
+    }
+
+    /// Returns the largest size of the bin, that satisfies the constraints.
+    fn max_size(&self) -> (T, T) {
+        let (mut w, mut h) = (self.max_width, self.max_height);
+        if self.square {
+            w = min(w, h);
+            h = w;
+        }
+        if self.power_of_two {
+            w = floor_power_of_two(w);
+            h = floor_power_of_two(h);
+        }
+        (w, h)
+    }
+
+    /// Returns the smallest size of the bin, that is not smaller than the given size and the
+    /// current size of the bin, and satisfies the constraints.
+    fn constrain(&self, mut w: T, mut h: T) -> (T, T) {
+        if self.square {
+            w = max(w, h);
+            h = w;
+        }
+        // Clamped before rounding, the maximum size is a power of two itself in this case, so the
+        // rounding never overflows.
+        let (max_w, max_h) = self.max_size();
+        (w, h) = (min(w, max_w), min(h, max_h));
+        if self.power_of_two {
+            w = ceil_power_of_two(w);
+            h = ceil_power_of_two(h);
+        }
+        let bounds = self.packer.bounds();
+        (max(w, bounds.w()), max(h, bounds.h()))
+    }
+
+    /// Grows the bin to the given size, if it is larger than the current size. Returns `false` if
+    /// the bin was not changed.
+    fn grow_to(&mut self, w: T, h: T) -> bool {
+        let bounds = self.packer.bounds();
+        if w == bounds.w() && h == bounds.h() {
+            false
+        } else {
+            self.packer.grow(w, h);
+            true
+        }
+    }
+
+    /// Inserts the rectangle in the inner packer, growing the bin until the rectangle fits or the
+    /// maximum size is reached.
+    fn insert_growing(&mut self, w: T, h: T, allow_rotation: bool) -> Option<Placement<T>> {
+        let (max_w, max_h) = self.max_size();
+        let fits = |w: T, h: T| w <= max_w && h <= max_h;
+        if !(fits(w, h) || (allow_rotation && fits(h, w))) {
+            return None;
+        }
+        let bounds = self.packer.bounds();
+        let (new_w, new_h) = self.constrain(bounds.w(), bounds.h());
+        self.grow_to(new_w, new_h);
+        loop {
+            let placement = if allow_rotation {
+                self.packer.insert(w, h)
+            } else {
+                self.packer.insert_unrotated(w, h)
+            };
+            if placement.is_some() {
+                return placement;
+            }
+            if !self.grow_step() {
+                return None;
+            }
+        }
+    }
+
+    /// Doubles the width or the height (alternately) or both of them (for square bins) of the
+    /// bin, clamped to the maximum size. Returns `false` if the bin already has the maximum size.
+    fn grow_step(&mut self) -> bool {
+        let bounds = self.packer.bounds();
+        let (w, h) = (bounds.w(), bounds.h());
+        let (max_w, max_h) = self.max_size();
+        let can_grow_width = w < max_w;
+        let can_grow_height = h < max_h;
+        let (new_w, new_h) = if self.square {
+            (double_clamped(w, max_w), double_clamped(h, max_h))
+        } else {
+            let grow_width = if can_grow_width && can_grow_height {
+                self.grow_width
+            } else {
+                can_grow_width
+            };
+            self.grow_width = !grow_width;
+            if grow_width {
+                (double_clamped(w, max_w), h)
+            } else {
+                (w, double_clamped(h, max_h))
+            }
+        };
+        let (new_w, new_h) = self.constrain(new_w, new_h);
+        self.grow_to(new_w, new_h)
+    }
+}
+
+/// Returns the doubled value, clamped to the maximum, without overflowing near the maximum of the
+/// type.
+fn double_clamped<T: Number>(value: T, max_value: T) -> T {
+    if value >= max_value {
+        value
+    } else {
+        value + min(value, max_value - value)
+    }
+}
+
+/// Returns the largest power of two, that is not larger than the given value (or one, if the
+/// value is smaller than one).
+fn floor_power_of_two<T: Number>(value: T) -> T {
+    let mut power = T::one();
+    // Compared with the difference, so the doubled power never overflows.
+    while power <= value && power <= value - power {
+        power += power;
+    }
+    power
+}
+
+impl<T, P> Packer<T> for GrowablePacker<T, P>
+where
+    T: Number,
+    P: Packer<T>,
+{
+    fn insert(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.insert_growing(w, h, true)
+    }
+
+    fn insert_unrotated(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.insert_growing(w, h, false)
+    }
+
+    fn remove(&mut self, rect: Rect<T>) -> bool {
+        self.packer.remove(rect)
+    }
+
+    fn free_space(&self) -> T {
+        self.packer.free_space()
+    }
+
+    fn bounds(&self) -> Rect<T> {
+        self.packer.bounds()
+    }
+
+    fn can_rotate(&self) -> bool {
+        self.packer.can_rotate()
+    }
+
+    fn free_rects(&self) -> Vec<Rect<T>> {
+        self.packer.free_rects()
+    }
+
+    fn grow(&mut self, w: T, h: T) {
+        self.packer.grow(w, h)
+    }
+
+    fn reserve(&mut self, rect: Rect<T>) -> bool {
+        self.packer.reserve(rect)
+    }
+
+    fn clear(&mut self) {
+        self.packer.clear()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GrowablePacker;
+    use crate::{
+        pack::{GuillotinePacker, Packer, RectPacker, ShelfPacker},
+        Rect,
+    };
+
+    fn check_growth<P: Packer<u32>>(packer: P) {
+        let mut packer = GrowablePacker::new(packer, 32, 16);
+        assert_eq!(packer.insert(33, 1), None);
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 8, 8));
+
+        let mut placed: Vec<Rect<u32>> = Vec::new();
+        for _ in 0..8 {
+            let rect = packer.insert(8, 8).unwrap().rect;
+            assert!(placed.iter().all(|other| !other.intersects(rect)));
+            placed.push(rect);
+        }
+        // Placed rectangles are not moved by growing.
+        assert_eq!(placed[0], Rect::new(0, 0, 8, 8));
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 32, 16));
+        assert_eq!(packer.free_space(), 0);
+        assert_eq!(packer.insert(8, 8), None);
+    }
+
+    #[test]
+    fn growable_packer() {
+        check_growth(RectPacker::new(8, 8));
+        check_growth(GuillotinePacker::new(8, 8));
+        check_growth(ShelfPacker::new(8, 8));
+    }
+
+    #[test]
+    fn growable_packer_constraints() {
+        let mut packer = GrowablePacker::new(GuillotinePacker::new(5, 3), 100, 100)
+            .with_power_of_two(true)
+            .with_square(true);
+        packer.insert(2, 2).unwrap();
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 8, 8));
+        packer.insert(10, 4).unwrap();
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 16, 16));
+        // The largest square power of two bin, that fits in the maximum size, is 64x64.
+        assert_eq!(packer.insert(65, 1), None);
+        packer.insert(40, 1).unwrap();
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 64, 64));
+
+        let mut packer =
+            GrowablePacker::new(ShelfPacker::new(3, 3), 100, 100).with_power_of_two(true);
+        packer.insert(1, 1).unwrap();
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 4, 4));
+        packer.insert(4, 2).unwrap();
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 4, 4));
+        // Neither the width nor the height alone is enough for a new shelf.
+        packer.insert(4, 4).unwrap();
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 8, 8));
+        // Doubling must not overflow near the maximum of the type.
+        let mut packer = GrowablePacker::new(ShelfPacker::new(16384u16, 16384), 65535, 65535);
+        packer.insert(60000, 1).unwrap();
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 65535, 32768));
+        let mut packer = GrowablePacker::new(ShelfPacker::new(16384u16, 16384), 65535, 65535)
+            .with_power_of_two(true);
+        assert_eq!(packer.insert(60000, 1), None);
+        packer.insert(30000, 30000).unwrap();
+        assert_eq!(packer.bounds(), Rect::new(0, 0, 32768, 32768));
+    }
+}
@@ -0,0 +1,593 @@
+//! Guillotine rectangle packer, every split of the free space is a straight cut through the whole
+//! free rectangle.
+
+use crate::{
+    geometry::intersection,
+    pack::{better_score, carve, is_free, min, Heuristic, Packer, Placement},
+    Number, Rect,
+};
+use num_traits::Zero;
+
+/// A rule that decides how a free rectangle is cut in two after a rectangle was placed in its
+/// top-left corner. "Horizontal" cut separates the free space below the placed rectangle from the
+/// free space to the right of it along the whole width of the free rectangle.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SplitRule {
+    /// Cut along the shorter leftover side, which keeps the larger leftover rectangle as big as
+    /// possible.
+    #[default]
+    ShorterLeftoverAxis,
+    /// Cut along the longer leftover side.
+    LongerLeftoverAxis,
+    /// Cut along the shorter side of the free rectangle.
+    ShorterAxis,
+    /// Cut along the longer side of the free rectangle.
+    LongerAxis,
+    /// Cut so that the smaller of the two leftover rectangles is as small as possible.
+    MinimizeArea,
+    /// Cut so that the larger of the two leftover rectangles is as big as possible.
+    MaximizeArea,
+}
+
+impl SplitRule {
+    fn split_horizontally<T: Number>(self, free: &Rect<T>, w: T, h: T) -> bool {
+        let leftover_w = free.w() - w;
+        let leftover_h = free.h() - h;
+        match self {
+            SplitRule::ShorterLeftoverAxis => leftover_w <= leftover_h,
+            SplitRule::LongerLeftoverAxis => leftover_w > leftover_h,
+            SplitRule::ShorterAxis => free.w() <= free.h(),
+            SplitRule::LongerAxis => free.w() > free.h(),
+            SplitRule::MinimizeArea => w * leftover_h > leftover_w * h,
+            SplitRule::MaximizeArea => w * leftover_h <= leftover_w * h,
+        }
+    }
+}
+
+/// Guillotine rectangle packer. It keeps a list of free rectangles, places each new rectangle in
+/// the top-left corner of the free rectangle, that fits it best according to the [`Heuristic`],
+/// and cuts the rest of the free rectangle in two according to the [`SplitRule`]. Resulting
+/// layouts can always be cut out of the bin with a sequence of edge-to-edge cuts, which is
+/// required for cutting stock problems.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GuillotinePacker<T>
+where
+    T: Number,
+{
+    free: Vec<Rect<T>>,
+    /// Indices of the free rectangles sorted by width, so the search skips the free rectangles,
+    /// that are too narrow, without looking at them.
+    by_width: Vec<usize>,
+    /// Indices of the free rectangles sorted by their top-left corners column by column and row
+    /// by row, so the neighbours of a free rectangle are found without looking at the others.
+    by_column: Vec<usize>,
+    by_row: Vec<usize>,
+    /// Free rectangles, that were added or changed since the last merge. The buffer is reused
+    /// between the insertions.
+    dirty: Vec<usize>,
+    used: Vec<Rect<T>>,
+    reserved: T,
+    width: T,
+    height: T,
+    split_rule: SplitRule,
+    heuristic: Heuristic,
+    merge: bool,
+    allow_rotation: bool,
+}
+
+impl<T> GuillotinePacker<T>
+where
+    T: Number,
+{
+    /// Creates new instance of the guillotine packer with given bounds.
+    pub fn new(w: T, h: T) -> Self {
+        Self {
+            free: vec![Rect::new(Zero::zero(), Zero::zero(), w, h)],
+            by_width: vec![0],
+            by_column: vec![0],
+            by_row: vec![0],
+            dirty: Default::default(),
+            used: Default::default(),
+            reserved: Zero::zero(),
+            width: w,
+            height: h,
+            split_rule: Default::default(),
+            heuristic: Default::default(),
+            merge: true,
+            allow_rotation: false,
+        }
+    }
+
+    /// Sets the rule, that is used to split free rectangles.
+    pub fn with_split_rule(mut self, split_rule: SplitRule) -> Self {
+        self.split_rule = split_rule;
+        self
+    }
+
+    /// Sets the rule, that is used to choose a free rectangle for a new rectangle.
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    /// Allows the packer to rotate rectangles by 90 degrees, if it makes them fit better.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+
+    /// Enables or disables merging of adjacent free rectangles, that form a rectangle. Merging
+    /// makes room for bigger rectangles at the cost of slower insertion.
+    pub fn with_merge(mut self, merge: bool) -> Self {
+        self.merge = merge;
+        self
+    }
+
+    /// Returns the rule, that is used to split free rectangles.
+    pub fn split_rule(&self) -> SplitRule {
+        self.split_rule
+    }
+
+    /// Clears packer and prepares it for another run.
+    pub fn clear(&mut self) {
+        self.free.clear();
+        self.by_width.clear();
+        self.by_column.clear();
+        self.by_row.clear();
+        self.used.clear();
+        self.reserved = Zero::zero();
+        self.push_free(Rect::new(
+            Zero::zero(),
+            Zero::zero(),
+            self.width,
+            self.height,
+        ));
+        self.dirty.clear();
+    }
+
+    /// Tries to find free place to put rectangle with given size. Returns None if there
+    /// insufficient space. The rectangle could be rotated (see [`Self::with_rotation`]), in this
+    /// case the width and the height of the returned rectangle are swapped.
+    pub fn find_free(&mut self, w: T, h: T) -> Option<Rect<T>> {
+        self.place(w, h, self.allow_rotation)
+            .map(|placement| placement.rect)
+    }
+
+    fn place(&mut self, w: T, h: T, allow_rotation: bool) -> Option<Placement<T>> {
+        let rotations: &[bool] = if allow_rotation && w != h {
+            &[false, true]
+        } else {
+            &[false]
+        };
+
+        let width_bound = |free_w: T| {
+            rotations
+                .iter()
+                .map(|&rotated| {
+                    let (w, h) = if rotated { (h, w) } else { (w, h) };
+                    self.heuristic.width_bound(free_w, w, h)
+                })
+                .reduce(|a, b| a.zip(b).map(|(a, b)| min(a, b)))
+                .flatten()
+        };
+
+        // The candidates are visited in the order of their width, ties are resolved in favor of
+        // the free rectangle that comes first in the list, as if the list was scanned linearly.
+        // The scan stops as soon as the width alone makes the score worse than the best one.
+        let narrowest = if rotations.len() > 1 { min(w, h) } else { w };
+        let mut best: Option<(usize, bool, (T, T))> = None;
+        for &i in &self.by_width[self.width_start(narrowest)..] {
+            let free = &self.free[i];
+            if let Some((_, _, best_score)) = best {
+                if width_bound(free.w()).is_some_and(|bound| bound > best_score.0) {
+                    break;
+                }
+            }
+            for &rotated in rotations {
+                let (w, h) = if rotated { (h, w) } else { (w, h) };
+                if free.w() >= w && free.h() >= h {
+                    let score = self.heuristic.score(free, w, h);
+                    if best.map_or(true, |(best_i, _, best_score)| {
+                        better_score(score, best_score)
+                            || (!better_score(best_score, score) && i < best_i)
+                    }) {
+                        best = Some((i, rotated, score));
+                    }
+                }
+            }
+        }
+
+        let (index, rotated, _) = best?;
+        let (w, h) = if rotated { (h, w) } else { (w, h) };
+        let free = self.remove_free(index);
+        let placed = Rect::new(free.x(), free.y(), w, h);
+        self.used.push(placed);
+
+        let (bottom, right) = if self.split_rule.split_horizontally(&free, w, h) {
+            (
+                Rect::new(free.x(), free.y() + h, free.w(), free.h() - h),
+                Rect::new(free.x() + w, free.y(), free.w() - w, h),
+            )
+        } else {
+            (
+                Rect::new(free.x(), free.y() + h, w, free.h() - h),
+                Rect::new(free.x() + w, free.y(), free.w() - w, free.h()),
+            )
+        };
+        for rect in [bottom, right] {
+            if rect.w() > Zero::zero() && rect.h() > Zero::zero() {
+                self.push_free(rect);
+            }
+        }
+        self.merge_free_rects();
+
+        Some(Placement {
+            rect: placed,
+            rotated,
+        })
+    }
+
+    /// Returns the position in the width index of the first free rectangle, that is at least `w`
+    /// wide.
+    fn width_start(&self, w: T) -> usize {
+        self.by_width.partition_point(|&i| self.free[i].w() < w)
+    }
+
+    /// Returns the free rectangles and their sorted indices along with the sort keys.
+    fn indices(&mut self) -> (&[Rect<T>], [SortedIndex<'_, T>; 3]) {
+        (
+            &self.free,
+            [
+                (&mut self.by_width, width_key),
+                (&mut self.by_column, column_key),
+                (&mut self.by_row, row_key),
+            ],
+        )
+    }
+
+    fn push_free(&mut self, rect: Rect<T>) {
+        let index = self.free.len();
+        self.free.push(rect);
+        let (free, indices) = self.indices();
+        for (sorted, key) in indices {
+            let slot = lower_bound(sorted, free, key, key(&rect));
+            sorted.insert(slot, index);
+        }
+        self.dirty.push(index);
+    }
+
+    /// Removes the free rectangle the same way as [`Vec::swap_remove`] does.
+    fn remove_free(&mut self, index: usize) -> Rect<T> {
+        let last = self.free.len() - 1;
+        let (free, indices) = self.indices();
+        for (sorted, key) in indices {
+            let slot = slot_of(sorted, free, key, index);
+            sorted.remove(slot);
+            if index != last {
+                let slot = slot_of(sorted, free, key, last);
+                sorted[slot] = index;
+            }
+        }
+        if let Some(position) = self.dirty.iter().position(|&d| d == index) {
+            self.dirty.swap_remove(position);
+        }
+        if let Some(moved) = self.dirty.iter_mut().find(|d| **d == last) {
+            *moved = index;
+        }
+        self.free.swap_remove(index)
+    }
+
+    fn set_free(&mut self, index: usize, rect: Rect<T>) {
+        let (free, indices) = self.indices();
+        for (sorted, key) in indices {
+            let slot = slot_of(sorted, free, key, index);
+            sorted.remove(slot);
+        }
+        self.free[index] = rect;
+        let (free, indices) = self.indices();
+        for (sorted, key) in indices {
+            let slot = lower_bound(sorted, free, key, key(&rect));
+            sorted.insert(slot, index);
+        }
+        if !self.dirty.contains(&index) {
+            self.dirty.push(index);
+        }
+    }
+
+    /// Returns the free rectangles, that may share a whole edge with the given one: the ones,
+    /// that start right below it and right after it, and the closest ones above it and to the
+    /// left of it. Free rectangles do not overlap, so no other free rectangle could share an edge.
+    fn neighbours(&self, index: usize) -> [Option<usize>; 4] {
+        let rect = &self.free[index];
+        let after = |sorted: &[usize], key: SortKey<T>, corner: (T, T)| {
+            sorted
+                .get(lower_bound(sorted, &self.free, key, corner))
+                .copied()
+        };
+        let before = |sorted: &[usize], key: SortKey<T>| {
+            lower_bound(sorted, &self.free, key, key(rect))
+                .checked_sub(1)
+                .map(|slot| sorted[slot])
+        };
+        [
+            after(&self.by_column, column_key, (rect.x(), rect.y() + rect.h())),
+            before(&self.by_column, column_key),
+            after(&self.by_row, row_key, (rect.y(), rect.x() + rect.w())),
+            before(&self.by_row, row_key),
+        ]
+    }
+
+    /// Merges pairs of free rectangles, that share a whole edge. Merged rectangle is a guillotine
+    /// cut away from both of its parts, so it does not break the layout guarantee. Only the pairs
+    /// with at least one dirty (new or changed) rectangle are checked, since the rest of them were
+    /// already checked before. The pairs are merged in the same order as a full pairwise scan
+    /// would merge them.
+    fn merge_free_rects(&mut self) {
+        if !self.merge {
+            self.dirty.clear();
+            return;
+        }
+        loop {
+            let mut first: Option<(usize, usize, Rect<T>)> = None;
+            for &d in &self.dirty {
+                for k in self.neighbours(d).into_iter().flatten() {
+                    let (i, j) = if d < k { (d, k) } else { (k, d) };
+                    if first.is_some_and(|(first_i, first_j, _)| (first_i, first_j) <= (i, j)) {
+                        continue;
+                    }
+                    if let Some(merged) = merge(&self.free[i], &self.free[j]) {
+                        first = Some((i, j, merged));
+                    }
+                }
+            }
+            let Some((i, j, merged)) = first else {
+                break;
+            };
+            self.set_free(i, merged);
+            self.remove_free(j);
+        }
+        self.dirty.clear();
+    }
+}
+
+impl<T> Packer<T> for GuillotinePacker<T>
+where
+    T: Number,
+{
+    fn insert(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.place(w, h, self.allow_rotation)
+    }
+
+    fn insert_unrotated(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.place(w, h, false)
+    }
+
+    fn remove(&mut self, rect: Rect<T>) -> bool {
+        match self.used.iter().position(|used| *used == rect) {
+            Some(index) => {
+                self.used.swap_remove(index);
+                if rect.w() > Zero::zero() && rect.h() > Zero::zero() {
+                    self.push_free(rect);
+                    self.merge_free_rects();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn free_space(&self) -> T {
+        self.used
+            .iter()
+            .fold(self.width * self.height - self.reserved, |free, used| {
+                free - used.w() * used.h()
+            })
+    }
+
+    fn free_rects(&self) -> Vec<Rect<T>> {
+        self.free.clone()
+    }
+
+    fn bounds(&self) -> Rect<T> {
+        Rect::new(Zero::zero(), Zero::zero(), self.width, self.height)
+    }
+
+    fn can_rotate(&self) -> bool {
+        self.allow_rotation
+    }
+
+    fn grow(&mut self, w: T, h: T) {
+        // Cut the new bin into the old one, the strip to the right of it and the strip below.
+        let right = Rect::new(self.width, Zero::zero(), w - self.width, self.height);
+        let bottom = Rect::new(Zero::zero(), self.height, w, h - self.height);
+        for rect in [right, bottom] {
+            if rect.w() > Zero::zero() && rect.h() > Zero::zero() {
+                self.push_free(rect);
+            }
+        }
+        self.width = w;
+        self.height = h;
+        self.merge_free_rects();
+    }
+
+    fn reserve(&mut self, rect: Rect<T>) -> bool {
+        if !is_free(&rect, &self.free) {
+            return false;
+        }
+        let mut index = 0;
+        while index < self.free.len() {
+            let free = self.free[index];
+            match intersection(&free, &rect) {
+                Some(hole) => {
+                    self.remove_free(index);
+                    for part in carve(&free, &hole) {
+                        if part.w() > Zero::zero() && part.h() > Zero::zero() {
+                            self.push_free(part);
+                        }
+                    }
+                }
+                None => index += 1,
+            }
+        }
+        self.reserved += rect.w() * rect.h();
+        self.merge_free_rects();
+        true
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+/// Sort key of an index of the free rectangles.
+type SortKey<T> = fn(&Rect<T>) -> (T, T);
+
+/// An index of the free rectangles along with its sort key.
+type SortedIndex<'a, T> = (&'a mut Vec<usize>, SortKey<T>);
+
+fn width_key<T: Number>(rect: &Rect<T>) -> (T, T) {
+    (rect.w(), rect.h())
+}
+
+fn column_key<T: Number>(rect: &Rect<T>) -> (T, T) {
+    (rect.x(), rect.y())
+}
+
+fn row_key<T: Number>(rect: &Rect<T>) -> (T, T) {
+    (rect.y(), rect.x())
+}
+
+/// Returns the position in the sorted index of the first free rectangle, whose key is not less
+/// than the given one.
+fn lower_bound<T: Number>(
+    sorted: &[usize],
+    free: &[Rect<T>],
+    key: SortKey<T>,
+    value: (T, T),
+) -> usize {
+    sorted.partition_point(|&i| key(&free[i]) < value)
+}
+
+/// Returns the position of the free rectangle with the given index in the sorted index.
+fn slot_of<T: Number>(sorted: &[usize], free: &[Rect<T>], key: SortKey<T>, index: usize) -> usize {
+    let start = lower_bound(sorted, free, key, key(&free[index]));
+    start
+        + sorted[start..]
+            .iter()
+            .position(|&i| i == index)
+            .expect("every free rectangle must be indexed")
+}
+
+/// Returns a rectangle, that is the union of the given rectangles, if they share a whole edge.
+fn merge<T: Number>(a: &Rect<T>, b: &Rect<T>) -> Option<Rect<T>> {
+    if a.x() == b.x() && a.w() == b.w() {
+        if a.y() + a.h() == b.y() {
+            return Some(Rect::new(a.x(), a.y(), a.w(), a.h() + b.h()));
+        } else if b.y() + b.h() == a.y() {
+            return Some(Rect::new(a.x(), b.y(), a.w(), a.h() + b.h()));
+        }
+    } else if a.y() == b.y() && a.h() == b.h() {
+        if a.x() + a.w() == b.x() {
+            return Some(Rect::new(a.x(), a.y(), a.w() + b.w(), a.h()));
+        } else if b.x() + b.w() == a.x() {
+            return Some(Rect::new(b.x(), a.y(), a.w() + b.w(), a.h()));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{column_key, merge, row_key, width_key, GuillotinePacker, SortKey, SplitRule};
+    use crate::{pack::Packer, Rect};
+
+    #[test]
+    fn guillotine_packer_find_free() {
+        let mut packer = GuillotinePacker::new(10, 10);
+
+        assert_eq!(packer.find_free(20, 20), None);
+        assert_eq!(packer.find_free(4, 6), Some(Rect::new(0, 0, 4, 6)));
+        // Leftover height (4) is shorter than leftover width (6), so the cut is vertical and the
+        // free space to the right is 6x10.
+        assert_eq!(packer.find_free(6, 6), Some(Rect::new(4, 0, 6, 6)));
+        // Two 4 pixels high strips below the placed rectangles are merged together.
+        assert_eq!(packer.find_free(10, 4), Some(Rect::new(0, 6, 10, 4)));
+        assert_eq!(packer.find_free(1, 1), None);
+
+        packer.clear();
+        assert_eq!(packer.find_free(10, 10), Some(Rect::new(0, 0, 10, 10)));
+    }
+
+    #[test]
+    fn guillotine_packer_split_rules() {
+        let rules = [
+            SplitRule::ShorterLeftoverAxis,
+            SplitRule::LongerLeftoverAxis,
+            SplitRule::ShorterAxis,
+            SplitRule::LongerAxis,
+            SplitRule::MinimizeArea,
+            SplitRule::MaximizeArea,
+        ];
+        for rule in rules {
+            let mut packer = GuillotinePacker::new(64, 64).with_split_rule(rule);
+            assert_eq!(packer.split_rule(), rule);
+
+            let mut placed: Vec<Rect<i32>> = Vec::new();
+            while let Some(rect) = packer.find_free(8, 4) {
+                assert!(rect.x() + rect.w() <= 64 && rect.y() + rect.h() <= 64);
+                assert!(placed.iter().all(|other| !other.intersects(rect)));
+                placed.push(rect);
+            }
+            // Sizes divide the bin evenly, so every rule must fill it completely.
+            assert_eq!(placed.len(), 128, "{:?}", rule);
+        }
+    }
+
+    #[test]
+    fn guillotine_packer_merge() {
+        let a = Rect::new(0, 0, 5, 5);
+        assert_eq!(
+            merge(&a, &Rect::new(5, 0, 5, 5)),
+            Some(Rect::new(0, 0, 10, 5))
+        );
+        assert_eq!(
+            merge(&Rect::new(0, 5, 5, 3), &a),
+            Some(Rect::new(0, 0, 5, 8))
+        );
+        assert_eq!(merge(&a, &Rect::new(5, 1, 5, 5)), None);
+        assert_eq!(merge(&a, &Rect::new(6, 0, 5, 5)), None);
+    }
+
+    #[test]
+    fn guillotine_packer_width_index() {
+        let mut packer = GuillotinePacker::new(256, 256).with_rotation(true);
+        let mut placed = Vec::new();
+        for i in 0..400u32 {
+            if let Some(placement) = packer.insert(1 + i * 7 % 13, 1 + i * 5 % 11) {
+                placed.push(placement.rect);
+            }
+            if i % 3 == 0 {
+                packer.remove(placed.swap_remove(0));
+            }
+        }
+        let free = packer.free_rects()[0];
+        assert!(packer.reserve(free));
+
+        // Each index must cover every free rectangle exactly once, sorted by its key.
+        for (sorted, key) in [
+            (&packer.by_width, width_key as SortKey<u32>),
+            (&packer.by_column, column_key),
+            (&packer.by_row, row_key),
+        ] {
+            let mut indices = sorted.clone();
+            indices.sort_unstable();
+            assert_eq!(indices, (0..packer.free.len()).collect::<Vec<_>>());
+            assert!(sorted
+                .windows(2)
+                .all(|pair| key(&packer.free[pair[0]]) <= key(&packer.free[pair[1]])));
+        }
+        assert!(packer.dirty.is_empty());
+    }
+}
@@ -0,0 +1,378 @@
+//! Multi-bin packer, that packs rectangles into as many pages as needed.
+
+use crate::{
+    pack::{Packer, Placement, SortKey},
+    Number, Rect,
+};
+use nalgebra::Vector2;
+use std::{collections::HashMap, marker::PhantomData};
+
+/// A place in one of the pages, that was found for a rectangle by [`MultiBinPacker`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct MultiBinPlacement<T> {
+    /// Index of the page, the rectangle was placed in.
+    pub page_index: usize,
+    /// The space occupied by the rectangle in the page.
+    pub rect: Rect<T>,
+    /// Whether the rectangle was rotated by 90 degrees to fit better.
+    pub rotated: bool,
+}
+
+/// Multi-bin packer packs rectangles into a set of equally sized pages, adding new pages when the
+/// existing ones are full. Each page is a copy of the template packer, so any packer (with any
+/// options) could be used to pack the pages. A rectangle is placed into the first page, that has
+/// enough room for it, so the space left in the earlier pages is used by smaller rectangles.
+///
+/// Rectangles could be tagged with a group id (for example, frames of one animation), the packer
+/// tries to keep the rectangles of the same group on the same page, which helps to batch draw
+/// calls.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiBinPacker<T, P>
+where
+    T: Number,
+    P: Packer<T> + Clone,
+{
+    template: P,
+    pages: Vec<P>,
+    spare_pages: Vec<P>,
+    /// Pages used by each group, in the order of their usage.
+    group_pages: HashMap<usize, Vec<usize>>,
+    max_pages: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T, P> MultiBinPacker<T, P>
+where
+    T: Number,
+    P: Packer<T> + Clone,
+{
+    /// Creates new multi-bin packer, that uses a copy of the given empty packer for every page.
+    pub fn new(template: P) -> Self {
+        Self {
+            template,
+            pages: Default::default(),
+            spare_pages: Default::default(),
+            group_pages: Default::default(),
+            max_pages: usize::MAX,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the maximum amount of pages. Default is unlimited.
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Returns the pages created so far.
+    pub fn pages(&self) -> &[P] {
+        &self.pages
+    }
+
+    /// Tries to find free place to put rectangle with given size in one of the pages, adding a new
+    /// page if needed. Returns None if the rectangle does not fit in an empty page or the maximum
+    /// amount of pages is reached.
+    pub fn insert(&mut self, w: T, h: T) -> Option<MultiBinPlacement<T>> {
+        for (page_index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(placement) = page.insert(w, h) {
+                return Some(multi_bin_placement(page_index, placement));
+            }
+        }
+
+        let mut page = self.new_page()?;
+        let Some(placement) = page.insert(w, h) else {
+            self.spare_pages.push(page);
+            return None;
+        };
+        self.pages.push(page);
+        Some(multi_bin_placement(self.pages.len() - 1, placement))
+    }
+
+    /// Same as [`Self::insert`], but tries the pages, that already contain the rectangles of the
+    /// given group, first.
+    pub fn insert_in_group(&mut self, w: T, h: T, group: usize) -> Option<MultiBinPlacement<T>> {
+        let group_pages = self.group_pages.entry(group).or_default();
+        for &page_index in group_pages.iter() {
+            if let Some(placement) = self.pages[page_index].insert(w, h) {
+                return Some(multi_bin_placement(page_index, placement));
+            }
+        }
+        let placement = self.insert(w, h)?;
+        self.add_group_page(group, placement.page_index);
+        Some(placement)
+    }
+
+    /// Packs a whole group of rectangles with the given sizes (where X - width, Y - height). The
+    /// packer tries to put all of them on the same page: first on the pages of the group, then on
+    /// the other pages and then on a new page. If the group does not fit in a single page, the
+    /// rectangles are inserted one by one with [`Self::insert_in_group`]. Returns the placements
+    /// in the order of the sizes.
+    pub fn insert_group(
+        &mut self,
+        sizes: &[Vector2<T>],
+        group: usize,
+    ) -> Vec<Option<MultiBinPlacement<T>>> {
+        let group_pages = self.group_pages.get(&group).cloned().unwrap_or_default();
+        let other_pages = (0..self.pages.len()).filter(|index| !group_pages.contains(index));
+        let candidates = group_pages
+            .iter()
+            .copied()
+            .chain(other_pages)
+            .collect::<Vec<_>>();
+        for page_index in candidates {
+            let mut page = self.pages[page_index].clone();
+            let result = page.pack_all(sizes, SortKey::Area);
+            if result.is_complete() {
+                self.pages[page_index] = page;
+                self.add_group_page(group, page_index);
+                return group_placements(page_index, result.placements);
+            }
+        }
+
+        if let Some(mut page) = self.new_page() {
+            let result = page.pack_all(sizes, SortKey::Area);
+            if result.is_complete() {
+                self.pages.push(page);
+                let page_index = self.pages.len() - 1;
+                self.add_group_page(group, page_index);
+                return group_placements(page_index, result.placements);
+            }
+            page.clear();
+            self.spare_pages.push(page);
+        }
+
+        sizes
+            .iter()
+            .map(|size| self.insert_in_group(size.x, size.y, group))
+            .collect()
+    }
+
+    /// Returns an empty page, if the maximum amount of pages is not reached.
+    fn new_page(&mut self) -> Option<P> {
+        if self.pages.len() >= self.max_pages {
+            return None;
+        }
+        Some(
+            self.spare_pages
+                .pop()
+                .unwrap_or_else(|| self.template.clone()),
+        )
+    }
+
+    fn add_group_page(&mut self, group: usize, page_index: usize) {
+        let group_pages = self.group_pages.entry(group).or_default();
+        if !group_pages.contains(&page_index) {
+            group_pages.push(page_index);
+        }
+    }
+
+    /// Releases the space occupied by a rectangle in the given page. Returns `false` if there is
+    /// no such rectangle in the page.
+    pub fn remove(&mut self, page_index: usize, rect: Rect<T>) -> bool {
+        self.pages
+            .get_mut(page_index)
+            .is_some_and(|page| page.remove(rect))
+    }
+
+    /// Removes all the pages. The pages are cleared and kept for reuse, so packing another set of
+    /// rectangles does not allocate memory again.
+    pub fn clear(&mut self) {
+        for mut page in self.pages.drain(..) {
+            page.clear();
+            self.spare_pages.push(page);
+        }
+        self.group_pages.clear();
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T, P> MultiBinPacker<T, P>
+where
+    T: Number + Send + Sync,
+    P: Packer<T> + Clone + Send,
+{
+    /// Packs a set of rectangles with the given sizes (where X - width, Y - height) into new
+    /// pages in parallel. The rectangles are distributed over the estimated amount of pages, so
+    /// that each page gets a similar total area, then each page is packed on its own thread.
+    /// Rectangles, that did not fit in their pages, are inserted one by one afterwards (which may
+    /// add more pages). Returns the placements in the order of the sizes.
+    pub fn pack_all_par(
+        &mut self,
+        sizes: &[Vector2<T>],
+        sort: SortKey,
+    ) -> Vec<Option<MultiBinPlacement<T>>> {
+        use crate::pack::batch::packing_order;
+        use rayon::prelude::*;
+
+        let bounds = self.template.bounds();
+        let page_area = bounds.w() * bounds.h();
+        let total_area = sizes
+            .iter()
+            .fold(T::zero(), |area, size| area + size.x * size.y);
+        let max_new_pages = self.max_pages - self.pages.len();
+        let (mut page_count, mut capacity) = (1, page_area);
+        while capacity < total_area && page_count < max_new_pages {
+            page_count += 1;
+            capacity += page_area;
+        }
+        let page_count = page_count.min(max_new_pages);
+
+        // Give the next largest rectangle to the page with the least total area.
+        let mut partitions = vec![(T::zero(), Vec::new()); page_count];
+        for index in packing_order(sizes, SortKey::Area) {
+            let size = sizes[index];
+            if let Some((area, indices)) =
+                partitions
+                    .iter_mut()
+                    .reduce(|a, b| if b.0 < a.0 { b } else { a })
+            {
+                *area += size.x * size.y;
+                indices.push(index);
+            }
+        }
+
+        let pages = partitions
+            .into_iter()
+            .map(|(_, indices)| {
+                let page = self.new_page().unwrap_or_else(|| self.template.clone());
+                (page, indices)
+            })
+            .collect::<Vec<_>>();
+        let packed = pages
+            .into_par_iter()
+            .map(|(mut page, indices)| {
+                let page_sizes = indices.iter().map(|&i| sizes[i]).collect::<Vec<_>>();
+                let result = page.pack_all(&page_sizes, sort);
+                (page, indices, result.placements)
+            })
+            .collect::<Vec<_>>();
+
+        let mut placements = vec![None; sizes.len()];
+        for (page, indices, page_placements) in packed {
+            self.pages.push(page);
+            let page_index = self.pages.len() - 1;
+            for (index, placement) in indices.into_iter().zip(page_placements) {
+                placements[index] =
+                    placement.map(|placement| multi_bin_placement(page_index, placement));
+            }
+        }
+        // Spill the rectangles, that did not fit, to any page with enough free space.
+        for (placement, size) in placements.iter_mut().zip(sizes) {
+            if placement.is_none() {
+                *placement = self.insert(size.x, size.y);
+            }
+        }
+        placements
+    }
+}
+
+fn multi_bin_placement<T>(page_index: usize, placement: Placement<T>) -> MultiBinPlacement<T> {
+    MultiBinPlacement {
+        page_index,
+        rect: placement.rect,
+        rotated: placement.rotated,
+    }
+}
+
+fn group_placements<T>(
+    page_index: usize,
+    placements: Vec<Option<Placement<T>>>,
+) -> Vec<Option<MultiBinPlacement<T>>> {
+    placements
+        .into_iter()
+        .map(|placement| placement.map(|placement| multi_bin_placement(page_index, placement)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::MultiBinPacker;
+    use crate::{
+        pack::{GuillotinePacker, Packer, ShelfPacker},
+        Rect,
+    };
+    use nalgebra::Vector2;
+
+    #[test]
+    fn multi_bin_packer() {
+        let mut packer = MultiBinPacker::new(GuillotinePacker::new(10, 10)).with_max_pages(2);
+        assert_eq!(packer.insert(11, 1), None);
+        assert!(packer.pages().is_empty());
+
+        let a = packer.insert(8, 8).unwrap();
+        assert_eq!(a.page_index, 0);
+        let b = packer.insert(8, 8).unwrap();
+        assert_eq!(b.page_index, 1);
+        // Smaller rectangles fill the space left in the first page.
+        let c = packer.insert(2, 8).unwrap();
+        assert_eq!(c.page_index, 0);
+        assert_eq!(c.rect, Rect::new(8, 0, 2, 8));
+        assert_eq!(packer.insert(8, 8), None);
+
+        assert!(packer.remove(0, a.rect));
+        assert!(!packer.remove(2, a.rect));
+        assert_eq!(packer.pages()[0].free_space(), 84);
+        assert_eq!(packer.insert(8, 8).unwrap().page_index, 0);
+
+        packer.clear();
+        assert!(packer.pages().is_empty());
+        assert_eq!(packer.spare_pages.len(), 2);
+        let a = packer.insert(8, 8).unwrap();
+        assert_eq!(a.page_index, 0);
+        assert_eq!(a.rect, Rect::new(0, 0, 8, 8));
+        assert_eq!(packer.spare_pages.len(), 1);
+    }
+
+    #[test]
+    fn multi_bin_packer_groups() {
+        let mut packer = MultiBinPacker::new(ShelfPacker::new(10, 10));
+        let a = packer.insert_in_group(6, 6, 0).unwrap();
+        let b = packer.insert_in_group(6, 6, 1).unwrap();
+        assert_eq!((a.page_index, b.page_index), (0, 1));
+        // Fits in the first page too, but the page of the group is preferred.
+        assert_eq!(packer.insert_in_group(4, 4, 1).unwrap().page_index, 1);
+        assert_eq!(packer.insert_in_group(4, 4, 0).unwrap().page_index, 0);
+
+        // The whole group does not fit in the existing pages, so it goes to a new one.
+        let sizes = [Vector2::new(5, 5), Vector2::new(5, 5), Vector2::new(10, 4)];
+        let placements = packer.insert_group(&sizes, 2);
+        assert!(placements.iter().all(|p| p.unwrap().page_index == 2));
+
+        // Too large for a single page, spread over the pages.
+        let sizes = vec![Vector2::new(10, 10); 2];
+        let placements = packer.insert_group(&sizes, 3);
+        assert_eq!(placements[0].unwrap().page_index, 3);
+        assert_eq!(placements[1].unwrap().page_index, 4);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn multi_bin_packer_par() {
+        let mut seed = 7u32;
+        let sizes = (0..500)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                Vector2::new(1 + (seed >> 16) % 16, 1 + (seed >> 8) % 16)
+            })
+            .collect::<Vec<_>>();
+        let mut packer = MultiBinPacker::new(GuillotinePacker::new(64, 64));
+        let placements = packer.pack_all_par(&sizes, crate::pack::SortKey::Area);
+        assert!(packer.pages().len() >= 5);
+        for (i, a) in placements.iter().enumerate() {
+            let a = a.unwrap();
+            assert_eq!((a.rect.w(), a.rect.h()), (sizes[i].x, sizes[i].y));
+            for b in placements[i + 1..].iter().map(|b| b.unwrap()) {
+                assert!(a.page_index != b.page_index || !a.rect.intersects(b.rect));
+            }
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! Shelf rectangle packer, packs rectangles in rows.
+
+use crate::{
+    pack::{better_score, Heuristic, Packer, Placement},
+    Number, Rect,
+};
+use num_traits::Zero;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Shelf<T> {
+    y: T,
+    height: T,
+    /// Horizontal position of the free space left at the end of the shelf.
+    cursor: T,
+    /// Free spans (position and width) left by removed rectangles, sorted by position.
+    gaps: Vec<(T, T)>,
+}
+
+impl<T> Shelf<T>
+where
+    T: Number,
+{
+    fn release(&mut self, x: T, w: T) {
+        if x + w == self.cursor {
+            self.cursor = x;
+            while let Some(&(gap_x, gap_w)) = self.gaps.last() {
+                if gap_x + gap_w != self.cursor {
+                    break;
+                }
+                self.cursor = gap_x;
+                self.gaps.pop();
+            }
+            return;
+        }
+
+        let index = self.gaps.iter().position(|(gap_x, _)| *gap_x > x);
+        let index = index.unwrap_or(self.gaps.len());
+        self.gaps.insert(index, (x, w));
+        // Merge with the neighbours.
+        if index + 1 < self.gaps.len() && x + w == self.gaps[index + 1].0 {
+            let next_w = self.gaps[index + 1].1;
+            self.gaps[index].1 += next_w;
+            self.gaps.remove(index + 1);
+        }
+        if index > 0 && self.gaps[index - 1].0 + self.gaps[index - 1].1 == x {
+            let w = self.gaps[index].1;
+            self.gaps[index - 1].1 += w;
+            self.gaps.remove(index);
+        }
+    }
+}
+
+/// Where a rectangle will be placed in a shelf.
+#[derive(Copy, Clone)]
+enum Slot {
+    Gap(usize),
+    End,
+}
+
+/// Shelf rectangle packer. It places rectangles left to right in horizontal rows (shelves), the
+/// height of a shelf is the height of the first rectangle placed in it. A rectangle goes to the
+/// free place in a shelf, that is high enough and fits it best according to the [`Heuristic`],
+/// otherwise a new shelf is opened below the last one. This is the cheapest packer, and it works
+/// best for rectangles of similar height, such as glyphs of a font or icons of the same size.
+/// Shelf packer does not support [`Packer::reserve`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShelfPacker<T>
+where
+    T: Number,
+{
+    shelves: Vec<Shelf<T>>,
+    used: Vec<Rect<T>>,
+    width: T,
+    height: T,
+    heuristic: Heuristic,
+    allow_rotation: bool,
+}
+
+impl<T> ShelfPacker<T>
+where
+    T: Number,
+{
+    /// Creates new instance of the shelf packer with given bounds.
+    pub fn new(w: T, h: T) -> Self {
+        Self {
+            shelves: Default::default(),
+            used: Default::default(),
+            width: w,
+            height: h,
+            heuristic: Heuristic::FirstFit,
+            allow_rotation: false,
+        }
+    }
+
+    /// Sets the rule, that is used to choose a free place in the shelves for a new rectangle.
+    /// Default is [`Heuristic::FirstFit`].
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    /// Allows the packer to rotate rectangles by 90 degrees, if it makes them fit better.
+    pub fn with_rotation(mut self, allow_rotation: bool) -> Self {
+        self.allow_rotation = allow_rotation;
+        self
+    }
+
+    /// Clears packer and prepares it for another run.
+    pub fn clear(&mut self) {
+        self.shelves.clear();
+        self.used.clear();
+    }
+
+    /// Tries to find free place to put rectangle with given size. Returns None if there
+    /// insufficient space. The rectangle could be rotated (see [`Self::with_rotation`]), in this
+    /// case the width and the height of the returned rectangle are swapped.
+    pub fn find_free(&mut self, w: T, h: T) -> Option<Rect<T>> {
+        self.place(w, h, self.allow_rotation)
+            .map(|placement| placement.rect)
+    }
+
+    fn place(&mut self, w: T, h: T, allow_rotation: bool) -> Option<Placement<T>> {
+        let rotations: &[bool] = if allow_rotation && w != h {
+            &[false, true]
+        } else {
+            &[false]
+        };
+
+        let mut best: Option<(usize, Slot, bool, (T, T))> = None;
+        'search: for (index, shelf) in self.shelves.iter().enumerate() {
+            let slots = shelf
+                .gaps
+                .iter()
+                .enumerate()
+                .map(|(i, (x, gap_w))| (Slot::Gap(i), *x, *gap_w))
+                .chain(std::iter::once((
+                    Slot::End,
+                    shelf.cursor,
+                    self.width - shelf.cursor,
+                )));
+            for (slot, x, free_w) in slots {
+                for &rotated in rotations {
+                    let (w, h) = if rotated { (h, w) } else { (w, h) };
+                    if free_w < w || shelf.height < h {
+                        continue;
+                    }
+                    let free = Rect::new(x, shelf.y, free_w, shelf.height);
+                    let score = self.heuristic.score(&free, w, h);
+                    if best.map_or(true, |(_, _, _, best_score)| {
+                        better_score(score, best_score)
+                    }) {
+                        best = Some((index, slot, rotated, score));
+                    }
+                    if self.heuristic == Heuristic::FirstFit {
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        let placement = match best {
+            Some((index, slot, rotated, _)) => {
+                let (w, h) = if rotated { (h, w) } else { (w, h) };
+                let shelf = &mut self.shelves[index];
+                let x = match slot {
+                    Slot::Gap(i) => {
+                        let (x, gap_w) = shelf.gaps[i];
+                        if gap_w == w {
+                            shelf.gaps.remove(i);
+                        } else {
+                            shelf.gaps[i] = (x + w, gap_w - w);
+                        }
+                        x
+                    }
+                    Slot::End => {
+                        let x = shelf.cursor;
+                        shelf.cursor += w;
+                        x
+                    }
+                };
+                Placement {
+                    rect: Rect::new(x, shelf.y, w, h),
+                    rotated,
+                }
+            }
+            None => {
+                // Open a new shelf, as low as possible to leave more space for the next ones.
+                let y = self
+                    .shelves
+                    .last()
+                    .map_or(Zero::zero(), |shelf| shelf.y + shelf.height);
+                let mut best: Option<(T, T, bool)> = None;
+                for &rotated in rotations {
+                    let (w, h) = if rotated { (h, w) } else { (w, h) };
+                    if w <= self.width
+                        && y + h <= self.height
+                        && best.map_or(true, |(_, best_h, _)| h < best_h)
+                    {
+                        best = Some((w, h, rotated));
+                    }
+                }
+                let (w, h, rotated) = best?;
+                self.shelves.push(Shelf {
+                    y,
+                    height: h,
+                    cursor: w,
+                    gaps: Default::default(),
+                });
+                Placement {
+                    rect: Rect::new(Zero::zero(), y, w, h),
+                    rotated,
+                }
+            }
+        };
+        self.used.push(placement.rect);
+        Some(placement)
+    }
+}
+
+impl<T> Packer<T> for ShelfPacker<T>
+where
+    T: Number,
+{
+    fn insert(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.place(w, h, self.allow_rotation)
+    }
+
+    fn insert_unrotated(&mut self, w: T, h: T) -> Option<Placement<T>> {
+        self.place(w, h, false)
+    }
+
+    fn remove(&mut self, rect: Rect<T>) -> bool {
+        let Some(index) = self.used.iter().position(|used| *used == rect) else {
+            return false;
+        };
+        self.used.swap_remove(index);
+
+        if let Some(shelf) = self.shelves.iter_mut().find(|shelf| shelf.y == rect.y()) {
+            shelf.release(rect.x(), rect.w());
+        }
+        // Empty shelves at the bottom could be opened again with a different height.
+        while self
+            .shelves
+            .last()
+            .is_some_and(|shelf| shelf.cursor == Zero::zero())
+        {
+            self.shelves.pop();
+        }
+        true
+    }
+
+    fn free_space(&self) -> T {
+        self.used
+            .iter()
+            .fold(self.width * self.height, |free, used| {
+                free - used.w() * used.h()
+            })
+    }
+
+    fn free_rects(&self) -> Vec<Rect<T>> {
+        let mut free = Vec::new();
+        for shelf in self.shelves.iter() {
+            for &(x, w) in shelf.gaps.iter() {
+                free.push(Rect::new(x, shelf.y, w, shelf.height));
+            }
+            if shelf.cursor < self.width {
+                free.push(Rect::new(
+                    shelf.cursor,
+                    shelf.y,
+                    self.width - shelf.cursor,
+                    shelf.height,
+                ));
+            }
+        }
+        // The space below the last shelf is used for new shelves.
+        let bottom = self
+            .shelves
+            .last()
+            .map_or(Zero::zero(), |shelf| shelf.y + shelf.height);
+        if bottom < self.height {
+            free.push(Rect::new(
+                Zero::zero(),
+                bottom,
+                self.width,
+                self.height - bottom,
+            ));
+        }
+        free
+    }
+
+    fn bounds(&self) -> Rect<T> {
+        Rect::new(Zero::zero(), Zero::zero(), self.width, self.height)
+    }
+
+    fn can_rotate(&self) -> bool {
+        self.allow_rotation
+    }
+
+    fn grow(&mut self, w: T, h: T) {
+        // Shelves are extended to the new width, new shelves are opened in the added height.
+        self.width = w;
+        self.height = h;
+    }
+
+    fn reserve(&mut self, _rect: Rect<T>) -> bool {
+        // Shelves could not be built around arbitrary rectangles.
+        false
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ShelfPacker;
+    use crate::{
+        pack::{Heuristic, Packer},
+        Rect,
+    };
+
+    #[test]
+    fn shelf_packer_find_free() {
+        let mut packer = ShelfPacker::new(10, 10);
+
+        assert_eq!(packer.find_free(11, 1), None);
+        assert_eq!(packer.find_free(4, 4), Some(Rect::new(0, 0, 4, 4)));
+        assert_eq!(packer.find_free(4, 3), Some(Rect::new(4, 0, 4, 3)));
+        // Does not fit in the first shelf, opens a new one.
+        assert_eq!(packer.find_free(4, 4), Some(Rect::new(0, 4, 4, 4)));
+        // Fits in the rest of the first shelf.
+        assert_eq!(packer.find_free(2, 4), Some(Rect::new(8, 0, 2, 4)));
+        assert_eq!(packer.find_free(10, 3), None);
+        assert_eq!(packer.find_free(10, 2), Some(Rect::new(0, 8, 10, 2)));
+
+        packer.clear();
+        assert_eq!(packer.find_free(10, 10), Some(Rect::new(0, 0, 10, 10)));
+    }
+
+    #[test]
+    fn shelf_packer_remove() {
+        let mut packer = ShelfPacker::new(10, 10).with_heuristic(Heuristic::BestAreaFit);
+        let a = packer.find_free(3, 5).unwrap();
+        let b = packer.find_free(3, 5).unwrap();
+        let c = packer.find_free(3, 5).unwrap();
+        assert!(packer.remove(a));
+        assert!(packer.remove(b));
+        // Adjacent gaps are merged.
+        assert_eq!(packer.find_free(6, 2), Some(Rect::new(0, 0, 6, 2)));
+        // Removing the last rectangles of the last shelf releases the shelf.
+        let d = packer.find_free(10, 5).unwrap();
+        assert!(packer.remove(d));
+        assert_eq!(packer.shelves.len(), 1);
+        assert!(packer.remove(c));
+        assert_eq!(packer.shelves[0].cursor, 6);
+    }
+}
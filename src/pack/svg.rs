@@ -0,0 +1,96 @@
+//! Visualization of packing results in SVG format.
+
+use crate::{Number, Rect};
+use std::fmt::{Display, Write};
+
+/// Draws the bin with the placed and the free rectangles as an SVG image, that is useful to see
+/// how much space is wasted by a layout. The bin itself is drawn red, so every red area in the
+/// image is wasted space (neither placed nor free). Free rectangles are drawn green (with a
+/// dashed outline, they may overlap for some packers), placed rectangles are drawn blue with
+/// their labels in the middle.
+pub fn to_svg<T, L>(bounds: &Rect<T>, placed: &[(Rect<T>, L)], free: &[Rect<T>]) -> String
+where
+    T: Number,
+    L: Display,
+{
+    let mut svg = String::new();
+    // Writing to a string never fails.
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{:?} {:?} {:?} {:?}" width="{:?}" height="{:?}">"#,
+        bounds.x(),
+        bounds.y(),
+        bounds.w(),
+        bounds.h(),
+        bounds.w(),
+        bounds.h()
+    );
+    write_rect(&mut svg, bounds, r##"fill="#e74c3c""##);
+    for rect in free {
+        write_rect(
+            &mut svg,
+            rect,
+            r##"fill="#2ecc71" fill-opacity="0.5" stroke="#27ae60" stroke-dasharray="2""##,
+        );
+    }
+    for (rect, label) in placed {
+        write_rect(&mut svg, rect, r##"fill="#3498db" stroke="#1f618d""##);
+        let center = rect.center();
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:?}" y="{:?}" text-anchor="middle" dominant-baseline="middle" font-size="10">{}</text>"#,
+            center.x,
+            center.y,
+            escape(&label.to_string())
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn write_rect<T: Number>(svg: &mut String, rect: &Rect<T>, style: &str) {
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{:?}" y="{:?}" width="{:?}" height="{:?}" {}/>"#,
+        rect.x(),
+        rect.y(),
+        rect.w(),
+        rect.h(),
+        style
+    );
+}
+
+/// Escapes the characters, that have special meaning in XML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::to_svg;
+    use crate::Rect;
+
+    #[test]
+    fn svg_export() {
+        let svg = to_svg(
+            &Rect::new(0, 0, 16, 8),
+            &[(Rect::new(0, 0, 8, 8), "a<b")],
+            &[Rect::new(8, 0, 8, 4)],
+        );
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 16 8""#));
+        assert!(svg.contains(r##"<rect x="8" y="0" width="8" height="4" fill="#2ecc71""##));
+        assert!(svg.contains(r##"<rect x="0" y="0" width="8" height="8" fill="#3498db""##));
+        assert!(svg.contains(">a&lt;b</text>"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+}
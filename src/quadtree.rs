@@ -1,20 +1,132 @@
 //! Quadrilateral (quad) tree is used for space partitioning and fast spatial queries.
 
-use crate::Rect;
+use crate::{curve::morton_code, geometry::contains_rect, OptionRect, Rect};
 use arrayvec::ArrayVec;
 use nalgebra::Vector2;
+use std::{
+    collections::{BTreeSet, HashSet, VecDeque},
+    fmt::{Display, Formatter},
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    ops::{ControlFlow, Range},
+};
 
-enum QuadTreeNode<T> {
-    Leaf {
-        bounds: Rect<f32>,
-        ids: Vec<T>,
-    },
+mod int;
+mod linear;
+
+pub use int::IntQuadTree;
+pub use linear::LinearQuadTree;
+
+/// Node of the quad tree. Nodes do not store their bounds, because they can be derived from the
+/// bounds of the parent node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum QuadTreeNode {
+    /// Range of the entities in the arena of the tree.
+    Leaf { first: u32, count: u32 },
+    /// Index of the first of four consecutive child nodes and the range of the entities, that
+    /// are stored in the branch itself (see [`QuadTreeBuilder::with_branch_entries`]).
     Branch {
-        bounds: Rect<f32>,
-        leaves: [usize; 4],
+        children: u32,
+        first: u32,
+        count: u32,
     },
 }
 
+const EMPTY_LEAF: QuadTreeNode = QuadTreeNode::Leaf { first: 0, count: 0 };
+
+impl QuadTreeNode {
+    fn leaf(range: Range<usize>) -> Self {
+        QuadTreeNode::Leaf {
+            first: to_index(range.start),
+            count: to_index(range.len()),
+        }
+    }
+
+    fn branch(children: usize, range: Range<usize>) -> Self {
+        QuadTreeNode::Branch {
+            children: to_index(children),
+            first: to_index(range.start),
+            count: to_index(range.len()),
+        }
+    }
+
+    /// Range of the entities of the node in the arena of the tree.
+    fn range(&self) -> Range<usize> {
+        match *self {
+            QuadTreeNode::Leaf { first, count } | QuadTreeNode::Branch { first, count, .. } => {
+                first as usize..first as usize + count as usize
+            }
+        }
+    }
+
+    fn with_range(self, range: Range<usize>) -> Self {
+        match self {
+            QuadTreeNode::Leaf { .. } => QuadTreeNode::leaf(range),
+            QuadTreeNode::Branch { children, .. } => QuadTreeNode::branch(children as usize, range),
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    fn relocated(&self, node_offset: u32, arena_offset: u32) -> Self {
+        match *self {
+            QuadTreeNode::Leaf { first, count } => QuadTreeNode::Leaf {
+                first: first + arena_offset,
+                count,
+            },
+            QuadTreeNode::Branch {
+                children,
+                first,
+                count,
+            } => QuadTreeNode::Branch {
+                children: children + node_offset,
+                first: first + arena_offset,
+                count,
+            },
+        }
+    }
+}
+
+fn to_index(value: usize) -> u32 {
+    u32::try_from(value).expect("quad tree cannot hold more than u32::MAX nodes or entities")
+}
+
+/// Unlike [`Rect::contains`], a point on a shared edge of adjacent nodes is contained in only one
+/// of them.
+fn contains_half_open(rect: &Rect<f32>, point: Vector2<f32>) -> bool {
+    point.x >= rect.x()
+        && point.x < rect.x() + rect.w()
+        && point.y >= rect.y()
+        && point.y < rect.y() + rect.h()
+}
+
+/// Returns the distance, at which the ray enters the rectangle (or zero, if it starts inside of
+/// it).
+fn ray_enters_at(origin: Vector2<f32>, dir: Vector2<f32>, rect: &Rect<f32>) -> Option<f32> {
+    let mut enter = 0.0f32;
+    let mut exit = f32::INFINITY;
+    for (o, d, min, size) in [
+        (origin.x, dir.x, rect.x(), rect.w()),
+        (origin.y, dir.y, rect.y(), rect.h()),
+    ] {
+        if d == 0.0 {
+            if o < min || o > min + size {
+                return None;
+            }
+        } else {
+            let t0 = (min - o) / d;
+            let t1 = (min + size - o) / d;
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+    }
+    if enter <= exit {
+        Some(enter)
+    } else {
+        None
+    }
+}
+
 fn split_rect(rect: &Rect<f32>) -> [Rect<f32>; 4] {
     let half_size = rect.size.scale(0.5);
     [
@@ -38,94 +150,609 @@ fn split_rect(rect: &Rect<f32>) -> [Rect<f32>; 4] {
 }
 
 /// Quadrilateral (quad) tree is used for space partitioning and fast spatial queries.
+///
+/// # Memory layout
+///
+/// Nodes are stored in a single array and the children of a branch node are always stored next
+/// to each other, so a branch only needs the index of its first child. The ids (and optionally
+/// the bounds) of the entities of all leaf nodes are stored in a single shared arena, each leaf
+/// node holds a range in it.
+///
+/// # Query order
+///
+/// All the queries traverse the tree depth-first. The entities of a branch node (if any) are
+/// visited first, then its children in the order: top-left, top-right, bottom-right,
+/// bottom-left. The entities of a node are visited in the order they were added to it (removal
+/// may move the last entity of a node in place of the removed one). The order does not depend on
+/// anything but the sequence of operations done with the tree, so the same sequence of
+/// operations produces the same query results in the same order on every run and platform. Use
+/// [`QuadTree::sorted_query`] if the order must not depend on the history of the tree at all.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "QuadTreeData<T>"))]
 pub struct QuadTree<T> {
-    nodes: Vec<QuadTreeNode<T>>,
+    nodes: Vec<QuadTreeNode>,
+    ids: Vec<T>,
+    /// Bounds of each entity in `ids`. Empty if the tree does not store entity bounds.
+    entry_bounds: Vec<Rect<f32>>,
+    /// Key of each entity in `ids`. All the copies of an entity, that is stored in multiple
+    /// nodes, share the same key, so queries could visit the entity only once.
+    keys: Vec<u32>,
+    /// Amount of the keys given to the entities so far. Keys of the removed entities are not
+    /// reused until the tree is rebuilt.
+    key_count: u32,
     root: usize,
+    root_bounds: Rect<f32>,
+    /// Amount of the items in the arena, that are not referenced by any leaf node.
+    garbage: usize,
     split_threshold: usize,
+    store_bounds: bool,
+    branch_entries: bool,
 }
 
 impl<T: 'static> Default for QuadTree<T> {
     fn default() -> Self {
         Self {
             nodes: Default::default(),
+            ids: Default::default(),
+            entry_bounds: Default::default(),
+            keys: Default::default(),
+            key_count: 0,
             root: Default::default(),
+            root_bounds: Default::default(),
+            garbage: 0,
+            split_threshold: 16,
+            store_bounds: false,
+            branch_entries: false,
+        }
+    }
+}
+
+/// Unchecked contents of a deserialized quad tree. The tree is accepted only if its nodes and the
+/// arena are consistent, otherwise queries could index out of bounds or loop forever.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct QuadTreeData<T> {
+    nodes: Vec<QuadTreeNode>,
+    ids: Vec<T>,
+    entry_bounds: Vec<Rect<f32>>,
+    keys: Vec<u32>,
+    key_count: u32,
+    root: usize,
+    root_bounds: Rect<f32>,
+    garbage: usize,
+    split_threshold: usize,
+    store_bounds: bool,
+    branch_entries: bool,
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<QuadTreeData<T>> for QuadTree<T> {
+    type Error = &'static str;
+
+    fn try_from(data: QuadTreeData<T>) -> Result<Self, Self::Error> {
+        let arena_len = data.ids.len();
+        if data.split_threshold == 0 {
+            return Err("quad tree split threshold must be greater than zero");
+        }
+        if data.entry_bounds.len() != if data.store_bounds { arena_len } else { 0 } {
+            return Err("quad tree entity bounds do not match its arena");
+        }
+        if data.keys.len() != arena_len || data.keys.iter().any(|&key| key >= data.key_count) {
+            return Err("quad tree entity keys do not match its arena");
+        }
+        if data.garbage > arena_len {
+            return Err("quad tree garbage exceeds its arena");
+        }
+        if !data.nodes.is_empty() {
+            // No node may be reachable from the root twice, otherwise the nodes do not form a tree.
+            let mut visited = vec![false; data.nodes.len()];
+            let mut stack = vec![data.root];
+            while let Some(index) = stack.pop() {
+                match visited.get_mut(index) {
+                    Some(visited) if !*visited => *visited = true,
+                    _ => return Err("quad tree nodes do not form a tree"),
+                }
+                let node = &data.nodes[index];
+                if node.range().end > arena_len {
+                    return Err("quad tree node references entities outside of its arena");
+                }
+                if let QuadTreeNode::Branch { children, .. } = *node {
+                    stack.extend((0..4).map(|i| children as usize + i));
+                }
+            }
+        }
+        Ok(Self {
+            nodes: data.nodes,
+            ids: data.ids,
+            entry_bounds: data.entry_bounds,
+            keys: data.keys,
+            key_count: data.key_count,
+            root: data.root,
+            root_bounds: data.root_bounds,
+            garbage: data.garbage,
+            split_threshold: data.split_threshold,
+            store_bounds: data.store_bounds,
+            branch_entries: data.branch_entries,
+        })
+    }
+}
+
+/// Quad tree builder allows you to set various options of the quad tree before building it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuadTreeBuilder {
+    split_threshold: usize,
+    store_bounds: bool,
+    branch_entries: bool,
+}
+
+impl Default for QuadTreeBuilder {
+    fn default() -> Self {
+        Self {
             split_threshold: 16,
+            store_bounds: false,
+            branch_entries: false,
+        }
+    }
+}
+
+impl QuadTreeBuilder {
+    /// Creates new builder with default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum amount of entities in a leaf node, before it will be split in four.
+    pub fn with_split_threshold(mut self, split_threshold: usize) -> Self {
+        self.split_threshold = split_threshold;
+        self
+    }
+
+    /// Enables or disables storing bounds of each entity in leaf nodes. When enabled, queries
+    /// will test the actual bounds of the entities instead of returning everything in the leaf
+    /// node. This is more precise, but requires more memory.
+    pub fn with_stored_bounds(mut self, store_bounds: bool) -> Self {
+        self.store_bounds = store_bounds;
+        self
+    }
+
+    /// Enables or disables storing entities in branch nodes. When enabled, an entity is stored
+    /// only once - in the deepest node that fully contains it. Otherwise, an entity is stored in
+    /// every leaf node it intersects, which may explode memory usage and produce duplicate query
+    /// results for large entities.
+    pub fn with_branch_entries(mut self, branch_entries: bool) -> Self {
+        self.branch_entries = branch_entries;
+        self
+    }
+
+    fn empty_tree<I>(&self, root_bounds: Rect<f32>) -> QuadTree<I> {
+        QuadTree {
+            nodes: vec![EMPTY_LEAF],
+            ids: Default::default(),
+            entry_bounds: Default::default(),
+            keys: Default::default(),
+            key_count: 0,
+            root: 0,
+            root_bounds,
+            garbage: 0,
+            split_threshold: self.split_threshold,
+            store_bounds: self.store_bounds,
+            branch_entries: self.branch_entries,
+        }
+    }
+
+    /// Builds new quad tree from the given initial bounds and the set of objects.
+    pub fn build<T, I>(
+        self,
+        root_bounds: Rect<f32>,
+        objects: impl Iterator<Item = T>,
+    ) -> Result<QuadTree<I>, QuadTreeBuildError>
+    where
+        T: BoundsProvider<Id = I>,
+        I: Clone + 'static,
+    {
+        if self.split_threshold == 0 {
+            return Err(QuadTreeBuildError::ZeroSplitThreshold);
+        }
+
+        let entries = collect_entries(root_bounds, objects);
+
+        let mut tree = self.empty_tree(root_bounds);
+        tree.build_node(0, root_bounds, &entries, 0)?;
+        tree.key_count = to_index(entries.len());
+        Ok(tree)
+    }
+
+    /// Builds new quad tree from the given initial bounds and the set of objects, sorting the
+    /// objects by the Morton code of their centers first. Entities of every node then occupy
+    /// mostly contiguous memory, which makes partitioning of large sets of objects much faster
+    /// and improves memory locality of the queries. The resulting tree has the same structure as
+    /// the one built by [`Self::build`], but the entities of each node are stored in Morton order
+    /// instead of the order of the objects.
+    pub fn build_morton<T, I>(
+        self,
+        root_bounds: Rect<f32>,
+        objects: impl Iterator<Item = T>,
+    ) -> Result<QuadTree<I>, QuadTreeBuildError>
+    where
+        T: BoundsProvider<Id = I>,
+        I: Clone + 'static,
+    {
+        if self.split_threshold == 0 {
+            return Err(QuadTreeBuildError::ZeroSplitThreshold);
+        }
+
+        let mut entries = collect_entries(root_bounds, objects);
+        entries.sort_by_cached_key(|e| morton_code(&root_bounds, e.bounds.center()));
+
+        let mut tree = self.empty_tree(root_bounds);
+        tree.build_node(0, root_bounds, &entries, 0)?;
+        tree.key_count = to_index(entries.len());
+        Ok(tree)
+    }
+
+    /// Builds new quad tree from the set of objects. Root bounds of the tree are computed as the
+    /// union of the bounds of all the objects, inflated by the given margin on each side. Unlike
+    /// [`Self::build`], no object is dropped because it lies outside of the guessed root bounds.
+    pub fn build_with_auto_bounds<T, I>(
+        self,
+        objects: impl Iterator<Item = T>,
+        margin: f32,
+    ) -> Result<QuadTree<I>, QuadTreeBuildError>
+    where
+        T: BoundsProvider<Id = I>,
+        I: Clone + 'static,
+    {
+        if self.split_threshold == 0 {
+            return Err(QuadTreeBuildError::ZeroSplitThreshold);
+        }
+
+        let mut union = OptionRect::default();
+        let entries = objects
+            .enumerate()
+            .map(|(key, o)| {
+                let bounds = o.bounds();
+                union.extend_to_contain(bounds);
+                Entry {
+                    id: o.id(),
+                    bounds,
+                    key: to_index(key),
+                }
+            })
+            .collect::<Vec<_>>();
+        let root_bounds = union.unwrap_or_default().inflate(margin, margin);
+
+        let mut tree = self.empty_tree(root_bounds);
+        tree.build_node(0, root_bounds, &entries, 0)?;
+        tree.key_count = to_index(entries.len());
+        Ok(tree)
+    }
+
+    /// Builds new quad tree from the given initial bounds and the set of objects, using multiple
+    /// threads. Large nodes are split in parallel, the small ones are built the same way as in
+    /// [`Self::build`]. The resulting tree is identical to the one built by [`Self::build`].
+    #[cfg(feature = "rayon")]
+    pub fn build_par<T, I>(
+        self,
+        root_bounds: Rect<f32>,
+        objects: impl Iterator<Item = T>,
+    ) -> Result<QuadTree<I>, QuadTreeBuildError>
+    where
+        T: BoundsProvider<Id = I>,
+        I: Clone + Send + Sync + 'static,
+    {
+        if self.split_threshold == 0 {
+            return Err(QuadTreeBuildError::ZeroSplitThreshold);
+        }
+
+        let entries = collect_entries(root_bounds, objects);
+
+        let mut tree = self.empty_tree(root_bounds);
+        tree.build_node_par(0, root_bounds, &entries, 0)?;
+        tree.key_count = to_index(entries.len());
+        Ok(tree)
+    }
+}
+
+/// Kind of a quad tree node, passed to the visitor in [`QuadTree::visit`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeKind {
+    /// Terminal node that holds the ids of the entities.
+    Leaf,
+    /// Intermediate node that has exactly four children.
+    Branch,
+}
+
+/// A shape, that is used to select nodes and entities during queries.
+#[derive(Copy, Clone, Debug)]
+enum QueryShape {
+    Point(Vector2<f32>),
+    Rect(Rect<f32>),
+    Circle { center: Vector2<f32>, radius: f32 },
+}
+
+impl QueryShape {
+    fn intersects(&self, bounds: &Rect<f32>) -> bool {
+        match *self {
+            QueryShape::Point(point) => bounds.contains(point),
+            QueryShape::Rect(rect) => bounds.intersects(rect),
+            QueryShape::Circle { center, radius } => bounds.intersects_circle(center, radius),
+        }
+    }
+}
+
+/// An iterator over the ids of the entities that intersect some shape. See
+/// [`QuadTree::point_query_iter`], [`QuadTree::rect_query_iter`] and
+/// [`QuadTree::circle_query_iter`].
+pub struct QueryIter<'a, I> {
+    tree: &'a QuadTree<I>,
+    shape: QueryShape,
+    // The tree could grow above its root without a limit on the depth, so the stack is on the heap.
+    stack: Vec<(u32, Rect<f32>)>,
+    range: Range<usize>,
+}
+
+impl<'a, I> Iterator for QueryIter<'a, I> {
+    type Item = &'a I;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            for index in self.range.by_ref() {
+                if self
+                    .tree
+                    .entry_bounds
+                    .get(index)
+                    .map_or(true, |b| self.shape.intersects(b))
+                {
+                    return self.tree.ids.get(index);
+                }
+            }
+
+            let (node, bounds) = self.stack.pop()?;
+            match self.tree.nodes.get(node as usize) {
+                Some(node @ QuadTreeNode::Leaf { .. }) if self.shape.intersects(&bounds) => {
+                    self.range = node.range();
+                }
+                Some(node @ QuadTreeNode::Branch { children, .. })
+                    if self.shape.intersects(&bounds) =>
+                {
+                    self.range = node.range();
+                    for (i, child_bounds) in split_rect(&bounds).into_iter().enumerate().rev() {
+                        self.stack.push((children + i as u32, child_bounds));
+                    }
+                }
+                _ => (),
+            }
         }
     }
 }
 
-/// A trait for anything that has rectangular bounds.
-pub trait BoundsProvider {
+/// A trait for anything that has rectangular bounds. The bounds are floating point by default,
+/// integer bounds are used by [`IntQuadTree`].
+pub trait BoundsProvider<T = f32> {
     /// Identifier of the bounds provider.
     type Id: Clone;
 
     /// Returns bounds of the bounds provider.
-    fn bounds(&self) -> Rect<f32>;
+    fn bounds(&self) -> Rect<T>;
 
     /// Returns id of the bounds provider.
     fn id(&self) -> Self::Id;
 }
 
+/// Maximum depth of the quad tree. Nodes at this depth cannot be split anymore.
+const MAX_DEPTH: usize = 64;
+
 /// An error, that may occur during the build of the quad tree.
+#[derive(Clone, Debug, PartialEq)]
 pub enum QuadTreeBuildError {
     /// It means that given split threshold is too low for an algorithm to build quad tree.
-    /// Make it larger and try again. Also this might mean that your initial bounds are too small.
-    ReachedRecursionLimit,
+    /// Make it larger and try again. Also this might mean that your initial bounds are too small
+    /// or that there are too many entries sharing the same location.
+    ReachedRecursionLimit {
+        /// Depth at which the recursion was stopped.
+        depth: usize,
+        /// Amount of entries in the node that could not be split.
+        entry_count: usize,
+        /// Bounding rectangle of the entries in the node that could not be split.
+        bounds: Rect<f32>,
+    },
+    /// Split threshold must be at least one, otherwise any non-empty node would be split forever.
+    ZeroSplitThreshold,
+    /// Bounds of an inserted object are NaN or infinite.
+    NonFiniteBounds {
+        /// Bounds of the object.
+        bounds: Rect<f32>,
+    },
+    /// The object is so far from the tree, that the root would have to grow more than the
+    /// maximum depth of the tree times to reach it.
+    ReachedGrowthLimit {
+        /// Bounds of the object.
+        bounds: Rect<f32>,
+    },
+}
+
+impl Display for QuadTreeBuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuadTreeBuildError::ReachedRecursionLimit {
+                depth,
+                entry_count,
+                bounds,
+            } => write!(
+                f,
+                "quad tree reached recursion limit at depth {depth}: {entry_count} entries \
+                 within {bounds:?} cannot be separated, consider increasing split threshold",
+            ),
+            QuadTreeBuildError::ZeroSplitThreshold => {
+                write!(f, "quad tree split threshold must be greater than zero")
+            }
+            QuadTreeBuildError::NonFiniteBounds { bounds } => {
+                write!(f, "object bounds {bounds:?} are not finite")
+            }
+            QuadTreeBuildError::ReachedGrowthLimit { bounds } => write!(
+                f,
+                "quad tree cannot grow to reach the object bounds {bounds:?}, it is too far away"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QuadTreeBuildError {}
+
+/// An error, that is returned by [`QuadTree::intersecting_pairs`], if the tree does not store
+/// bounds of the entities (see [`QuadTreeBuilder::with_stored_bounds`]).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoundsNotStored;
+
+impl Display for BoundsNotStored {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "quad tree does not store bounds of the entities")
+    }
 }
 
+impl std::error::Error for BoundsNotStored {}
+
 #[derive(Clone)]
 struct Entry<I: Clone> {
     id: I,
     bounds: Rect<f32>,
+    key: u32,
+}
+
+/// Returns the bounds of a root, that is twice as large as the given one and grows towards the
+/// target.
+fn grown_root_bounds(bounds: &Rect<f32>, target: &Rect<f32>) -> Rect<f32> {
+    Rect::new(
+        if target.x() < bounds.x() {
+            bounds.x() - bounds.w()
+        } else {
+            bounds.x()
+        },
+        if target.y() < bounds.y() {
+            bounds.y() - bounds.h()
+        } else {
+            bounds.y()
+        },
+        bounds.w() * 2.0,
+        bounds.h() * 2.0,
+    )
+}
+
+fn collect_entries<T, I>(root_bounds: Rect<f32>, objects: impl Iterator<Item = T>) -> Vec<Entry<I>>
+where
+    T: BoundsProvider<Id = I>,
+    I: Clone,
+{
+    objects
+        .filter(|o| root_bounds.intersects(o.bounds()))
+        .enumerate()
+        .map(|(key, o)| Entry {
+            id: o.id(),
+            bounds: o.bounds(),
+            key: to_index(key),
+        })
+        .collect::<Vec<_>>()
+}
+
+fn filter_entries<I>(bounds: &Rect<f32>, entries: &[Entry<I>]) -> Vec<Entry<I>>
+where
+    I: Clone,
+{
+    entries
+        .iter()
+        .filter_map(|e| {
+            if bounds.intersects(e.bounds) {
+                Some(e.clone())
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>()
 }
 
-fn build_recursive<I>(
-    nodes: &mut Vec<QuadTreeNode<I>>,
+fn recursion_limit_error<I>(
     bounds: Rect<f32>,
     entries: &[Entry<I>],
-    split_threshold: usize,
     depth: usize,
-) -> Result<usize, QuadTreeBuildError>
+) -> QuadTreeBuildError
 where
-    I: Clone + 'static,
+    I: Clone,
 {
-    if depth >= 64 {
-        Err(QuadTreeBuildError::ReachedRecursionLimit)
-    } else if entries.len() <= split_threshold {
-        let index = nodes.len();
-        nodes.push(QuadTreeNode::Leaf {
-            bounds,
-            ids: entries.iter().map(|e| e.id.clone()).collect::<Vec<_>>(),
-        });
-        Ok(index)
-    } else {
-        let leaf_bounds = split_rect(&bounds);
-        let mut leaves = [usize::MAX; 4];
-
-        for (leaf, &leaf_bounds) in leaves.iter_mut().zip(leaf_bounds.iter()) {
-            let leaf_entries = entries
-                .iter()
-                .filter_map(|e| {
-                    if leaf_bounds.intersects(e.bounds) {
-                        Some(e.clone())
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
+    let mut entries_bounds = OptionRect::default();
+    for entry in entries {
+        entries_bounds.extend_to_contain(entry.bounds);
+    }
+    QuadTreeBuildError::ReachedRecursionLimit {
+        depth,
+        entry_count: entries.len(),
+        bounds: entries_bounds.unwrap_or(bounds),
+    }
+}
+
+/// Nodes with less entries than this are built on the current thread.
+#[cfg(feature = "rayon")]
+const PARALLEL_BUILD_CUTOFF: usize = 4096;
+
+#[cfg(feature = "rayon")]
+impl<I> QuadTree<I>
+where
+    I: Clone + Send + Sync + 'static,
+{
+    fn build_node_par(
+        &mut self,
+        node: usize,
+        bounds: Rect<f32>,
+        entries: &[Entry<I>],
+        depth: usize,
+    ) -> Result<(), QuadTreeBuildError> {
+        use rayon::prelude::*;
 
-            *leaf = build_recursive(
-                nodes,
-                leaf_bounds,
-                &leaf_entries,
-                split_threshold,
-                depth + 1,
-            )?;
+        if entries.len() < PARALLEL_BUILD_CUTOFF
+            || entries.len() <= self.split_threshold
+            || depth >= MAX_DEPTH
+            || self.branch_entries
+        {
+            return self.build_node(node, bounds, entries, depth);
         }
 
-        let index = nodes.len();
-        nodes.push(QuadTreeNode::Branch { bounds, leaves });
-        Ok(index)
+        // Each child is built as a separate tree, which is then appended to this one.
+        let settings = QuadTreeBuilder {
+            split_threshold: self.split_threshold,
+            store_bounds: self.store_bounds,
+            branch_entries: self.branch_entries,
+        };
+        let subtrees = split_rect(&bounds)
+            .to_vec()
+            .into_par_iter()
+            .map(|child_bounds| {
+                let child_entries = filter_entries(&child_bounds, entries);
+                let mut subtree = settings.empty_tree(child_bounds);
+                subtree.build_node_par(0, child_bounds, &child_entries, depth + 1)?;
+                Ok(subtree)
+            })
+            .collect::<Result<Vec<_>, QuadTreeBuildError>>()?;
+
+        let children = self.nodes.len();
+        self.nodes[node] = QuadTreeNode::branch(children, 0..0);
+        self.nodes.extend([EMPTY_LEAF; 4]);
+        for (i, subtree) in subtrees.into_iter().enumerate() {
+            self.append_subtree(children + i, subtree);
+        }
+        Ok(())
+    }
+
+    fn append_subtree(&mut self, slot: usize, subtree: QuadTree<I>) {
+        // The root of the subtree takes the given slot, the rest of its nodes go to the end.
+        let node_offset = to_index(self.nodes.len()) - 1;
+        let arena_offset = to_index(self.ids.len());
+        let relocate = |node: &QuadTreeNode| node.relocated(node_offset, arena_offset);
+        self.nodes[slot] = subtree.nodes[subtree.root].relocated(node_offset, arena_offset);
+        self.nodes.extend(subtree.nodes[1..].iter().map(relocate));
+        self.ids.extend(subtree.ids);
+        self.entry_bounds.extend(subtree.entry_bounds);
+        self.keys.extend(subtree.keys);
     }
 }
 
@@ -142,215 +769,2230 @@ where
     where
         T: BoundsProvider<Id = I>,
     {
-        let entries = objects
-            .filter_map(|o| {
-                if root_bounds.intersects(o.bounds()) {
-                    Some(Entry {
-                        id: o.id(),
-                        bounds: o.bounds(),
-                    })
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-
-        let mut nodes = Vec::new();
-        let root = build_recursive(&mut nodes, root_bounds, &entries, split_threshold, 0)?;
-        Ok(Self {
-            nodes,
-            root,
-            split_threshold,
-        })
+        QuadTreeBuilder::new()
+            .with_split_threshold(split_threshold)
+            .build(root_bounds, objects)
     }
 
-    /// Searches for a leaf node in the tree, that contains the given point and writes ids of the
-    /// entities stored in the leaf node to the output storage.
-    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    /// Creates new quad tree from the set of objects, with the root bounds that tightly enclose
+    /// all the objects. See [`QuadTreeBuilder::build_with_auto_bounds`] for more info.
+    pub fn with_auto_bounds<T>(
+        objects: impl Iterator<Item = T>,
+        split_threshold: usize,
+    ) -> Result<Self, QuadTreeBuildError>
     where
-        S: QueryStorage<Id = I>,
+        T: BoundsProvider<Id = I>,
     {
-        self.point_query_recursive(self.root, point, storage)
+        QuadTreeBuilder::new()
+            .with_split_threshold(split_threshold)
+            .build_with_auto_bounds(objects, 0.0)
     }
 
-    fn point_query_recursive<S>(&self, node: usize, point: Vector2<f32>, storage: &mut S)
-    where
-        S: QueryStorage<Id = I>,
-    {
-        if let Some(node) = self.nodes.get(node) {
-            match node {
-                QuadTreeNode::Leaf { bounds, ids } => {
-                    if bounds.contains(point) {
-                        for id in ids {
-                            if !storage.try_push(id.clone()) {
-                                return;
+    fn build_node(
+        &mut self,
+        node: usize,
+        bounds: Rect<f32>,
+        entries: &[Entry<I>],
+        depth: usize,
+    ) -> Result<(), QuadTreeBuildError> {
+        if depth >= MAX_DEPTH {
+            Err(recursion_limit_error(bounds, entries, depth))
+        } else if entries.len() <= self.split_threshold {
+            let first = self.ids.len();
+            self.ids.extend(entries.iter().map(|e| e.id.clone()));
+            self.keys.extend(entries.iter().map(|e| e.key));
+            if self.store_bounds {
+                self.entry_bounds.extend(entries.iter().map(|e| e.bounds));
+            }
+            self.nodes[node] = QuadTreeNode::leaf(first..self.ids.len());
+            Ok(())
+        } else {
+            let child_bounds = split_rect(&bounds);
+            let mut child_entries: [Vec<Entry<I>>; 4] = Default::default();
+
+            let first = self.ids.len();
+            if self.branch_entries {
+                for entry in entries {
+                    // An entity on the border of two children goes to the first one.
+                    match child_bounds
+                        .iter()
+                        .position(|b| contains_rect(b, &entry.bounds))
+                    {
+                        Some(i) => child_entries[i].push(entry.clone()),
+                        None => {
+                            // Entities that do not fit into a single child stay in the branch.
+                            self.ids.push(entry.id.clone());
+                            self.keys.push(entry.key);
+                            if self.store_bounds {
+                                self.entry_bounds.push(entry.bounds);
                             }
                         }
                     }
                 }
-                QuadTreeNode::Branch { bounds, leaves } => {
-                    if bounds.contains(point) {
-                        for &leaf in leaves {
-                            self.point_query_recursive(leaf, point, storage)
-                        }
-                    }
+            } else {
+                for (entries_of_child, bounds) in child_entries.iter_mut().zip(&child_bounds) {
+                    *entries_of_child = filter_entries(bounds, entries);
                 }
             }
+
+            let children = self.nodes.len();
+            self.nodes[node] = QuadTreeNode::branch(children, first..self.ids.len());
+            self.nodes.extend([EMPTY_LEAF; 4]);
+
+            for (i, (bounds, entries)) in child_bounds.iter().zip(&child_entries).enumerate() {
+                self.build_node(children + i, *bounds, entries, depth + 1)?;
+            }
+
+            Ok(())
         }
     }
 
-    /// Returns current split threshold, that was used to build the quad tree.
-    pub fn split_threshold(&self) -> usize {
-        self.split_threshold
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.ids.clear();
+        self.entry_bounds.clear();
+        self.keys.clear();
+        self.key_count = 0;
+        self.root = 0;
+        self.garbage = 0;
     }
-}
 
-/// Arbitrary storage for query results.
-pub trait QueryStorage {
-    /// Id of an entity in the storage.
-    type Id;
+    /// Rebuilds the tree from the given initial bounds and the set of objects, using the same
+    /// options that were used to build the tree. Unlike creating a new tree, it reuses the memory
+    /// allocated for the nodes and the entities, which makes it much cheaper for trees that are
+    /// rebuilt often (for example, every frame). If the rebuild fails, the tree will be empty.
+    pub fn rebuild<T>(
+        &mut self,
+        root_bounds: Rect<f32>,
+        objects: impl Iterator<Item = T>,
+    ) -> Result<(), QuadTreeBuildError>
+    where
+        T: BoundsProvider<Id = I>,
+    {
+        self.clear();
+        if self.split_threshold == 0 {
+            return Err(QuadTreeBuildError::ZeroSplitThreshold);
+        }
 
-    /// Tries to push a new id in the storage.
-    fn try_push(&mut self, id: Self::Id) -> bool;
+        let entries = collect_entries(root_bounds, objects);
+        self.nodes.push(EMPTY_LEAF);
+        self.root_bounds = root_bounds;
+        let result = self.build_node(0, root_bounds, &entries, 0);
+        if result.is_err() {
+            self.clear();
+        } else {
+            self.key_count = to_index(entries.len());
+        }
+        result
+    }
 
-    /// Clears the storage.
-    fn clear(&mut self);
-}
+    /// Inserts a new object in the tree. If the object does not fit in the root bounds, the tree
+    /// grows towards the object: a new root, twice as large as the old one, is created with the
+    /// old root as one of its children. This repeats until the root contains the object, so the
+    /// tree does not require world bounds to be known up front.
+    ///
+    /// Leaf nodes, that exceed the split threshold, are split only if the tree stores bounds of
+    /// the entities (see [`QuadTreeBuilder::with_stored_bounds`]), otherwise they keep growing.
+    ///
+    /// Fails, if the bounds of the object are not finite or if the root would have to grow more
+    /// than 64 times (the maximum depth of the tree) to reach the object, the tree is not changed
+    /// then.
+    pub fn insert<T>(&mut self, object: T) -> Result<(), QuadTreeBuildError>
+    where
+        T: BoundsProvider<Id = I>,
+    {
+        let mut entry = Entry {
+            id: object.id(),
+            bounds: object.bounds(),
+            key: 0,
+        };
+        let bounds = entry.bounds;
+        if ![bounds.x(), bounds.y(), bounds.w(), bounds.h()]
+            .iter()
+            .all(|v| v.is_finite())
+        {
+            return Err(QuadTreeBuildError::NonFiniteBounds { bounds });
+        }
 
-impl<I> QueryStorage for Vec<I> {
-    type Id = I;
+        if self.nodes.get(self.root).is_none() {
+            self.clear();
+            self.nodes.push(EMPTY_LEAF);
+            self.root_bounds = Rect::new(
+                entry.bounds.x(),
+                entry.bounds.y(),
+                entry.bounds.w().max(1.0),
+                entry.bounds.h().max(1.0),
+            );
+        }
+
+        // A degenerate root could not grow by doubling, but a leaf root does not depend on its
+        // size, so it could be given some extent.
+        if matches!(self.nodes[self.root], QuadTreeNode::Leaf { .. })
+            && (self.root_bounds.w() <= 0.0 || self.root_bounds.h() <= 0.0)
+        {
+            let extent = self.root_bounds.w().max(self.root_bounds.h()).max(1.0);
+            self.root_bounds.size = Vector2::new(extent, extent);
+        }
+
+        let mut grown_bounds = self.root_bounds;
+        let mut growth = 0;
+        while !contains_rect(&grown_bounds, &entry.bounds) {
+            if growth >= MAX_DEPTH {
+                return Err(QuadTreeBuildError::ReachedGrowthLimit { bounds });
+            }
+            grown_bounds = grown_root_bounds(&grown_bounds, &entry.bounds);
+            growth += 1;
+        }
+        for _ in 0..growth {
+            self.grow_towards(&entry.bounds);
+        }
+
+        entry.key = self.key_count;
+        self.key_count = self
+            .key_count
+            .checked_add(1)
+            .expect("quad tree cannot hold more than u32::MAX entities");
+        self.insert_recursive(self.root, self.root_bounds, &entry, 0);
+
+        if self.garbage > self.ids.len() / 2 {
+            self.collect_garbage();
+        }
+        Ok(())
+    }
+
+    fn grow_towards(&mut self, target: &Rect<f32>) {
+        let bounds = self.root_bounds;
+        let left = target.x() < bounds.x();
+        let up = target.y() < bounds.y();
+        self.root_bounds = grown_root_bounds(&bounds, target);
+        // The old root takes the quadrant opposite to the growth direction, its slot is reused
+        // by the new root.
+        let old_root_quadrant = match (left, up) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+            (false, true) => 3,
+        };
+        let children = self.nodes.len();
+        self.nodes.extend([EMPTY_LEAF; 4]);
+        self.nodes[children + old_root_quadrant] = self.nodes[self.root];
+        self.nodes[self.root] = QuadTreeNode::branch(children, 0..0);
+    }
+
+    fn insert_recursive(&mut self, node: usize, bounds: Rect<f32>, entry: &Entry<I>, depth: usize) {
+        if !bounds.intersects(entry.bounds) {
+            return;
+        }
+
+        match self.nodes[node] {
+            QuadTreeNode::Branch { children, .. } => {
+                let child_bounds = split_rect(&bounds);
+                if self.branch_entries {
+                    match child_bounds
+                        .iter()
+                        .position(|b| contains_rect(b, &entry.bounds))
+                    {
+                        Some(i) => self.insert_recursive(
+                            children as usize + i,
+                            child_bounds[i],
+                            entry,
+                            depth + 1,
+                        ),
+                        None => {
+                            self.push_entry(node, entry);
+                        }
+                    }
+                } else {
+                    for (i, child_bounds) in child_bounds.into_iter().enumerate() {
+                        self.insert_recursive(
+                            children as usize + i,
+                            child_bounds,
+                            entry,
+                            depth + 1,
+                        );
+                    }
+                }
+            }
+            QuadTreeNode::Leaf { .. } => {
+                let count = self.push_entry(node, entry);
+                if self.store_bounds && count > self.split_threshold {
+                    self.split_leaf(node, bounds, depth);
+                }
+            }
+        }
+    }
+
+    /// Adds the entry to the entities of the node and returns the new amount of its entities.
+    fn push_entry(&mut self, node: usize, entry: &Entry<I>) -> usize {
+        let mut range = self.nodes[node].range();
+        // Entities of a node must be contiguous, move them to the end of the arena if needed.
+        if range.end != self.ids.len() {
+            let new_start = self.ids.len();
+            self.ids.extend_from_within(range.clone());
+            self.keys.extend_from_within(range.clone());
+            if self.store_bounds {
+                self.entry_bounds.extend_from_within(range.clone());
+            }
+            self.garbage += range.len();
+            range = new_start..self.ids.len();
+        }
+        self.ids.push(entry.id.clone());
+        self.keys.push(entry.key);
+        if self.store_bounds {
+            self.entry_bounds.push(entry.bounds);
+        }
+        self.nodes[node] = self.nodes[node].with_range(range.start..range.end + 1);
+        range.len() + 1
+    }
+
+    fn split_leaf(&mut self, node: usize, bounds: Rect<f32>, depth: usize) {
+        let leaf = self.nodes[node];
+        let range = leaf.range();
+        let entries = self.ids[range.clone()]
+            .iter()
+            .zip(&self.entry_bounds[range.clone()])
+            .zip(&self.keys[range.clone()])
+            .map(|((id, bounds), key)| Entry {
+                id: id.clone(),
+                bounds: *bounds,
+                key: *key,
+            })
+            .collect::<Vec<_>>();
+
+        let nodes_len = self.nodes.len();
+        let ids_len = self.ids.len();
+        if self.build_node(node, bounds, &entries, depth).is_ok() {
+            self.garbage += range.len();
+        } else {
+            // The entities cannot be separated, keep them in the overfull leaf.
+            self.nodes.truncate(nodes_len);
+            self.ids.truncate(ids_len);
+            self.keys.truncate(ids_len);
+            self.entry_bounds
+                .truncate(ids_len.min(self.entry_bounds.len()));
+            self.nodes[node] = leaf;
+        }
+    }
+
+    /// Removes the entities of the arena and the nodes, that are not reachable from the root.
+    fn collect_garbage(&mut self) {
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        let mut ids = Vec::with_capacity(self.ids.len() - self.garbage);
+        let mut entry_bounds = Vec::with_capacity(self.entry_bounds.len());
+        let mut keys = Vec::with_capacity(ids.capacity());
+        if let Some(root) = self.nodes.get(self.root) {
+            nodes.push(*root);
+        }
+        // Nodes are copied in breadth-first order, so the children of a branch stay contiguous.
+        let mut i = 0;
+        while i < nodes.len() {
+            let range = nodes[i].range();
+            let node = nodes[i].with_range(ids.len()..ids.len() + range.len());
+            ids.extend_from_slice(&self.ids[range.clone()]);
+            keys.extend_from_slice(&self.keys[range.clone()]);
+            if self.store_bounds {
+                entry_bounds.extend_from_slice(&self.entry_bounds[range]);
+            }
+            nodes[i] = match node {
+                QuadTreeNode::Branch {
+                    children,
+                    first,
+                    count,
+                } => {
+                    let new_children = to_index(nodes.len());
+                    nodes.extend_from_slice(&self.nodes[children as usize..children as usize + 4]);
+                    QuadTreeNode::Branch {
+                        children: new_children,
+                        first,
+                        count,
+                    }
+                }
+                leaf => leaf,
+            };
+            i += 1;
+        }
+        self.nodes = nodes;
+        self.ids = ids;
+        self.entry_bounds = entry_bounds;
+        self.keys = keys;
+        self.root = 0;
+        self.garbage = 0;
+    }
+
+    /// Calls the given closure for every leaf node and every non-empty branch node, whose bounds
+    /// pass the given filter, with the index of the node, its bounds and the range of its
+    /// entities in the arena. Branch nodes are visited before their children.
+    fn walk_nodes<P, F>(
+        &self,
+        node: usize,
+        bounds: Rect<f32>,
+        filter: &P,
+        func: &mut F,
+    ) -> ControlFlow<()>
+    where
+        P: Fn(&Rect<f32>) -> bool,
+        F: FnMut(usize, Rect<f32>, Range<usize>) -> ControlFlow<()>,
+    {
+        match self.nodes.get(node) {
+            Some(leaf @ QuadTreeNode::Leaf { .. }) if filter(&bounds) => {
+                func(node, bounds, leaf.range())?
+            }
+            Some(branch @ QuadTreeNode::Branch { children, .. }) if filter(&bounds) => {
+                if !branch.range().is_empty() {
+                    func(node, bounds, branch.range())?;
+                }
+                for (i, child_bounds) in split_rect(&bounds).into_iter().enumerate() {
+                    self.walk_nodes(*children as usize + i, child_bounds, filter, func)?;
+                }
+            }
+            _ => (),
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Searches for a leaf node in the tree, that contains the given point and writes ids of the
+    /// entities stored in the leaf node to the output storage. If the tree stores bounds of the
+    /// entities (see [`QuadTreeBuilder::with_stored_bounds`]), only the ids of the entities that
+    /// contain the point will be written.
+    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+    {
+        let _ = self.point_query_with(point, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Searches for leaf nodes in the tree, that contain the given point and passes ids of the
+    /// entities stored in them to the given closure. The closure may filter the ids as it likes
+    /// and stop the traversal by returning [`ControlFlow::Break`]. Returns
+    /// [`ControlFlow::Break`] if the traversal was stopped by the closure.
+    pub fn point_query_with<F>(&self, point: Vector2<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.query_with(QueryShape::Point(point), func)
+    }
+
+    /// Searches for leaf nodes in the tree, that intersect the given rectangle and passes ids of
+    /// the entities stored in them to the given closure, see [`Self::point_query_with`]. An
+    /// entity that is stored in multiple leaf nodes could be passed multiple times.
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.query_with(QueryShape::Rect(rect), func)
+    }
+
+    /// Searches for leaf nodes in the tree, that intersect the given circle and passes ids of the
+    /// entities stored in them to the given closure, see [`Self::point_query_with`]. An entity
+    /// that is stored in multiple leaf nodes could be passed multiple times.
+    pub fn circle_query_with<F>(
+        &self,
+        center: Vector2<f32>,
+        radius: f32,
+        func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.query_with(QueryShape::Circle { center, radius }, func)
+    }
+
+    fn query_with<F>(&self, shape: QueryShape, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.walk_nodes(
+            self.root,
+            self.root_bounds,
+            &|bounds| shape.intersects(bounds),
+            &mut |_, _, range| {
+                for index in range {
+                    if self
+                        .entry_bounds
+                        .get(index)
+                        .map_or(true, |b| shape.intersects(b))
+                    {
+                        func(&self.ids[index])?;
+                    }
+                }
+                ControlFlow::Continue(())
+            },
+        )
+    }
+
+    /// Returns an iterator over the ids of the entities stored in the leaf nodes, that contain
+    /// the given point. It yields the same ids as [`Self::point_query`] in the same order.
+    pub fn point_query_iter(&self, point: Vector2<f32>) -> QueryIter<'_, I> {
+        self.query_iter(QueryShape::Point(point))
+    }
+
+    /// Returns an iterator over the ids of the entities stored in the leaf nodes, that intersect
+    /// the given rectangle. An entity that is stored in multiple leaf nodes could be yielded
+    /// multiple times. If the tree stores bounds of the entities, only the entities that
+    /// intersect the rectangle are yielded.
+    pub fn rect_query_iter(&self, rect: Rect<f32>) -> QueryIter<'_, I> {
+        self.query_iter(QueryShape::Rect(rect))
+    }
+
+    /// Returns an iterator over the ids of the entities stored in the leaf nodes, that intersect
+    /// the given circle. An entity that is stored in multiple leaf nodes could be yielded
+    /// multiple times. If the tree stores bounds of the entities, only the entities that
+    /// intersect the circle are yielded.
+    pub fn circle_query_iter(&self, center: Vector2<f32>, radius: f32) -> QueryIter<'_, I> {
+        self.query_iter(QueryShape::Circle { center, radius })
+    }
+
+    fn query_iter(&self, shape: QueryShape) -> QueryIter<'_, I> {
+        let mut stack = Vec::new();
+        if self.root < self.nodes.len() {
+            stack.push((to_index(self.root), self.root_bounds));
+        }
+        QueryIter {
+            tree: self,
+            shape,
+            stack,
+            range: 0..0,
+        }
+    }
+
+    /// Writes the ids of the entities, that contain the given point, to the output storage. Unlike
+    /// [`Self::point_query`], each entity is written only once, even if it is stored in multiple
+    /// leaf nodes, using the given context to remember the visited entities.
+    pub fn point_query_with_context<S>(
+        &self,
+        point: Vector2<f32>,
+        context: &mut QueryContext,
+        storage: &mut S,
+    ) where
+        S: QueryStorage<Id = I>,
+    {
+        self.query_with_context(QueryShape::Point(point), context, storage)
+    }
+
+    /// Writes the ids of the entities, that intersect the given rectangle, to the output storage.
+    /// Each entity is written only once, see [`Self::point_query_with_context`].
+    pub fn rect_query_with_context<S>(
+        &self,
+        rect: Rect<f32>,
+        context: &mut QueryContext,
+        storage: &mut S,
+    ) where
+        S: QueryStorage<Id = I>,
+    {
+        self.query_with_context(QueryShape::Rect(rect), context, storage)
+    }
+
+    /// Writes the ids of the entities, that intersect the given circle, to the output storage.
+    /// Each entity is written only once, see [`Self::point_query_with_context`].
+    pub fn circle_query_with_context<S>(
+        &self,
+        center: Vector2<f32>,
+        radius: f32,
+        context: &mut QueryContext,
+        storage: &mut S,
+    ) where
+        S: QueryStorage<Id = I>,
+    {
+        self.query_with_context(QueryShape::Circle { center, radius }, context, storage)
+    }
+
+    fn query_with_context<S>(&self, shape: QueryShape, context: &mut QueryContext, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+    {
+        context.begin_query(self.key_count);
+        let _ = self.walk_nodes(
+            self.root,
+            self.root_bounds,
+            &|bounds| shape.intersects(bounds),
+            &mut |_, _, range| {
+                for index in range {
+                    // Test the bounds only once per entity.
+                    if context.visit(self.keys[index])
+                        && self
+                            .entry_bounds
+                            .get(index)
+                            .map_or(true, |b| shape.intersects(b))
+                        && !storage.try_push(self.ids[index].clone())
+                    {
+                        return ControlFlow::Break(());
+                    }
+                }
+                ControlFlow::Continue(())
+            },
+        );
+    }
+
+    /// Returns the ids of the entities, that intersect the given rectangle, sorted in ascending
+    /// order and without duplicates. Unlike the other queries, the result depends only on the set
+    /// of entities in the tree, but not on the way the tree was built or modified.
+    pub fn sorted_query(&self, rect: Rect<f32>) -> Vec<I>
+    where
+        I: Ord,
+    {
+        let mut ids = self.rect_query_iter(rect).cloned().collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /// Passes the bounds and the ids of every leaf node, that contains the given point, to the
+    /// given closure. Leaf nodes are visited in the same order as in the other queries. The ids
+    /// are passed as is, without filtering them by their bounds. If branch entries are enabled
+    /// (see [`QuadTreeBuilder::with_branch_entries`]), non-empty branch nodes are passed as well,
+    /// before their children.
+    pub fn point_query_leaves<F>(&self, point: Vector2<f32>, func: F)
+    where
+        F: FnMut(Rect<f32>, &[I]),
+    {
+        self.query_leaves(QueryShape::Point(point), func)
+    }
+
+    /// Passes the bounds and the ids of every leaf node, that intersects the given rectangle, to
+    /// the given closure. This allows processing the results in spatially coherent chunks. The
+    /// ids are passed as is, without filtering them by their bounds. Non-empty branch nodes are
+    /// passed as in [`Self::point_query_leaves`].
+    pub fn rect_query_leaves<F>(&self, rect: Rect<f32>, func: F)
+    where
+        F: FnMut(Rect<f32>, &[I]),
+    {
+        self.query_leaves(QueryShape::Rect(rect), func)
+    }
+
+    /// Passes the bounds and the ids of every leaf node, that intersects the given circle, to
+    /// the given closure. This allows processing the results in spatially coherent chunks. The
+    /// ids are passed as is, without filtering them by their bounds. Non-empty branch nodes are
+    /// passed as in [`Self::point_query_leaves`].
+    pub fn circle_query_leaves<F>(&self, center: Vector2<f32>, radius: f32, func: F)
+    where
+        F: FnMut(Rect<f32>, &[I]),
+    {
+        self.query_leaves(QueryShape::Circle { center, radius }, func)
+    }
+
+    fn query_leaves<F>(&self, shape: QueryShape, mut func: F)
+    where
+        F: FnMut(Rect<f32>, &[I]),
+    {
+        let _ = self.walk_nodes(
+            self.root,
+            self.root_bounds,
+            &|bounds| shape.intersects(bounds),
+            &mut |_, bounds, range| {
+                func(bounds, &self.ids[range]);
+                ControlFlow::Continue(())
+            },
+        );
+    }
+
+    /// Casts a ray from the given origin in the given direction and returns the id of the closest
+    /// entity hit by the ray together with the distance to it, measured in the lengths of the
+    /// direction vector. Nodes are traversed from near to far and the given closure is called
+    /// for the entities of every node the ray passes through, it must return the distance to the
+    /// entity or `None` if the ray misses it. The traversal stops as soon as the closest hit is
+    /// nearer than all the remaining nodes. If the tree stores bounds of the entities, the
+    /// closure is called only for the entities whose bounds are hit by the ray. An entity, that is
+    /// stored in multiple leaf nodes, could be passed to the closure multiple times.
+    pub fn raycast<F>(
+        &self,
+        origin: Vector2<f32>,
+        dir: Vector2<f32>,
+        mut func: F,
+    ) -> Option<(I, f32)>
+    where
+        F: FnMut(&I) -> Option<f32>,
+    {
+        let mut closest = None;
+        if self.root < self.nodes.len() {
+            self.raycast_recursive(
+                self.root,
+                self.root_bounds,
+                origin,
+                dir,
+                &mut func,
+                &mut closest,
+            );
+        }
+        closest.map(|(index, distance)| (self.ids[index].clone(), distance))
+    }
+
+    fn raycast_recursive<F>(
+        &self,
+        node: usize,
+        bounds: Rect<f32>,
+        origin: Vector2<f32>,
+        dir: Vector2<f32>,
+        func: &mut F,
+        closest: &mut Option<(usize, f32)>,
+    ) where
+        F: FnMut(&I) -> Option<f32>,
+    {
+        let node = self.nodes[node];
+        for index in node.range() {
+            let max_distance = closest.map_or(f32::INFINITY, |(_, d)| d);
+            if self.entry_bounds.get(index).map_or(true, |b| {
+                ray_enters_at(origin, dir, b).is_some_and(|t| t < max_distance)
+            }) {
+                if let Some(distance) = func(&self.ids[index]) {
+                    if distance >= 0.0 && distance < max_distance {
+                        *closest = Some((index, distance));
+                    }
+                }
+            }
+        }
+
+        if let QuadTreeNode::Branch { children, .. } = node {
+            let mut hits = ArrayVec::<(usize, Rect<f32>, f32), 4>::new();
+            for (i, child_bounds) in split_rect(&bounds).into_iter().enumerate() {
+                if let Some(t) = ray_enters_at(origin, dir, &child_bounds) {
+                    hits.push((children as usize + i, child_bounds, t));
+                }
+            }
+            hits.sort_unstable_by(|a, b| a.2.total_cmp(&b.2));
+            for (child, child_bounds, t) in hits {
+                if closest.is_some_and(|(_, d)| t >= d) {
+                    break;
+                }
+                self.raycast_recursive(child, child_bounds, origin, dir, func, closest);
+            }
+        }
+    }
+
+    /// Writes every pair of entities whose bounds overlap to the output storage. Each pair is
+    /// reported exactly once, even if both entities are stored in multiple leaf nodes. Pairs whose
+    /// overlap lies entirely outside the root bounds are not reported.
+    ///
+    /// Returns an error if the tree does not store bounds of the entities (see
+    /// [`QuadTreeBuilder::with_stored_bounds`]), without them the pairs could not be told apart
+    /// from the entities, that just share a node.
+    pub fn intersecting_pairs<S>(&self, storage: &mut S) -> Result<(), BoundsNotStored>
+    where
+        S: QueryStorage<Id = (I, I)>,
+    {
+        if !self.store_bounds {
+            return Err(BoundsNotStored);
+        }
+        let mut push =
+            |i: usize, j: usize| storage.push_or_break((self.ids[i].clone(), self.ids[j].clone()));
+        let overlaps = |i: usize, j: usize| self.entry_bounds[i].intersects(self.entry_bounds[j]);
+
+        let _ = self.walk_nodes(
+            self.root,
+            self.root_bounds,
+            &|_| true,
+            &mut |node, bounds, range| {
+                for i in range.clone() {
+                    for j in (i + 1)..range.end {
+                        if !overlaps(i, j) {
+                            continue;
+                        }
+
+                        // Entities are stored only once when branch entries are enabled, otherwise
+                        // only the leaf that contains the top-left corner of the part of the
+                        // overlap inside of the root reports the pair, so it is reported once even
+                        // if it is shared by many leaves.
+                        if !self.branch_entries {
+                            let (a, b) = (self.entry_bounds[i], self.entry_bounds[j]);
+                            let Some(overlap) = *a.clip_by(b) else {
+                                continue;
+                            };
+                            let Some(overlap) = *overlap.clip_by(self.root_bounds) else {
+                                continue;
+                            };
+                            if !contains_half_open(&bounds, overlap.position) {
+                                continue;
+                            }
+                        }
+
+                        push(i, j)?;
+                    }
+                }
+
+                // Entities of a branch may overlap any entity of its descendants.
+                if let QuadTreeNode::Branch { children, .. } = self.nodes[node] {
+                    for i in range {
+                        let entity_bounds = self.entry_bounds[i];
+                        for (k, child_bounds) in split_rect(&bounds).into_iter().enumerate() {
+                            self.walk_nodes(
+                                children as usize + k,
+                                child_bounds,
+                                &|b| entity_bounds.intersects(*b),
+                                &mut |_, _, descendants| {
+                                    for j in descendants {
+                                        if overlaps(i, j) {
+                                            push(i, j)?;
+                                        }
+                                    }
+                                    ControlFlow::Continue(())
+                                },
+                            )?;
+                        }
+                    }
+                }
+
+                ControlFlow::Continue(())
+            },
+        );
+        Ok(())
+    }
+
+    /// Visits every node of the tree in depth-first order (parents first) and passes its bounds,
+    /// its kind and the ids stored in it to the given closure. Branch nodes are passed with an
+    /// empty slice of ids, unless branch entries are enabled (see
+    /// [`QuadTreeBuilder::with_branch_entries`]). This is useful to draw the structure of the tree
+    /// for debugging.
+    pub fn visit<F>(&self, mut func: F)
+    where
+        F: FnMut(&Rect<f32>, NodeKind, &[I]),
+    {
+        self.visit_recursive(self.root, self.root_bounds, &mut func)
+    }
+
+    fn visit_recursive<F>(&self, node: usize, bounds: Rect<f32>, func: &mut F)
+    where
+        F: FnMut(&Rect<f32>, NodeKind, &[I]),
+    {
+        match self.nodes.get(node) {
+            Some(leaf @ QuadTreeNode::Leaf { .. }) => {
+                func(&bounds, NodeKind::Leaf, &self.ids[leaf.range()])
+            }
+            Some(branch @ QuadTreeNode::Branch { children, .. }) => {
+                func(&bounds, NodeKind::Branch, &self.ids[branch.range()]);
+                for (i, child_bounds) in split_rect(&bounds).into_iter().enumerate() {
+                    self.visit_recursive(*children as usize + i, child_bounds, func);
+                }
+            }
+            None => (),
+        }
+    }
+
+    /// Removes the object from the tree. The object must have the same bounds, as it had when it
+    /// was added to the tree. Returns `true` if the object was found in the tree. Removal does not
+    /// merge the nodes, that became underfull, use [`Self::compact`] to do that.
+    pub fn remove<T>(&mut self, object: T) -> bool
+    where
+        T: BoundsProvider<Id = I>,
+        I: PartialEq,
+    {
+        let id = object.id();
+        let bounds = object.bounds();
+        let mut found = Vec::new();
+        let _ = self.walk_nodes(
+            self.root,
+            self.root_bounds,
+            &|node_bounds| node_bounds.intersects(bounds),
+            &mut |node, _, range| {
+                if let Some(index) = range.clone().find(|i| self.ids[*i] == id) {
+                    found.push((node, index));
+                }
+                ControlFlow::Continue(())
+            },
+        );
+
+        for &(node, index) in found.iter() {
+            let range = self.nodes[node].range();
+            let last = range.end - 1;
+            self.ids.swap(index, last);
+            self.keys.swap(index, last);
+            if self.store_bounds {
+                self.entry_bounds.swap(index, last);
+            }
+            self.nodes[node] = self.nodes[node].with_range(range.start..last);
+            self.garbage += 1;
+        }
+
+        if self.garbage > self.ids.len() / 2 {
+            self.collect_garbage();
+        }
+
+        !found.is_empty()
+    }
+
+    /// Merges every branch node, whose descendants collectively hold no more unique entities than
+    /// the split threshold, into a single leaf node and releases the memory of unused nodes and
+    /// entities. Call it from time to time after removing objects from a long-living tree,
+    /// otherwise it accumulates deep branches with few entities in them.
+    pub fn compact(&mut self) {
+        if self.nodes.get(self.root).is_some() {
+            self.collapse_recursive(self.root);
+        }
+        self.collect_garbage();
+    }
+
+    fn collapse_recursive(&mut self, node: usize) {
+        let QuadTreeNode::Branch { children, .. } = self.nodes[node] else {
+            return;
+        };
+
+        let children = children as usize..children as usize + 4;
+        for child in children.clone() {
+            self.collapse_recursive(child);
+        }
+        if children
+            .clone()
+            .any(|child| matches!(self.nodes[child], QuadTreeNode::Branch { .. }))
+        {
+            return;
+        }
+
+        // An entity could be stored in multiple children, count it only once. Its copies share
+        // the key, while separate entities with equal ids do not.
+        let mut unique = Vec::new();
+        for source in std::iter::once(node).chain(children.clone()) {
+            for index in self.nodes[source].range() {
+                if !unique
+                    .iter()
+                    .any(|i: &usize| self.keys[*i] == self.keys[index])
+                {
+                    if unique.len() == self.split_threshold {
+                        return;
+                    }
+                    unique.push(index);
+                }
+            }
+        }
+
+        let first = self.ids.len();
+        for &index in unique.iter() {
+            self.ids.push(self.ids[index].clone());
+            self.keys.push(self.keys[index]);
+            if self.store_bounds {
+                self.entry_bounds.push(self.entry_bounds[index]);
+            }
+        }
+        for source in std::iter::once(node).chain(children) {
+            self.garbage += self.nodes[source].range().len();
+        }
+        self.nodes[node] = QuadTreeNode::leaf(first..self.ids.len());
+    }
+
+    /// Returns an iterator over the bounds and the ids of all the entities stored in the tree, so
+    /// it could be converted back into the source data, e.g. to rebuild it with different
+    /// parameters. Each entity is yielded once, if the tree stores bounds of the entities (see
+    /// [`QuadTreeBuilder::with_stored_bounds`]). Otherwise, the bounds of the node the entity is
+    /// stored in are yielded instead and an entity, that is stored in multiple leaf nodes, is
+    /// yielded once per leaf.
+    pub fn entries(&self) -> impl Iterator<Item = (Rect<f32>, &I)> + '_ {
+        self.entry_slots()
+            .into_iter()
+            .map(|(bounds, index)| (bounds, &self.ids[index]))
+    }
+
+    /// Converts the tree into an iterator over the bounds and the ids of all the entities stored
+    /// in it. It yields the same items as [`Self::entries`].
+    pub fn into_entries(self) -> impl Iterator<Item = (Rect<f32>, I)> {
+        let slots = self.entry_slots();
+        let mut ids = self.ids.into_iter().map(Some).collect::<Vec<_>>();
+        slots
+            .into_iter()
+            .filter_map(move |(bounds, index)| ids[index].take().map(|id| (bounds, id)))
+    }
+
+    /// Collects the bounds and the arena index of every entity, skipping the duplicates of the
+    /// entities, that are stored in multiple leaf nodes.
+    fn entry_slots(&self) -> Vec<(Rect<f32>, usize)> {
+        let mut slots = Vec::new();
+        let _ = self.walk_nodes(
+            self.root,
+            self.root_bounds,
+            &|_| true,
+            &mut |_, bounds, range| {
+                for index in range {
+                    match self.entry_bounds.get(index) {
+                        Some(entry_bounds) => {
+                            // Only the leaf that contains the top-left corner of the visible part
+                            // of the entity yields it.
+                            if !self.branch_entries {
+                                let Some(visible) = *entry_bounds.clip_by(self.root_bounds) else {
+                                    continue;
+                                };
+                                if !contains_half_open(&bounds, visible.position) {
+                                    continue;
+                                }
+                            }
+                            slots.push((*entry_bounds, index));
+                        }
+                        None => slots.push((bounds, index)),
+                    }
+                }
+                ControlFlow::Continue(())
+            },
+        );
+        slots
+    }
+
+    /// Returns the bounds of the root node of the tree.
+    pub fn root_bounds(&self) -> Rect<f32> {
+        self.root_bounds
+    }
+
+    /// Returns current split threshold, that was used to build the quad tree.
+    pub fn split_threshold(&self) -> usize {
+        self.split_threshold
+    }
+
+    /// Returns `true` if the tree stores bounds of each entity in its leaf nodes.
+    pub fn stores_bounds(&self) -> bool {
+        self.store_bounds
+    }
+}
+
+/// Arbitrary storage for query results.
+pub trait QueryStorage {
+    /// Id of an entity in the storage.
+    type Id;
+
+    /// Tries to push a new id in the storage.
+    fn try_push(&mut self, id: Self::Id) -> bool;
+
+    /// Same as [`Self::try_push`], but returns [`ControlFlow::Break`] if the storage is full, so
+    /// it could be returned from the closures of the queries directly.
+    fn push_or_break(&mut self, id: Self::Id) -> ControlFlow<()> {
+        if self.try_push(id) {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    }
+
+    /// Clears the storage.
+    fn clear(&mut self);
+}
+
+impl<I> QueryStorage for Vec<I> {
+    type Id = I;
+
+    fn try_push(&mut self, intersection: I) -> bool {
+        self.push(intersection);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+impl<I, const CAP: usize> QueryStorage for ArrayVec<I, CAP> {
+    type Id = I;
+
+    fn try_push(&mut self, intersection: I) -> bool {
+        self.try_push(intersection).is_ok()
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+impl<I> QueryStorage for VecDeque<I> {
+    type Id = I;
+
+    fn try_push(&mut self, intersection: I) -> bool {
+        self.push_back(intersection);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+impl<I, S> QueryStorage for HashSet<I, S>
+where
+    I: Hash + Eq,
+    S: BuildHasher,
+{
+    type Id = I;
+
+    fn try_push(&mut self, intersection: I) -> bool {
+        self.insert(intersection);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+impl<I> QueryStorage for BTreeSet<I>
+where
+    I: Ord,
+{
+    type Id = I;
 
     fn try_push(&mut self, intersection: I) -> bool {
+        self.insert(intersection);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<A> QueryStorage for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+{
+    type Id = A::Item;
+
+    fn try_push(&mut self, intersection: A::Item) -> bool {
         self.push(intersection);
         true
     }
 
-    fn clear(&mut self) {
-        self.clear()
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+/// A query storage, that does not store anything and only counts the ids pushed in it. It is
+/// useful when only the amount of the entities matching a query is needed.
+pub struct CountingStorage<I> {
+    count: usize,
+    phantom: PhantomData<fn(I)>,
+}
+
+impl<I> Default for CountingStorage<I> {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I> CountingStorage<I> {
+    /// Creates new empty counting storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the amount of the ids pushed in the storage since the last clear.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<I> QueryStorage for CountingStorage<I> {
+    type Id = I;
+
+    fn try_push(&mut self, _intersection: I) -> bool {
+        self.count += 1;
+        true
+    }
+
+    fn clear(&mut self) {
+        self.count = 0;
+    }
+}
+
+/// A context of the queries, that remembers which entities were already visited by the current
+/// query, so an entity stored in multiple nodes is tested and reported only once. It keeps a
+/// stamp per entity and uses generation counters, so starting a new query does not require
+/// clearing or allocating memory: the context should be created once and reused by many queries.
+/// It only grows when the tree gets new entities.
+#[derive(Default)]
+pub struct QueryContext {
+    generation: u32,
+    stamps: Vec<u32>,
+}
+
+impl QueryContext {
+    /// Creates new empty query context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets every visited entity and releases the memory used by the context.
+    pub fn clear(&mut self) {
+        self.generation = 0;
+        self.stamps = Default::default();
+    }
+
+    fn begin_query(&mut self, key_count: u32) {
+        if self.stamps.len() < key_count as usize {
+            self.stamps.resize(key_count as usize, 0);
+        }
+        self.generation = match self.generation.checked_add(1) {
+            Some(generation) => generation,
+            None => {
+                self.stamps.fill(0);
+                1
+            }
+        };
+    }
+
+    /// Marks the entity with the given key as visited by the current query. Returns `false`, if
+    /// it was already visited.
+    fn visit(&mut self, key: u32) -> bool {
+        let stamp = &mut self.stamps[key as usize];
+        if *stamp == self.generation {
+            false
+        } else {
+            *stamp = self.generation;
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::TestObject;
+    use crate::Rect;
+
+    #[test]
+    #[allow(clippy::useless_vec)]
+    fn test_quad_tree() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = vec![
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 1,
+            },
+        ];
+        // Infinite recursion prevention check (when there are multiple objects share same location).
+        assert!(matches!(
+            QuadTree::new(root_bounds, objects.iter(), 1),
+            Err(QuadTreeBuildError::ReachedRecursionLimit {
+                depth: 64,
+                entry_count: 2,
+                bounds,
+            }) if bounds == Rect::new(10.0, 10.0, 10.0, 10.0)
+        ));
+
+        let objects = vec![
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(20.0, 20.0, 10.0, 10.0),
+                id: 1,
+            },
+        ];
+        assert!(QuadTree::new(root_bounds, objects.iter(), 1).is_ok());
+        assert!(matches!(
+            QuadTree::new(root_bounds, objects.iter(), 0),
+            Err(QuadTreeBuildError::ZeroSplitThreshold)
+        ));
+    }
+
+    #[test]
+    fn quad_tree_build_error_display() {
+        let err = QuadTreeBuildError::ReachedRecursionLimit {
+            depth: 64,
+            entry_count: 2,
+            bounds: Rect::new(1.0, 2.0, 3.0, 4.0),
+        };
+        let message = err.to_string();
+        assert!(message.contains("depth 64"));
+        assert!(message.contains("2 entries"));
+
+        let err: Box<dyn std::error::Error> = Box::new(QuadTreeBuildError::ZeroSplitThreshold);
+        assert!(err.to_string().contains("split threshold"));
+    }
+
+    #[test]
+    fn default_for_quad_tree() {
+        let tree = QuadTree::<u32>::default();
+
+        assert_eq!(tree.split_threshold, 16);
+        assert_eq!(tree.root, 0);
+    }
+
+    #[test]
+    #[allow(clippy::vec_init_then_push)]
+    fn quad_tree_point_query() {
+        // empty
+        let tree = QuadTree::<f32>::default();
+        let mut s = Vec::<f32>::new();
+
+        tree.point_query(Vector2::new(0.0, 0.0), &mut s);
+        assert_eq!(s, vec![]);
+
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+
+        // leaf
+        let mut s = Vec::<usize>::new();
+        let mut pool = Vec::new();
+        pool.push(QuadTreeNode::Leaf { first: 0, count: 2 });
+
+        let tree = QuadTree {
+            root: 0,
+            root_bounds,
+            nodes: pool,
+            ids: vec![0, 1],
+            ..Default::default()
+        };
+
+        tree.point_query(Vector2::new(10.0, 10.0), &mut s);
+        assert_eq!(s, vec![0, 1]);
+
+        // branch, all four leaves share the same entities.
+        let mut s = Vec::<usize>::new();
+        let mut pool = Vec::new();
+        let b = 0;
+        pool.push(QuadTreeNode::Branch {
+            children: 1,
+            first: 0,
+            count: 0,
+        });
+        for _ in 0..4 {
+            pool.push(QuadTreeNode::Leaf { first: 0, count: 2 });
+        }
+
+        let tree = QuadTree {
+            root: b,
+            root_bounds,
+            nodes: pool,
+            ids: vec![0, 1],
+            ..Default::default()
+        };
+
+        // The center point is contained by all four leaves.
+        tree.point_query(Vector2::new(100.0, 100.0), &mut s);
+        assert_eq!(s, vec![0, 1, 0, 1, 0, 1, 0, 1]);
+
+        s.clear();
+        tree.point_query(Vector2::new(10.0, 10.0), &mut s);
+        assert_eq!(s, vec![0, 1]);
+    }
+
+    #[test]
+    fn quad_tree_point_query_with_stored_bounds() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = [
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(50.0, 50.0, 10.0, 10.0),
+                id: 1,
+            },
+        ];
+
+        // Both entities are in the same leaf, so the point query returns both of them.
+        let tree = QuadTree::new(root_bounds, objects.iter(), 16).unwrap();
+        assert!(!tree.stores_bounds());
+        let mut s = Vec::new();
+        tree.point_query(Vector2::new(15.0, 15.0), &mut s);
+        assert_eq!(s, vec![0, 1]);
+
+        let tree = QuadTreeBuilder::new()
+            .with_stored_bounds(true)
+            .build(root_bounds, objects.iter())
+            .unwrap();
+        assert!(tree.stores_bounds());
+        let mut s = Vec::new();
+        tree.point_query(Vector2::new(15.0, 15.0), &mut s);
+        assert_eq!(s, vec![0]);
+
+        s.clear();
+        tree.point_query(Vector2::new(100.0, 100.0), &mut s);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn quad_tree_intersecting_pairs() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = [
+            // Spans all four quadrants.
+            TestObject {
+                bounds: Rect::new(90.0, 90.0, 20.0, 20.0),
+                id: 0,
+            },
+            // Overlaps with the first object in all four quadrants.
+            TestObject {
+                bounds: Rect::new(80.0, 80.0, 40.0, 40.0),
+                id: 1,
+            },
+            // Overlaps with the second object only.
+            TestObject {
+                bounds: Rect::new(115.0, 115.0, 10.0, 10.0),
+                id: 2,
+            },
+            // Overlaps with nothing.
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 3,
+            },
+        ];
+        let tree = QuadTreeBuilder::new()
+            .with_split_threshold(2)
+            .with_stored_bounds(true)
+            .build(root_bounds, objects.iter())
+            .unwrap();
+
+        let mut pairs = Vec::new();
+        tree.intersecting_pairs(&mut pairs).unwrap();
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 1), (1, 2)]);
+
+        // The overlap sticks out of the root to the left.
+        let edge_objects = [
+            TestObject {
+                bounds: Rect::new(-10.0, 10.0, 20.0, 20.0),
+                id: 4,
+            },
+            TestObject {
+                bounds: Rect::new(-5.0, 15.0, 10.0, 10.0),
+                id: 5,
+            },
+            TestObject {
+                bounds: Rect::new(150.0, 150.0, 10.0, 10.0),
+                id: 6,
+            },
+        ];
+        let edge_tree = QuadTreeBuilder::new()
+            .with_split_threshold(2)
+            .with_stored_bounds(true)
+            .build(root_bounds, edge_objects.iter())
+            .unwrap();
+        let mut pairs = Vec::new();
+        edge_tree.intersecting_pairs(&mut pairs).unwrap();
+        assert_eq!(pairs, vec![(4, 5)]);
+
+        // Storage capacity stops the query.
+        let mut pairs = ArrayVec::<(usize, usize), 1>::new();
+        tree.intersecting_pairs(&mut pairs).unwrap();
+        assert_eq!(pairs.len(), 1);
+
+        let tree = QuadTreeBuilder::new()
+            .with_split_threshold(2)
+            .build(root_bounds, objects.iter())
+            .unwrap();
+        let mut pairs = Vec::new();
+        assert_eq!(tree.intersecting_pairs(&mut pairs), Err(BoundsNotStored));
+    }
+
+    #[test]
+    fn quad_tree_point_query_with() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = (0..4)
+            .map(|id| TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let tree = QuadTree::new(root_bounds, objects.iter(), 16).unwrap();
+
+        // Filtering.
+        let mut odd = Vec::new();
+        let flow = tree.point_query_with(Vector2::new(15.0, 15.0), |&id| {
+            if id % 2 == 1 {
+                odd.push(id);
+            }
+            ControlFlow::Continue(())
+        });
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(odd, vec![1, 3]);
+
+        // Early exit.
+        let mut visited = Vec::new();
+        let flow = tree.point_query_with(Vector2::new(15.0, 15.0), |&id| {
+            visited.push(id);
+            if id == 1 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(flow, ControlFlow::Break(()));
+        assert_eq!(visited, vec![0, 1]);
+
+        // Rectangles and circles.
+        let mut visited = Vec::new();
+        let flow = tree.rect_query_with(Rect::new(0.0, 0.0, 50.0, 50.0), |&id| {
+            visited.push(id);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(visited, vec![0, 1, 2, 3]);
+        let flow = tree.circle_query_with(Vector2::new(15.0, 15.0), 1.0, |&id| {
+            if id == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(flow, ControlFlow::Break(()));
+        assert_eq!(
+            tree.circle_query_with(Vector2::new(500.0, 500.0), 1.0, |_| ControlFlow::Break(())),
+            ControlFlow::Continue(())
+        );
+    }
+
+    #[test]
+    fn quad_tree_query_iter() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = [
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(150.0, 10.0, 10.0, 10.0),
+                id: 1,
+            },
+            TestObject {
+                bounds: Rect::new(150.0, 150.0, 10.0, 10.0),
+                id: 2,
+            },
+        ];
+        let tree = QuadTreeBuilder::new()
+            .with_split_threshold(1)
+            .with_stored_bounds(true)
+            .build(root_bounds, objects.iter())
+            .unwrap();
+
+        let point = Vector2::new(15.0, 15.0);
+        let mut s = Vec::new();
+        tree.point_query(point, &mut s);
+        assert_eq!(tree.point_query_iter(point).cloned().collect::<Vec<_>>(), s);
+        assert_eq!(s, vec![0]);
+
+        assert_eq!(
+            tree.rect_query_iter(Rect::new(0.0, 0.0, 200.0, 50.0))
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            tree.circle_query_iter(Vector2::new(155.0, 100.0), 85.0)
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            tree.rect_query_iter(Rect::new(50.0, 50.0, 10.0, 10.0))
+                .count(),
+            0
+        );
+
+        // empty
+        let tree = QuadTree::<u32>::default();
+        assert_eq!(tree.point_query_iter(point).count(), 0);
+    }
+
+    #[test]
+    fn quad_tree_query_leaves() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = [
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(20.0, 20.0, 10.0, 10.0),
+                id: 1,
+            },
+            TestObject {
+                bounds: Rect::new(150.0, 10.0, 10.0, 10.0),
+                id: 2,
+            },
+        ];
+        let tree = QuadTree::new(root_bounds, objects.iter(), 2).unwrap();
+
+        let mut leaves = Vec::new();
+        tree.rect_query_leaves(Rect::new(0.0, 0.0, 200.0, 50.0), |bounds, ids| {
+            leaves.push((bounds, ids.to_vec()))
+        });
+        assert_eq!(
+            leaves,
+            vec![
+                (Rect::new(0.0, 0.0, 100.0, 100.0), vec![0, 1]),
+                (Rect::new(100.0, 0.0, 100.0, 100.0), vec![2]),
+            ]
+        );
+
+        let mut leaves = Vec::new();
+        tree.point_query_leaves(Vector2::new(150.0, 150.0), |bounds, ids| {
+            leaves.push((bounds, ids.to_vec()))
+        });
+        assert_eq!(
+            leaves,
+            vec![(Rect::new(100.0, 100.0, 100.0, 100.0), vec![])]
+        );
+
+        let mut count = 0;
+        tree.circle_query_leaves(Vector2::new(100.0, 100.0), 10.0, |_, _| count += 1);
+        assert_eq!(count, 4);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn quad_tree_build_par() {
+        let root_bounds = Rect::new(0.0, 0.0, 1000.0, 1000.0);
+        let objects = (0..10000)
+            .map(|id| TestObject {
+                bounds: Rect::new((id % 100) as f32 * 10.0, (id / 100) as f32 * 10.0, 5.0, 5.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+
+        let builder = QuadTreeBuilder::new().with_stored_bounds(true);
+        let tree = builder.build(root_bounds, objects.iter()).unwrap();
+        let tree_par = builder.build_par(root_bounds, objects.iter()).unwrap();
+
+        let mut nodes = Vec::new();
+        tree.visit(|bounds, kind, ids| nodes.push((*bounds, kind, ids.to_vec())));
+        let mut nodes_par = Vec::new();
+        tree_par.visit(|bounds, kind, ids| nodes_par.push((*bounds, kind, ids.to_vec())));
+        assert_eq!(nodes, nodes_par);
+
+        let point = Vector2::new(502.0, 502.0);
+        assert_eq!(
+            tree_par.point_query_iter(point).collect::<Vec<_>>(),
+            vec![&5050]
+        );
     }
-}
 
-impl<I, const CAP: usize> QueryStorage for ArrayVec<I, CAP> {
-    type Id = I;
+    #[cfg(feature = "serde")]
+    #[test]
+    fn quad_tree_serde() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = [
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(150.0, 10.0, 10.0, 10.0),
+                id: 1,
+            },
+        ];
+        let tree = QuadTreeBuilder::new()
+            .with_split_threshold(1)
+            .with_stored_bounds(true)
+            .build(root_bounds, objects.iter())
+            .unwrap();
 
-    fn try_push(&mut self, intersection: I) -> bool {
-        self.try_push(intersection).is_ok()
+        let bytes = bincode::serialize(&tree).unwrap();
+        let loaded: QuadTree<usize> = bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(loaded.split_threshold(), tree.split_threshold());
+        assert!(loaded.stores_bounds());
+        let mut nodes = Vec::new();
+        tree.visit(|bounds, kind, ids| nodes.push((*bounds, kind, ids.to_vec())));
+        let mut loaded_nodes = Vec::new();
+        loaded.visit(|bounds, kind, ids| loaded_nodes.push((*bounds, kind, ids.to_vec())));
+        assert_eq!(nodes, loaded_nodes);
+
+        let corrupt = |corrupt: fn(&mut QuadTree<usize>)| {
+            let mut tree = QuadTreeBuilder::new()
+                .with_split_threshold(1)
+                .with_stored_bounds(true)
+                .build(root_bounds, objects.iter())
+                .unwrap();
+            corrupt(&mut tree);
+            let bytes = bincode::serialize(&tree).unwrap();
+            bincode::deserialize::<QuadTree<usize>>(&bytes).is_err()
+        };
+        assert!(corrupt(|tree| tree.root = tree.nodes.len()));
+        assert!(corrupt(|tree| tree.nodes[1] = tree.nodes[0]));
+        assert!(corrupt(|tree| {
+            tree.ids.pop();
+        }));
+        assert!(corrupt(|tree| tree.entry_bounds.clear()));
+        assert!(corrupt(|tree| tree.key_count = 0));
+        assert!(corrupt(|tree| tree.split_threshold = 0));
     }
 
-    fn clear(&mut self) {
-        self.clear()
+    #[test]
+    fn quad_tree_rebuild() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let mut objects = [
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(150.0, 10.0, 10.0, 10.0),
+                id: 1,
+            },
+        ];
+        let mut tree = QuadTreeBuilder::new()
+            .with_split_threshold(1)
+            .with_stored_bounds(true)
+            .build(root_bounds, objects.iter())
+            .unwrap();
+        let capacity = tree.nodes.capacity();
+
+        objects[0].bounds = Rect::new(150.0, 150.0, 10.0, 10.0);
+        tree.rebuild(root_bounds, objects.iter()).unwrap();
+        assert_eq!(tree.nodes.capacity(), capacity);
+        assert!(tree.stores_bounds());
+        assert_eq!(tree.point_query_iter(Vector2::new(15.0, 15.0)).count(), 0);
+        assert_eq!(
+            tree.point_query_iter(Vector2::new(155.0, 155.0))
+                .collect::<Vec<_>>(),
+            vec![&0]
+        );
+
+        // Failed rebuild leaves the tree empty.
+        objects[1].bounds = objects[0].bounds;
+        assert!(tree.rebuild(root_bounds, objects.iter()).is_err());
+        assert!(tree.nodes.is_empty());
+        assert_eq!(tree.point_query_iter(Vector2::new(155.0, 155.0)).count(), 0);
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::Rect;
+    #[test]
+    fn quad_tree_insert() {
+        let mut tree = QuadTreeBuilder::new()
+            .with_split_threshold(1)
+            .with_stored_bounds(true)
+            .build::<&TestObject, usize>(Rect::new(0.0, 0.0, 100.0, 100.0), [].into_iter())
+            .unwrap();
 
-    struct TestObject {
-        bounds: Rect<f32>,
-        id: usize,
+        let objects = [
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(60.0, 60.0, 10.0, 10.0),
+                id: 1,
+            },
+            // Outside of the initial root bounds.
+            TestObject {
+                bounds: Rect::new(-250.0, 320.0, 10.0, 10.0),
+                id: 2,
+            },
+        ];
+        for object in objects.iter() {
+            tree.insert(object).unwrap();
+        }
+
+        for object in objects.iter() {
+            assert_eq!(
+                tree.point_query_iter(object.bounds.center())
+                    .collect::<Vec<_>>(),
+                vec![&object.id]
+            );
+        }
+
+        let mut root = None;
+        tree.visit(|bounds, _, _| {
+            if root.is_none() {
+                root = Some(*bounds);
+            }
+        });
+        assert_eq!(root, Some(Rect::new(-300.0, 0.0, 400.0, 400.0)));
     }
 
-    impl BoundsProvider for &TestObject {
-        type Id = usize;
+    #[test]
+    fn quad_tree_insert_far_and_degenerate() {
+        // The root of a single point has no size, it must not prevent the tree from growing.
+        let point = TestObject {
+            bounds: Rect::new(5.0, 5.0, 0.0, 0.0),
+            id: 0,
+        };
+        let mut tree = QuadTree::with_auto_bounds([&point].into_iter(), 1).unwrap();
+        let object = TestObject {
+            bounds: Rect::new(20.0, -10.0, 1.0, 1.0),
+            id: 1,
+        };
+        tree.insert(&object).unwrap();
+        assert_eq!(
+            tree.point_query_iter(Vector2::new(20.5, -9.5))
+                .collect::<Vec<_>>(),
+            vec![&1]
+        );
+
+        let far = TestObject {
+            bounds: Rect::new(1.0e30, 0.0, 1.0, 1.0),
+            id: 2,
+        };
+        assert!(matches!(
+            tree.insert(&far),
+            Err(QuadTreeBuildError::ReachedGrowthLimit { .. })
+        ));
+        let nan = TestObject {
+            bounds: Rect::new(f32::NAN, 0.0, 1.0, 1.0),
+            id: 3,
+        };
+        assert!(matches!(
+            tree.insert(&nan),
+            Err(QuadTreeBuildError::NonFiniteBounds { .. })
+        ));
+        assert_eq!(
+            tree.rect_query_iter(Rect::new(-100.0, -100.0, 200.0, 200.0))
+                .count(),
+            2
+        );
+    }
 
-        fn bounds(&self) -> Rect<f32> {
-            self.bounds
+    #[test]
+    fn quad_tree_insert_into_empty_tree() {
+        let mut tree = QuadTree::default();
+        let objects = (0..20)
+            .map(|id| TestObject {
+                bounds: Rect::new(id as f32 * 10.0, 0.0, 5.0, 5.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        for object in objects.iter() {
+            tree.insert(object).unwrap();
         }
 
-        fn id(&self) -> Self::Id {
-            self.id
+        for object in objects.iter() {
+            assert!(tree
+                .point_query_iter(object.bounds.center())
+                .any(|id| *id == object.id));
         }
     }
 
     #[test]
-    fn test_quad_tree() {
-        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
-        let objects = vec![
+    fn quad_tree_branch_entries() {
+        let objects = [
             TestObject {
                 bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
                 id: 0,
             },
             TestObject {
-                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                bounds: Rect::new(30.0, 10.0, 10.0, 10.0),
                 id: 1,
             },
+            // Spans all four quadrants of the root.
+            TestObject {
+                bounds: Rect::new(40.0, 40.0, 20.0, 20.0),
+                id: 2,
+            },
         ];
-        // Infinite recursion prevention check (when there are multiple objects share same location).
-        assert!(QuadTree::new(root_bounds, objects.iter(), 1).is_err());
+        let mut tree = QuadTreeBuilder::new()
+            .with_split_threshold(1)
+            .with_stored_bounds(true)
+            .with_branch_entries(true)
+            .build(Rect::new(0.0, 0.0, 100.0, 100.0), objects.iter())
+            .unwrap();
 
-        let objects = vec![
+        let mut stored = Vec::new();
+        tree.visit(|_, _, ids| stored.extend_from_slice(ids));
+        stored.sort();
+        assert_eq!(stored, vec![0, 1, 2]);
+
+        let mut ids = tree
+            .rect_query_iter(Rect::new(0.0, 0.0, 100.0, 100.0))
+            .cloned()
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2]);
+
+        let mut pairs = Vec::new();
+        tree.intersecting_pairs(&mut pairs).unwrap();
+        assert!(pairs.is_empty());
+
+        let object = TestObject {
+            bounds: Rect::new(15.0, 15.0, 30.0, 30.0),
+            id: 3,
+        };
+        tree.insert(&object).unwrap();
+        let mut pairs = Vec::new();
+        tree.intersecting_pairs(&mut pairs).unwrap();
+        for pair in pairs.iter_mut() {
+            *pair = (pair.0.min(pair.1), pair.0.max(pair.1));
+        }
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 3), (1, 3), (2, 3)]);
+        assert_eq!(tree.point_query_iter(Vector2::new(50.0, 50.0)).count(), 1);
+    }
+
+    #[test]
+    fn quad_tree_auto_bounds() {
+        let objects = [
+            TestObject {
+                bounds: Rect::new(-50.0, 10.0, 20.0, 20.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(100.0, 200.0, 50.0, 10.0),
+                id: 1,
+            },
+        ];
+        let tree = QuadTree::with_auto_bounds(objects.iter(), 1).unwrap();
+        assert_eq!(tree.root_bounds(), Rect::new(-50.0, 10.0, 200.0, 200.0));
+        for object in objects.iter() {
+            assert!(tree
+                .point_query_iter(object.bounds.center())
+                .any(|id| *id == object.id));
+        }
+
+        let tree = QuadTreeBuilder::new()
+            .build_with_auto_bounds(objects.iter(), 5.0)
+            .unwrap();
+        assert_eq!(tree.root_bounds(), Rect::new(-55.0, 5.0, 210.0, 210.0));
+
+        let tree =
+            QuadTree::<usize>::with_auto_bounds(Vec::<&TestObject>::new().into_iter(), 1).unwrap();
+        assert_eq!(tree.root_bounds(), Rect::default());
+    }
+
+    #[test]
+    fn quad_tree_entries() {
+        let objects = [
             TestObject {
                 bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
                 id: 0,
             },
+            // Stored in all four leaves.
             TestObject {
-                bounds: Rect::new(20.0, 20.0, 10.0, 10.0),
+                bounds: Rect::new(40.0, 40.0, 20.0, 20.0),
                 id: 1,
             },
+            TestObject {
+                bounds: Rect::new(70.0, 70.0, 10.0, 10.0),
+                id: 2,
+            },
         ];
-        assert!(QuadTree::new(root_bounds, objects.iter(), 1).is_ok());
+        let tree = QuadTreeBuilder::new()
+            .with_split_threshold(1)
+            .with_stored_bounds(true)
+            .build(Rect::new(0.0, 0.0, 100.0, 100.0), objects.iter())
+            .unwrap();
+
+        let mut entries = tree
+            .entries()
+            .map(|(bounds, id)| (*id, bounds))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(id, _)| *id);
+        let expected = objects.iter().map(|o| (o.id, o.bounds)).collect::<Vec<_>>();
+        assert_eq!(entries, expected);
+
+        let mut entries = tree
+            .into_entries()
+            .map(|(bounds, id)| (id, bounds))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(id, _)| *id);
+        assert_eq!(entries, expected);
     }
 
     #[test]
-    fn default_for_quad_tree() {
-        let tree = QuadTree::<u32>::default();
+    fn quad_tree_remove_and_compact() {
+        let objects = (0..64)
+            .map(|id| TestObject {
+                bounds: Rect::new((id % 8) as f32 * 10.0, (id / 8) as f32 * 10.0, 5.0, 5.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        for store_bounds in [false, true] {
+            let mut tree = QuadTreeBuilder::new()
+                .with_split_threshold(2)
+                .with_stored_bounds(store_bounds)
+                .build(Rect::new(0.0, 0.0, 80.0, 80.0), objects.iter())
+                .unwrap();
+            let node_count = tree.nodes.len();
 
-        assert_eq!(tree.split_threshold, 16);
-        assert_eq!(tree.root, 0);
+            for object in objects.iter().skip(3) {
+                assert!(tree.remove(object));
+                assert!(!tree.remove(object));
+            }
+            for object in objects.iter() {
+                assert_eq!(
+                    tree.point_query_iter(object.bounds.center())
+                        .any(|id| *id == object.id),
+                    object.id < 3
+                );
+            }
+
+            tree.compact();
+            assert!(tree.nodes.len() < node_count);
+            assert_eq!(tree.garbage, 0);
+            let mut ids = tree
+                .rect_query_iter(tree.root_bounds())
+                .cloned()
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids.dedup();
+            assert_eq!(ids, vec![0, 1, 2]);
+        }
+
+        // Separate entities with equal ids both survive the collapse.
+        let shared = [
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 5.0, 5.0),
+                id: 7,
+            },
+            TestObject {
+                bounds: Rect::new(60.0, 60.0, 5.0, 5.0),
+                id: 7,
+            },
+            TestObject {
+                bounds: Rect::new(60.0, 10.0, 5.0, 5.0),
+                id: 8,
+            },
+        ];
+        let mut tree = QuadTreeBuilder::new()
+            .with_split_threshold(2)
+            .build(Rect::new(0.0, 0.0, 80.0, 80.0), shared.iter())
+            .unwrap();
+        assert!(tree.remove(&shared[2]));
+        tree.compact();
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(tree.rect_query_iter(tree.root_bounds()).count(), 2);
     }
 
     #[test]
-    fn quad_tree_point_query() {
-        // empty
-        let tree = QuadTree::<f32>::default();
-        let mut s = Vec::<f32>::new();
+    fn quad_tree_raycast() {
+        let objects = (0..16)
+            .map(|id| TestObject {
+                bounds: Rect::new((id % 4) as f32 * 25.0, (id / 4) as f32 * 25.0, 10.0, 10.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        for store_bounds in [false, true] {
+            let tree = QuadTreeBuilder::new()
+                .with_split_threshold(1)
+                .with_stored_bounds(store_bounds)
+                .build(Rect::new(0.0, 0.0, 100.0, 100.0), objects.iter())
+                .unwrap();
+            let narrow_phase = |origin: Vector2<f32>, dir: Vector2<f32>| {
+                let objects = &objects;
+                move |id: &usize| ray_enters_at(origin, dir, &objects[*id].bounds)
+            };
 
-        tree.point_query(Vector2::new(0.0, 0.0), &mut s);
-        assert_eq!(s, vec![]);
+            let mut calls = 0;
+            let origin = Vector2::new(-10.0, 30.0);
+            let dir = Vector2::new(1.0, 0.0);
+            let func = narrow_phase(origin, dir);
+            let hit = tree.raycast(origin, dir, |id| {
+                calls += 1;
+                func(id)
+            });
+            assert_eq!(hit, Some((4, 10.0)));
+            // The far nodes must be skipped once the nearest hit is found.
+            assert!(calls < objects.len());
 
-        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+            let origin = Vector2::new(105.0, 105.0);
+            let dir = Vector2::new(-1.0, -1.0);
+            assert_eq!(
+                tree.raycast(origin, dir, narrow_phase(origin, dir)),
+                Some((15, 20.0))
+            );
 
-        // leaf
-        let mut s = Vec::<usize>::new();
-        let mut pool = Vec::new();
-        pool.push(QuadTreeNode::Leaf {
-            bounds: root_bounds,
-            ids: vec![0, 1],
-        });
+            let origin = Vector2::new(15.0, 0.0);
+            let dir = Vector2::new(0.0, 1.0);
+            assert_eq!(tree.raycast(origin, dir, narrow_phase(origin, dir)), None);
+        }
+    }
 
-        let tree = QuadTree {
-            root: 0,
-            nodes: pool,
-            ..Default::default()
-        };
+    #[test]
+    fn quad_tree_build_morton() {
+        let objects = (0..256)
+            .map(|id| TestObject {
+                bounds: Rect::new(
+                    ((id * 37) % 97) as f32,
+                    ((id * 53) % 89) as f32,
+                    (id % 7) as f32 + 1.0,
+                    (id % 5) as f32 + 1.0,
+                ),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let root_bounds = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let builder = QuadTreeBuilder::new().with_split_threshold(4);
+        let tree = builder.build(root_bounds, objects.iter()).unwrap();
+        let morton_tree = builder.build_morton(root_bounds, objects.iter()).unwrap();
+        assert_eq!(tree.nodes.len(), morton_tree.nodes.len());
 
-        tree.point_query(Vector2::new(10.0, 10.0), &mut s);
-        assert_eq!(s, vec![0, 1]);
+        for query in [
+            Rect::new(0.0, 0.0, 10.0, 10.0),
+            Rect::new(20.0, 40.0, 35.0, 15.0),
+            root_bounds,
+        ] {
+            let mut expected = tree.rect_query_iter(query).cloned().collect::<Vec<_>>();
+            let mut ids = morton_tree
+                .rect_query_iter(query)
+                .cloned()
+                .collect::<Vec<_>>();
+            expected.sort();
+            ids.sort();
+            assert_eq!(ids, expected);
+        }
 
-        // branch
-        let mut s = Vec::<usize>::new();
-        let mut pool = Vec::new();
-        let a = 0;
-        pool.push(QuadTreeNode::Leaf {
-            bounds: root_bounds,
-            ids: vec![0, 1],
-        });
-        let b = 1;
-        pool.push(QuadTreeNode::Branch {
-            bounds: root_bounds,
-            leaves: [a, a, a, a],
-        });
+        assert_eq!(morton_code(&root_bounds, Vector2::new(0.0, 0.0)), 0);
+        assert_eq!(
+            morton_code(&root_bounds, Vector2::new(100.0, 0.0)),
+            0x5555_5555
+        );
+        assert_eq!(
+            morton_code(&root_bounds, Vector2::new(0.0, 100.0)),
+            0xaaaa_aaaa
+        );
+    }
 
-        let tree = QuadTree {
-            root: b,
-            nodes: pool,
-            ..Default::default()
+    #[test]
+    fn quad_tree_query_with_context() {
+        let objects = [
+            TestObject {
+                bounds: Rect::new(40.0, 40.0, 20.0, 20.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 1,
+            },
+            TestObject {
+                bounds: Rect::new(80.0, 10.0, 10.0, 10.0),
+                id: 2,
+            },
+        ];
+        let tree = QuadTree::new(Rect::new(0.0, 0.0, 100.0, 100.0), objects.iter(), 1).unwrap();
+        assert_eq!(
+            tree.rect_query_iter(Rect::new(0.0, 0.0, 100.0, 100.0))
+                .filter(|id| **id == 0)
+                .count(),
+            4
+        );
+
+        let mut context = QueryContext::new();
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            ids.clear();
+            tree.rect_query_with_context(Rect::new(0.0, 0.0, 100.0, 100.0), &mut context, &mut ids);
+            ids.sort();
+            assert_eq!(ids, vec![0, 1, 2]);
+        }
+
+        ids.clear();
+        tree.circle_query_with_context(Vector2::new(50.0, 50.0), 5.0, &mut context, &mut ids);
+        assert_eq!(ids, vec![0]);
+
+        ids.clear();
+        tree.point_query_with_context(Vector2::new(15.0, 15.0), &mut context, &mut ids);
+        assert_eq!(ids, vec![1]);
+
+        context.generation = u32::MAX;
+        ids.clear();
+        tree.rect_query_with_context(Rect::new(0.0, 0.0, 100.0, 100.0), &mut context, &mut ids);
+        assert_eq!(ids.len(), 3);
+
+        // Inserted entities, that span multiple leaves, get their own keys as well.
+        let mut tree = QuadTreeBuilder::new()
+            .with_split_threshold(1)
+            .with_stored_bounds(true)
+            .build(Rect::new(0.0, 0.0, 100.0, 100.0), objects.iter())
+            .unwrap();
+        let inserted = TestObject {
+            bounds: Rect::new(30.0, 5.0, 60.0, 60.0),
+            id: 3,
         };
+        tree.insert(&inserted).unwrap();
+        assert!(tree.remove(&objects[1]));
+        ids.clear();
+        tree.rect_query_with_context(Rect::new(0.0, 0.0, 100.0, 100.0), &mut context, &mut ids);
+        ids.sort();
+        assert_eq!(ids, vec![0, 2, 3]);
+    }
 
-        tree.point_query(Vector2::new(10.0, 10.0), &mut s);
-        assert_eq!(s, vec![0, 1, 0, 1, 0, 1, 0, 1]);
+    #[test]
+    fn quad_tree_sorted_query() {
+        let objects = (0..32)
+            .rev()
+            .map(|id| TestObject {
+                bounds: Rect::new((id % 8) as f32 * 12.0, (id / 8) as f32 * 12.0, 10.0, 10.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let root_bounds = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let builder = QuadTreeBuilder::new()
+            .with_split_threshold(2)
+            .with_stored_bounds(true);
+        let tree = builder.build(root_bounds, objects.iter()).unwrap();
+        let query = Rect::new(5.0, 5.0, 30.0, 10.0);
+        assert_eq!(tree.sorted_query(query), vec![0, 1, 2, 8, 9, 10]);
+
+        // Same sequence of operations gives the same order of the results.
+        let first = tree.rect_query_iter(query).cloned().collect::<Vec<_>>();
+        let other_tree = builder.build(root_bounds, objects.iter()).unwrap();
+        let second = other_tree
+            .rect_query_iter(query)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(first, second);
+
+        let mut inserted = builder
+            .build(root_bounds, std::iter::empty::<&TestObject>())
+            .unwrap();
+        for object in objects.iter().rev() {
+            inserted.insert(object).unwrap();
+        }
+        assert_eq!(inserted.sorted_query(query), tree.sorted_query(query));
+    }
+
+    #[test]
+    fn quad_tree_compact_layout() {
+        assert!(std::mem::size_of::<QuadTreeNode>() <= 16);
+
+        let mut tree = QuadTreeBuilder::new()
+            .with_split_threshold(4)
+            .with_stored_bounds(true)
+            .build::<&TestObject, usize>(Rect::new(0.0, 0.0, 100.0, 100.0), [].into_iter())
+            .unwrap();
+        let objects = (0..400)
+            .map(|id| TestObject {
+                bounds: Rect::new((id % 20) as f32 * 5.0, (id / 20) as f32 * 5.0, 2.0, 2.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        for object in objects.iter() {
+            tree.insert(object).unwrap();
+        }
+
+        // Leaf relocations leave garbage in the arena, which must be collected from time to time.
+        assert!(tree.garbage <= tree.ids.len() / 2);
+        for object in objects.iter() {
+            assert_eq!(
+                tree.point_query_iter(object.bounds.center())
+                    .collect::<Vec<_>>(),
+                vec![&object.id]
+            );
+        }
+    }
+
+    #[test]
+    fn quad_tree_visit() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = [
+            TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id: 0,
+            },
+            TestObject {
+                bounds: Rect::new(110.0, 10.0, 10.0, 10.0),
+                id: 1,
+            },
+        ];
+        let tree = QuadTree::new(root_bounds, objects.iter(), 1).unwrap();
+
+        let mut visited = Vec::new();
+        tree.visit(|bounds, kind, ids| visited.push((*bounds, kind, ids.to_vec())));
+        assert_eq!(
+            visited,
+            vec![
+                (root_bounds, NodeKind::Branch, vec![]),
+                (Rect::new(0.0, 0.0, 100.0, 100.0), NodeKind::Leaf, vec![0]),
+                (Rect::new(100.0, 0.0, 100.0, 100.0), NodeKind::Leaf, vec![1]),
+                (
+                    Rect::new(100.0, 100.0, 100.0, 100.0),
+                    NodeKind::Leaf,
+                    vec![]
+                ),
+                (Rect::new(0.0, 100.0, 100.0, 100.0), NodeKind::Leaf, vec![]),
+            ]
+        );
+
+        // empty
+        let mut count = 0;
+        QuadTree::<u32>::default().visit(|_, _, _| count += 1);
+        assert_eq!(count, 0);
     }
 
     #[test]
@@ -383,4 +3025,67 @@ mod test {
         QueryStorage::clear(&mut s);
         assert!(s.is_empty());
     }
+
+    #[test]
+    fn query_storage_for_vec_deque() {
+        let mut s = VecDeque::new();
+
+        assert!(QueryStorage::try_push(&mut s, 1));
+        assert!(QueryStorage::try_push(&mut s, 2));
+        assert_eq!(s, [1, 2]);
+
+        QueryStorage::clear(&mut s);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn query_storage_for_sets() {
+        let mut s = HashSet::new();
+        assert!(QueryStorage::try_push(&mut s, 1));
+        assert!(QueryStorage::try_push(&mut s, 1));
+        assert_eq!(s.len(), 1);
+        QueryStorage::clear(&mut s);
+        assert!(s.is_empty());
+
+        let mut s = BTreeSet::new();
+        assert!(QueryStorage::try_push(&mut s, 2));
+        assert!(QueryStorage::try_push(&mut s, 1));
+        assert!(QueryStorage::try_push(&mut s, 2));
+        assert_eq!(s.iter().cloned().collect::<Vec<_>>(), vec![1, 2]);
+        QueryStorage::clear(&mut s);
+        assert!(s.is_empty());
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn query_storage_for_small_vec() {
+        let mut s = smallvec::SmallVec::<[i32; 2]>::new();
+
+        for i in 0..3 {
+            assert!(QueryStorage::try_push(&mut s, i));
+        }
+        assert_eq!(s.as_slice(), &[0, 1, 2]);
+
+        QueryStorage::clear(&mut s);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn counting_storage() {
+        let root_bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let objects = (0..5)
+            .map(|id| TestObject {
+                bounds: Rect::new(10.0, 10.0, 10.0, 10.0),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let tree = QuadTree::new(root_bounds, objects.iter(), 16).unwrap();
+
+        let mut s = CountingStorage::new();
+        tree.point_query(Vector2::new(15.0, 15.0), &mut s);
+        assert_eq!(s.count(), 5);
+
+        QueryStorage::clear(&mut s);
+        assert_eq!(s.count(), 0);
+    }
 }
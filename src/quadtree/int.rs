@@ -0,0 +1,335 @@
+//! Quad tree with integer coordinates.
+
+use super::{BoundsProvider, NodeKind, QuadTreeBuildError, QuadTreeNode, QueryStorage, EMPTY_LEAF};
+use crate::{geometry::intersection, Rect};
+use nalgebra::Vector2;
+use std::ops::ControlFlow;
+
+/// Quad tree with integer coordinates. Unlike [`super::QuadTree`], nodes are split at exact
+/// integer midpoints, so the boundaries of the nodes match the boundaries of the cells at any
+/// coordinates, without seams caused by floating point rounding. This makes it a good fit for
+/// tile-based worlds.
+///
+/// All the rectangles are half-open: a rectangle covers the cells from its position (inclusive)
+/// to its position plus its size (exclusive). Each cell belongs to exactly one leaf node, which
+/// allows the queries to report every entity exactly once. Bounds of the entities are always
+/// stored in the tree.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IntQuadTree<I> {
+    nodes: Vec<QuadTreeNode>,
+    ids: Vec<I>,
+    entry_bounds: Vec<Rect<i32>>,
+    root_bounds: Rect<i32>,
+    split_threshold: usize,
+}
+
+impl<I> Default for IntQuadTree<I> {
+    fn default() -> Self {
+        Self {
+            nodes: Default::default(),
+            ids: Default::default(),
+            entry_bounds: Default::default(),
+            root_bounds: Default::default(),
+            split_threshold: 16,
+        }
+    }
+}
+
+fn contains_cell(rect: &Rect<i32>, cell: Vector2<i32>) -> bool {
+    cell.x >= rect.x()
+        && cell.x < rect.x() + rect.w()
+        && cell.y >= rect.y()
+        && cell.y < rect.y() + rect.h()
+}
+
+/// Splits the rectangle in four at the integer midpoint. The children are in the same order as
+/// the children of [`super::QuadTree`] nodes: top-left, top-right, bottom-right, bottom-left.
+fn split_rect(rect: &Rect<i32>) -> [Rect<i32>; 4] {
+    let left = rect.w() / 2;
+    let top = rect.h() / 2;
+    let right = rect.w() - left;
+    let bottom = rect.h() - top;
+    [
+        Rect::new(rect.x(), rect.y(), left, top),
+        Rect::new(rect.x() + left, rect.y(), right, top),
+        Rect::new(rect.x() + left, rect.y() + top, right, bottom),
+        Rect::new(rect.x(), rect.y() + top, left, bottom),
+    ]
+}
+
+impl<I> IntQuadTree<I>
+where
+    I: Clone + 'static,
+{
+    /// Creates new quad tree from the given initial bounds and the set of objects. Objects
+    /// outside of the root bounds are ignored. Nodes, that are a single cell in size, are never
+    /// split, even if they hold more entities than the split threshold.
+    pub fn new<T>(
+        root_bounds: Rect<i32>,
+        objects: impl Iterator<Item = T>,
+        split_threshold: usize,
+    ) -> Result<Self, QuadTreeBuildError>
+    where
+        T: BoundsProvider<i32, Id = I>,
+    {
+        if split_threshold == 0 {
+            return Err(QuadTreeBuildError::ZeroSplitThreshold);
+        }
+
+        let entries = objects
+            .filter_map(|o| {
+                let bounds = o.bounds();
+                intersection(&root_bounds, &bounds).map(|_| (o.id(), bounds))
+            })
+            .collect::<Vec<_>>();
+
+        let mut tree = Self {
+            nodes: vec![EMPTY_LEAF],
+            root_bounds,
+            split_threshold,
+            ..Default::default()
+        };
+        tree.build_node(0, root_bounds, &entries);
+        Ok(tree)
+    }
+
+    fn build_node(&mut self, node: usize, bounds: Rect<i32>, entries: &[(I, Rect<i32>)]) {
+        if entries.len() <= self.split_threshold || (bounds.w() < 2 && bounds.h() < 2) {
+            let first = self.ids.len();
+            self.ids.extend(entries.iter().map(|(id, _)| id.clone()));
+            self.entry_bounds
+                .extend(entries.iter().map(|(_, bounds)| *bounds));
+            self.nodes[node] = QuadTreeNode::leaf(first..self.ids.len());
+        } else {
+            let children = self.nodes.len();
+            self.nodes[node] = QuadTreeNode::branch(children, 0..0);
+            self.nodes.extend([EMPTY_LEAF; 4]);
+
+            for (i, child_bounds) in split_rect(&bounds).into_iter().enumerate() {
+                let child_entries = entries
+                    .iter()
+                    .filter(|(_, bounds)| intersection(&child_bounds, bounds).is_some())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                self.build_node(children + i, child_bounds, &child_entries);
+            }
+        }
+    }
+
+    /// Writes the ids of the entities, that contain the given cell, to the output storage.
+    pub fn point_query<S>(&self, point: Vector2<i32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+    {
+        if !contains_cell(&self.root_bounds, point) {
+            return;
+        }
+
+        let mut node = 0;
+        let mut bounds = self.root_bounds;
+        loop {
+            match self.nodes.get(node) {
+                Some(QuadTreeNode::Branch { children, .. }) => {
+                    for (i, child_bounds) in split_rect(&bounds).into_iter().enumerate() {
+                        if contains_cell(&child_bounds, point) {
+                            node = *children as usize + i;
+                            bounds = child_bounds;
+                            break;
+                        }
+                    }
+                }
+                Some(leaf @ QuadTreeNode::Leaf { .. }) => {
+                    for index in leaf.range() {
+                        if contains_cell(&self.entry_bounds[index], point)
+                            && storage.push_or_break(self.ids[index].clone()).is_break()
+                        {
+                            return;
+                        }
+                    }
+                    return;
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Writes the ids of the entities, that intersect the given rectangle, to the output storage.
+    /// Each entity is written exactly once, even if it is stored in multiple leaf nodes.
+    pub fn rect_query<S>(&self, rect: Rect<i32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+    {
+        let _ = self.rect_query_recursive(0, self.root_bounds, &rect, storage);
+    }
+
+    fn rect_query_recursive<S>(
+        &self,
+        node: usize,
+        bounds: Rect<i32>,
+        rect: &Rect<i32>,
+        storage: &mut S,
+    ) -> ControlFlow<()>
+    where
+        S: QueryStorage<Id = I>,
+    {
+        if intersection(&bounds, rect).is_none() {
+            return ControlFlow::Continue(());
+        }
+
+        match self.nodes.get(node) {
+            Some(QuadTreeNode::Branch { children, .. }) => {
+                for (i, child_bounds) in split_rect(&bounds).into_iter().enumerate() {
+                    self.rect_query_recursive(*children as usize + i, child_bounds, rect, storage)?;
+                }
+            }
+            Some(leaf @ QuadTreeNode::Leaf { .. }) => {
+                for index in leaf.range() {
+                    let Some(overlap) = intersection(&self.entry_bounds[index], rect)
+                        .and_then(|overlap| intersection(&overlap, &self.root_bounds))
+                    else {
+                        continue;
+                    };
+                    // Only the leaf that contains the top-left cell of the overlap reports the
+                    // entity, so it is reported once even if it is shared by many leaves.
+                    if contains_cell(&bounds, overlap.position) {
+                        storage.push_or_break(self.ids[index].clone())?;
+                    }
+                }
+            }
+            None => (),
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Visits every node of the tree in depth-first order (parents first) and passes its bounds,
+    /// its kind and the ids stored in it to the given closure.
+    pub fn visit<F>(&self, mut func: F)
+    where
+        F: FnMut(&Rect<i32>, NodeKind, &[I]),
+    {
+        self.visit_recursive(0, self.root_bounds, &mut func)
+    }
+
+    fn visit_recursive<F>(&self, node: usize, bounds: Rect<i32>, func: &mut F)
+    where
+        F: FnMut(&Rect<i32>, NodeKind, &[I]),
+    {
+        match self.nodes.get(node) {
+            Some(leaf @ QuadTreeNode::Leaf { .. }) => {
+                func(&bounds, NodeKind::Leaf, &self.ids[leaf.range()])
+            }
+            Some(QuadTreeNode::Branch { children, .. }) => {
+                func(&bounds, NodeKind::Branch, &[]);
+                for (i, child_bounds) in split_rect(&bounds).into_iter().enumerate() {
+                    self.visit_recursive(*children as usize + i, child_bounds, func);
+                }
+            }
+            None => (),
+        }
+    }
+
+    /// Returns the bounds of the root node of the tree.
+    pub fn root_bounds(&self) -> Rect<i32> {
+        self.root_bounds
+    }
+
+    /// Returns current split threshold, that was used to build the quad tree.
+    pub fn split_threshold(&self) -> usize {
+        self.split_threshold
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IntQuadTree;
+    use crate::{
+        quadtree::{BoundsProvider, NodeKind, QuadTreeBuildError},
+        Rect,
+    };
+    use nalgebra::Vector2;
+
+    struct Tile {
+        bounds: Rect<i32>,
+        id: usize,
+    }
+
+    impl BoundsProvider<i32> for &Tile {
+        type Id = usize;
+
+        fn bounds(&self) -> Rect<i32> {
+            self.bounds
+        }
+
+        fn id(&self) -> Self::Id {
+            self.id
+        }
+    }
+
+    #[test]
+    fn int_quad_tree_exact_cells() {
+        // Far from the origin, where f32 cannot represent every integer coordinate.
+        let origin = 1 << 25;
+        let tiles = (0..256)
+            .map(|id| Tile {
+                bounds: Rect::new(origin + (id % 16) as i32, origin + (id / 16) as i32, 1, 1),
+                id,
+            })
+            .collect::<Vec<_>>();
+        let tree = IntQuadTree::new(Rect::new(origin, origin, 16, 16), tiles.iter(), 1).unwrap();
+
+        let mut leaf_count = 0;
+        tree.visit(|bounds, kind, ids| {
+            if kind == NodeKind::Leaf {
+                leaf_count += 1;
+                assert_eq!(bounds.w() * bounds.h(), 1);
+                assert_eq!(ids.len(), 1);
+            }
+        });
+        assert_eq!(leaf_count, 256);
+
+        for tile in tiles.iter() {
+            let mut ids = Vec::new();
+            tree.point_query(tile.bounds.position, &mut ids);
+            assert_eq!(ids, vec![tile.id]);
+        }
+    }
+
+    #[test]
+    fn int_quad_tree_rect_query() {
+        let tiles = [
+            Tile {
+                bounds: Rect::new(0, 0, 2, 2),
+                id: 0,
+            },
+            // Spans many leaves.
+            Tile {
+                bounds: Rect::new(3, 3, 5, 4),
+                id: 1,
+            },
+            Tile {
+                bounds: Rect::new(8, 8, 1, 1),
+                id: 2,
+            },
+        ];
+        let tree = IntQuadTree::new(Rect::new(0, 0, 9, 9), tiles.iter(), 1).unwrap();
+
+        let mut ids = Vec::new();
+        tree.rect_query(Rect::new(0, 0, 9, 9), &mut ids);
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2]);
+
+        let mut ids = Vec::new();
+        tree.rect_query(Rect::new(2, 2, 2, 2), &mut ids);
+        assert_eq!(ids, vec![1]);
+
+        let mut ids = Vec::new();
+        tree.point_query(Vector2::new(2, 2), &mut ids);
+        assert!(ids.is_empty());
+
+        assert_eq!(
+            IntQuadTree::<usize>::new(Rect::new(0, 0, 9, 9), tiles.iter(), 0).err(),
+            Some(QuadTreeBuildError::ZeroSplitThreshold)
+        );
+    }
+}
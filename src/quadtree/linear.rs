@@ -0,0 +1,289 @@
+//! Linear (pointer-free) quad tree.
+
+use super::{BoundsProvider, QueryStorage};
+use crate::{
+    curve::{coarse_morton_ranges, morton_encode, quantize},
+    Rect,
+};
+use nalgebra::Vector2;
+use std::ops::{ControlFlow, RangeInclusive};
+
+/// Amount of levels of the cells: from single cells of the 65536x65536 grid to the root cell.
+const LEVELS: usize = 17;
+
+/// How many times the smallest cell, that contains a query, is split to cover the query. Deeper
+/// splits produce exponentially more ranges of codes, each of them costs a binary search, while
+/// the extra entities of the coarse cells are cheap to skip.
+const QUERY_SPLITS: u32 = 3;
+
+/// Static quad tree without nodes. The bounds of the tree are divided into a 65536x65536 grid and
+/// each entity is assigned to the smallest aligned quad tree cell, that contains the entity. The
+/// entities are stored in flat arrays sorted by the level of the cell and by the Morton code of
+/// the cell within the level, so the entities of any cell (and of any subtree) are adjacent.
+/// Queries are answered with binary searches over the ranges of the Morton codes of the cells,
+/// that intersect the query area.
+///
+/// It takes much less memory than [`super::QuadTree`] and has better cache locality, but it cannot
+/// be changed after it was built. Entities outside of the bounds of the tree are supported, but
+/// they are clamped to the cells on the border, which makes queries near the border slower.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinearQuadTree<I> {
+    bounds: Rect<f32>,
+    /// Morton codes of the cells of the entities within the levels.
+    keys: Vec<u32>,
+    entry_bounds: Vec<Rect<f32>>,
+    ids: Vec<I>,
+    /// Index of the first entity of each level, plus the amount of the entities.
+    level_starts: Vec<usize>,
+}
+
+impl<I> Default for LinearQuadTree<I> {
+    fn default() -> Self {
+        Self {
+            bounds: Default::default(),
+            keys: Default::default(),
+            entry_bounds: Default::default(),
+            ids: Default::default(),
+            level_starts: vec![0; LEVELS + 1],
+        }
+    }
+}
+
+/// Returns the quantized corners of the rectangle.
+fn quantize_rect(bounds: &Rect<f32>, rect: &Rect<f32>) -> (Vector2<u16>, Vector2<u16>) {
+    (
+        quantize(bounds, rect.left_top_corner()),
+        quantize(bounds, rect.right_bottom_corner()),
+    )
+}
+
+/// Returns the level and the Morton code of the smallest cell, that contains the rectangle.
+fn cell_of(min: Vector2<u16>, max: Vector2<u16>) -> (usize, u32) {
+    let difference = ((min.x ^ max.x) | (min.y ^ max.y)) as u32;
+    let level = (u32::BITS - difference.leading_zeros()) as usize;
+    (level, morton_encode(min.map(|c| shift(c, level))))
+}
+
+fn shift(coordinate: u16, level: usize) -> u16 {
+    (coordinate as u32 >> level) as u16
+}
+
+/// Returns the ranges of the Morton codes of the cells, that must be scanned to find the entities
+/// in the rectangle from `min` to `max` within a level. The ranges are coarse (see
+/// [`QUERY_SPLITS`]) and the ranges with small gaps between them are merged, so the query does
+/// only a few binary searches per level.
+fn query_ranges(min: Vector2<u16>, max: Vector2<u16>) -> Vec<RangeInclusive<u32>> {
+    let (query_level, _) = cell_of(min, max);
+    let min_level = (query_level as u32).saturating_sub(QUERY_SPLITS);
+    // Scanning a gap no larger than a cell of the minimum level is not slower than scanning one
+    // more partially covered cell.
+    let max_gap = 1u64 << (2 * min_level);
+    let mut merged: Vec<RangeInclusive<u32>> = Vec::new();
+    for range in coarse_morton_ranges(min, max, min_level) {
+        match merged.last_mut() {
+            Some(last) if *range.start() as u64 - *last.end() as u64 <= max_gap => {
+                *last = *last.start()..=*range.end();
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+impl<I> LinearQuadTree<I> {
+    /// Builds the tree with the given bounds from the given objects.
+    pub fn new<T>(bounds: Rect<f32>, objects: impl Iterator<Item = T>) -> Self
+    where
+        T: BoundsProvider<Id = I>,
+    {
+        let mut entries = objects
+            .map(|object| {
+                let entry_bounds = object.bounds();
+                let (min, max) = quantize_rect(&bounds, &entry_bounds);
+                (cell_of(min, max), entry_bounds, object.id())
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(cell, _, _)| *cell);
+
+        let mut level_starts = vec![0; LEVELS + 1];
+        for ((level, _), _, _) in entries.iter() {
+            level_starts[level + 1] += 1;
+        }
+        for level in 0..LEVELS {
+            level_starts[level + 1] += level_starts[level];
+        }
+
+        let mut tree = Self {
+            bounds,
+            keys: Vec::with_capacity(entries.len()),
+            entry_bounds: Vec::with_capacity(entries.len()),
+            ids: Vec::with_capacity(entries.len()),
+            level_starts,
+        };
+        for ((_, key), entry_bounds, id) in entries {
+            tree.keys.push(key);
+            tree.entry_bounds.push(entry_bounds);
+            tree.ids.push(id);
+        }
+        tree
+    }
+
+    /// Returns the bounds of the tree.
+    pub fn bounds(&self) -> Rect<f32> {
+        self.bounds
+    }
+
+    /// Returns the amount of entities in the tree.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns `true` if the tree has no entities.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    fn query<F>(
+        &self,
+        area: Rect<f32>,
+        filter: impl Fn(&Rect<f32>) -> bool,
+        mut func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let (min, max) = quantize_rect(&self.bounds, &area);
+        for level in 0..LEVELS {
+            let range = self.level_starts[level]..self.level_starts[level + 1];
+            if range.is_empty() {
+                continue;
+            }
+            let keys = &self.keys[range.clone()];
+            let ranges = query_ranges(min.map(|c| shift(c, level)), max.map(|c| shift(c, level)));
+            for codes in ranges {
+                let first = keys.partition_point(|key| key < codes.start());
+                for (offset, key) in keys[first..].iter().enumerate() {
+                    if key > codes.end() {
+                        break;
+                    }
+                    let index = range.start + first + offset;
+                    if filter(&self.entry_bounds[index]) {
+                        func(&self.ids[index])?;
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the ids of the entities, that intersect the given rectangle, to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.query(rect, |bounds| bounds.intersects(rect), func)
+    }
+
+    /// Passes the ids of the entities, that contain the given point, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn point_query_with<F>(&self, point: Vector2<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let area = Rect::new(point.x, point.y, 0.0, 0.0);
+        self.query(area, |bounds| bounds.contains(point), func)
+    }
+
+    /// Writes the ids of the entities, that intersect the given rectangle, to the output storage.
+    pub fn rect_query<S>(&self, rect: Rect<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.rect_query_with(rect, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the entities, that contain the given point, to the output storage.
+    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.point_query_with(point, |id| storage.push_or_break(id.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{curve::morton_ranges, test_utils::TestObject};
+
+    #[test]
+    fn linear_quad_tree_queries() {
+        let objects = (0..400)
+            .map(|id| TestObject {
+                bounds: Rect::new(
+                    (id * 37 % 101) as f32 * 10.0,
+                    (id * 53 % 97) as f32 * 10.0,
+                    (id % 7) as f32 * 15.0,
+                    (id % 5) as f32 * 20.0,
+                ),
+                id,
+            })
+            .chain([TestObject {
+                bounds: Rect::new(-50.0, -50.0, 20.0, 20.0),
+                id: 400,
+            }])
+            .collect::<Vec<_>>();
+        let tree = LinearQuadTree::new(Rect::new(0.0, 0.0, 1100.0, 1100.0), objects.iter());
+        assert_eq!(tree.len(), 401);
+
+        for query in [
+            Rect::new(100.0, 200.0, 150.0, 80.0),
+            Rect::new(-100.0, -100.0, 70.0, 70.0),
+            Rect::new(0.0, 0.0, 1100.0, 1100.0),
+        ] {
+            let mut found = Vec::new();
+            tree.rect_query(query, &mut found);
+            found.sort_unstable();
+            let expected = objects
+                .iter()
+                .filter(|object| object.bounds.intersects(query))
+                .map(|object| object.id)
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected);
+        }
+
+        let mut found = Vec::new();
+        tree.point_query(Vector2::new(505.0, 305.0), &mut found);
+        found.sort_unstable();
+        let expected = objects
+            .iter()
+            .filter(|object| object.bounds.contains(Vector2::new(505.0, 305.0)))
+            .map(|object| object.id)
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn linear_quad_tree_query_ranges() {
+        let bounds = Rect::new(0.0, 0.0, 10000.0, 10000.0);
+        let (min, max) = quantize_rect(&bounds, &Rect::new(1234.0, 5678.0, 150.0, 80.0));
+        // The exact decomposition takes more than a thousand ranges at the finest level, the
+        // coarse one takes a few ranges at every level.
+        assert!(morton_ranges(min, max).len() > 1000);
+        for level in 0..LEVELS {
+            let (min, max) = (min.map(|c| shift(c, level)), max.map(|c| shift(c, level)));
+            let ranges = query_ranges(min, max);
+            assert!(ranges.len() <= 4);
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    let code = morton_encode(Vector2::new(x, y));
+                    assert!(ranges.iter().any(|range| range.contains(&code)));
+                }
+            }
+        }
+    }
+}
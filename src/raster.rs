@@ -0,0 +1,153 @@
+//! Conservative rasterization of rectangles onto a uniform grid of square cells, with the fraction
+//! of each cell, that is covered by the rectangles, and the visible ranges of tile maps.
+
+use crate::Rect;
+use nalgebra::Vector2;
+use std::{collections::HashMap, ops::ControlFlow, ops::Range};
+
+/// Returns the range of the cells, that overlap the given span along one axis. A span of zero
+/// length still occupies the cell it lies in.
+fn cell_range(min: f32, max: f32, cell_size: f32) -> Range<i32> {
+    // The casts saturate, so the spans far away share the outermost cells.
+    let start = (min / cell_size).floor() as i32;
+    let end = (max / cell_size).ceil() as i32;
+    start..end.max(start.saturating_add(1))
+}
+
+/// Returns the fraction of the cell with the given index, that is covered by the span.
+fn coverage(min: f32, max: f32, cell: i32, cell_size: f32) -> f32 {
+    let cell_min = cell as f32 * cell_size;
+    let cell_max = cell_min + cell_size;
+    ((max.min(cell_max) - min.max(cell_min)) / cell_size).max(0.0)
+}
+
+/// Passes every cell, that is overlapped by the rectangle, to the given closure along with the
+/// covered fraction of the cell, in range `0.0..=1.0`. Cells are identified by their column and
+/// row, the cell `(0, 0)` starts at the origin. The rasterization is conservative: every cell, that
+/// the rectangle touches, is reported, even if the rectangle has zero width or height and covers
+/// nothing. The closure may stop the iteration by returning [`ControlFlow::Break`].
+pub fn rect_coverage_with<F>(rect: Rect<f32>, cell_size: f32, mut func: F) -> ControlFlow<()>
+where
+    F: FnMut(Vector2<i32>, f32) -> ControlFlow<()>,
+{
+    let (x0, x1) = (rect.x(), rect.x() + rect.w());
+    let (y0, y1) = (rect.y(), rect.y() + rect.h());
+    let columns = cell_range(x0, x1, cell_size);
+    for row in cell_range(y0, y1, cell_size) {
+        let row_coverage = coverage(y0, y1, row, cell_size);
+        for column in columns.clone() {
+            let cell_coverage = coverage(x0, x1, column, cell_size) * row_coverage;
+            func(Vector2::new(column, row), cell_coverage)?;
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+/// Rasterizes the rectangles and returns the covered fraction of every overlapped cell. See
+/// [`rect_coverage_with`] for details. The fractions of overlapping rectangles are summed, so a
+/// cell could be covered more than once.
+pub fn rects_coverage<R>(rects: R, cell_size: f32) -> HashMap<Vector2<i32>, f32>
+where
+    R: IntoIterator<Item = Rect<f32>>,
+{
+    let mut cells = HashMap::new();
+    for rect in rects {
+        let _ = rect_coverage_with(rect, cell_size, |cell, coverage| {
+            *cells.entry(cell).or_insert(0.0) += coverage;
+            ControlFlow::Continue(())
+        });
+    }
+    cells
+}
+
+/// Returns the range of the tiles, that must be drawn to fill the view, clipped by the bounds of
+/// the tile map (in tiles). The position of the returned rectangle is the first visible tile and
+/// its size is the number of visible tiles along each axis, partially visible tiles included.
+/// Tile `(0, 0)` starts at the origin, so the tiles at negative coordinates have negative indices.
+/// The size is zero along both axes if the view does not overlap the map.
+pub fn visible_tile_range(
+    view: Rect<f32>,
+    tile_size: Vector2<f32>,
+    map_bounds: Rect<i32>,
+) -> Rect<i32> {
+    let columns = cell_range(view.x(), view.x() + view.w(), tile_size.x);
+    let rows = cell_range(view.y(), view.y() + view.h(), tile_size.y);
+    let clip = |range: Range<i32>, start: i32, len: i32| {
+        let end = start.saturating_add(len);
+        let first = range.start.clamp(start, end);
+        (first, range.end.clamp(first, end) - first)
+    };
+    let (x, w) = clip(columns, map_bounds.x(), map_bounds.w());
+    let (y, h) = clip(rows, map_bounds.y(), map_bounds.h());
+    if w == 0 || h == 0 {
+        Rect::new(x, y, 0, 0)
+    } else {
+        Rect::new(x, y, w, h)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rect_coverage_with, rects_coverage, visible_tile_range};
+    use crate::Rect;
+    use nalgebra::Vector2;
+    use std::ops::ControlFlow;
+
+    #[test]
+    fn rasterize_rects() {
+        let mut cells = Vec::new();
+        let _ = rect_coverage_with(Rect::new(-5.0, 5.0, 15.0, 5.0), 10.0, |cell, coverage| {
+            cells.push((cell, coverage));
+            ControlFlow::Continue(())
+        });
+        assert_eq!(
+            cells,
+            vec![(Vector2::new(-1, 0), 0.25), (Vector2::new(0, 0), 0.5)]
+        );
+
+        // A line touches the cells, but covers nothing.
+        cells.clear();
+        let _ = rect_coverage_with(Rect::new(1.0, 1.0, 0.0, 15.0), 10.0, |cell, coverage| {
+            cells.push((cell, coverage));
+            ControlFlow::Continue(())
+        });
+        assert_eq!(
+            cells,
+            vec![(Vector2::new(0, 0), 0.0), (Vector2::new(0, 1), 0.0)]
+        );
+
+        let coverage = rects_coverage(
+            [
+                Rect::new(0.0, 0.0, 20.0, 10.0),
+                Rect::new(15.0, 0.0, 5.0, 5.0),
+            ],
+            10.0,
+        );
+        assert_eq!(coverage.len(), 2);
+        assert_eq!(coverage[&Vector2::new(0, 0)], 1.0);
+        assert_eq!(coverage[&Vector2::new(1, 0)], 1.25);
+    }
+
+    #[test]
+    fn visible_tiles() {
+        let tile = Vector2::new(16.0, 8.0);
+        let map = Rect::new(-10, -10, 20, 20);
+        assert_eq!(
+            visible_tile_range(Rect::new(-20.0, 4.0, 40.0, 8.0), tile, map),
+            Rect::new(-2, 0, 4, 2)
+        );
+        assert_eq!(
+            visible_tile_range(Rect::new(0.0, 0.0, 32.0, 16.0), tile, map),
+            Rect::new(0, 0, 2, 2)
+        );
+        // Clipped by the map and entirely outside of it.
+        assert_eq!(
+            visible_tile_range(Rect::new(-1000.0, -1.0, 1000.0, 1.0), tile, map),
+            Rect::new(-10, -1, 10, 1)
+        );
+        assert_eq!(
+            visible_tile_range(Rect::new(1000.0, 0.0, 10.0, 10.0), tile, map).size,
+            Vector2::new(0, 0)
+        );
+    }
+}
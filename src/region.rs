@@ -0,0 +1,564 @@
+//! Regions of arbitrary rectilinear shape, kept in the canonical banded form, and the set
+//! operations on them.
+
+use crate::{
+    decompose::{rects_from_mask, sort_and_dedup},
+    Number, Rect,
+};
+use nalgebra::Vector2;
+
+/// Horizontal band of a region: the spans `(left, right)` are sorted, do not overlap and do not
+/// touch each other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Band<T> {
+    top: T,
+    bottom: T,
+    spans: Vec<(T, T)>,
+}
+
+/// An area of arbitrary rectilinear shape, for example a visible part of a window or a damaged
+/// area of the screen. The region is stored in the canonical banded form: a sequence of
+/// non-overlapping horizontal bands, sorted from the top to the bottom, each split into sorted
+/// non-touching spans, and the neighbouring bands, that touch each other, always have different
+/// spans. Every area has exactly one such form, so equal areas compare equal.
+///
+/// The region includes the left and top edges of its rectangles, but not the right and bottom
+/// ones, so the regions of the rectangles, that only touch each other, do not intersect.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Region<T> {
+    bands: Vec<Band<T>>,
+}
+
+/// Combines two sets of spans of a band, the operation tells whether a point, that is inside of
+/// the first and/or the second set, is inside of the result.
+fn combine_spans<T, F>(a: &[(T, T)], b: &[(T, T)], op: &F) -> Vec<(T, T)>
+where
+    T: Number,
+    F: Fn(bool, bool) -> bool,
+{
+    let mut xs = a
+        .iter()
+        .chain(b)
+        .flat_map(|(left, right)| [*left, *right])
+        .collect::<Vec<_>>();
+    sort_and_dedup(&mut xs);
+    let mut spans = Vec::<(T, T)>::new();
+    let (mut i, mut j) = (0, 0);
+    for pair in xs.windows(2) {
+        let (left, right) = (pair[0], pair[1]);
+        while i < a.len() && a[i].1 <= left {
+            i += 1;
+        }
+        while j < b.len() && b[j].1 <= left {
+            j += 1;
+        }
+        let in_a = i < a.len() && a[i].0 <= left;
+        let in_b = j < b.len() && b[j].0 <= left;
+        if op(in_a, in_b) {
+            match spans.last_mut() {
+                Some(last) if last.1 == left => last.1 = right,
+                _ => spans.push((left, right)),
+            }
+        }
+    }
+    spans
+}
+
+/// Returns the spans of the band at the index, if it covers the row, or no spans otherwise.
+fn spans_at<T: Number>(bands: &[Band<T>], index: usize, row: T) -> &[(T, T)] {
+    match bands.get(index) {
+        Some(band) if band.top <= row => &band.spans,
+        _ => &[],
+    }
+}
+
+impl<T> Region<T>
+where
+    T: Number,
+{
+    /// Creates new empty region.
+    pub fn new() -> Self {
+        Self {
+            bands: Default::default(),
+        }
+    }
+
+    /// Creates new region, that covers the rectangle. Empty rectangles give empty regions.
+    pub fn from_rect(rect: Rect<T>) -> Self {
+        Self::from_rects([rect])
+    }
+
+    /// Creates new region, that covers the union of the rectangles.
+    pub fn from_rects(rects: impl IntoIterator<Item = Rect<T>>) -> Self {
+        let rects = rects
+            .into_iter()
+            .filter(|rect| rect.w() > T::zero() && rect.h() > T::zero())
+            .collect::<Vec<_>>();
+        let mut rows = rects
+            .iter()
+            .flat_map(|rect| [rect.y(), rect.y() + rect.h()])
+            .collect::<Vec<_>>();
+        sort_and_dedup(&mut rows);
+
+        let mut region = Self::new();
+        let mut spans = Vec::new();
+        for pair in rows.windows(2) {
+            let (top, bottom) = (pair[0], pair[1]);
+            spans.clear();
+            spans.extend(
+                rects
+                    .iter()
+                    .filter(|rect| rect.y() <= top && rect.y() + rect.h() >= bottom)
+                    .map(|rect| (rect.x(), rect.x() + rect.w())),
+            );
+            spans.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            let mut merged = Vec::<(T, T)>::new();
+            for (left, right) in spans.iter().copied() {
+                match merged.last_mut() {
+                    Some(last) if left <= last.1 => {
+                        if right > last.1 {
+                            last.1 = right;
+                        }
+                    }
+                    _ => merged.push((left, right)),
+                }
+            }
+            region.push_band(top, bottom, merged);
+        }
+        region
+    }
+
+    /// Appends the band to the bottom of the region, coalescing it with the last band if
+    /// possible.
+    fn push_band(&mut self, top: T, bottom: T, spans: Vec<(T, T)>) {
+        if spans.is_empty() {
+            return;
+        }
+        match self.bands.last_mut() {
+            Some(last) if last.bottom == top && last.spans == spans => last.bottom = bottom,
+            _ => self.bands.push(Band { top, bottom, spans }),
+        }
+    }
+
+    /// Combines two regions band by band, the operation tells whether a point, that is inside of
+    /// the first and/or the second region, is inside of the result.
+    fn combine<F>(&self, other: &Self, op: F) -> Self
+    where
+        F: Fn(bool, bool) -> bool,
+    {
+        let mut rows = self
+            .bands
+            .iter()
+            .chain(other.bands.iter())
+            .flat_map(|band| [band.top, band.bottom])
+            .collect::<Vec<_>>();
+        sort_and_dedup(&mut rows);
+
+        let mut region = Self::new();
+        let (mut i, mut j) = (0, 0);
+        for pair in rows.windows(2) {
+            let (top, bottom) = (pair[0], pair[1]);
+            while i < self.bands.len() && self.bands[i].bottom <= top {
+                i += 1;
+            }
+            while j < other.bands.len() && other.bands[j].bottom <= top {
+                j += 1;
+            }
+            let spans = combine_spans(
+                spans_at(&self.bands, i, top),
+                spans_at(&other.bands, j, top),
+                &op,
+            );
+            region.push_band(top, bottom, spans);
+        }
+        region
+    }
+
+    /// Returns `true` if the region covers nothing.
+    pub fn is_empty(&self) -> bool {
+        self.bands.is_empty()
+    }
+
+    /// Returns the rectangles of the region in the canonical order: from the top to the bottom
+    /// and from the left to the right. The rectangles do not overlap.
+    pub fn rects(&self) -> impl Iterator<Item = Rect<T>> + '_ {
+        self.bands.iter().flat_map(|band| {
+            band.spans.iter().map(move |(left, right)| {
+                Rect::new(*left, band.top, *right - *left, band.bottom - band.top)
+            })
+        })
+    }
+
+    /// Returns the bounding rectangle of the region, or `None` if the region is empty.
+    pub fn bounds(&self) -> Option<Rect<T>> {
+        let (first, last) = (self.bands.first()?, self.bands.last()?);
+        let mut left = first.spans[0].0;
+        let mut right = first.spans[first.spans.len() - 1].1;
+        for band in self.bands.iter() {
+            if band.spans[0].0 < left {
+                left = band.spans[0].0;
+            }
+            if band.spans[band.spans.len() - 1].1 > right {
+                right = band.spans[band.spans.len() - 1].1;
+            }
+        }
+        Some(Rect::new(
+            left,
+            first.top,
+            right - left,
+            last.bottom - first.top,
+        ))
+    }
+
+    /// Returns the region, that covers both this and the other region.
+    pub fn union(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a || b)
+    }
+
+    /// Returns the region, that is covered by both this and the other region.
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a && b)
+    }
+
+    /// Returns the part of this region, that is not covered by the other region.
+    pub fn subtract(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a && !b)
+    }
+
+    /// Returns the region moved by the offset.
+    pub fn translate(&self, offset: Vector2<T>) -> Self {
+        Self {
+            bands: self
+                .bands
+                .iter()
+                .map(|band| Band {
+                    top: band.top + offset.y,
+                    bottom: band.bottom + offset.y,
+                    spans: band
+                        .spans
+                        .iter()
+                        .map(|(left, right)| (*left + offset.x, *right + offset.x))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the region scaled relative to the origin by the given factors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the factors is not positive.
+    pub fn scale(&self, factor: Vector2<T>) -> Self {
+        assert!(factor.x > T::zero() && factor.y > T::zero());
+        Self {
+            bands: self
+                .bands
+                .iter()
+                .map(|band| Band {
+                    top: band.top * factor.y,
+                    bottom: band.bottom * factor.y,
+                    spans: band
+                        .spans
+                        .iter()
+                        .map(|(left, right)| (*left * factor.x, *right * factor.x))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the part of the region inside of the rectangle.
+    pub fn clip_to(&self, rect: Rect<T>) -> Self {
+        let (left, right) = (rect.x(), rect.x() + rect.w());
+        let (top, bottom) = (rect.y(), rect.y() + rect.h());
+        let max = |a: T, b: T| if a > b { a } else { b };
+        let min = |a: T, b: T| if a < b { a } else { b };
+        let mut region = Self::new();
+        for band in self.bands.iter() {
+            let (band_top, band_bottom) = (max(band.top, top), min(band.bottom, bottom));
+            if band_top >= band_bottom {
+                continue;
+            }
+            let spans = band
+                .spans
+                .iter()
+                .map(|(span_left, span_right)| (max(*span_left, left), min(*span_right, right)))
+                .filter(|(span_left, span_right)| span_left < span_right)
+                .collect();
+            region.push_band(band_top, band_bottom, spans);
+        }
+        region
+    }
+
+    /// Builds the index of the region for fast point queries, see [`RegionIndex`].
+    pub fn build_index(&self) -> RegionIndex<T> {
+        let mut bands = Vec::with_capacity(self.bands.len());
+        let mut spans = Vec::new();
+        for band in self.bands.iter() {
+            bands.push((band.top, band.bottom, spans.len()));
+            spans.extend_from_slice(&band.spans);
+        }
+        RegionIndex { bands, spans }
+    }
+
+    /// Checks if the point is inside of the region. It checks every band, so for many queries
+    /// against the same region use [`Self::build_index`].
+    pub fn contains(&self, point: Vector2<T>) -> bool {
+        self.bands.iter().any(|band| {
+            band.top <= point.y
+                && point.y < band.bottom
+                && band
+                    .spans
+                    .iter()
+                    .any(|(left, right)| *left <= point.x && point.x < *right)
+        })
+    }
+
+    /// Checks if the region covers the entire rectangle. Empty rectangles are always covered.
+    pub fn contains_rect(&self, rect: Rect<T>) -> bool {
+        Self::from_rect(rect).subtract(self).is_empty()
+    }
+
+    /// Checks if the region covers any part of the rectangle. Rectangles, that only touch the
+    /// region, do not intersect it.
+    pub fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        let (left, right) = (rect.x(), rect.x() + rect.w());
+        let (top, bottom) = (rect.y(), rect.y() + rect.h());
+        left < right
+            && top < bottom
+            && self.bands.iter().any(|band| {
+                band.top < bottom
+                    && top < band.bottom
+                    && band
+                        .spans
+                        .iter()
+                        .any(|(span_left, span_right)| *span_left < right && left < *span_right)
+            })
+    }
+}
+
+impl Region<i32> {
+    /// Returns the runs of the region on each row of pixels as `(y, x_start, x_end)`, the end is
+    /// excluded. The runs go from the top to the bottom and from the left to the right, so a
+    /// software rasterizer could fill the region directly.
+    pub fn spans(&self) -> impl Iterator<Item = (i32, i32, i32)> + '_ {
+        self.bands.iter().flat_map(|band| {
+            (band.top..band.bottom).flat_map(move |y| {
+                band.spans
+                    .iter()
+                    .map(move |(left, right)| (y, *left, *right))
+            })
+        })
+    }
+
+    /// Rasterizes the region into a mask of cells of the given size, that cover the bounds. The
+    /// mask is stored row by row, it has `ceil(bounds.w / cell_size.x)` cells in a row and
+    /// `ceil(bounds.h / cell_size.y)` rows. A cell is set, if any part of it is covered by the
+    /// region.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell size is not positive.
+    pub fn to_mask(&self, bounds: Rect<i32>, cell_size: Vector2<i32>) -> Vec<bool> {
+        assert!(cell_size.x > 0 && cell_size.y > 0);
+        let (columns, rows) = mask_size(bounds, cell_size);
+        let mut mask = vec![false; columns as usize * rows as usize];
+        // Range of the cells, that overlap the given range of coordinates.
+        let cells = |min: i32, max: i32, origin: i32, size: i32, count: i32| {
+            let first = (min - origin).div_euclid(size).clamp(0, count);
+            let last = -(origin - max).div_euclid(size);
+            first..last.clamp(first, count)
+        };
+        for band in self.bands.iter() {
+            for row in cells(band.top, band.bottom, bounds.y(), cell_size.y, rows) {
+                for (left, right) in band.spans.iter() {
+                    let start = (row * columns) as usize;
+                    let range = cells(*left, *right, bounds.x(), cell_size.x, columns);
+                    mask[start + range.start as usize..start + range.end as usize].fill(true);
+                }
+            }
+        }
+        mask
+    }
+
+    /// Creates new region from the mask of cells, that is laid out as in [`Self::to_mask`]. The
+    /// set cells are merged into rectangles with [`rects_from_mask`], and the cells on the right
+    /// and bottom edges are clipped by the bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell size is not positive or if the mask has a wrong size.
+    pub fn from_mask(bounds: Rect<i32>, cell_size: Vector2<i32>, mask: &[bool]) -> Self {
+        assert!(cell_size.x > 0 && cell_size.y > 0);
+        let (columns, rows) = mask_size(bounds, cell_size);
+        assert_eq!(mask.len(), columns as usize * rows as usize);
+        let (right, bottom) = (bounds.x() + bounds.w(), bounds.y() + bounds.h());
+        Self::from_rects(
+            rects_from_mask(columns, rows, |x, y| mask[(y * columns + x) as usize])
+                .into_iter()
+                .map(|cells| {
+                    let x = bounds.x() + cells.x() * cell_size.x;
+                    let y = bounds.y() + cells.y() * cell_size.y;
+                    let w = (cells.w() * cell_size.x).min(right - x);
+                    let h = (cells.h() * cell_size.y).min(bottom - y);
+                    Rect::new(x, y, w, h)
+                }),
+        )
+    }
+}
+
+/// Returns the amount of columns and rows of a mask of cells of the given size, that covers the
+/// bounds.
+fn mask_size(bounds: Rect<i32>, cell_size: Vector2<i32>) -> (i32, i32) {
+    let count = |len: i32, size: i32| (len.max(0) + size - 1) / size;
+    (
+        count(bounds.w(), cell_size.x),
+        count(bounds.h(), cell_size.y),
+    )
+}
+
+/// Flattened copy of a [`Region`], that answers point queries in logarithmic time, by binary search
+/// over the bands and then over the spans of the band. The index does not track the changes of the
+/// region, so it has to be rebuilt after the region is changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegionIndex<T> {
+    /// Top, bottom and the index of the first span of each band.
+    bands: Vec<(T, T, usize)>,
+    spans: Vec<(T, T)>,
+}
+
+impl<T> RegionIndex<T>
+where
+    T: Number,
+{
+    /// Checks if the point is inside of the indexed region.
+    pub fn contains(&self, point: Vector2<T>) -> bool {
+        let band = self
+            .bands
+            .partition_point(|(_, bottom, _)| *bottom <= point.y);
+        let Some((top, _, first)) = self.bands.get(band) else {
+            return false;
+        };
+        if *top > point.y {
+            return false;
+        }
+        let end = self
+            .bands
+            .get(band + 1)
+            .map_or(self.spans.len(), |(_, _, first)| *first);
+        let spans = &self.spans[*first..end];
+        let span = spans.partition_point(|(_, right)| *right <= point.x);
+        spans.get(span).is_some_and(|(left, _)| *left <= point.x)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Region;
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn region_operations() {
+        let window = Region::from_rect(Rect::new(0, 0, 100, 100));
+        let popup = Region::from_rects([Rect::new(20, 20, 30, 30), Rect::new(40, 40, 30, 30)]);
+        assert_eq!(
+            popup.rects().collect::<Vec<_>>(),
+            [
+                Rect::new(20, 20, 30, 20),
+                Rect::new(20, 40, 50, 10),
+                Rect::new(40, 50, 30, 20)
+            ]
+        );
+        assert_eq!(popup.bounds(), Some(Rect::new(20, 20, 50, 50)));
+
+        let visible = window.subtract(&popup);
+        assert_eq!(visible.rects().count(), 8);
+        assert!(visible.contains(Vector2::new(10, 10)));
+        assert!(!visible.contains(Vector2::new(45, 45)));
+        assert!(visible.contains_rect(Rect::new(0, 0, 100, 20)));
+        assert!(!visible.contains_rect(Rect::new(0, 0, 100, 21)));
+        assert!(visible.intersects_rect(Rect::new(60, 20, 10, 10)));
+        assert!(!visible.intersects_rect(Rect::new(30, 30, 10, 10)));
+        assert!(!visible.intersects_rect(Rect::new(100, 0, 10, 10)));
+
+        // The canonical form does not depend on how the region was built.
+        assert_eq!(visible.union(&popup), window);
+        assert_eq!(visible.intersection(&popup), Region::new());
+        assert!(window.subtract(&window).is_empty());
+
+        let spans = Region::from_rects([Rect::new(0, 0, 2, 2), Rect::new(1, 1, 2, 2)])
+            .spans()
+            .collect::<Vec<_>>();
+        assert_eq!(spans, [(0, 0, 2), (1, 0, 3), (2, 1, 3)]);
+    }
+
+    #[test]
+    fn region_index() {
+        let region = Region::from_rects([
+            Rect::new(0, 0, 10, 10),
+            Rect::new(20, 0, 10, 10),
+            Rect::new(5, 20, 10, 5),
+        ]);
+        let index = region.build_index();
+        for y in -2..30 {
+            for x in -2..35 {
+                let point = Vector2::new(x, y);
+                assert_eq!(index.contains(point), region.contains(point));
+            }
+        }
+        assert!(!Region::new().build_index().contains(Vector2::new(0, 0)));
+    }
+
+    #[test]
+    fn region_mask() {
+        let region = Region::from_rects([Rect::new(0, 0, 4, 4), Rect::new(6, 5, 1, 2)]);
+        let bounds = Rect::new(-2, 0, 10, 7);
+        let mask = region.to_mask(bounds, Vector2::new(3, 3));
+        #[rustfmt::skip]
+        assert_eq!(
+            mask,
+            [
+                true, true, false, false,
+                true, true, true, false,
+                false, false, true, false,
+            ]
+        );
+        assert_eq!(
+            Region::from_mask(bounds, Vector2::new(3, 3), &mask),
+            Region::from_rects([Rect::new(-2, 0, 6, 6), Rect::new(4, 3, 3, 4)])
+        );
+
+        // With single pixel cells the conversion is exact.
+        let bounds = Rect::new(0, 0, 8, 8);
+        let mask = region.to_mask(bounds, Vector2::new(1, 1));
+        assert_eq!(mask.iter().filter(|set| **set).count(), 18);
+        assert_eq!(Region::from_mask(bounds, Vector2::new(1, 1), &mask), region);
+    }
+
+    #[test]
+    fn region_transforms() {
+        let region = Region::from_rects([Rect::new(0, 0, 4, 2), Rect::new(0, 2, 2, 2)]);
+        assert_eq!(
+            region.translate(Vector2::new(-1, 3)),
+            Region::from_rects([Rect::new(-1, 3, 4, 2), Rect::new(-1, 5, 2, 2)])
+        );
+        assert_eq!(
+            region.scale(Vector2::new(2, 3)),
+            Region::from_rects([Rect::new(0, 0, 8, 6), Rect::new(0, 6, 4, 6)])
+        );
+
+        // Clipping away the wider part leaves a single band.
+        let clipped = region.clip_to(Rect::new(1, 1, 1, 10));
+        assert_eq!(clipped.rects().collect::<Vec<_>>(), [Rect::new(1, 1, 1, 3)]);
+        assert_eq!(
+            region.clip_to(Rect::new(-5, 1, 20, 2)),
+            region.intersection(&Region::from_rect(Rect::new(-5, 1, 20, 2)))
+        );
+        assert!(region.clip_to(Rect::new(4, 0, 5, 5)).is_empty());
+    }
+}
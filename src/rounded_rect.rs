@@ -0,0 +1,165 @@
+//! Rectangle with rounded corners.
+
+use crate::{Number, Rect};
+use nalgebra::{RealField, Vector2};
+
+/// A rectangle with rounded corners, each corner has its own radius. The radii are in the order of
+/// the corners: left top, right top, right bottom, left bottom. A radius larger than half of the
+/// shortest side of the rectangle is treated as half of that side.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct RoundedRect<T> {
+    /// Bounds of the rectangle.
+    pub rect: Rect<T>,
+    /// Radii of the corners: left top, right top, right bottom, left bottom.
+    pub radii: [T; 4],
+}
+
+impl<T> RoundedRect<T>
+where
+    T: Number + RealField,
+{
+    /// Creates new rounded rectangle with the given radii of the corners: left top, right top,
+    /// right bottom, left bottom.
+    #[inline]
+    pub fn new(rect: Rect<T>, radii: [T; 4]) -> Self {
+        Self { rect, radii }
+    }
+
+    /// Creates new rounded rectangle with the same radius at every corner.
+    #[inline]
+    pub fn uniform(rect: Rect<T>, radius: T) -> Self {
+        Self {
+            rect,
+            radii: [radius; 4],
+        }
+    }
+
+    /// Returns the bounding rectangle.
+    #[inline]
+    pub fn bounding_rect(&self) -> Rect<T> {
+        self.rect
+    }
+
+    /// Returns the radius of the corner with the given index, clamped to half of the shortest side
+    /// of the rectangle.
+    #[inline]
+    fn radius(&self, corner: usize) -> T {
+        let two = T::one() + T::one();
+        let limit = self.rect.w().min(self.rect.h()) / two;
+        self.radii[corner].max(T::zero()).min(limit)
+    }
+
+    /// Returns the corner, that is the closest to the point: left top, right top, right bottom or
+    /// left bottom.
+    #[inline]
+    fn corner(&self, point: Vector2<T>) -> usize {
+        let center = self.rect.center();
+        match (point.x >= center.x, point.y >= center.y) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+            (false, true) => 3,
+        }
+    }
+
+    /// Returns the center of the arc of the corner with the given index.
+    #[inline]
+    fn arc_center(&self, corner: usize) -> Vector2<T> {
+        let radius = self.radius(corner);
+        let min = self.rect.left_top_corner();
+        let max = self.rect.right_bottom_corner();
+        match corner {
+            0 => Vector2::new(min.x + radius, min.y + radius),
+            1 => Vector2::new(max.x - radius, min.y + radius),
+            2 => Vector2::new(max.x - radius, max.y - radius),
+            _ => Vector2::new(min.x + radius, max.y - radius),
+        }
+    }
+
+    /// Returns the signed distance from the point to the border of the rectangle: negative inside
+    /// of the rectangle, positive outside and zero on the border.
+    pub fn signed_distance(&self, point: Vector2<T>) -> T {
+        let two = T::one() + T::one();
+        let radius = self.radius(self.corner(point));
+        let offset = point - self.rect.center();
+        let q = Vector2::new(
+            offset.x.abs() - self.rect.w() / two + radius,
+            offset.y.abs() - self.rect.h() / two + radius,
+        );
+        let outside = Vector2::new(q.x.max(T::zero()), q.y.max(T::zero())).norm();
+        let inside = q.x.max(q.y).min(T::zero());
+        outside + inside - radius
+    }
+
+    /// Checks if the given point lies within the rectangle, the border included.
+    #[inline]
+    pub fn contains(&self, point: Vector2<T>) -> bool {
+        self.signed_distance(point) <= T::zero()
+    }
+
+    /// Checks if the rounded rectangle intersects with the given rectangle. As with
+    /// [`Rect::intersects`], rectangles, that only touch each other, do not intersect.
+    pub fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        if !self.rect.intersects(rect) {
+            return false;
+        }
+        let Some(clipped) = *rect.clip_by(self.rect) else {
+            return false;
+        };
+        let min = clipped.left_top_corner();
+        let max = clipped.right_bottom_corner();
+        // The rectangles could miss each other only if the clipped part lies entirely in the
+        // square of one corner, outside of its arc.
+        for corner in 0..4 {
+            let center = self.arc_center(corner);
+            let in_square = match corner {
+                0 => max.x <= center.x && max.y <= center.y,
+                1 => min.x >= center.x && max.y <= center.y,
+                2 => min.x >= center.x && min.y >= center.y,
+                _ => max.x <= center.x && min.y >= center.y,
+            };
+            if in_square {
+                let closest =
+                    Vector2::new(center.x.clamp(min.x, max.x), center.y.clamp(min.y, max.y));
+                return (closest - center).norm() < self.radius(corner);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RoundedRect;
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn rounded_rect_hit_test() {
+        let rect = RoundedRect::new(Rect::new(0.0, 0.0, 100.0, 50.0), [10.0, 0.0, 20.0, 100.0]);
+        assert!(rect.contains(Vector2::new(50.0, 25.0)));
+        // The left top corner is cut, the right top one is sharp.
+        assert!(!rect.contains(Vector2::new(1.0, 1.0)));
+        assert!(rect.contains(Vector2::new(99.0, 1.0)));
+        assert_eq!(rect.signed_distance(Vector2::new(50.0, 0.0)), 0.0);
+        assert_eq!(rect.signed_distance(Vector2::new(50.0, 10.0)), -10.0);
+        assert_eq!(rect.signed_distance(Vector2::new(110.0, 25.0)), 10.0);
+        // The radius of the left bottom corner is clamped to 25.
+        let d = rect.signed_distance(Vector2::new(0.0, 50.0));
+        assert!((d - (25.0 * 2.0f32.sqrt() - 25.0)).abs() < 1.0e-4);
+
+        assert!(!rect.intersects_rect(Rect::new(-5.0, -5.0, 7.0, 7.0)));
+        assert!(rect.intersects_rect(Rect::new(-5.0, -5.0, 9.0, 9.0)));
+        assert!(rect.intersects_rect(Rect::new(95.0, -5.0, 10.0, 10.0)));
+        assert!(rect.intersects_rect(Rect::new(-5.0, 20.0, 10.0, 10.0)));
+        assert!(!rect.intersects_rect(Rect::new(100.0, 0.0, 10.0, 10.0)));
+    }
+}
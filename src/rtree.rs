@@ -0,0 +1,732 @@
+//! R*-tree is a balanced tree of bounding rectangles, that is used for fast spatial queries over a
+//! set of objects, which changes over time.
+
+use crate::{
+    geometry::{area, contains_rect, union},
+    quadtree::{BoundsProvider, QueryStorage},
+    Rect,
+};
+use nalgebra::Vector2;
+use std::{cmp::Ordering, ops::ControlFlow};
+
+/// Maximum amount of children of a node.
+const MAX_CHILDREN: usize = 16;
+
+/// Minimum amount of children of a node (except the root), 40% of the maximum as recommended by
+/// the authors of the R*-tree.
+const MIN_CHILDREN: usize = 6;
+
+/// Amount of children, that are reinserted when a node overflows for the first time during an
+/// insertion (30% of the maximum).
+const REINSERT_COUNT: usize = 5;
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Entry<I> {
+    bounds: Rect<f32>,
+    id: I,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Children<I> {
+    Leaf(Vec<Entry<I>>),
+    Branch(Vec<Node<I>>),
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Node<I> {
+    bounds: Rect<f32>,
+    children: Children<I>,
+}
+
+/// An entry or a whole subtree, that is inserted at a specific level of the tree.
+enum Item<I> {
+    Entry(Entry<I>),
+    Node(Node<I>),
+}
+
+trait HasBounds {
+    fn bounds(&self) -> Rect<f32>;
+}
+
+impl<I> HasBounds for Entry<I> {
+    fn bounds(&self) -> Rect<f32> {
+        self.bounds
+    }
+}
+
+impl<I> HasBounds for Node<I> {
+    fn bounds(&self) -> Rect<f32> {
+        self.bounds
+    }
+}
+
+impl<I> HasBounds for Item<I> {
+    fn bounds(&self) -> Rect<f32> {
+        match self {
+            Item::Entry(entry) => entry.bounds,
+            Item::Node(node) => node.bounds,
+        }
+    }
+}
+
+fn margin(rect: &Rect<f32>) -> f32 {
+    rect.w() + rect.h()
+}
+
+fn overlap(a: &Rect<f32>, b: &Rect<f32>) -> f32 {
+    let w = (a.x() + a.w()).min(b.x() + b.w()) - a.x().max(b.x());
+    let h = (a.y() + a.h()).min(b.y() + b.h()) - a.y().max(b.y());
+    w.max(0.0) * h.max(0.0)
+}
+
+fn bounds_of<C: HasBounds>(children: &[C]) -> Rect<f32> {
+    let mut iter = children.iter();
+    match iter.next() {
+        Some(first) => iter.fold(first.bounds(), |bounds, child| {
+            union(&bounds, &child.bounds())
+        }),
+        None => Default::default(),
+    }
+}
+
+fn compare(a: f32, b: f32) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+/// Splits the overflowing children in two groups by the R*-tree split algorithm: the axis is
+/// chosen by the smallest sum of the margins of the groups, the split position along the axis is
+/// chosen by the smallest overlap of the groups (and then by the smallest area). Returns the
+/// second group.
+fn split_children<C: HasBounds>(children: &mut Vec<C>) -> Vec<C> {
+    let bounds = children.iter().map(|c| c.bounds()).collect::<Vec<_>>();
+    let sorted = |key: &dyn Fn(&Rect<f32>) -> f32| {
+        let mut order = (0..bounds.len()).collect::<Vec<_>>();
+        order.sort_by(|a, b| compare(key(&bounds[*a]), key(&bounds[*b])));
+        order
+    };
+    let groups = |order: &[usize], split: usize| {
+        let first = order[..split].iter().map(|i| bounds[*i]);
+        let second = order[split..].iter().map(|i| bounds[*i]);
+        (
+            first.reduce(|a, b| union(&a, &b)).unwrap_or_default(),
+            second.reduce(|a, b| union(&a, &b)).unwrap_or_default(),
+        )
+    };
+    let splits = MIN_CHILDREN..=bounds.len() - MIN_CHILDREN;
+
+    let mut best_axis: Option<(f32, [Vec<usize>; 2])> = None;
+    for axis in 0..2 {
+        let orders = [
+            sorted(&|r: &Rect<f32>| r.position[axis]),
+            sorted(&|r: &Rect<f32>| r.position[axis] + r.size[axis]),
+        ];
+        let mut margins = 0.0;
+        for order in orders.iter() {
+            for split in splits.clone() {
+                let (a, b) = groups(order, split);
+                margins += margin(&a) + margin(&b);
+            }
+        }
+        if best_axis.as_ref().map_or(true, |(best, _)| margins < *best) {
+            best_axis = Some((margins, orders));
+        }
+    }
+
+    let (_, orders) = best_axis.expect("there are two axes");
+    let mut best: Option<([f32; 2], usize, usize)> = None;
+    for (o, order) in orders.iter().enumerate() {
+        for split in splits.clone() {
+            let (a, b) = groups(order, split);
+            let cost = [overlap(&a, &b), area(&a) + area(&b)];
+            if best.map_or(true, |(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, o, split));
+            }
+        }
+    }
+
+    let (_, o, split) = best.expect("an overflowing node has enough children to split");
+    let mut slots = children.drain(..).map(Some).collect::<Vec<_>>();
+    children.extend(orders[o].iter().filter_map(|i| slots[*i].take()));
+    children.split_off(split)
+}
+
+/// Removes the children, whose centers are the farthest from the given center, and returns them
+/// sorted from the farthest to the closest.
+fn take_farthest<C: HasBounds>(children: &mut Vec<C>, center: Vector2<f32>) -> Vec<C> {
+    let distance = |child: &C| (child.bounds().center() - center).norm_squared();
+    children.sort_by(|a, b| compare(distance(a), distance(b)));
+    let mut farthest = children.split_off(children.len() - REINSERT_COUNT);
+    farthest.reverse();
+    farthest
+}
+
+/// Chooses the child node to insert the rectangle in. For the nodes, whose children are leaves,
+/// it is the child, that needs the smallest enlargement of the overlap with its siblings, for
+/// the other nodes it is the child, that needs the smallest enlargement of its area.
+fn choose_subtree<I>(nodes: &[Node<I>], rect: &Rect<f32>, leaves: bool) -> usize {
+    let mut best: Option<([f32; 3], usize)> = None;
+    for (i, node) in nodes.iter().enumerate() {
+        let enlarged = union(&node.bounds, rect);
+        let area_cost = area(&enlarged) - area(&node.bounds);
+        let cost = if leaves {
+            let overlap_cost = nodes
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| {
+                    overlap(&enlarged, &other.bounds) - overlap(&node.bounds, &other.bounds)
+                })
+                .sum::<f32>();
+            [overlap_cost, area_cost, area(&node.bounds)]
+        } else {
+            [area_cost, area(&node.bounds), 0.0]
+        };
+        if best.map_or(true, |(best_cost, _)| cost < best_cost) {
+            best = Some((cost, i));
+        }
+    }
+    best.map_or(0, |(_, i)| i)
+}
+
+impl<I> Node<I> {
+    fn empty_leaf() -> Self {
+        Self {
+            bounds: Default::default(),
+            children: Children::Leaf(Vec::new()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &self.children {
+            Children::Leaf(entries) => entries.len(),
+            Children::Branch(nodes) => nodes.len(),
+        }
+    }
+
+    fn update_bounds(&mut self) {
+        self.bounds = match &self.children {
+            Children::Leaf(entries) => bounds_of(entries),
+            Children::Branch(nodes) => bounds_of(nodes),
+        };
+    }
+
+    /// Splits the node in two, keeps the first half and returns the second one.
+    fn split(&mut self) -> Node<I> {
+        let children = match &mut self.children {
+            Children::Leaf(entries) => Children::Leaf(split_children(entries)),
+            Children::Branch(nodes) => Children::Branch(split_children(nodes)),
+        };
+        self.update_bounds();
+        let mut sibling = Node {
+            bounds: Default::default(),
+            children,
+        };
+        sibling.update_bounds();
+        sibling
+    }
+
+    fn into_items(self) -> Vec<Item<I>> {
+        match self.children {
+            Children::Leaf(entries) => entries.into_iter().map(Item::Entry).collect(),
+            Children::Branch(nodes) => nodes.into_iter().map(Item::Node).collect(),
+        }
+    }
+
+    fn collect_entries(self, entries: &mut Vec<Entry<I>>) {
+        match self.children {
+            Children::Leaf(leaf) => entries.extend(leaf),
+            Children::Branch(nodes) => {
+                for node in nodes {
+                    node.collect_entries(entries);
+                }
+            }
+        }
+    }
+
+    /// Inserts the item in the subtree at the given level (leaves are at level zero). Returns the
+    /// new sibling of the node, if the node was split.
+    fn insert(
+        &mut self,
+        level: usize,
+        item: Item<I>,
+        target_level: usize,
+        is_root: bool,
+        reinserted: &mut [bool],
+        pending: &mut Vec<(Item<I>, usize)>,
+    ) -> Option<Node<I>> {
+        if level == target_level {
+            match (&mut self.children, item) {
+                (Children::Leaf(entries), Item::Entry(entry)) => entries.push(entry),
+                (Children::Branch(nodes), Item::Node(node)) => nodes.push(node),
+                _ => unreachable!("items are inserted at the level of their siblings"),
+            }
+        } else {
+            let Children::Branch(nodes) = &mut self.children else {
+                unreachable!("nodes above the leaf level are branches");
+            };
+            let index = choose_subtree(nodes, &item.bounds(), level == 1);
+            if let Some(sibling) =
+                nodes[index].insert(level - 1, item, target_level, false, reinserted, pending)
+            {
+                nodes.push(sibling);
+            }
+        }
+        self.update_bounds();
+
+        if self.len() <= MAX_CHILDREN {
+            return None;
+        }
+        if is_root || reinserted[level] {
+            return Some(self.split());
+        }
+        // Forced reinsertion: the first overflow on each level moves the farthest children to
+        // other nodes, which often avoids the split and makes the tree better balanced.
+        reinserted[level] = true;
+        let center = self.bounds.center();
+        match &mut self.children {
+            Children::Leaf(entries) => pending.extend(
+                take_farthest(entries, center)
+                    .into_iter()
+                    .map(|entry| (Item::Entry(entry), level)),
+            ),
+            Children::Branch(nodes) => pending.extend(
+                take_farthest(nodes, center)
+                    .into_iter()
+                    .map(|node| (Item::Node(node), level)),
+            ),
+        }
+        self.update_bounds();
+        None
+    }
+
+    /// Removes the entry from the subtree at the given level. Nodes, that became underfull, are
+    /// removed too, their children are added to the orphans to be reinserted.
+    fn remove(
+        &mut self,
+        level: usize,
+        bounds: &Rect<f32>,
+        id: &I,
+        orphans: &mut Vec<(Item<I>, usize)>,
+    ) -> bool
+    where
+        I: PartialEq,
+    {
+        match &mut self.children {
+            Children::Leaf(entries) => match entries.iter().position(|entry| entry.id == *id) {
+                Some(index) => {
+                    entries.swap_remove(index);
+                }
+                None => return false,
+            },
+            Children::Branch(nodes) => {
+                let Some(index) = (0..nodes.len()).find(|i| {
+                    contains_rect(&nodes[*i].bounds, bounds)
+                        && nodes[*i].remove(level - 1, bounds, id, orphans)
+                }) else {
+                    return false;
+                };
+                if nodes[index].len() < MIN_CHILDREN {
+                    let node = nodes.swap_remove(index);
+                    orphans.extend(node.into_items().into_iter().map(|item| (item, level - 1)));
+                }
+            }
+        }
+        self.update_bounds();
+        true
+    }
+
+    fn query<I2>(
+        &self,
+        filter: &dyn Fn(&Rect<f32>) -> bool,
+        func: &mut dyn FnMut(&I) -> ControlFlow<I2>,
+    ) -> ControlFlow<I2> {
+        match &self.children {
+            Children::Leaf(entries) => {
+                for entry in entries {
+                    if filter(&entry.bounds) {
+                        func(&entry.id)?;
+                    }
+                }
+            }
+            Children::Branch(nodes) => {
+                for node in nodes {
+                    if filter(&node.bounds) {
+                        node.query(filter, func)?;
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Sort-tile-recursive packing of the children into the nodes of the next level. The children
+/// are distributed evenly, so every node gets at least a half of the maximum amount of children.
+fn pack_level<C, I>(mut children: Vec<C>, make: fn(Vec<C>) -> Children<I>) -> Vec<Node<I>>
+where
+    C: HasBounds,
+{
+    let total = children.len();
+    let node_count = (total + MAX_CHILDREN - 1) / MAX_CHILDREN;
+    let slab_count = (node_count as f32).sqrt().ceil() as usize;
+    // Index of the first child of each node in the order sorted by X.
+    let first_child = |node: usize| total * node / node_count;
+    let center = |child: &C, axis: usize| child.bounds().center()[axis];
+    children.sort_by(|a, b| compare(center(a, 0), center(b, 0)));
+
+    let mut nodes = Vec::with_capacity(node_count);
+    let mut rest = children.into_iter();
+    for slab in 0..slab_count {
+        let first_node = node_count * slab / slab_count;
+        let last_node = node_count * (slab + 1) / slab_count;
+        let start = first_child(first_node);
+        let mut slab = rest
+            .by_ref()
+            .take(first_child(last_node) - start)
+            .collect::<Vec<_>>();
+        slab.sort_by(|a, b| compare(center(a, 1), center(b, 1)));
+        let mut slab = slab.into_iter();
+        for node in first_node..last_node {
+            let count = first_child(node + 1) - first_child(node);
+            let mut node = Node {
+                bounds: Default::default(),
+                children: make(slab.by_ref().take(count).collect()),
+            };
+            node.update_bounds();
+            nodes.push(node);
+        }
+    }
+    nodes
+}
+
+/// R*-tree with dynamic insertion and deletion of the objects. Unlike the quad tree, the R*-tree
+/// does not need the bounds of the world in advance, every object is stored exactly once, and
+/// the tree stays balanced under any sequence of updates: overflowing nodes reinsert a part of
+/// their children or split, underfull nodes are dissolved and their children are reinserted.
+///
+/// Use [`Self::bulk_load`] to build the tree from a large set of objects at once, it is much
+/// faster than inserting them one by one and gives a better tree.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RTree<I> {
+    root: Node<I>,
+    /// Level of the root node, leaves are at level zero.
+    height: usize,
+    len: usize,
+}
+
+impl<I> Default for RTree<I> {
+    fn default() -> Self {
+        Self {
+            root: Node::empty_leaf(),
+            height: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<I> RTree<I> {
+    /// Creates new empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the tree from the given objects using sort-tile-recursive packing, which fills the
+    /// nodes completely and keeps them from overlapping for evenly distributed objects.
+    pub fn bulk_load<T>(objects: impl Iterator<Item = T>) -> Self
+    where
+        T: BoundsProvider<Id = I>,
+    {
+        let entries = objects
+            .map(|object| Entry {
+                bounds: object.bounds(),
+                id: object.id(),
+            })
+            .collect::<Vec<_>>();
+        let len = entries.len();
+        if len == 0 {
+            return Self::default();
+        }
+        let mut nodes = pack_level(entries, Children::Leaf);
+        let mut height = 0;
+        while nodes.len() > 1 {
+            nodes = pack_level(nodes, Children::Branch);
+            height += 1;
+        }
+        Self {
+            root: nodes.pop().expect("there is at least one node"),
+            height,
+            len,
+        }
+    }
+
+    /// Returns the amount of objects in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the tree has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the length of the path from the root to the leaves, zero means that the root is a
+    /// leaf.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the bounds of all the objects in the tree, or None if the tree is empty.
+    pub fn bounds(&self) -> Option<Rect<f32>> {
+        (!self.is_empty()).then_some(self.root.bounds)
+    }
+
+    /// Adds the object to the tree.
+    pub fn insert<T>(&mut self, object: T)
+    where
+        T: BoundsProvider<Id = I>,
+    {
+        let entry = Entry {
+            bounds: object.bounds(),
+            id: object.id(),
+        };
+        self.insert_item(Item::Entry(entry), 0);
+        self.len += 1;
+    }
+
+    fn insert_item(&mut self, item: Item<I>, level: usize) {
+        let mut reinserted = vec![false; self.height + 1];
+        let mut pending = vec![(item, level)];
+        while let Some((item, level)) = pending.pop() {
+            let sibling = self.root.insert(
+                self.height,
+                item,
+                level,
+                true,
+                &mut reinserted,
+                &mut pending,
+            );
+            if let Some(sibling) = sibling {
+                let root = std::mem::replace(&mut self.root, Node::empty_leaf());
+                self.root = Node {
+                    bounds: union(&root.bounds, &sibling.bounds),
+                    children: Children::Branch(vec![root, sibling]),
+                };
+                self.height += 1;
+                reinserted.push(false);
+            }
+        }
+    }
+
+    /// Removes the object from the tree. The object must have the same bounds, as it had when it
+    /// was added to the tree. Returns `true` if the object was found in the tree.
+    pub fn remove<T>(&mut self, object: T) -> bool
+    where
+        T: BoundsProvider<Id = I>,
+        I: PartialEq,
+    {
+        let bounds = object.bounds();
+        let mut orphans = Vec::new();
+        if !self
+            .root
+            .remove(self.height, &bounds, &object.id(), &mut orphans)
+        {
+            return false;
+        }
+        self.len -= 1;
+
+        while self.height > 0 && self.root.len() <= 1 {
+            let root = std::mem::replace(&mut self.root, Node::empty_leaf());
+            if let Children::Branch(mut nodes) = root.children {
+                if let Some(node) = nodes.pop() {
+                    self.root = node;
+                }
+            }
+            self.height -= 1;
+        }
+        for (item, level) in orphans {
+            match item {
+                // The tree became lower than the subtree, so its entries are inserted one by one.
+                Item::Node(node) if level > self.height => {
+                    let mut entries = Vec::new();
+                    node.collect_entries(&mut entries);
+                    for entry in entries {
+                        self.insert_item(Item::Entry(entry), 0);
+                    }
+                }
+                item => self.insert_item(item, level),
+            }
+        }
+        true
+    }
+
+    /// Removes all the objects from the tree.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Passes the ids of the objects, that intersect the given rectangle, to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        if self.is_empty() {
+            return ControlFlow::Continue(());
+        }
+        self.root
+            .query(&|bounds| bounds.intersects(rect), &mut func)
+    }
+
+    /// Passes the ids of the objects, that contain the given point, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn point_query_with<F>(&self, point: Vector2<f32>, mut func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        if self.is_empty() {
+            return ControlFlow::Continue(());
+        }
+        self.root.query(&|bounds| bounds.contains(point), &mut func)
+    }
+
+    /// Writes the ids of the objects, that intersect the given rectangle, to the output storage.
+    pub fn rect_query<S>(&self, rect: Rect<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.rect_query_with(rect, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the objects, that contain the given point, to the output storage.
+    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.point_query_with(point, |id| storage.push_or_break(id.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Children, Node, RTree, MAX_CHILDREN, MIN_CHILDREN};
+    use crate::{test_utils::TestObject, Rect};
+
+    fn objects(count: usize) -> Vec<TestObject> {
+        // Simple deterministic pseudo-random layout.
+        let mut seed = 12345u32;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as f32 / 65536.0
+        };
+        (0..count)
+            .map(|id| TestObject {
+                bounds: Rect::new(
+                    next() * 1000.0,
+                    next() * 1000.0,
+                    next() * 20.0,
+                    next() * 20.0,
+                ),
+                id,
+            })
+            .collect()
+    }
+
+    /// Checks that the nodes are filled properly, their bounds are tight and all the leaves are
+    /// at the same level. Returns the amount of entries in the subtree.
+    fn check_node(node: &Node<usize>, level: usize, is_root: bool) -> usize {
+        assert!(node.len() <= MAX_CHILDREN);
+        assert!(is_root || node.len() >= MIN_CHILDREN);
+        let mut expected = node.clone();
+        expected.update_bounds();
+        assert_eq!(expected.bounds, node.bounds);
+        match &node.children {
+            Children::Leaf(entries) => {
+                assert_eq!(level, 0);
+                entries.len()
+            }
+            Children::Branch(nodes) => nodes
+                .iter()
+                .map(|child| check_node(child, level - 1, false))
+                .sum(),
+        }
+    }
+
+    fn check_queries(tree: &RTree<usize>, objects: &[TestObject], alive: &[bool]) {
+        assert_eq!(check_node(&tree.root, tree.height, true), tree.len());
+        for query in [
+            Rect::new(100.0, 100.0, 200.0, 150.0),
+            Rect::new(500.0, 0.0, 30.0, 1000.0),
+        ] {
+            let mut found = Vec::new();
+            tree.rect_query(query, &mut found);
+            found.sort_unstable();
+            let expected = objects
+                .iter()
+                .filter(|object| alive[object.id] && object.bounds.intersects(query))
+                .map(|object| object.id)
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn rtree_insert_remove() {
+        let objects = objects(1000);
+        let mut alive = vec![true; objects.len()];
+        let mut tree = RTree::new();
+        for object in objects.iter() {
+            tree.insert(object);
+        }
+        assert_eq!(tree.len(), 1000);
+        assert!(tree.height() >= 2);
+        check_queries(&tree, &objects, &alive);
+
+        for object in objects.iter().filter(|object| object.id % 3 != 0) {
+            assert!(tree.remove(object));
+            alive[object.id] = false;
+        }
+        assert!(!tree.remove(&objects[1]));
+        check_queries(&tree, &objects, &alive);
+
+        let mut found = Vec::new();
+        let object = &objects[300];
+        tree.point_query(object.bounds.center(), &mut found);
+        assert!(found.contains(&300));
+
+        for object in objects.iter().filter(|object| object.id % 3 == 0) {
+            assert!(tree.remove(object));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.height(), 0);
+        assert_eq!(tree.bounds(), None);
+    }
+
+    #[test]
+    fn rtree_bulk_load() {
+        let objects = objects(500);
+        let mut alive = vec![true; objects.len()];
+        let mut tree = RTree::bulk_load(objects.iter());
+        assert_eq!(tree.len(), 500);
+        check_queries(&tree, &objects, &alive);
+
+        // Bulk loaded tree could be updated as any other.
+        for object in objects.iter().take(100) {
+            assert!(tree.remove(object));
+            alive[object.id] = false;
+        }
+        tree.insert(&objects[0]);
+        alive[0] = true;
+        check_queries(&tree, &objects, &alive);
+        assert_eq!(
+            RTree::bulk_load(objects.iter().take(1)).bounds(),
+            Some(objects[0].bounds)
+        );
+        assert!(RTree::<usize>::bulk_load(std::iter::empty::<&TestObject>()).is_empty());
+    }
+}
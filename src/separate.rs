@@ -0,0 +1,148 @@
+//! Separation of overlapping rectangles, such as labels on a map or nodes of a graph.
+
+use crate::Rect;
+use nalgebra::Vector2;
+
+/// Returns the pairs of indices of the rectangles, that overlap each other.
+fn overlapping_pairs(rects: &[Rect<f32>]) -> Vec<(usize, usize)> {
+    let mut order = (0..rects.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| rects[a].x().total_cmp(&rects[b].x()));
+    let mut pairs = Vec::new();
+    for (position, &index) in order.iter().enumerate() {
+        let right = rects[index].x() + rects[index].w();
+        for &other in order[position + 1..].iter() {
+            if rects[other].x() >= right {
+                break;
+            }
+            if rects[index].intersects(rects[other]) {
+                pairs.push((index, other));
+            }
+        }
+    }
+    pairs
+}
+
+/// Returns the offset of the second rectangle, that is needed to push it out of the first one
+/// along the axis of the smallest penetration.
+fn separation(a: &Rect<f32>, b: &Rect<f32>, a_index: usize, b_index: usize) -> Vector2<f32> {
+    let penetration = |min_a: f32, size_a: f32, min_b: f32, size_b: f32| {
+        let center_a = min_a + size_a * 0.5;
+        let center_b = min_b + size_b * 0.5;
+        // Rectangles with the same centers are pushed apart in the order of their indices.
+        if center_b > center_a || (center_b == center_a && b_index > a_index) {
+            (min_a + size_a - min_b).max(0.0)
+        } else {
+            -(min_b + size_b - min_a).max(0.0)
+        }
+    };
+    let x = penetration(a.x(), a.w(), b.x(), b.w());
+    let y = penetration(a.y(), a.h(), b.y(), b.h());
+    if x.abs() <= y.abs() {
+        Vector2::new(x, 0.0)
+    } else {
+        Vector2::new(0.0, y)
+    }
+}
+
+/// Moves the rectangle inside of the bounds. A rectangle larger than the bounds is aligned with
+/// the left top corner of the bounds.
+fn keep_inside(rect: &mut Rect<f32>, bounds: &Rect<f32>) {
+    let x = rect
+        .x()
+        .min(bounds.x() + bounds.w() - rect.w())
+        .max(bounds.x());
+    let y = rect
+        .y()
+        .min(bounds.y() + bounds.h() - rect.h())
+        .max(bounds.y());
+    rect.position = Vector2::new(x, y);
+}
+
+/// Iteratively pushes the overlapping rectangles apart, until none of them overlap or the given
+/// amount of iterations is reached. On each iteration, every overlapping pair is separated along
+/// the axis of the smallest penetration and each rectangle of the pair moves by half of it (or
+/// further, if the other one is stopped by the bounds), so the rectangles move as little as
+/// possible. Rectangles, that only touch each other, do not overlap.
+/// If the bounds are given, the rectangles are kept inside of them, which may make the overlaps
+/// impossible to resolve, if there is not enough space.
+///
+/// Returns `true` if no rectangles overlap in the end.
+pub fn separate_rects(
+    rects: &mut [Rect<f32>],
+    bounds: Option<Rect<f32>>,
+    max_iterations: usize,
+) -> bool {
+    if let Some(bounds) = bounds {
+        for rect in rects.iter_mut() {
+            keep_inside(rect, &bounds);
+        }
+    }
+    for _ in 0..max_iterations {
+        let pairs = overlapping_pairs(rects);
+        if pairs.is_empty() {
+            return true;
+        }
+        for (a, b) in pairs {
+            let offset = separation(&rects[a], &rects[b], a, b);
+            let position = rects[a].position;
+            rects[a].position -= offset * 0.5;
+            if let Some(bounds) = bounds {
+                keep_inside(&mut rects[a], &bounds);
+            }
+            // If the first rectangle is stopped by the bounds, the second one moves further.
+            rects[b].position += offset - (position - rects[a].position);
+            if let Some(bounds) = bounds {
+                keep_inside(&mut rects[b], &bounds);
+            }
+        }
+    }
+    overlapping_pairs(rects).is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::separate_rects;
+    use crate::Rect;
+
+    #[test]
+    fn separate_labels() {
+        let mut rects = [
+            Rect::new(0.0, 0.0, 10.0, 4.0),
+            Rect::new(8.0, 1.0, 10.0, 4.0),
+            Rect::new(100.0, 0.0, 10.0, 4.0),
+        ];
+        assert!(separate_rects(&mut rects, None, 10));
+        // The smallest penetration is along the X axis, both labels move by a half of it.
+        assert_eq!(rects[0], Rect::new(-1.0, 0.0, 10.0, 4.0));
+        assert_eq!(rects[1], Rect::new(9.0, 1.0, 10.0, 4.0));
+        assert_eq!(rects[2], Rect::new(100.0, 0.0, 10.0, 4.0));
+
+        // Crowded labels spread out, but stay inside of the bounds.
+        let bounds = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let mut rects = [
+            Rect::new(40.0, 40.0, 10.0, 10.0),
+            Rect::new(42.0, 41.0, 12.0, 8.0),
+            Rect::new(44.0, 47.0, 10.0, 10.0),
+            Rect::new(38.0, 45.0, 8.0, 10.0),
+            Rect::new(45.0, 43.0, 20.0, 5.0),
+            Rect::new(0.0, 0.0, 10.0, 10.0),
+            Rect::new(0.0, 0.0, 10.0, 10.0),
+            Rect::new(3.0, 2.0, 10.0, 10.0),
+        ];
+        assert!(separate_rects(&mut rects, Some(bounds), 100));
+        for (i, a) in rects.iter().enumerate() {
+            assert!(bounds.contains(a.position) && bounds.contains(a.right_bottom_corner()));
+            for b in rects[i + 1..].iter() {
+                assert!(!a.intersects(*b));
+            }
+        }
+
+        // Not enough space.
+        let mut rects = [Rect::new(0.0, 0.0, 10.0, 10.0); 3];
+        assert!(!separate_rects(
+            &mut rects,
+            Some(Rect::new(0.0, 0.0, 20.0, 10.0)),
+            100
+        ));
+    }
+}
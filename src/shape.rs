@@ -0,0 +1,362 @@
+//! Simple two-dimensional shapes and the [`Shape2`] trait, that unifies them with rectangles.
+
+use crate::{clip::clip_segment_to_rect, obb::Obb, rounded_rect::RoundedRect, Number, Rect};
+use nalgebra::{RealField, Vector2};
+
+/// A common interface of two-dimensional shapes. The bounds allow to put the shapes in spatial
+/// structures (such as [`crate::quadtree::QuadTree`]), while the other methods give precise
+/// answers for the candidates, that were found by the structures.
+pub trait Shape2<T> {
+    /// Returns the smallest axis-aligned rectangle, that contains the shape.
+    fn bounds(&self) -> Rect<T>;
+
+    /// Checks if the given point lies within the shape, the border included.
+    fn contains(&self, point: Vector2<T>) -> bool;
+
+    /// Checks if the shape intersects with the given rectangle. As with [`Rect::intersects`],
+    /// shapes, that only touch the rectangle, do not intersect it.
+    fn intersects_rect(&self, rect: Rect<T>) -> bool;
+}
+
+/// A circle defined by its center and radius.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Circle<T> {
+    /// Center of the circle.
+    pub center: Vector2<T>,
+    /// Radius of the circle.
+    pub radius: T,
+}
+
+impl<T> Circle<T>
+where
+    T: Number,
+{
+    /// Creates new circle from its center and radius.
+    #[inline]
+    pub fn new(center: Vector2<T>, radius: T) -> Self {
+        Self { center, radius }
+    }
+}
+
+/// An axis-aligned ellipse defined by its center and radii along X and Y axes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Ellipse<T> {
+    /// Center of the ellipse.
+    pub center: Vector2<T>,
+    /// Radii of the ellipse along X and Y axes.
+    pub radii: Vector2<T>,
+}
+
+impl<T> Ellipse<T>
+where
+    T: Number,
+{
+    /// Creates new ellipse from its center and radii.
+    #[inline]
+    pub fn new(center: Vector2<T>, radii: Vector2<T>) -> Self {
+        Self { center, radii }
+    }
+
+    /// Creates the ellipse, that is inscribed in the given rectangle.
+    #[inline]
+    pub fn inscribed(rect: Rect<T>) -> Self {
+        let two = T::one() + T::one();
+        Self {
+            center: rect.center(),
+            radii: Vector2::new(rect.w() / two, rect.h() / two),
+        }
+    }
+}
+
+/// A capsule (stadium) is a set of points within the given radius from a segment. It is the usual
+/// shape of character controllers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: nalgebra::Scalar + serde::Serialize",
+        deserialize = "T: nalgebra::Scalar + serde::Deserialize<'de>"
+    ))
+)]
+pub struct Capsule<T> {
+    /// The first end of the segment.
+    pub a: Vector2<T>,
+    /// The second end of the segment.
+    pub b: Vector2<T>,
+    /// Radius of the capsule.
+    pub radius: T,
+}
+
+impl<T> Capsule<T>
+where
+    T: Number + RealField,
+{
+    /// Creates new capsule from the ends of its segment and its radius.
+    #[inline]
+    pub fn new(a: Vector2<T>, b: Vector2<T>, radius: T) -> Self {
+        Self { a, b, radius }
+    }
+
+    /// Creates new vertical capsule, that fits into the given rectangle, the radius of the capsule
+    /// is half of the width of the rectangle.
+    #[inline]
+    pub fn vertical(rect: Rect<T>) -> Self {
+        let two = T::one() + T::one();
+        let radius = rect.w() / two;
+        let x = rect.x() + radius;
+        let (top, bottom) = (rect.y() + radius, rect.y() + rect.h() - radius);
+        let (top, bottom) = if top <= bottom {
+            (top, bottom)
+        } else {
+            let middle = rect.y() + rect.h() / two;
+            (middle, middle)
+        };
+        Self {
+            a: Vector2::new(x, top),
+            b: Vector2::new(x, bottom),
+            radius,
+        }
+    }
+
+    /// Returns the squared distance from the point to the segment of the capsule.
+    fn segment_distance_squared(&self, point: Vector2<T>) -> T {
+        let direction = self.b - self.a;
+        let length_squared = direction.norm_squared();
+        let t = if length_squared > T::zero() {
+            ((point - self.a).dot(&direction) / length_squared).clamp(T::zero(), T::one())
+        } else {
+            T::zero()
+        };
+        (self.a + direction * t - point).norm_squared()
+    }
+
+    /// Returns the distance between the segment of the capsule and the rectangle, zero if the
+    /// segment crosses the rectangle.
+    pub fn segment_distance_to_rect(&self, rect: &Rect<T>) -> T {
+        if clip_segment_to_rect(self.a, self.b, *rect).is_some() {
+            return T::zero();
+        }
+        let rect_distance_squared = |point: Vector2<T>| {
+            let x = point.x.clamp(rect.x(), rect.x() + rect.w());
+            let y = point.y.clamp(rect.y(), rect.y() + rect.h());
+            (point - Vector2::new(x, y)).norm_squared()
+        };
+        [
+            rect.left_top_corner(),
+            rect.right_top_corner(),
+            rect.right_bottom_corner(),
+            rect.left_bottom_corner(),
+        ]
+        .into_iter()
+        .map(|corner| self.segment_distance_squared(corner))
+        .chain([rect_distance_squared(self.a), rect_distance_squared(self.b)])
+        .fold(T::max_value().unwrap_or_else(T::one), |a, b| a.min(b))
+        .sqrt()
+    }
+}
+
+impl<T> Shape2<T> for Rect<T>
+where
+    T: Number,
+{
+    fn bounds(&self) -> Rect<T> {
+        *self
+    }
+
+    fn contains(&self, point: Vector2<T>) -> bool {
+        Rect::contains(self, point)
+    }
+
+    fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        self.intersects(rect)
+    }
+}
+
+impl<T> Shape2<T> for Circle<T>
+where
+    T: Number,
+{
+    fn bounds(&self) -> Rect<T> {
+        Rect::new(
+            self.center.x - self.radius,
+            self.center.y - self.radius,
+            self.radius + self.radius,
+            self.radius + self.radius,
+        )
+    }
+
+    fn contains(&self, point: Vector2<T>) -> bool {
+        let offset = point - self.center;
+        offset.x * offset.x + offset.y * offset.y <= self.radius * self.radius
+    }
+
+    fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        rect.intersects_circle(self.center, self.radius)
+    }
+}
+
+impl<T> Shape2<T> for Ellipse<T>
+where
+    T: Number,
+{
+    fn bounds(&self) -> Rect<T> {
+        Rect::new(
+            self.center.x - self.radii.x,
+            self.center.y - self.radii.y,
+            self.radii.x + self.radii.x,
+            self.radii.y + self.radii.y,
+        )
+    }
+
+    fn contains(&self, point: Vector2<T>) -> bool {
+        let x = (point.x - self.center.x) / self.radii.x;
+        let y = (point.y - self.center.y) / self.radii.y;
+        x * x + y * y <= T::one()
+    }
+
+    fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        // Scaling the space by the inverse radii turns the ellipse into a unit circle and keeps the
+        // rectangle axis-aligned.
+        let scaled = Rect::new(
+            (rect.x() - self.center.x) / self.radii.x,
+            (rect.y() - self.center.y) / self.radii.y,
+            rect.w() / self.radii.x,
+            rect.h() / self.radii.y,
+        );
+        scaled.intersects_circle(Vector2::new(T::zero(), T::zero()), T::one())
+    }
+}
+
+impl<T> Shape2<T> for Capsule<T>
+where
+    T: Number + RealField,
+{
+    fn bounds(&self) -> Rect<T> {
+        let min = Vector2::new(self.a.x.min(self.b.x), self.a.y.min(self.b.y));
+        let max = Vector2::new(self.a.x.max(self.b.x), self.a.y.max(self.b.y));
+        let two = T::one() + T::one();
+        Rect::new(
+            min.x - self.radius,
+            min.y - self.radius,
+            max.x - min.x + self.radius * two,
+            max.y - min.y + self.radius * two,
+        )
+    }
+
+    fn contains(&self, point: Vector2<T>) -> bool {
+        self.segment_distance_squared(point) <= self.radius * self.radius
+    }
+
+    fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        self.segment_distance_to_rect(&rect) < self.radius
+    }
+}
+
+impl<T> Shape2<T> for Obb<T>
+where
+    T: Number + RealField,
+{
+    fn bounds(&self) -> Rect<T> {
+        self.bounding_rect()
+    }
+
+    fn contains(&self, point: Vector2<T>) -> bool {
+        Obb::contains(self, point)
+    }
+
+    fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        Obb::intersects_rect(self, rect)
+    }
+}
+
+impl<T> Shape2<T> for RoundedRect<T>
+where
+    T: Number + RealField,
+{
+    fn bounds(&self) -> Rect<T> {
+        self.bounding_rect()
+    }
+
+    fn contains(&self, point: Vector2<T>) -> bool {
+        RoundedRect::contains(self, point)
+    }
+
+    fn intersects_rect(&self, rect: Rect<T>) -> bool {
+        RoundedRect::intersects_rect(self, rect)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Capsule, Circle, Ellipse, Shape2};
+    use crate::{obb::Obb, Rect};
+    use nalgebra::Vector2;
+
+    #[test]
+    fn shapes_narrow_phase() {
+        let shapes: Vec<Box<dyn Shape2<f32>>> = vec![
+            Box::new(Rect::new(0.0, 0.0, 10.0, 10.0)),
+            Box::new(Circle::new(Vector2::new(5.0, 5.0), 5.0)),
+            Box::new(Ellipse::inscribed(Rect::new(0.0, 0.0, 10.0, 4.0))),
+            Box::new(Obb::from(Rect::new(0.0, 0.0, 10.0, 10.0)).with_rotation(0.5)),
+        ];
+        let corner = Rect::new(-1.0, -1.0, 1.5, 1.5);
+        let hits = shapes
+            .iter()
+            .map(|shape| {
+                assert!(shape.bounds().intersects(corner));
+                shape.intersects_rect(corner)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(hits, [true, false, false, false]);
+
+        let ellipse = Ellipse::new(Vector2::new(0.0, 0.0), Vector2::new(4.0, 1.0));
+        assert!(ellipse.contains(Vector2::new(3.9, 0.0)));
+        assert!(!ellipse.contains(Vector2::new(0.0, 1.5)));
+        assert!(ellipse.intersects_rect(Rect::new(3.5, -0.1, 1.0, 0.2)));
+        assert!(!ellipse.intersects_rect(Rect::new(3.0, 0.8, 1.0, 1.0)));
+        assert_eq!(
+            Circle::new(Vector2::new(1.0, 1.0), 2.0).bounds(),
+            Rect::new(-1.0, -1.0, 4.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn capsule_vs_rect() {
+        let capsule = Capsule::vertical(Rect::new(0.0, 0.0, 2.0, 6.0));
+        assert_eq!(capsule.a, Vector2::new(1.0, 1.0));
+        assert_eq!(capsule.b, Vector2::new(1.0, 5.0));
+        assert_eq!(capsule.bounds(), Rect::new(0.0, 0.0, 2.0, 6.0));
+        assert!(capsule.contains(Vector2::new(0.0, 3.0)));
+        assert!(!capsule.contains(Vector2::new(0.1, 0.1)));
+
+        // Standing on the floor, resting on a ledge by the rounded bottom, crossing a wall.
+        assert!(capsule.intersects_rect(Rect::new(-5.0, 5.9, 10.0, 1.0)));
+        assert!(!capsule.intersects_rect(Rect::new(-5.0, 6.0, 10.0, 1.0)));
+        assert!(!capsule.intersects_rect(Rect::new(1.8, 5.8, 1.0, 1.0)));
+        assert!(capsule.intersects_rect(Rect::new(1.5, 5.2, 1.0, 1.0)));
+        let slanted = Capsule::new(Vector2::new(-3.0, -3.0), Vector2::new(3.0, 3.0), 0.1);
+        assert!(slanted.intersects_rect(Rect::new(-0.5, -0.5, 1.0, 1.0)));
+        assert_eq!(
+            slanted.segment_distance_to_rect(&Rect::new(-0.5, -0.5, 1.0, 1.0)),
+            0.0
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Spatial hash is a sparse uniform grid, that does not need the bounds of the world.
+
+use crate::{
+    quadtree::{BoundsProvider, QueryStorage},
+    Rect,
+};
+use nalgebra::Vector2;
+use std::{collections::HashMap, ops::ControlFlow, ops::RangeInclusive};
+
+/// Bounds and ids of the objects, that overlap a cell.
+type Cell<I> = Vec<(Rect<f32>, I)>;
+
+/// Sparse grid of square cells, that are stored in a hash map keyed by the coordinates of the
+/// cells. Only the cells, that hold objects, take memory, so the objects could be anywhere in an
+/// unbounded (for example, procedurally generated) world. Each object is stored in every cell it
+/// overlaps, queries report each object once.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpatialHash<I> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Cell<I>>,
+    len: usize,
+}
+
+impl<I> SpatialHash<I> {
+    /// Creates new empty spatial hash with the cells of the given size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell size is not positive.
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cell size must be positive");
+        Self {
+            cell_size,
+            cells: Default::default(),
+            len: 0,
+        }
+    }
+
+    /// Returns the size of the cells.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Returns the amount of objects in the spatial hash.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the spatial hash has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the amount of cells, that hold at least one object.
+    pub fn occupied_cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Removes all the objects.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.len = 0;
+    }
+
+    fn cell(&self, coordinate: f32) -> i32 {
+        // The cast saturates, so the objects far away share the outermost cells.
+        (coordinate / self.cell_size).floor() as i32
+    }
+
+    /// Returns the ranges of the columns and the rows of the cells, that overlap the rectangle.
+    fn cell_range(&self, rect: &Rect<f32>) -> (RangeInclusive<i32>, RangeInclusive<i32>) {
+        (
+            self.cell(rect.x())..=self.cell(rect.x() + rect.w()),
+            self.cell(rect.y())..=self.cell(rect.y() + rect.h()),
+        )
+    }
+
+    /// Adds the object to every cell, that it overlaps.
+    pub fn insert<T>(&mut self, object: T)
+    where
+        T: BoundsProvider<Id = I>,
+        I: Clone,
+    {
+        let bounds = object.bounds();
+        let id = object.id();
+        let (columns, rows) = self.cell_range(&bounds);
+        for row in rows {
+            for column in columns.clone() {
+                self.cells
+                    .entry((column, row))
+                    .or_default()
+                    .push((bounds, id.clone()));
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Removes the object from the spatial hash. The object must have the same bounds, as it had
+    /// when it was added. Returns `true` if the object was found. Cells, that became empty, are
+    /// released.
+    pub fn remove<T>(&mut self, object: T) -> bool
+    where
+        T: BoundsProvider<Id = I>,
+        I: PartialEq,
+    {
+        let id = object.id();
+        let (columns, rows) = self.cell_range(&object.bounds());
+        let mut found = false;
+        for row in rows {
+            for column in columns.clone() {
+                let Some(cell) = self.cells.get_mut(&(column, row)) else {
+                    continue;
+                };
+                if let Some(index) = cell.iter().position(|(_, other)| *other == id) {
+                    cell.swap_remove(index);
+                    found = true;
+                    if cell.is_empty() {
+                        self.cells.remove(&(column, row));
+                    }
+                }
+            }
+        }
+        if found {
+            self.len -= 1;
+        }
+        found
+    }
+
+    /// Passes the objects, that are stored in the cells overlapping the given area and pass the
+    /// filter, to the closure. An object is reported only in the first cell of the area, that it
+    /// overlaps, so each object is reported once.
+    fn query<F>(
+        &self,
+        area: Rect<f32>,
+        filter: impl Fn(&Rect<f32>) -> bool,
+        mut func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let (columns, rows) = self.cell_range(&area);
+        let mut visit = |column: i32, row: i32, cell: &Cell<I>| {
+            for (bounds, id) in cell {
+                let (object_columns, object_rows) = self.cell_range(bounds);
+                if column == *object_columns.start().max(columns.start())
+                    && row == *object_rows.start().max(rows.start())
+                    && filter(bounds)
+                {
+                    func(id)?;
+                }
+            }
+            ControlFlow::Continue(())
+        };
+
+        let area_cells = (*columns.end() as i64 - *columns.start() as i64 + 1)
+            * (*rows.end() as i64 - *rows.start() as i64 + 1);
+        if area_cells > self.cells.len() as i64 {
+            // Large areas are cheaper to check against the occupied cells only.
+            for (&(column, row), cell) in self.cells.iter() {
+                if columns.contains(&column) && rows.contains(&row) {
+                    visit(column, row, cell)?;
+                }
+            }
+        } else {
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    if let Some(cell) = self.cells.get(&(column, row)) {
+                        visit(column, row, cell)?;
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Passes the ids of the objects, that intersect the given rectangle, to the given closure.
+    /// The closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn rect_query_with<F>(&self, rect: Rect<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        self.query(rect, |bounds| bounds.intersects(rect), func)
+    }
+
+    /// Passes the ids of the objects, that contain the given point, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn point_query_with<F>(&self, point: Vector2<f32>, func: F) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let area = Rect::new(point.x, point.y, 0.0, 0.0);
+        self.query(area, |bounds| bounds.contains(point), func)
+    }
+
+    /// Passes the ids of the objects, that intersect the given circle, to the given closure. The
+    /// closure may stop the query by returning [`ControlFlow::Break`].
+    pub fn circle_query_with<F>(
+        &self,
+        center: Vector2<f32>,
+        radius: f32,
+        func: F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(&I) -> ControlFlow<()>,
+    {
+        let area = Rect::new(
+            center.x - radius,
+            center.y - radius,
+            radius + radius,
+            radius + radius,
+        );
+        self.query(
+            area,
+            |bounds| bounds.intersects_circle(center, radius),
+            func,
+        )
+    }
+
+    /// Writes the ids of the objects, that intersect the given rectangle, to the output storage.
+    pub fn rect_query<S>(&self, rect: Rect<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.rect_query_with(rect, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the objects, that contain the given point, to the output storage.
+    pub fn point_query<S>(&self, point: Vector2<f32>, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.point_query_with(point, |id| storage.push_or_break(id.clone()));
+    }
+
+    /// Writes the ids of the objects, that intersect the given circle, to the output storage.
+    pub fn circle_query<S>(&self, center: Vector2<f32>, radius: f32, storage: &mut S)
+    where
+        S: QueryStorage<Id = I>,
+        I: Clone,
+    {
+        let _ = self.circle_query_with(center, radius, |id| storage.push_or_break(id.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpatialHash;
+    use crate::{test_utils::TestObject, Rect};
+    use nalgebra::Vector2;
+
+    #[test]
+    fn spatial_hash_queries() {
+        let objects = [
+            Rect::new(-1000.0, -1000.0, 5.0, 5.0),
+            Rect::new(1.0e6, 2.0e6, 30.0, 30.0),
+            Rect::new(-5.0, -5.0, 10.0, 10.0),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(id, bounds)| TestObject { bounds, id })
+        .collect::<Vec<_>>();
+        let mut hash = SpatialHash::new(16.0);
+        for object in objects.iter() {
+            hash.insert(object);
+        }
+        assert_eq!(hash.len(), 3);
+        assert_eq!(hash.occupied_cells(), 1 + 4 + 4);
+
+        let mut found = Vec::new();
+        hash.rect_query(
+            Rect::new(1.0e6 + 10.0, 2.0e6 + 10.0, 100.0, 100.0),
+            &mut found,
+        );
+        assert_eq!(found, [1]);
+
+        // Huge query areas are checked against the occupied cells.
+        found.clear();
+        hash.rect_query(Rect::new(-1.0e7, -1.0e7, 2.0e7, 2.0e7), &mut found);
+        found.sort_unstable();
+        assert_eq!(found, [0, 1, 2]);
+
+        found.clear();
+        hash.circle_query(Vector2::new(0.0, 0.0), 2.0, &mut found);
+        assert_eq!(found, [2]);
+
+        assert!(hash.remove(&objects[2]));
+        assert!(!hash.remove(&objects[2]));
+        assert_eq!(hash.occupied_cells(), 1 + 4);
+        found.clear();
+        hash.point_query(Vector2::new(0.0, 0.0), &mut found);
+        assert!(found.is_empty());
+    }
+}
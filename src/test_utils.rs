@@ -0,0 +1,20 @@
+//! Fixtures shared by the tests of the spatial structures.
+
+use crate::{quadtree::BoundsProvider, Rect};
+
+pub(crate) struct TestObject {
+    pub(crate) bounds: Rect<f32>,
+    pub(crate) id: usize,
+}
+
+impl BoundsProvider for &TestObject {
+    type Id = usize;
+
+    fn bounds(&self) -> Rect<f32> {
+        self.bounds
+    }
+
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+}
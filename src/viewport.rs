@@ -0,0 +1,281 @@
+//! Mapping between the world and the screen for two-dimensional cameras, and fitting of the
+//! content into windows.
+
+use crate::Rect;
+use arrayvec::ArrayVec;
+use nalgebra::{Matrix3, Vector2};
+
+/// A two-dimensional camera, that shows the world around its center in a viewport on the screen.
+/// The zoom is the number of screen units per one world unit, so the larger the zoom, the smaller
+/// the visible part of the world.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Camera2 {
+    /// The rectangle on the screen, where the camera draws the world.
+    pub viewport: Rect<f32>,
+    /// The point in the world, that is shown at the center of the viewport.
+    pub center: Vector2<f32>,
+    /// The number of screen units per one world unit.
+    pub zoom: f32,
+}
+
+impl Camera2 {
+    /// Creates new camera for the given viewport. Initially, the world and the screen coordinates
+    /// are the same.
+    #[inline]
+    pub fn new(viewport: Rect<f32>) -> Self {
+        Self {
+            viewport,
+            center: viewport.center(),
+            zoom: 1.0,
+        }
+    }
+
+    /// Sets the new point in the world, that is shown at the center of the viewport.
+    #[inline]
+    pub fn with_center(mut self, center: Vector2<f32>) -> Self {
+        self.center = center;
+        self
+    }
+
+    /// Sets the new zoom of the camera.
+    #[inline]
+    pub fn with_zoom(mut self, zoom: f32) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    /// Maps the point from the world to the screen.
+    #[inline]
+    pub fn world_to_screen(&self, point: Vector2<f32>) -> Vector2<f32> {
+        self.viewport.center() + (point - self.center) * self.zoom
+    }
+
+    /// Maps the point from the screen to the world.
+    #[inline]
+    pub fn screen_to_world(&self, point: Vector2<f32>) -> Vector2<f32> {
+        self.center + (point - self.viewport.center()) / self.zoom
+    }
+
+    /// Maps the rectangle from the world to the screen.
+    #[inline]
+    pub fn world_rect_to_screen(&self, rect: Rect<f32>) -> Rect<f32> {
+        Rect {
+            position: self.world_to_screen(rect.position),
+            size: rect.size * self.zoom,
+        }
+    }
+
+    /// Maps the rectangle from the screen to the world.
+    #[inline]
+    pub fn screen_rect_to_world(&self, rect: Rect<f32>) -> Rect<f32> {
+        Rect {
+            position: self.screen_to_world(rect.position),
+            size: rect.size / self.zoom,
+        }
+    }
+
+    /// Returns the part of the world, that is visible in the viewport.
+    #[inline]
+    pub fn visible_rect(&self) -> Rect<f32> {
+        self.screen_rect_to_world(self.viewport)
+    }
+
+    /// Returns the matrix, that maps the points from the world to the screen. It could be used
+    /// with [`Rect::transform`].
+    #[inline]
+    pub fn world_to_screen_matrix(&self) -> Matrix3<f32> {
+        let offset = self.viewport.center() - self.center * self.zoom;
+        Matrix3::new(
+            self.zoom, 0.0, offset.x, 0.0, self.zoom, offset.y, 0.0, 0.0, 1.0,
+        )
+    }
+
+    /// Moves the camera by the given offset on the screen, for example by the offset of the
+    /// cursor, when the world is dragged.
+    #[inline]
+    pub fn pan(&mut self, screen_offset: Vector2<f32>) {
+        self.center -= screen_offset / self.zoom;
+    }
+
+    /// Multiplies the zoom by the given factor, keeping the point of the world under the given
+    /// point of the screen (usually the cursor) at the same place.
+    #[inline]
+    pub fn zoom_at(&mut self, screen_point: Vector2<f32>, factor: f32) {
+        let world_point = self.screen_to_world(screen_point);
+        self.zoom *= factor;
+        self.center = world_point - (screen_point - self.viewport.center()) / self.zoom;
+    }
+
+    /// Centers the camera at the target and picks the largest zoom, at which the entire target is
+    /// visible. The zoom is not changed, if the target has zero size.
+    #[inline]
+    pub fn zoom_to_fit(&mut self, target: Rect<f32>) {
+        self.center = target.center();
+        let zoom_x = self.viewport.w() / target.w();
+        let zoom_y = self.viewport.h() / target.h();
+        let zoom = match (target.w() > 0.0, target.h() > 0.0) {
+            (true, true) => zoom_x.min(zoom_y),
+            (true, false) => zoom_x,
+            (false, true) => zoom_y,
+            (false, false) => return,
+        };
+        self.zoom = zoom;
+    }
+
+    /// Moves the camera, so it never shows the area outside of the content. See [`clamp_view`]
+    /// for details.
+    #[inline]
+    pub fn clamp_to(&mut self, content: Rect<f32>) {
+        let visible = self.visible_rect();
+        self.center += clamp_view(visible, content).position - visible.position;
+    }
+}
+
+/// Moves the view, so it never shows the area outside of the content. If the view is larger than
+/// the content along some axis, the view is centered on the content along that axis. Only the
+/// position of the view changes, its size stays the same.
+pub fn clamp_view(view: Rect<f32>, content: Rect<f32>) -> Rect<f32> {
+    let clamp = |position: f32, size: f32, min: f32, content_size: f32| {
+        if size >= content_size {
+            min + (content_size - size) * 0.5
+        } else {
+            position.clamp(min, min + content_size - size)
+        }
+    };
+    view.with_position(Vector2::new(
+        clamp(view.x(), view.w(), content.x(), content.w()),
+        clamp(view.y(), view.h(), content.y(), content.h()),
+    ))
+}
+
+/// Fits the content of the given size into the window, preserving the aspect ratio of the content.
+/// Returns the rectangle of the content, centered in the window, and the rectangles of the bars,
+/// that are not covered by the content and need to be cleared: none if the aspect ratios match,
+/// top and bottom bars (letterbox) if the window is taller than the content, or left and right
+/// bars (pillarbox) if the window is wider.
+pub fn letterbox(
+    content_size: Vector2<f32>,
+    window: Rect<f32>,
+) -> (Rect<f32>, ArrayVec<Rect<f32>, 2>) {
+    let mut bars = ArrayVec::new();
+    if content_size.x <= 0.0 || content_size.y <= 0.0 {
+        return (window, bars);
+    }
+    let scale = (window.w() / content_size.x).min(window.h() / content_size.y);
+    let size = content_size * scale;
+    let position = window.position + (window.size - size) * 0.5;
+    let content = Rect { position, size };
+    if size.x < window.w() {
+        let left = position.x - window.x();
+        bars.push(Rect::new(window.x(), window.y(), left, window.h()));
+        bars.push(Rect::new(
+            position.x + size.x,
+            window.y(),
+            window.w() - size.x - left,
+            window.h(),
+        ));
+    } else if size.y < window.h() {
+        let top = position.y - window.y();
+        bars.push(Rect::new(window.x(), window.y(), window.w(), top));
+        bars.push(Rect::new(
+            window.x(),
+            position.y + size.y,
+            window.w(),
+            window.h() - size.y - top,
+        ));
+    }
+    (content, bars)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clamp_view, letterbox, Camera2};
+    use crate::Rect;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn camera_mapping() {
+        let mut camera = Camera2::new(Rect::new(0.0, 0.0, 800.0, 600.0))
+            .with_center(Vector2::new(0.0, 0.0))
+            .with_zoom(2.0);
+        assert_eq!(
+            camera.world_to_screen(Vector2::new(10.0, -10.0)),
+            Vector2::new(420.0, 280.0)
+        );
+        assert_eq!(
+            camera.screen_to_world(Vector2::new(420.0, 280.0)),
+            Vector2::new(10.0, -10.0)
+        );
+        assert_eq!(
+            camera.visible_rect(),
+            Rect::new(-200.0, -150.0, 400.0, 300.0)
+        );
+        let rect = Rect::new(-10.0, 5.0, 20.0, 10.0);
+        assert_eq!(
+            camera.world_rect_to_screen(rect),
+            rect.transform(&camera.world_to_screen_matrix())
+        );
+
+        // The point under the cursor stays in place.
+        let cursor = Vector2::new(100.0, 500.0);
+        let under_cursor = camera.screen_to_world(cursor);
+        camera.zoom_at(cursor, 4.0);
+        assert_eq!(camera.zoom, 8.0);
+        assert_eq!(camera.world_to_screen(under_cursor), cursor);
+
+        camera.zoom_to_fit(Rect::new(100.0, 100.0, 200.0, 50.0));
+        assert_eq!(camera.zoom, 4.0);
+        assert_eq!(camera.visible_rect(), Rect::new(100.0, 50.0, 200.0, 150.0));
+    }
+
+    #[test]
+    fn letterbox_and_pillarbox() {
+        let (content, bars) = letterbox(Vector2::new(16.0, 9.0), Rect::new(0.0, 0.0, 160.0, 120.0));
+        assert_eq!(content, Rect::new(0.0, 15.0, 160.0, 90.0));
+        assert_eq!(
+            bars.as_slice(),
+            [
+                Rect::new(0.0, 0.0, 160.0, 15.0),
+                Rect::new(0.0, 105.0, 160.0, 15.0)
+            ]
+        );
+
+        let (content, bars) = letterbox(Vector2::new(4.0, 3.0), Rect::new(10.0, 0.0, 200.0, 60.0));
+        assert_eq!(content, Rect::new(70.0, 0.0, 80.0, 60.0));
+        assert_eq!(
+            bars.as_slice(),
+            [
+                Rect::new(10.0, 0.0, 60.0, 60.0),
+                Rect::new(150.0, 0.0, 60.0, 60.0)
+            ]
+        );
+
+        let (content, bars) = letterbox(Vector2::new(2.0, 1.0), Rect::new(0.0, 0.0, 20.0, 10.0));
+        assert_eq!(content, Rect::new(0.0, 0.0, 20.0, 10.0));
+        assert!(bars.is_empty());
+    }
+
+    #[test]
+    fn clamp_scroll() {
+        let content = Rect::new(0.0, 0.0, 1000.0, 100.0);
+        assert_eq!(
+            clamp_view(Rect::new(-50.0, 20.0, 200.0, 50.0), content),
+            Rect::new(0.0, 20.0, 200.0, 50.0)
+        );
+        assert_eq!(
+            clamp_view(Rect::new(900.0, 80.0, 200.0, 50.0), content),
+            Rect::new(800.0, 50.0, 200.0, 50.0)
+        );
+        // Taller than the content, so centered vertically.
+        assert_eq!(
+            clamp_view(Rect::new(500.0, 80.0, 200.0, 300.0), content),
+            Rect::new(500.0, -100.0, 200.0, 300.0)
+        );
+
+        let mut camera =
+            Camera2::new(Rect::new(0.0, 0.0, 200.0, 50.0)).with_center(Vector2::new(-1000.0, 50.0));
+        camera.clamp_to(content);
+        assert_eq!(camera.center, Vector2::new(100.0, 50.0));
+    }
+}